@@ -31,6 +31,7 @@ pub enum Membership {
     Invited,
     Joined,
     Left,
+    Knocked,
 }
 
 pub(crate) type TimelineLock = Arc<RwLock<Option<Arc<Timeline>>>>;
@@ -87,6 +88,7 @@ impl Room {
             SdkRoom::Invited(_) => Membership::Invited,
             SdkRoom::Joined(_) => Membership::Joined,
             SdkRoom::Left(_) => Membership::Left,
+            SdkRoom::Knocked(_) => Membership::Knocked,
         }
     }
 
@@ -99,8 +101,7 @@ impl Room {
                     .and_then(|a| a.inviter)
                     .map(|m| Arc::new(RoomMember::new(m)))
             }),
-            SdkRoom::Joined(_) => None,
-            SdkRoom::Left(_) => None,
+            SdkRoom::Joined(_) | SdkRoom::Left(_) | SdkRoom::Knocked(_) => None,
         }
     }
 
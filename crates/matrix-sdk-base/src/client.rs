@@ -41,6 +41,7 @@ use ruma::{
     api::client::{self as api, push::get_notifications::v3::Notification},
     events::{
         push_rules::{PushRulesEvent, PushRulesEventContent},
+        receipt::{ReceiptEventContent, ReceiptType},
         room::{
             member::{MembershipState, SyncRoomMemberEvent},
             power_levels::{RoomPowerLevelsEvent, RoomPowerLevelsEventContent},
@@ -51,7 +52,7 @@ use ruma::{
     },
     push::{Action, PushConditionRoomCtx, Ruleset},
     serde::Raw,
-    MilliSecondsSinceUnixEpoch, OwnedUserId, RoomId, UInt, UserId,
+    MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedUserId, RoomId, UInt, UserId,
 };
 use tokio::sync::RwLock;
 use tracing::{debug, info, trace, warn};
@@ -152,6 +153,11 @@ impl BaseClient {
         self.store.set_session_tokens(tokens)
     }
 
+    /// Clear the session tokens, for example after logging out.
+    pub fn clear_session_tokens(&self) {
+        self.store.clear_session_tokens()
+    }
+
     /// Get the user login session.
     ///
     /// If the client is currently logged in, this will return a
@@ -646,6 +652,25 @@ impl BaseClient {
         Ok(room)
     }
 
+    /// User has knocked on a room.
+    ///
+    /// Update the internal and cached state accordingly. Return the final Room.
+    pub async fn room_knocked(&self, room_id: &RoomId) -> Result<Room> {
+        let room = self.store.get_or_create_room(room_id, RoomState::Knocked).await;
+        if room.state() != RoomState::Knocked {
+            let _sync_lock = self.sync_lock().read().await;
+
+            let mut room_info = room.clone_info();
+            room_info.mark_as_knocked();
+            let mut changes = StateChanges::default();
+            changes.add_stripped_room(room_info.clone());
+            self.store.save_changes(&changes).await?; // Update the store
+            room.update_summary(room_info); // Update the cached room handle
+        }
+
+        Ok(room)
+    }
+
     /// Get access to the store's sync lock.
     pub fn sync_lock(&self) -> &RwLock<()> {
         self.store.sync_lock()
@@ -720,12 +745,15 @@ impl BaseClient {
                 )
                 .await?;
 
+            let mut read_receipts = BTreeSet::new();
+
             if let Some(event) =
                 new_info.ephemeral.events.iter().find_map(|e| match e.deserialize() {
                     Ok(AnySyncEphemeralRoomEvent::Receipt(event)) => Some(event.content),
                     _ => None,
                 })
             {
+                read_receipts = own_read_receipts(&event, room.own_user_id());
                 changes.add_receipts(&room_id, event);
             }
 
@@ -771,6 +799,7 @@ impl BaseClient {
 
             let notification_count = new_info.unread_notifications.into();
             room_info.update_notification_count(notification_count);
+            room_info.update_unread_counts(room.own_user_id(), &read_receipts, &timeline);
 
             new_rooms.join.insert(
                 room_id,
@@ -827,13 +856,7 @@ impl BaseClient {
         for (room_id, new_info) in rooms.invite {
             let room = self.store.get_or_create_stripped_room(&room_id).await;
             let mut room_info = room.clone_info();
-
-            if let Some(r) = self.store.get_room(&room_id) {
-                let mut room_info = r.clone_info();
-                room_info.mark_as_invited();
-                room_info.mark_state_fully_synced();
-                changes.add_room(room_info);
-            }
+            room_info.mark_as_invited();
 
             self.handle_invited_state(&new_info.invite_state.events, &mut room_info, &mut changes);
 
@@ -842,6 +865,18 @@ impl BaseClient {
             new_rooms.invite.insert(room_id, new_info);
         }
 
+        for (room_id, new_info) in rooms.knock {
+            let room = self.store.get_or_create_room(&room_id, RoomState::Knocked).await;
+            let mut room_info = room.clone_info();
+            room_info.mark_as_knocked();
+
+            self.handle_invited_state(&new_info.knock_state.events, &mut room_info, &mut changes);
+
+            changes.add_stripped_room(room_info);
+
+            new_rooms.knock.insert(room_id, new_info);
+        }
+
         // TODO remove this, we're processing account data events here again
         // because we want to have the push rules in place before we process
         // rooms and their events, but we want to create the rooms before we
@@ -1239,6 +1274,23 @@ impl Default for BaseClient {
     }
 }
 
+/// Collect the events that received a public or private read receipt from the
+/// given user.
+pub(crate) fn own_read_receipts(
+    content: &ReceiptEventContent,
+    user_id: &UserId,
+) -> BTreeSet<OwnedEventId> {
+    content
+        .iter()
+        .filter(|(_, receipts)| {
+            [ReceiptType::Read, ReceiptType::ReadPrivate].iter().any(|receipt_type| {
+                receipts.get(receipt_type).map_or(false, |users| users.contains_key(user_id))
+            })
+        })
+        .map(|(event_id, _)| event_id.clone())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use matrix_sdk_test::{
@@ -1249,10 +1301,10 @@ mod tests {
         api::{client as api, IncomingResponse},
         room_id, user_id,
     };
-    use serde_json::json;
+    use serde_json::{json, Value as JsonValue};
 
     use super::BaseClient;
-    use crate::{DisplayName, RoomState, SessionMeta};
+    use crate::{sync::UnreadCounts, DisplayName, RoomState, SessionMeta};
 
     #[async_test]
     async fn invite_after_leaving() {
@@ -1401,4 +1453,299 @@ mod tests {
             DisplayName::Calculated("Kyra".to_owned())
         );
     }
+
+    #[async_test]
+    async fn knocked_room() {
+        let user_id = user_id!("@alice:example.org");
+        let room_id = room_id!("!knock:example.org");
+
+        let client = BaseClient::new();
+        client
+            .set_session_meta(SessionMeta {
+                user_id: user_id.to_owned(),
+                device_id: "FOOBAR".into(),
+            })
+            .await
+            .unwrap();
+
+        let response = api::sync::sync_events::v3::Response::try_from_http_response(
+            response_from_file(&json!({
+                "next_batch": "knock_batch",
+                "rooms": {
+                    "knock": {
+                        "!knock:example.org": {
+                            "knock_state": {
+                                "events": [
+                                    {
+                                        "content": {
+                                            "name": "Knock knock"
+                                        },
+                                        "sender": "@test:example.org",
+                                        "state_key": "",
+                                        "type": "m.room.name"
+                                    },
+                                    {
+                                        "content": {
+                                            "membership": "knock"
+                                        },
+                                        "sender": "@alice:example.org",
+                                        "state_key": "@alice:example.org",
+                                        "type": "m.room.member"
+                                    }
+                                ]
+                            }
+                        }
+                    }
+                }
+            })),
+        )
+        .expect("static json doesn't fail to parse");
+
+        let sync_response = client.receive_sync_response(response).await.unwrap();
+        assert!(sync_response.rooms.knock.contains_key(room_id));
+
+        let room = client.get_room(room_id).expect("Room not found");
+        assert_eq!(room.state(), RoomState::Knocked);
+        assert_eq!(room.name().as_deref(), Some("Knock knock"));
+
+        client.room_joined(room_id).await.unwrap();
+        assert_eq!(client.get_room(room_id).unwrap().state(), RoomState::Joined);
+    }
+
+    #[async_test]
+    async fn knocked_room_invited() {
+        let user_id = user_id!("@alice:example.org");
+        let room_id = room_id!("!knock:example.org");
+
+        let client = BaseClient::new();
+        client
+            .set_session_meta(SessionMeta {
+                user_id: user_id.to_owned(),
+                device_id: "FOOBAR".into(),
+            })
+            .await
+            .unwrap();
+
+        let member = |membership: &str| {
+            json!({
+                "content": {
+                    "membership": membership
+                },
+                "sender": "@alice:example.org",
+                "state_key": "@alice:example.org",
+                "type": "m.room.member"
+            })
+        };
+
+        let response = api::sync::sync_events::v3::Response::try_from_http_response(
+            response_from_file(&json!({
+                "next_batch": "knock_batch",
+                "rooms": {
+                    "knock": {
+                        "!knock:example.org": {
+                            "knock_state": {
+                                "events": [member("knock")]
+                            }
+                        }
+                    }
+                }
+            })),
+        )
+        .expect("static json doesn't fail to parse");
+        client.receive_sync_response(response).await.unwrap();
+        assert_eq!(client.get_room(room_id).unwrap().state(), RoomState::Knocked);
+
+        let response = api::sync::sync_events::v3::Response::try_from_http_response(
+            response_from_file(&json!({
+                "next_batch": "invite_batch",
+                "rooms": {
+                    "invite": {
+                        "!knock:example.org": {
+                            "invite_state": {
+                                "events": [member("invite")]
+                            }
+                        }
+                    }
+                }
+            })),
+        )
+        .expect("static json doesn't fail to parse");
+        let sync_response = client.receive_sync_response(response).await.unwrap();
+        assert!(sync_response.rooms.invite.contains_key(room_id));
+
+        let room = client.get_room(room_id).expect("Room not found");
+        assert_eq!(room.state(), RoomState::Invited);
+        let stripped_room_infos = client.store().get_stripped_room_infos().await.unwrap();
+        assert_eq!(stripped_room_infos.len(), 1);
+        assert_eq!(stripped_room_infos[0].state(), RoomState::Invited);
+        assert!(client.store().get_room_infos().await.unwrap().is_empty());
+    }
+
+    fn message(event_id: &str, sender: &str, body: &str) -> JsonValue {
+        json!({
+            "content": {
+                "body": body,
+                "msgtype": "m.text"
+            },
+            "event_id": event_id,
+            "origin_server_ts": 152037280,
+            "sender": sender,
+            "type": "m.room.message"
+        })
+    }
+
+    fn sync_response(
+        batch: &str,
+        state: Vec<JsonValue>,
+        timeline: Vec<JsonValue>,
+        ephemeral: Vec<JsonValue>,
+    ) -> api::sync::sync_events::v3::Response {
+        api::sync::sync_events::v3::Response::try_from_http_response(response_from_file(&json!({
+            "next_batch": batch,
+            "rooms": {
+                "join": {
+                    "!unread:example.org": {
+                        "state": { "events": state },
+                        "timeline": { "events": timeline },
+                        "ephemeral": { "events": ephemeral }
+                    }
+                }
+            }
+        })))
+        .expect("static json doesn't fail to parse")
+    }
+
+    #[async_test]
+    async fn unread_counts() {
+        let user_id = user_id!("@alice:example.org");
+        let room_id = room_id!("!unread:example.org");
+
+        let client = BaseClient::new();
+        client
+            .set_session_meta(SessionMeta {
+                user_id: user_id.to_owned(),
+                device_id: "FOOBAR".into(),
+            })
+            .await
+            .unwrap();
+
+        let state = vec![
+            json!({
+                "content": { "membership": "join" },
+                "event_id": "$member",
+                "origin_server_ts": 152037270,
+                "sender": "@alice:example.org",
+                "state_key": "@alice:example.org",
+                "type": "m.room.member"
+            }),
+            json!({
+                "content": { "users": { "@alice:example.org": 100 } },
+                "event_id": "$power_levels",
+                "origin_server_ts": 152037270,
+                "sender": "@alice:example.org",
+                "state_key": "",
+                "type": "m.room.power_levels"
+            }),
+        ];
+        let timeline = vec![
+            message("$1", "@bob:example.org", "Hello"),
+            message("$2", "@bob:example.org", "Are you there alice?"),
+            json!({
+                "content": {
+                    "m.relates_to": {
+                        "event_id": "$1",
+                        "key": "👍",
+                        "rel_type": "m.annotation"
+                    }
+                },
+                "event_id": "$3",
+                "origin_server_ts": 152037280,
+                "sender": "@bob:example.org",
+                "type": "m.reaction"
+            }),
+        ];
+
+        client.receive_sync_response(sync_response("1", state, timeline, vec![])).await.unwrap();
+
+        let room = client.get_room(room_id).unwrap();
+        assert_eq!(
+            room.unread_counts(),
+            UnreadCounts { unread_count: 2, notification_count: 2, highlight_count: 1 }
+        );
+
+        // With a read receipt in the batch, only later events are counted.
+        let timeline = vec![
+            message("$4", "@bob:example.org", "Hi alice"),
+            message("$5", "@bob:example.org", "How are you?"),
+        ];
+        let receipt = json!({
+            "content": {
+                "$4": {
+                    "m.read": {
+                        "@alice:example.org": { "ts": 152037290 }
+                    }
+                }
+            },
+            "type": "m.receipt"
+        });
+
+        client
+            .receive_sync_response(sync_response("2", vec![], timeline, vec![receipt]))
+            .await
+            .unwrap();
+        assert_eq!(
+            room.unread_counts(),
+            UnreadCounts { unread_count: 1, notification_count: 1, highlight_count: 0 }
+        );
+
+        // Sending a message marks everything before it as read.
+        let timeline = vec![
+            message("$6", "@bob:example.org", "Hello?"),
+            message("$7", "@alice:example.org", "Fine, thanks"),
+        ];
+
+        client.receive_sync_response(sync_response("3", vec![], timeline, vec![])).await.unwrap();
+        assert_eq!(room.unread_counts(), UnreadCounts::default());
+
+        let timeline = vec![message("$8", "@bob:example.org", "Good to hear")];
+        client.receive_sync_response(sync_response("4", vec![], timeline, vec![])).await.unwrap();
+        assert_eq!(
+            room.unread_counts(),
+            UnreadCounts { unread_count: 1, notification_count: 1, highlight_count: 0 }
+        );
+
+        // A read receipt for an event that is not in the batch keeps the
+        // previous counts.
+        let timeline = vec![message("$9", "@bob:example.org", "Anyone?")];
+        let receipt = json!({
+            "content": {
+                "$7": {
+                    "m.read": {
+                        "@alice:example.org": { "ts": 152037300 }
+                    }
+                }
+            },
+            "type": "m.receipt"
+        });
+
+        client
+            .receive_sync_response(sync_response("5", vec![], timeline, vec![receipt]))
+            .await
+            .unwrap();
+        assert_eq!(
+            room.unread_counts(),
+            UnreadCounts { unread_count: 2, notification_count: 2, highlight_count: 0 }
+        );
+
+        // A limited timeline starts the counts over.
+        let timeline = vec![message("$10", "@bob:example.org", "Bye")];
+        let mut response = sync_response("6", vec![], timeline, vec![]);
+        response.rooms.join.get_mut(room_id).unwrap().timeline.limited = true;
+
+        client.receive_sync_response(response).await.unwrap();
+        assert_eq!(
+            room.unread_counts(),
+            UnreadCounts { unread_count: 1, notification_count: 1, highlight_count: 0 }
+        );
+    }
 }
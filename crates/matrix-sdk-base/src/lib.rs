@@ -29,6 +29,7 @@ pub mod deserialized_responses;
 mod error;
 pub mod media;
 mod rooms;
+pub mod search_index;
 mod session;
 #[cfg(feature = "experimental-sliding-sync")]
 mod sliding_sync;
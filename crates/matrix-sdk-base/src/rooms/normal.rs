@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use std::{
-    collections::HashSet,
+    collections::{BTreeSet, HashSet},
     sync::{Arc, RwLock as SyncRwLock},
 };
 
@@ -26,12 +26,14 @@ use ruma::{
         room::{
             create::RoomCreateEventContent, encryption::RoomEncryptionEventContent,
             guest_access::GuestAccess, history_visibility::HistoryVisibility, join_rules::JoinRule,
-            redaction::OriginalSyncRoomRedactionEvent, tombstone::RoomTombstoneEventContent,
+            message::Relation, redaction::OriginalSyncRoomRedactionEvent,
+            tombstone::RoomTombstoneEventContent,
         },
         tag::Tags,
-        AnyRoomAccountDataEvent, AnyStrippedStateEvent, AnySyncStateEvent,
-        RoomAccountDataEventType,
+        AnyMessageLikeEventContent, AnyRoomAccountDataEvent, AnyStrippedStateEvent,
+        AnySyncMessageLikeEvent, AnySyncStateEvent, AnySyncTimelineEvent, RoomAccountDataEventType,
     },
+    push::Action,
     room::RoomType,
     EventId, OwnedEventId, OwnedMxcUri, OwnedRoomAliasId, OwnedUserId, RoomAliasId, RoomId,
     RoomVersionId, UserId,
@@ -43,7 +45,7 @@ use super::{BaseRoomInfo, DisplayName, RoomMember};
 use crate::{
     deserialized_responses::MemberEvent,
     store::{DynStateStore, Result as StoreResult, StateStoreExt},
-    sync::UnreadNotificationsCount,
+    sync::{Timeline, UnreadCounts, UnreadNotificationsCount},
     MinimalStateEvent,
 };
 
@@ -80,6 +82,8 @@ pub enum RoomState {
     Left,
     /// The room is in a invited state.
     Invited,
+    /// The room is in a knocked state, the user asked to join it.
+    Knocked,
 }
 
 impl Room {
@@ -131,6 +135,15 @@ impl Room {
         self.inner.read().unwrap().notification_counts
     }
 
+    /// Get the unread counts computed locally from the timeline.
+    ///
+    /// These are calculated after decryption using the user's push rules and
+    /// read receipt, in contrast to the server-side counts returned by
+    /// [`Room::unread_notification_counts()`].
+    pub fn unread_counts(&self) -> UnreadCounts {
+        self.inner.read().unwrap().unread_counts
+    }
+
     /// Check if the room has its members fully synced.
     ///
     /// Members might be missing if lazy member loading was enabled for the
@@ -211,7 +224,7 @@ impl Room {
     #[instrument(skip_all, fields(room_id = ?self.room_id))]
     pub async fn is_direct(&self) -> StoreResult<bool> {
         match self.state() {
-            RoomState::Joined | RoomState::Left => {
+            RoomState::Joined | RoomState::Left | RoomState::Knocked => {
                 Ok(!self.inner.read().unwrap().base_info.dm_targets.is_empty())
             }
             RoomState::Invited => {
@@ -408,7 +421,7 @@ impl Room {
         };
 
         let (joined, invited) = match self.state() {
-            RoomState::Invited => {
+            RoomState::Invited | RoomState::Knocked => {
                 // when we were invited we don't have a proper summary, we have to do best
                 // guessing
                 (members.len() as u64, 1u64)
@@ -556,6 +569,9 @@ pub struct RoomInfo {
     room_state: RoomState,
     /// The unread notifications counts.
     notification_counts: UnreadNotificationsCount,
+    /// The unread counts computed on the client.
+    #[serde(default)]
+    unread_counts: UnreadCounts,
     /// The summary of this room.
     summary: RoomSummary,
     /// Flag remembering if the room members are synced.
@@ -608,6 +624,22 @@ fn encryption_state_default() -> bool {
     true
 }
 
+/// Whether the given event should be counted as an unread message.
+///
+/// Redacted events, edits and events that aren't messages, like reactions,
+/// are ignored. Events we couldn't decrypt are counted since they most likely
+/// are messages.
+fn counts_as_unread(event: &AnySyncMessageLikeEvent) -> bool {
+    match event.original_content() {
+        Some(AnyMessageLikeEventContent::RoomMessage(content)) => {
+            !matches!(content.relates_to, Some(Relation::Replacement(_)))
+        }
+        Some(AnyMessageLikeEventContent::RoomEncrypted(_))
+        | Some(AnyMessageLikeEventContent::Sticker(_)) => true,
+        _ => false,
+    }
+}
+
 impl RoomInfo {
     #[doc(hidden)] // used by store tests, otherwise it would be pub(crate)
     pub fn new(room_id: &RoomId, room_state: RoomState) -> Self {
@@ -615,6 +647,7 @@ impl RoomInfo {
             room_id: room_id.into(),
             room_state,
             notification_counts: Default::default(),
+            unread_counts: Default::default(),
             summary: Default::default(),
             members_synced: false,
             last_prev_batch: None,
@@ -639,6 +672,11 @@ impl RoomInfo {
         self.room_state = RoomState::Invited;
     }
 
+    /// Mark this Room as knocked.
+    pub fn mark_as_knocked(&mut self) {
+        self.room_state = RoomState::Knocked;
+    }
+
     /// Mark this Room as having all the members synced.
     pub fn mark_members_synced(&mut self) {
         self.members_synced = true;
@@ -727,6 +765,58 @@ impl RoomInfo {
         self.notification_counts = notification_counts;
     }
 
+    /// Update the locally computed unread counts with a new batch of timeline
+    /// events.
+    ///
+    /// `read_receipts` contains the events that received a new read receipt
+    /// from the own user in the same sync. If one of them is part of the
+    /// timeline, the counts start over after it. Otherwise the receipt is for
+    /// an event that was already counted, so the previous counts are kept.
+    ///
+    /// If the timeline is limited, the events between the previous batch and
+    /// this one are unknown, so the counts start over from the beginning of
+    /// the batch.
+    pub(crate) fn update_unread_counts(
+        &mut self,
+        own_user_id: &UserId,
+        read_receipts: &BTreeSet<OwnedEventId>,
+        timeline: &Timeline,
+    ) {
+        let events = &timeline.events;
+        let mut start = 0;
+
+        let receipt_pos = events.iter().rposition(|event| {
+            event.event_id().map_or(false, |event_id| read_receipts.contains(&event_id))
+        });
+
+        if let Some(pos) = receipt_pos {
+            self.unread_counts = UnreadCounts::default();
+            start = pos + 1;
+        } else if timeline.limited {
+            self.unread_counts = UnreadCounts::default();
+        }
+
+        for event in &events[start..] {
+            let Ok(AnySyncTimelineEvent::MessageLike(e)) = event.event.deserialize() else {
+                continue;
+            };
+
+            if e.sender() == own_user_id {
+                // Sending a message implies the user read everything before it.
+                self.unread_counts = UnreadCounts::default();
+            } else if counts_as_unread(&e) {
+                self.unread_counts.unread_count += 1;
+
+                if event.push_actions.iter().any(Action::should_notify) {
+                    self.unread_counts.notification_count += 1;
+                }
+                if event.push_actions.iter().any(Action::is_highlight) {
+                    self.unread_counts.highlight_count += 1;
+                }
+            }
+        }
+    }
+
     /// Update the RoomSummary
     ///
     /// Returns true if the Summary modified the info, false otherwise.
@@ -873,6 +963,11 @@ mod test {
                 highlight_count: 1,
                 notification_count: 2,
             },
+            unread_counts: UnreadCounts {
+                unread_count: 5,
+                notification_count: 4,
+                highlight_count: 1,
+            },
             summary: RoomSummary {
                 heroes: vec!["Somebody".to_owned()],
                 joined_member_count: 5,
@@ -892,6 +987,11 @@ mod test {
                 "highlight_count": 1,
                 "notification_count": 2,
             },
+            "unread_counts": {
+                "unread_count": 5,
+                "notification_count": 4,
+                "highlight_count": 1,
+            },
             "summary": {
                 "heroes": ["Somebody"],
                 "joined_member_count": 5,
@@ -0,0 +1,389 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A local full-text search index of room messages.
+//!
+//! The homeserver can't search in encrypted rooms, so clients that want to
+//! offer search in those rooms need to index the decrypted messages locally.
+//! This module defines the [`SearchIndex`] trait implemented by the index
+//! backends, and a simple in-memory implementation, [`MemorySearchIndex`].
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+use async_trait::async_trait;
+use matrix_sdk_common::AsyncTraitDeps;
+use ruma::{
+    events::AnySyncTimelineEvent, serde::Raw, EventId, MilliSecondsSinceUnixEpoch, OwnedEventId,
+    OwnedRoomId, OwnedUserId,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::store::StoreError;
+
+/// The number of words kept before the first match in a snippet.
+const SNIPPET_WORDS_BEFORE: usize = 4;
+/// The maximum number of words in a snippet.
+const SNIPPET_MAX_WORDS: usize = 16;
+
+/// A message that can be added to a [`SearchIndex`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IndexedEvent {
+    /// The room of the event.
+    pub room_id: OwnedRoomId,
+    /// The ID of the event.
+    pub event_id: OwnedEventId,
+    /// The sender of the event.
+    pub sender: OwnedUserId,
+    /// The time the event was sent.
+    pub origin_server_ts: MilliSecondsSinceUnixEpoch,
+    /// The text content of the event.
+    pub body: String,
+}
+
+/// An event that couldn't be decrypted yet, kept by a [`SearchIndex`] to be
+/// indexed once the room key is available.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct UndecryptedEvent {
+    /// The room of the event.
+    pub room_id: OwnedRoomId,
+    /// The ID of the event.
+    pub event_id: OwnedEventId,
+    /// The encrypted event.
+    pub event: Raw<AnySyncTimelineEvent>,
+}
+
+/// Filters for the results of [`SearchIndex::search()`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct LocalSearchFilter {
+    /// Only return events from these rooms.
+    pub rooms: Option<Vec<OwnedRoomId>>,
+    /// Only return events from these senders.
+    pub senders: Option<Vec<OwnedUserId>>,
+    /// The maximum number of results.
+    ///
+    /// Default: 20.
+    pub limit: usize,
+}
+
+impl Default for LocalSearchFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LocalSearchFilter {
+    /// Creates an empty `LocalSearchFilter`.
+    pub fn new() -> Self {
+        Self { rooms: None, senders: None, limit: 20 }
+    }
+
+    /// Creates a new `LocalSearchFilter` from `self` that only matches events
+    /// from the given rooms.
+    pub fn rooms(self, rooms: Vec<OwnedRoomId>) -> Self {
+        Self { rooms: Some(rooms), ..self }
+    }
+
+    /// Creates a new `LocalSearchFilter` from `self` that only matches events
+    /// from the given senders.
+    pub fn senders(self, senders: Vec<OwnedUserId>) -> Self {
+        Self { senders: Some(senders), ..self }
+    }
+
+    /// Creates a new `LocalSearchFilter` from `self` with the `limit` field
+    /// set to the given value.
+    pub fn limit(self, limit: usize) -> Self {
+        Self { limit, ..self }
+    }
+
+    /// Whether the given event matches this filter.
+    pub fn matches(&self, event: &IndexedEvent) -> bool {
+        self.rooms.as_ref().map_or(true, |rooms| rooms.contains(&event.room_id))
+            && self.senders.as_ref().map_or(true, |senders| senders.contains(&event.sender))
+    }
+}
+
+/// A result of [`SearchIndex::search()`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct LocalSearchResult {
+    /// The room of the event.
+    pub room_id: OwnedRoomId,
+    /// The ID of the event.
+    pub event_id: OwnedEventId,
+    /// The sender of the event.
+    pub sender: OwnedUserId,
+    /// The time the event was sent.
+    pub origin_server_ts: MilliSecondsSinceUnixEpoch,
+    /// An extract of the body of the event around the first match.
+    pub snippet: String,
+}
+
+impl LocalSearchResult {
+    /// Create a result for the given event, matched by the given query
+    /// tokens.
+    pub fn new(event: IndexedEvent, query_tokens: &[String]) -> Self {
+        let snippet = snippet(&event.body, query_tokens);
+        Self {
+            room_id: event.room_id,
+            event_id: event.event_id,
+            sender: event.sender,
+            origin_server_ts: event.origin_server_ts,
+            snippet,
+        }
+    }
+}
+
+/// A local full-text search index of room messages.
+///
+/// Implementations are responsible for protecting the indexed content at
+/// rest, the messages come from encrypted rooms.
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+pub trait SearchIndex: AsyncTraitDeps {
+    /// Add the given events to the index, replacing events with the same ID.
+    async fn index_events(&self, events: Vec<IndexedEvent>) -> Result<(), StoreError>;
+
+    /// Remove the given events from the index, for example because they were
+    /// redacted.
+    ///
+    /// This also removes them from the undecrypted events.
+    async fn remove_events(&self, event_ids: Vec<OwnedEventId>) -> Result<(), StoreError>;
+
+    /// Search for events containing all the words of the query.
+    ///
+    /// The results are ordered from the most recent to the oldest.
+    async fn search(
+        &self,
+        query: &str,
+        filter: &LocalSearchFilter,
+    ) -> Result<Vec<LocalSearchResult>, StoreError>;
+
+    /// Get the indexed event with the given ID.
+    async fn get_event(&self, event_id: &EventId) -> Result<Option<IndexedEvent>, StoreError>;
+
+    /// Keep the given events that couldn't be decrypted, to index them once
+    /// the room key is available.
+    async fn save_undecrypted_events(
+        &self,
+        events: Vec<UndecryptedEvent>,
+    ) -> Result<(), StoreError>;
+
+    /// Get the events that couldn't be decrypted yet.
+    async fn undecrypted_events(&self) -> Result<Vec<UndecryptedEvent>, StoreError>;
+}
+
+/// A type-erased [`SearchIndex`].
+pub type DynSearchIndex = dyn SearchIndex;
+
+/// Split the given text into the lowercase words used for indexing and
+/// querying.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Extract the part of `body` around the first word matching one of the
+/// query tokens.
+pub fn snippet(body: &str, query_tokens: &[String]) -> String {
+    let words: Vec<_> = body.split_whitespace().collect();
+    let first_match = words
+        .iter()
+        .position(|word| tokenize(word).iter().any(|token| query_tokens.contains(token)))
+        .unwrap_or(0);
+
+    let start = first_match.saturating_sub(SNIPPET_WORDS_BEFORE);
+    let end = (start + SNIPPET_MAX_WORDS).min(words.len());
+
+    let mut snippet = words[start..end].join(" ");
+    if start > 0 {
+        snippet.insert_str(0, "… ");
+    }
+    if end < words.len() {
+        snippet.push_str(" …");
+    }
+
+    snippet
+}
+
+/// A [`SearchIndex`] that keeps everything in memory.
+#[derive(Clone, Default)]
+pub struct MemorySearchIndex {
+    events: Arc<RwLock<BTreeMap<OwnedEventId, IndexedEvent>>>,
+    undecrypted: Arc<RwLock<BTreeMap<OwnedEventId, UndecryptedEvent>>>,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for MemorySearchIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemorySearchIndex").finish_non_exhaustive()
+    }
+}
+
+impl MemorySearchIndex {
+    /// Create a new empty `MemorySearchIndex`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl SearchIndex for MemorySearchIndex {
+    async fn index_events(&self, events: Vec<IndexedEvent>) -> Result<(), StoreError> {
+        let mut indexed = self.events.write().unwrap();
+        let mut undecrypted = self.undecrypted.write().unwrap();
+
+        for event in events {
+            undecrypted.remove(&event.event_id);
+            indexed.insert(event.event_id.clone(), event);
+        }
+
+        Ok(())
+    }
+
+    async fn remove_events(&self, event_ids: Vec<OwnedEventId>) -> Result<(), StoreError> {
+        let mut indexed = self.events.write().unwrap();
+        let mut undecrypted = self.undecrypted.write().unwrap();
+
+        for event_id in event_ids {
+            indexed.remove(&event_id);
+            undecrypted.remove(&event_id);
+        }
+
+        Ok(())
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        filter: &LocalSearchFilter,
+    ) -> Result<Vec<LocalSearchResult>, StoreError> {
+        let query_tokens = tokenize(query);
+        if query_tokens.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut matches: Vec<_> = self
+            .events
+            .read()
+            .unwrap()
+            .values()
+            .filter(|event| {
+                let tokens = tokenize(&event.body);
+                filter.matches(event) && query_tokens.iter().all(|token| tokens.contains(token))
+            })
+            .cloned()
+            .collect();
+
+        matches.sort_by(|a, b| b.origin_server_ts.cmp(&a.origin_server_ts));
+
+        Ok(matches
+            .into_iter()
+            .take(filter.limit)
+            .map(|event| LocalSearchResult::new(event, &query_tokens))
+            .collect())
+    }
+
+    async fn get_event(&self, event_id: &EventId) -> Result<Option<IndexedEvent>, StoreError> {
+        Ok(self.events.read().unwrap().get(event_id).cloned())
+    }
+
+    async fn save_undecrypted_events(
+        &self,
+        events: Vec<UndecryptedEvent>,
+    ) -> Result<(), StoreError> {
+        let mut undecrypted = self.undecrypted.write().unwrap();
+
+        for event in events {
+            undecrypted.insert(event.event_id.clone(), event);
+        }
+
+        Ok(())
+    }
+
+    async fn undecrypted_events(&self) -> Result<Vec<UndecryptedEvent>, StoreError> {
+        Ok(self.undecrypted.read().unwrap().values().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk_test::async_test;
+    use ruma::{event_id, room_id, uint, user_id, MilliSecondsSinceUnixEpoch};
+
+    use super::{
+        snippet, tokenize, IndexedEvent, LocalSearchFilter, MemorySearchIndex, SearchIndex,
+    };
+
+    fn event(event_id: &str, room_id: &str, ts: u32, body: &str) -> IndexedEvent {
+        IndexedEvent {
+            room_id: room_id.try_into().unwrap(),
+            event_id: event_id.try_into().unwrap(),
+            sender: user_id!("@alice:localhost").to_owned(),
+            origin_server_ts: MilliSecondsSinceUnixEpoch(ts.into()),
+            body: body.to_owned(),
+        }
+    }
+
+    #[test]
+    fn tokenize_and_snippet() {
+        assert_eq!(tokenize("Hello, World! It's 5pm."), ["hello", "world", "it", "s", "5pm"]);
+
+        let body = "one two three four five six seven eight nine ten eleven twelve thirteen \
+                    fourteen fifteen sixteen seventeen eighteen nineteen twenty";
+        assert_eq!(
+            snippet(body, &["eight".to_owned()]),
+            "… four five six seven eight nine ten eleven twelve thirteen fourteen fifteen \
+             sixteen seventeen eighteen nineteen …"
+        );
+        assert_eq!(snippet("Short message", &["message".to_owned()]), "Short message");
+    }
+
+    #[async_test]
+    async fn memory_search_index() {
+        let index = MemorySearchIndex::new();
+        index
+            .index_events(vec![
+                event("$1", "!a:localhost", 1, "I love baking cupcakes"),
+                event("$2", "!b:localhost", 2, "Cupcakes are the best"),
+                event("$3", "!a:localhost", 3, "Something else"),
+            ])
+            .await
+            .unwrap();
+
+        let results = index.search("CUPCAKES", &LocalSearchFilter::new()).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].event_id, event_id!("$2"));
+        assert_eq!(results[1].event_id, event_id!("$1"));
+
+        let filter = LocalSearchFilter::new().rooms(vec![room_id!("!a:localhost").to_owned()]);
+        let results = index.search("cupcakes", &filter).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].snippet, "I love baking cupcakes");
+
+        assert!(index.search("cupcakes pie", &filter).await.unwrap().is_empty());
+
+        index.remove_events(vec![event_id!("$2").to_owned()]).await.unwrap();
+        let results = index.search("cupcakes", &LocalSearchFilter::new()).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].origin_server_ts, MilliSecondsSinceUnixEpoch(uint!(1)));
+    }
+}
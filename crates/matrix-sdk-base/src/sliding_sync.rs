@@ -13,7 +13,7 @@ use ruma::{
 };
 use tracing::{debug, info, instrument};
 
-use super::BaseClient;
+use super::{client::own_read_receipts, BaseClient};
 use crate::{
     deserialized_responses::AmbiguityChanges,
     error::Result,
@@ -52,7 +52,7 @@ impl BaseClient {
             return Ok(SyncResponse::default());
         };
 
-        let v4::Extensions { to_device, e2ee, account_data, receipts, .. } = extensions;
+        let v4::Extensions { to_device, e2ee, account_data, receipts, typing, .. } = extensions;
 
         let to_device_events = to_device.as_ref().map(|v4| v4.events.clone()).unwrap_or_default();
 
@@ -212,13 +212,28 @@ impl BaseClient {
                 let notification_count = room_data.unread_notifications.clone().into();
                 room_info.update_notification_count(notification_count);
 
+                let read_receipts = receipts
+                    .as_ref()
+                    .and_then(|receipts| receipts.rooms.get(room_id))
+                    .and_then(|raw| raw.deserialize().ok())
+                    .map(|receipt_edu| own_read_receipts(&receipt_edu.content, room.own_user_id()))
+                    .unwrap_or_default();
+                room_info.update_unread_counts(room.own_user_id(), &read_receipts, &timeline);
+
+                // Typing notifications come from an extension, pass them along as
+                // ephemeral events so they are handled like the ones from `/sync`.
+                let mut ephemeral = Ephemeral::default();
+                if let Some(event) = typing.as_ref().and_then(|typing| typing.rooms.get(room_id)) {
+                    ephemeral.events.push(event.clone().cast());
+                }
+
                 new_rooms.join.insert(
                     room_id.clone(),
                     JoinedRoom::new(
                         timeline,
                         v3::State::with_events(room_data.required_state.clone()),
                         room_account_data.unwrap_or_default(),
-                        Ephemeral::default(),
+                        ephemeral,
                         notification_count,
                     ),
                 );
@@ -7,7 +7,7 @@ use async_trait::async_trait;
 use matrix_sdk_test::test_json;
 use ruma::{
     api::client::media::get_content_thumbnail::v3::Method,
-    event_id,
+    device_id, event_id,
     events::{
         presence::PresenceEvent,
         receipt::{ReceiptThread, ReceiptType},
@@ -35,7 +35,7 @@ use crate::{
     deserialized_responses::MemberEvent,
     media::{MediaFormat, MediaRequest, MediaThumbnailSize},
     store::{Result, StateStoreExt},
-    RoomInfo, RoomState, StateChanges, StateStoreDataKey, StateStoreDataValue,
+    RoomInfo, RoomState, Session, StateChanges, StateStoreDataKey, StateStoreDataValue,
 };
 
 /// `StateStore` integration tests.
@@ -59,6 +59,8 @@ pub trait StateStoreIntegrationTests {
     async fn test_filter_saving(&self);
     /// Test sync token saving.
     async fn test_sync_token_saving(&self);
+    /// Test session saving.
+    async fn test_session_saving(&self);
     /// Test stripped room member saving.
     async fn test_stripped_member_saving(&self);
     /// Test room power levels saving.
@@ -449,6 +451,29 @@ impl StateStoreIntegrationTests for DynStateStore {
         assert_matches!(self.get_kv_data(StateStoreDataKey::SyncToken).await, Ok(None));
     }
 
+    async fn test_session_saving(&self) {
+        let session = Session {
+            access_token: "1234".to_owned(),
+            refresh_token: Some("5678".to_owned()),
+            user_id: user_id().to_owned(),
+            device_id: device_id!("DEVICEID").to_owned(),
+        };
+
+        assert_matches!(self.get_kv_data(StateStoreDataKey::Session).await, Ok(None));
+
+        self.set_kv_data(StateStoreDataKey::Session, StateStoreDataValue::Session(session.clone()))
+            .await
+            .unwrap();
+        let stored_session = assert_matches!(
+            self.get_kv_data(StateStoreDataKey::Session).await,
+            Ok(Some(StateStoreDataValue::Session(s))) => s
+        );
+        assert_eq!(stored_session, session);
+
+        self.remove_kv_data(StateStoreDataKey::Session).await.unwrap();
+        assert_matches!(self.get_kv_data(StateStoreDataKey::Session).await, Ok(None));
+    }
+
     async fn test_stripped_member_saving(&self) {
         let room_id = room_id!("!test_stripped_member_saving:localhost");
         let user_id = user_id();
@@ -937,6 +962,12 @@ macro_rules! statestore_integration_tests {
             store.test_sync_token_saving().await
         }
 
+        #[async_test]
+        async fn test_session_saving() {
+            let store = get_store().await.unwrap().into_state_store();
+            store.test_session_saving().await
+        }
+
         #[async_test]
         async fn test_stripped_member_saving() {
             let store = get_store().await.unwrap().into_state_store();
@@ -37,7 +37,7 @@ use tracing::{debug, warn};
 
 use super::{Result, RoomInfo, StateChanges, StateStore, StoreError};
 use crate::{
-    deserialized_responses::RawMemberEvent, media::MediaRequest, MinimalRoomMemberEvent,
+    deserialized_responses::RawMemberEvent, media::MediaRequest, MinimalRoomMemberEvent, Session,
     StateStoreDataKey, StateStoreDataValue,
 };
 
@@ -49,6 +49,7 @@ use crate::{
 pub struct MemoryStore {
     user_avatar_url: Arc<DashMap<String, String>>,
     sync_token: Arc<RwLock<Option<String>>>,
+    session: Arc<RwLock<Option<Session>>>,
     filters: Arc<DashMap<String, String>>,
     account_data: Arc<DashMap<GlobalAccountDataEventType, Raw<AnyGlobalAccountDataEvent>>>,
     members: Arc<DashMap<OwnedRoomId, DashSet<OwnedUserId>>>,
@@ -97,6 +98,7 @@ impl MemoryStore {
         Self {
             user_avatar_url: Default::default(),
             sync_token: Default::default(),
+            session: Default::default(),
             filters: Default::default(),
             account_data: Default::default(),
             members: Default::default(),
@@ -136,6 +138,9 @@ impl MemoryStore {
                 .user_avatar_url
                 .get(user_id.as_str())
                 .map(|u| StateStoreDataValue::UserAvatarUrl(u.value().clone()))),
+            StateStoreDataKey::Session => {
+                Ok(self.session.read().unwrap().clone().map(StateStoreDataValue::Session))
+            }
         }
     }
 
@@ -161,6 +166,10 @@ impl MemoryStore {
                     value.into_user_avatar_url().expect("Session data not a user avatar url"),
                 );
             }
+            StateStoreDataKey::Session => {
+                *self.session.write().unwrap() =
+                    Some(value.into_session().expect("Session data not a session"))
+            }
         }
 
         Ok(())
@@ -175,6 +184,7 @@ impl MemoryStore {
             StateStoreDataKey::UserAvatarUrl(user_id) => {
                 self.filters.remove(user_id.as_str());
             }
+            StateStoreDataKey::Session => *self.session.write().unwrap() = None,
         }
 
         Ok(())
@@ -216,6 +216,11 @@ impl Store {
         self.session_tokens.set(Some(tokens));
     }
 
+    /// Clear the current [`SessionTokens`], for example after logging out.
+    pub fn clear_session_tokens(&self) {
+        self.session_tokens.set(None);
+    }
+
     /// The current [`Session`] containing our user id, device ID, access
     /// token and optional refresh token.
     pub fn session(&self) -> Option<Session> {
@@ -236,7 +241,7 @@ impl Store {
             .and_then(|r| match r.state() {
                 RoomState::Joined => Some(r.clone()),
                 RoomState::Left => Some(r.clone()),
-                RoomState::Invited => self.get_stripped_room(room_id),
+                RoomState::Invited | RoomState::Knocked => self.get_stripped_room(room_id),
             })
             .or_else(|| self.get_stripped_room(room_id))
     }
@@ -254,6 +259,12 @@ impl Store {
     /// Lookup the stripped Room for the given RoomId, or create one, if it
     /// didn't exist yet in the store
     pub async fn get_or_create_stripped_room(&self, room_id: &RoomId) -> Room {
+        self.get_or_create_stripped_room_with_state(room_id, RoomState::Invited)
+    }
+
+    /// Lookup the stripped Room for the given RoomId, or create one with the
+    /// given state, if it didn't exist yet in the store.
+    fn get_or_create_stripped_room_with_state(&self, room_id: &RoomId, state: RoomState) -> Room {
         let user_id =
             &self.session_meta.get().expect("Creating room while not being logged in").user_id;
 
@@ -262,15 +273,16 @@ impl Store {
 
         self.stripped_rooms
             .entry(room_id.to_owned())
-            .or_insert_with(|| Room::new(user_id, self.inner.clone(), room_id, RoomState::Invited))
+            .or_insert_with(|| Room::new(user_id, self.inner.clone(), room_id, state))
             .clone()
     }
 
     /// Lookup the Room for the given RoomId, or create one, if it didn't exist
     /// yet in the store
     pub async fn get_or_create_room(&self, room_id: &RoomId, room_type: RoomState) -> Room {
-        if room_type == RoomState::Invited {
-            return self.get_or_create_stripped_room(room_id).await;
+        // Invited and knocked rooms only have stripped state.
+        if matches!(room_type, RoomState::Invited | RoomState::Knocked) {
+            return self.get_or_create_stripped_room_with_state(room_id, room_type);
         }
 
         let user_id =
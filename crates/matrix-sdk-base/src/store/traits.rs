@@ -33,6 +33,7 @@ use ruma::{
 use super::{StateChanges, StoreError};
 use crate::{
     deserialized_responses::RawMemberEvent, media::MediaRequest, MinimalRoomMemberEvent, RoomInfo,
+    Session,
 };
 
 /// An abstract state store trait that can be used to implement different stores
@@ -648,6 +649,9 @@ pub enum StateStoreDataValue {
 
     /// The user avatar url
     UserAvatarUrl(String),
+
+    /// The session of the client.
+    Session(Session),
 }
 
 impl StateStoreDataValue {
@@ -674,6 +678,14 @@ impl StateStoreDataValue {
             _ => None,
         }
     }
+
+    /// Get this value if it is a session.
+    pub fn into_session(self) -> Option<Session> {
+        match self {
+            Self::Session(session) => Some(session),
+            _ => None,
+        }
+    }
 }
 
 /// A key for key-value data.
@@ -687,6 +699,9 @@ pub enum StateStoreDataKey<'a> {
 
     /// Avatar URL
     UserAvatarUrl(&'a UserId),
+
+    /// The session of the client.
+    Session,
 }
 
 impl StateStoreDataKey<'_> {
@@ -697,4 +712,6 @@ impl StateStoreDataKey<'_> {
     /// Key prefix to use for the [`UserAvatarUrl`][Self::UserAvatarUrl]
     /// variant.
     pub const USER_AVATAR_URL: &str = "user_avatar_url";
+    /// Key to use for the [`Session`][Self::Session] variant.
+    pub const SESSION: &str = "session";
 }
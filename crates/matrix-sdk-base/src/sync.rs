@@ -23,7 +23,7 @@ use ruma::{
     api::client::{
         push::get_notifications::v3::Notification,
         sync::sync_events::{
-            v3::{Ephemeral, InvitedRoom, Presence, RoomAccountData, State},
+            v3::{Ephemeral, InvitedRoom, KnockedRoom, Presence, RoomAccountData, State},
             DeviceLists, UnreadNotificationsCount as RumaUnreadNotificationsCount,
         },
     },
@@ -87,6 +87,9 @@ pub struct Rooms {
     pub join: BTreeMap<OwnedRoomId, JoinedRoom>,
     /// The rooms that the user has been invited to.
     pub invite: BTreeMap<OwnedRoomId, InvitedRoom>,
+    /// The rooms that the user has knocked on.
+    #[serde(default)]
+    pub knock: BTreeMap<OwnedRoomId, KnockedRoom>,
 }
 
 /// Updates to joined rooms.
@@ -139,6 +142,23 @@ impl From<RumaUnreadNotificationsCount> for UnreadNotificationsCount {
     }
 }
 
+/// Counts of unread messages for a room, computed locally from the timeline.
+///
+/// Unlike [`UnreadNotificationsCount`], which is reported by the homeserver,
+/// these counts are calculated after decryption, so they are accurate in
+/// encrypted rooms as well.
+#[derive(Copy, Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct UnreadCounts {
+    /// The number of unread messages since the user's last read receipt.
+    pub unread_count: u64,
+    /// The number of unread messages for which the push rules say a
+    /// notification should be sent.
+    pub notification_count: u64,
+    /// The number of unread messages for which the push rules set the
+    /// highlight flag.
+    pub highlight_count: u64,
+}
+
 /// Updates to left rooms.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct LeftRoom {
@@ -1653,6 +1653,15 @@ impl OlmMachine {
         self.store.import_cross_signing_keys(export).await
     }
 
+    /// Export the secret with the given name.
+    ///
+    /// The secret is encoded as unpadded base64. This method returns `None` if
+    /// we don't have the secret, the recovery key of the key backup is only
+    /// available when the `backups_v1` feature is enabled.
+    pub async fn export_secret(&self, secret_name: &SecretName) -> Option<String> {
+        self.store.export_secret(secret_name).await
+    }
+
     async fn sign_with_master_key(
         &self,
         message: &str,
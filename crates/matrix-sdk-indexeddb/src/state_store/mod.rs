@@ -421,6 +421,7 @@ impl IndexeddbStateStore {
             StateStoreDataKey::UserAvatarUrl(user_id) => {
                 self.encode_key(keys::KV, (StateStoreDataKey::USER_AVATAR_URL, user_id))
             }
+            StateStoreDataKey::Session => self.encode_key(keys::KV, StateStoreDataKey::SESSION),
         }
     }
 }
@@ -461,22 +462,32 @@ impl_state_store! {
     ) -> Result<Option<StateStoreDataValue>> {
         let encoded_key = self.encode_kv_data_key(key);
 
-        let value = self
+        let Some(value) = self
             .inner
             .transaction_on_one_with_mode(keys::KV, IdbTransactionMode::Readonly)?
             .object_store(keys::KV)?
             .get(&encoded_key)?
             .await?
-            .map(|f| self.deserialize_event::<String>(f))
-            .transpose()?;
+        else {
+            return Ok(None);
+        };
 
         let value = match key {
-            StateStoreDataKey::SyncToken => value.map(StateStoreDataValue::SyncToken),
-            StateStoreDataKey::Filter(_) => value.map(StateStoreDataValue::Filter),
-            StateStoreDataKey::UserAvatarUrl(_) => value.map(StateStoreDataValue::UserAvatarUrl),
+            StateStoreDataKey::SyncToken => {
+                StateStoreDataValue::SyncToken(self.deserialize_event(value)?)
+            }
+            StateStoreDataKey::Filter(_) => {
+                StateStoreDataValue::Filter(self.deserialize_event(value)?)
+            }
+            StateStoreDataKey::UserAvatarUrl(_) => {
+                StateStoreDataValue::UserAvatarUrl(self.deserialize_event(value)?)
+            }
+            StateStoreDataKey::Session => {
+                StateStoreDataValue::Session(self.deserialize_event(value)?)
+            }
         };
 
-        Ok(value)
+        Ok(Some(value))
     }
 
     async fn set_kv_data(
@@ -487,14 +498,17 @@ impl_state_store! {
         let encoded_key = self.encode_kv_data_key(key);
 
         let value = match key {
-            StateStoreDataKey::SyncToken => {
-                value.into_sync_token().expect("Session data not a sync token")
-            }
+            StateStoreDataKey::SyncToken => self.serialize_event(
+                &value.into_sync_token().expect("Session data not a sync token"),
+            )?,
             StateStoreDataKey::Filter(_) => {
-                value.into_filter().expect("Session data not a filter")
+                self.serialize_event(&value.into_filter().expect("Session data not a filter"))?
             }
-            StateStoreDataKey::UserAvatarUrl(_) => {
-                value.into_user_avatar_url().expect("Session data not an user avatar url")
+            StateStoreDataKey::UserAvatarUrl(_) => self.serialize_event(
+                &value.into_user_avatar_url().expect("Session data not an user avatar url"),
+            )?,
+            StateStoreDataKey::Session => {
+                self.serialize_event(&value.into_session().expect("Session data not a session"))?
             }
         };
 
@@ -504,7 +518,7 @@ impl_state_store! {
 
         let obj = tx.object_store(keys::KV)?;
 
-        obj.put_key_val(&encoded_key, &self.serialize_event(&value)?)?;
+        obj.put_key_val(&encoded_key, &value)?;
 
         tx.await.into_result()?;
 
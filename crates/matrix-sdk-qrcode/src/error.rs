@@ -35,8 +35,8 @@ pub enum DecodingError {
     /// The QR code data uses an invalid or unsupported version.
     #[error("the QR code contains an invalid or unsupported version: {0}")]
     Version(u8),
-    /// The QR code data doesn't contain valid ed25519 keys.
-    #[error("the QR code contains invalid ed25519 keys: {0}")]
+    /// The QR code data doesn't contain valid ed25519 or curve25519 keys.
+    #[error("the QR code contains invalid keys: {0}")]
     Keys(#[from] vodozemac::KeyError),
 }
 
@@ -50,4 +50,8 @@ pub enum EncodingError {
     /// Error encoding the given flow id, the flow id is too large.
     #[error("The verification flow id length can't be converted into a u16: {0}")]
     FlowId(#[from] std::num::TryFromIntError),
+    /// Error encoding the rendezvous URL of a login QR code, the URL is too
+    /// large.
+    #[error("The rendezvous URL length can't be converted into a u16: {0}")]
+    RendezvousUrl(std::num::TryFromIntError),
 }
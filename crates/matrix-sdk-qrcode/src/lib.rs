@@ -17,10 +17,12 @@
 #![warn(missing_debug_implementations, missing_docs)]
 
 mod error;
+mod login;
 mod types;
 mod utils;
 
 pub use error::{DecodingError, EncodingError};
+pub use login::LoginQrCodeData;
 pub use qrcode;
 pub use types::{
     QrVerificationData, SelfVerificationData, SelfVerificationNoMasterKey, VerificationData,
@@ -28,7 +30,9 @@ pub use types::{
 
 #[cfg(test)]
 mod tests {
-    use crate::{DecodingError, QrVerificationData};
+    use vodozemac::Curve25519PublicKey;
+
+    use crate::{DecodingError, LoginQrCodeData, QrVerificationData};
 
     #[test]
     fn decode_invalid_header() {
@@ -82,4 +86,38 @@ mod tests {
         let result = QrVerificationData::from_bytes(data);
         assert!(matches!(result, Err(DecodingError::Keys(_))))
     }
+
+    #[test]
+    fn login_data_roundtrip() {
+        let data = LoginQrCodeData::new(
+            Curve25519PublicKey::from_bytes([7u8; 32]),
+            "https://rendezvous.example.org/abcdef".to_owned(),
+        );
+
+        let bytes = data.to_bytes().unwrap();
+        assert!(bytes.starts_with(b"MATRIX\x02\x03"));
+        assert_eq!(LoginQrCodeData::from_bytes(&bytes).unwrap(), data);
+
+        data.to_qr_code().unwrap();
+    }
+
+    #[test]
+    fn decode_login_data_invalid_mode() {
+        let data = b"MATRIX\x02\x02\
+                   AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA\
+                   \x00\x04\
+                   http";
+        let result = LoginQrCodeData::from_bytes(data);
+        assert!(matches!(result, Err(DecodingError::Mode(2))))
+    }
+
+    #[test]
+    fn decode_login_data_missing_url() {
+        let data = b"MATRIX\x02\x03\
+                   AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA\
+                   \x00\x10\
+                   http";
+        let result = LoginQrCodeData::from_bytes(data);
+        assert!(matches!(result, Err(DecodingError::Read(_))))
+    }
 }
@@ -0,0 +1,161 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::{Cursor, Read};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use qrcode::{bits::Bits, types::QrError, EcLevel, QrCode, Version};
+use vodozemac::Curve25519PublicKey;
+
+use crate::{
+    error::{DecodingError, EncodingError},
+    utils::{HEADER, VERSION},
+};
+
+/// The data of a QR code used to log in a new device with an existing device,
+/// as defined in [MSC3906].
+///
+/// The QR code is displayed by the new device and scanned by a device that is
+/// already logged in. It contains the ephemeral public key of the new device
+/// and the URL of the rendezvous session both devices use to communicate.
+///
+/// [MSC3906]: https://github.com/matrix-org/matrix-spec-proposals/pull/3906
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoginQrCodeData {
+    /// The ephemeral Curve25519 public key of the device displaying the QR
+    /// code.
+    pub public_key: Curve25519PublicKey,
+    /// The URL of the rendezvous session.
+    pub rendezvous_url: String,
+}
+
+impl TryFrom<&[u8]> for LoginQrCodeData {
+    type Error = DecodingError;
+
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        Self::from_bytes(value)
+    }
+}
+
+impl TryFrom<Vec<u8>> for LoginQrCodeData {
+    type Error = DecodingError;
+
+    fn try_from(value: Vec<u8>) -> Result<Self, Self::Error> {
+        Self::from_bytes(value)
+    }
+}
+
+impl LoginQrCodeData {
+    /// The mode of login QR codes.
+    pub const QR_MODE: u8 = 0x03;
+
+    /// Create a new `LoginQrCodeData`.
+    ///
+    /// # Arguments
+    ///
+    /// * `public_key` - The ephemeral public key of the device displaying the
+    ///   QR code.
+    ///
+    /// * `rendezvous_url` - The URL of the rendezvous session.
+    pub fn new(public_key: Curve25519PublicKey, rendezvous_url: String) -> Self {
+        Self { public_key, rendezvous_url }
+    }
+
+    /// Parse the decoded payload of a login QR code in byte slice form.
+    ///
+    /// The byte slice consists of the following parts:
+    ///
+    /// * the ASCII string MATRIX
+    /// * one byte indicating the QR code version (must be 0x02)
+    /// * one byte indicating the QR code mode (must be 0x03)
+    /// * the Curve25519 public key, as 32 bytes
+    /// * the rendezvous URL, encoded as:
+    ///     * two bytes in network byte order (big-endian) indicating the length
+    ///       in bytes of the URL as a UTF-8 string
+    ///     * the URL as a UTF-8 string
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The raw bytes of a decoded QR code.
+    pub fn from_bytes(bytes: impl AsRef<[u8]>) -> Result<Self, DecodingError> {
+        let mut decoded = Cursor::new(bytes);
+
+        let mut header = [0u8; 6];
+        let mut public_key = [0u8; 32];
+
+        decoded.read_exact(&mut header)?;
+        let version = decoded.read_u8()?;
+        let mode = decoded.read_u8()?;
+
+        if header != HEADER {
+            return Err(DecodingError::Header);
+        } else if version != VERSION {
+            return Err(DecodingError::Version(version));
+        } else if mode != Self::QR_MODE {
+            return Err(DecodingError::Mode(mode));
+        }
+
+        decoded.read_exact(&mut public_key)?;
+
+        let url_len = decoded.read_u16::<BigEndian>()?;
+        let mut rendezvous_url = vec![0; url_len.into()];
+        decoded.read_exact(&mut rendezvous_url)?;
+
+        let public_key = Curve25519PublicKey::from_slice(&public_key)?;
+        let rendezvous_url = String::from_utf8(rendezvous_url)?;
+
+        Ok(Self { public_key, rendezvous_url })
+    }
+
+    /// Encode the `LoginQrCodeData` into a vector of bytes that can be encoded
+    /// as a QR code.
+    ///
+    /// The encoding fails if the rendezvous URL is too long.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, EncodingError> {
+        let url_len: u16 =
+            self.rendezvous_url.len().try_into().map_err(EncodingError::RendezvousUrl)?;
+
+        let data = [
+            HEADER,
+            &[VERSION],
+            &[Self::QR_MODE],
+            self.public_key.as_bytes().as_slice(),
+            url_len.to_be_bytes().as_ref(),
+            self.rendezvous_url.as_bytes(),
+        ]
+        .concat();
+
+        Ok(data)
+    }
+
+    /// Encode the `LoginQrCodeData` into a `QrCode`.
+    ///
+    /// The encoding fails if the data doesn't fit into a QR code.
+    pub fn to_qr_code(&self) -> Result<QrCode, EncodingError> {
+        let data = self.to_bytes()?;
+
+        // Like for the verification QR codes, push the bytes as a single data
+        // segment, but use the smallest version the data fits into since the
+        // length of the rendezvous URL is not known in advance.
+        for version in 7..=40 {
+            let mut bits = Bits::new(Version::Normal(version));
+
+            if bits.push_byte_data(&data).is_ok() && bits.push_terminator(EcLevel::L).is_ok() {
+                return Ok(QrCode::with_bits(bits, EcLevel::L)?);
+            }
+        }
+
+        Err(QrError::DataTooLong.into())
+    }
+}
@@ -0,0 +1,65 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![doc = include_str!("../README.md")]
+#![warn(missing_debug_implementations, missing_docs)]
+
+use serde_json::Value as JsonValue;
+
+/// The keys of JSON objects, query strings and URL-encoded forms whose values
+/// are secrets.
+pub const SECRET_KEYS: &[&str] = &[
+    "access_token",
+    "client_secret",
+    "code",
+    "code_verifier",
+    "id_token",
+    "login_token",
+    "new_password",
+    "password",
+    "refresh_token",
+    "token",
+];
+
+/// Replace the values of the secret keys in the given JSON value, at any
+/// depth, with `replacement`.
+pub fn redact_json(value: &mut JsonValue, replacement: &str) {
+    match value {
+        JsonValue::Object(object) => {
+            for (key, value) in object {
+                if SECRET_KEYS.contains(&key.as_str()) {
+                    *value = replacement.into();
+                } else {
+                    redact_json(value, replacement);
+                }
+            }
+        }
+        JsonValue::Array(array) => {
+            array.iter_mut().for_each(|value| redact_json(value, replacement));
+        }
+        _ => {}
+    }
+}
+
+/// Get the given URL-encoded form, or query string, with the values of the
+/// secret keys replaced with `replacement`.
+pub fn redact_form(form: &str, replacement: &str) -> String {
+    form.split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((key, _)) if SECRET_KEYS.contains(&key) => format!("{key}={replacement}"),
+            _ => pair.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
@@ -412,22 +412,35 @@ impl SledStateStore {
             StateStoreDataKey::UserAvatarUrl(user_id) => {
                 self.encode_key(keys::SESSION, (StateStoreDataKey::USER_AVATAR_URL, user_id))
             }
+            StateStoreDataKey::Session => {
+                self.encode_key(keys::SESSION, StateStoreDataKey::SESSION)
+            }
         }
     }
 
     async fn get_kv_data(&self, key: StateStoreDataKey<'_>) -> Result<Option<StateStoreDataValue>> {
         let encoded_key = self.encode_kv_data_key(key);
 
-        let value =
-            self.kv.get(encoded_key)?.map(|e| self.deserialize_value::<String>(&e)).transpose()?;
+        let Some(value) = self.kv.get(encoded_key)? else {
+            return Ok(None);
+        };
 
         let value = match key {
-            StateStoreDataKey::SyncToken => value.map(StateStoreDataValue::SyncToken),
-            StateStoreDataKey::Filter(_) => value.map(StateStoreDataValue::Filter),
-            StateStoreDataKey::UserAvatarUrl(_) => value.map(StateStoreDataValue::UserAvatarUrl),
+            StateStoreDataKey::SyncToken => {
+                StateStoreDataValue::SyncToken(self.deserialize_value(&value)?)
+            }
+            StateStoreDataKey::Filter(_) => {
+                StateStoreDataValue::Filter(self.deserialize_value(&value)?)
+            }
+            StateStoreDataKey::UserAvatarUrl(_) => {
+                StateStoreDataValue::UserAvatarUrl(self.deserialize_value(&value)?)
+            }
+            StateStoreDataKey::Session => {
+                StateStoreDataValue::Session(self.deserialize_value(&value)?)
+            }
         };
 
-        Ok(value)
+        Ok(Some(value))
     }
 
     async fn set_kv_data(
@@ -438,16 +451,21 @@ impl SledStateStore {
         let encoded_key = self.encode_kv_data_key(key);
 
         let value = match key {
-            StateStoreDataKey::SyncToken => {
-                value.into_sync_token().expect("Session data not a sync token")
+            StateStoreDataKey::SyncToken => self.serialize_value(
+                &value.into_sync_token().expect("Session data not a sync token"),
+            )?,
+            StateStoreDataKey::Filter(_) => {
+                self.serialize_value(&value.into_filter().expect("Session data not a filter"))?
             }
-            StateStoreDataKey::Filter(_) => value.into_filter().expect("Session data not a filter"),
-            StateStoreDataKey::UserAvatarUrl(_) => {
-                value.into_user_avatar_url().expect("Session data not an user avatar url")
+            StateStoreDataKey::UserAvatarUrl(_) => self.serialize_value(
+                &value.into_user_avatar_url().expect("Session data not an user avatar url"),
+            )?,
+            StateStoreDataKey::Session => {
+                self.serialize_value(&value.into_session().expect("Session data not a session"))?
             }
         };
 
-        self.kv.insert(encoded_key, self.serialize_value(&value)?)?;
+        self.kv.insert(encoded_key, value)?;
 
         Ok(())
     }
@@ -0,0 +1,22 @@
+CREATE TABLE "kv" (
+    "key" TEXT PRIMARY KEY NOT NULL,
+    "value" BLOB NOT NULL
+);
+
+CREATE TABLE "event" (
+    "id" INTEGER PRIMARY KEY,
+    "event_id" BLOB UNIQUE NOT NULL,
+    "room_id" BLOB NOT NULL,
+    "sender" BLOB NOT NULL,
+    "origin_server_ts" INTEGER NOT NULL,
+    "data" BLOB NOT NULL
+);
+
+-- The words of the indexed events, hashed if the index is encrypted. The rowid
+-- is the id of the event in the "event" table.
+CREATE VIRTUAL TABLE "event_fts" USING fts5 ("tokens");
+
+CREATE TABLE "undecrypted_event" (
+    "event_id" BLOB PRIMARY KEY NOT NULL,
+    "data" BLOB NOT NULL
+);
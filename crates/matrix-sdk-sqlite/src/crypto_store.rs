@@ -39,6 +39,8 @@ use serde::{de::DeserializeOwned, Serialize};
 use tokio::{fs, sync::Mutex};
 use tracing::{debug, error, instrument, warn};
 
+#[cfg(feature = "search-index")]
+use crate::SqliteSearchIndex;
 use crate::{
     error::{Error, Result},
     get_or_create_store_cipher,
@@ -113,6 +115,19 @@ impl SqliteCryptoStore {
         })
     }
 
+    /// Open a sqlite-based search index at the given path, that encrypts the
+    /// indexed content with the same cipher as this store.
+    ///
+    /// If this store was opened without a passphrase, the content of the index
+    /// isn't encrypted either.
+    #[cfg(feature = "search-index")]
+    pub async fn open_search_index(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<SqliteSearchIndex, OpenStoreError> {
+        SqliteSearchIndex::open_with_store_cipher(path, self.store_cipher.clone()).await
+    }
+
     fn encode_value(&self, value: Vec<u8>) -> Result<Vec<u8>> {
         if let Some(key) = &self.store_cipher {
             let encrypted = key.encrypt_value_data(value)?;
@@ -13,6 +13,8 @@
 // limitations under the License.
 
 use deadpool_sqlite::{CreatePoolError, PoolError};
+#[cfg(feature = "search-index")]
+use matrix_sdk_base::StoreError;
 #[cfg(feature = "crypto-store")]
 use matrix_sdk_crypto::CryptoStoreError;
 use thiserror::Error;
@@ -96,4 +98,11 @@ impl From<Error> for CryptoStoreError {
     }
 }
 
+#[cfg(feature = "search-index")]
+impl From<Error> for StoreError {
+    fn from(e: Error) -> Self {
+        StoreError::backend(e)
+    }
+}
+
 pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
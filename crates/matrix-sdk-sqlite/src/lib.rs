@@ -19,11 +19,15 @@ use matrix_sdk_store_encryption::StoreCipher;
 #[cfg(feature = "crypto-store")]
 mod crypto_store;
 mod error;
+#[cfg(feature = "search-index")]
+mod search_index;
 mod utils;
 
 #[cfg(feature = "crypto-store")]
 pub use self::crypto_store::SqliteCryptoStore;
 pub use self::error::OpenStoreError;
+#[cfg(feature = "search-index")]
+pub use self::search_index::SqliteSearchIndex;
 use self::utils::SqliteObjectStoreExt;
 
 async fn get_or_create_store_cipher(
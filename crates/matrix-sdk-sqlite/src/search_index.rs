@@ -0,0 +1,512 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    borrow::Cow,
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use deadpool_sqlite::{Object as SqliteConn, Pool as SqlitePool, Runtime};
+use matrix_sdk_base::{
+    search_index::{
+        tokenize, IndexedEvent, LocalSearchFilter, LocalSearchResult, SearchIndex, UndecryptedEvent,
+    },
+    StoreError,
+};
+use matrix_sdk_store_encryption::StoreCipher;
+use ruma::{EventId, OwnedEventId};
+use rusqlite::{params_from_iter, types::Value, OptionalExtension};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::fs;
+use tracing::{debug, error};
+
+use crate::{
+    error::{Error, Result},
+    utils::{Key, SqliteObjectExt, SqliteObjectStoreExt as _},
+    OpenStoreError,
+};
+
+/// A sqlite based local search index, using the [FTS5] extension.
+///
+/// If the index is opened with `SqliteCryptoStore::open_search_index()` and
+/// the crypto store is encrypted, the content of the events is encrypted with
+/// the [`StoreCipher`] of the crypto store, and the words are hashed before
+/// being added to the full-text index. Queries are hashed the same way, so
+/// searching still works for whole words, but not for prefixes.
+///
+/// [FTS5]: https://www.sqlite.org/fts5.html
+#[derive(Clone)]
+pub struct SqliteSearchIndex {
+    store_cipher: Option<Arc<StoreCipher>>,
+    path: Option<PathBuf>,
+    pool: SqlitePool,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for SqliteSearchIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(path) = &self.path {
+            f.debug_struct("SqliteSearchIndex").field("path", &path).finish()
+        } else {
+            f.debug_struct("SqliteSearchIndex").field("path", &"memory store").finish()
+        }
+    }
+}
+
+impl SqliteSearchIndex {
+    /// Open the sqlite-based search index at the given path, without
+    /// encrypting the indexed content.
+    pub async fn open(path: impl AsRef<Path>) -> Result<Self, OpenStoreError> {
+        Self::open_with_store_cipher(path, None).await
+    }
+
+    /// Open the sqlite-based search index at the given path, encrypting the
+    /// indexed content with the given cipher if there is one.
+    pub(crate) async fn open_with_store_cipher(
+        path: impl AsRef<Path>,
+        store_cipher: Option<Arc<StoreCipher>>,
+    ) -> Result<Self, OpenStoreError> {
+        let path = path.as_ref();
+        fs::create_dir_all(path).await.map_err(OpenStoreError::CreateDir)?;
+        let cfg = deadpool_sqlite::Config::new(path.join("matrix-sdk-search.sqlite3"));
+        let pool = cfg.create_pool(Runtime::Tokio1)?;
+
+        let mut this = Self::open_with_pool(pool, store_cipher).await?;
+        this.path = Some(path.to_owned());
+        Ok(this)
+    }
+
+    async fn open_with_pool(
+        pool: SqlitePool,
+        store_cipher: Option<Arc<StoreCipher>>,
+    ) -> Result<Self, OpenStoreError> {
+        let conn = pool.get().await?;
+        run_migrations(&conn).await.map_err(OpenStoreError::Migration)?;
+
+        Ok(Self { store_cipher, path: None, pool })
+    }
+
+    fn encode_key(&self, table_name: &str, key: impl AsRef<[u8]>) -> Key {
+        let bytes = key.as_ref();
+        if let Some(store_cipher) = &self.store_cipher {
+            Key::Hashed(store_cipher.hash_key(table_name, bytes))
+        } else {
+            Key::Plain(bytes.to_owned())
+        }
+    }
+
+    fn serialize_json(&self, value: &impl Serialize) -> Result<Vec<u8>> {
+        let serialized = serde_json::to_vec(value)?;
+
+        if let Some(key) = &self.store_cipher {
+            let encrypted = key.encrypt_value_data(serialized)?;
+            Ok(rmp_serde::to_vec_named(&encrypted)?)
+        } else {
+            Ok(serialized)
+        }
+    }
+
+    fn deserialize_json<T: DeserializeOwned>(&self, data: &[u8]) -> Result<T> {
+        let decoded = if let Some(key) = &self.store_cipher {
+            let encrypted = rmp_serde::from_slice(data)?;
+            Cow::Owned(key.decrypt_value_data(encrypted)?)
+        } else {
+            Cow::Borrowed(data)
+        };
+
+        Ok(serde_json::from_slice(&decoded)?)
+    }
+
+    /// Convert a word to the token stored in the full-text index.
+    fn encode_token(&self, token: &str) -> String {
+        if let Some(store_cipher) = &self.store_cipher {
+            // Hex-encode the hash so the FTS tokenizer keeps it as a single
+            // word.
+            store_cipher
+                .hash_key("search_token", token.as_bytes())
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect()
+        } else {
+            token.to_owned()
+        }
+    }
+
+    async fn acquire(&self) -> Result<SqliteConn> {
+        Ok(self.pool.get().await?)
+    }
+
+    async fn index_events_inner(&self, events: Vec<IndexedEvent>) -> Result<()> {
+        let rows = events
+            .iter()
+            .map(|event| {
+                let event_id = event.event_id.as_bytes();
+                let undecrypted_id = self.encode_key("undecrypted_event", event_id);
+                let event_id = self.encode_key("event", event_id);
+                let room_id = self.encode_key("event_room", event.room_id.as_bytes());
+                let sender = self.encode_key("event_sender", event.sender.as_bytes());
+                let tokens: Vec<_> =
+                    tokenize(&event.body).iter().map(|token| self.encode_token(token)).collect();
+                let ts = i64::from(event.origin_server_ts.get());
+                let data = self.serialize_json(event)?;
+
+                Ok((event_id, undecrypted_id, room_id, sender, ts, tokens.join(" "), data))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.acquire()
+            .await?
+            .with_transaction(move |txn| {
+                for (event_id, undecrypted_id, room_id, sender, ts, tokens, data) in rows {
+                    let previous = txn
+                        .query_row("SELECT id FROM event WHERE event_id = ?", (&event_id,), |row| {
+                            row.get::<_, i64>(0)
+                        })
+                        .optional()?;
+                    if let Some(id) = previous {
+                        txn.execute("DELETE FROM event_fts WHERE rowid = ?", (id,))?;
+                        txn.execute("DELETE FROM event WHERE id = ?", (id,))?;
+                    }
+
+                    txn.execute(
+                        "INSERT INTO event (event_id, room_id, sender, origin_server_ts, data)
+                         VALUES (?1, ?2, ?3, ?4, ?5)",
+                        (&event_id, &room_id, &sender, ts, &data),
+                    )?;
+                    let id = txn.last_insert_rowid();
+                    txn.execute(
+                        "INSERT INTO event_fts (rowid, tokens) VALUES (?1, ?2)",
+                        (id, &tokens),
+                    )?;
+                    txn.execute(
+                        "DELETE FROM undecrypted_event WHERE event_id = ?",
+                        (&undecrypted_id,),
+                    )?;
+                }
+
+                Ok::<_, Error>(())
+            })
+            .await
+    }
+
+    async fn remove_events_inner(&self, event_ids: Vec<OwnedEventId>) -> Result<()> {
+        let keys: Vec<_> = event_ids
+            .iter()
+            .map(|event_id| {
+                let event_id = event_id.as_bytes();
+                (self.encode_key("event", event_id), self.encode_key("undecrypted_event", event_id))
+            })
+            .collect();
+
+        self.acquire()
+            .await?
+            .with_transaction(move |txn| {
+                for (event_id, undecrypted_id) in keys {
+                    let id = txn
+                        .query_row("SELECT id FROM event WHERE event_id = ?", (&event_id,), |row| {
+                            row.get::<_, i64>(0)
+                        })
+                        .optional()?;
+                    if let Some(id) = id {
+                        txn.execute("DELETE FROM event_fts WHERE rowid = ?", (id,))?;
+                        txn.execute("DELETE FROM event WHERE id = ?", (id,))?;
+                    }
+
+                    txn.execute(
+                        "DELETE FROM undecrypted_event WHERE event_id = ?",
+                        (&undecrypted_id,),
+                    )?;
+                }
+
+                Ok::<_, Error>(())
+            })
+            .await
+    }
+
+    async fn search_inner(
+        &self,
+        query: &str,
+        filter: &LocalSearchFilter,
+    ) -> Result<Vec<LocalSearchResult>> {
+        let query_tokens = tokenize(query);
+        if query_tokens.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Quote every token so they are matched literally, the implicit
+        // operator between them is AND.
+        let match_query = query_tokens
+            .iter()
+            .map(|token| format!("\"{}\"", self.encode_token(token).replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut sql = "SELECT event.data FROM event_fts
+             JOIN event ON event.id = event_fts.rowid
+             WHERE event_fts MATCH ?"
+            .to_owned();
+        let mut params = vec![Value::Text(match_query)];
+
+        if let Some(rooms) = &filter.rooms {
+            sql.push_str(&format!(" AND event.room_id IN ({})", repeat_vars(rooms.len())));
+            params.extend(rooms.iter().map(|room_id| {
+                Value::Blob(self.encode_key("event_room", room_id.as_bytes()).to_vec())
+            }));
+        }
+        if let Some(senders) = &filter.senders {
+            sql.push_str(&format!(" AND event.sender IN ({})", repeat_vars(senders.len())));
+            params.extend(senders.iter().map(|sender| {
+                Value::Blob(self.encode_key("event_sender", sender.as_bytes()).to_vec())
+            }));
+        }
+
+        sql.push_str(" ORDER BY event.origin_server_ts DESC LIMIT ?");
+        params.push(Value::Integer(filter.limit.try_into().unwrap_or(i64::MAX)));
+
+        let rows: Vec<Vec<u8>> = self
+            .acquire()
+            .await?
+            .with_transaction(move |txn| {
+                let mut stmt = txn.prepare(&sql)?;
+                let rows = stmt.query(params_from_iter(params))?.mapped(|row| row.get(0));
+                Ok::<_, Error>(rows.collect::<rusqlite::Result<_>>()?)
+            })
+            .await?;
+
+        rows.iter()
+            .map(|data| Ok(LocalSearchResult::new(self.deserialize_json(data)?, &query_tokens)))
+            .collect()
+    }
+
+    async fn get_event_inner(&self, event_id: &EventId) -> Result<Option<IndexedEvent>> {
+        let event_id = self.encode_key("event", event_id.as_bytes());
+        let data = self
+            .acquire()
+            .await?
+            .query_row("SELECT data FROM event WHERE event_id = ?", (event_id,), |row| {
+                row.get::<_, Vec<u8>>(0)
+            })
+            .await
+            .optional()?;
+
+        data.map(|data| self.deserialize_json(&data)).transpose()
+    }
+
+    async fn save_undecrypted_events_inner(&self, events: Vec<UndecryptedEvent>) -> Result<()> {
+        let rows = events
+            .iter()
+            .map(|event| {
+                let event_id = self.encode_key("undecrypted_event", event.event_id.as_bytes());
+                Ok((event_id, self.serialize_json(event)?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.acquire()
+            .await?
+            .with_transaction(move |txn| {
+                for (event_id, data) in rows {
+                    txn.execute(
+                        "INSERT INTO undecrypted_event (event_id, data) VALUES (?1, ?2)
+                         ON CONFLICT (event_id) DO UPDATE SET data = ?2",
+                        (&event_id, &data),
+                    )?;
+                }
+
+                Ok::<_, Error>(())
+            })
+            .await
+    }
+
+    async fn undecrypted_events_inner(&self) -> Result<Vec<UndecryptedEvent>> {
+        let rows: Vec<Vec<u8>> = self
+            .acquire()
+            .await?
+            .prepare("SELECT data FROM undecrypted_event", |mut stmt| {
+                stmt.query(())?.mapped(|row| row.get(0)).collect()
+            })
+            .await?;
+
+        rows.iter().map(|data| self.deserialize_json(data)).collect()
+    }
+}
+
+/// Get a list of `count` SQL variables, to use in an `IN` clause.
+fn repeat_vars(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
+
+const DATABASE_VERSION: u8 = 1;
+
+async fn run_migrations(conn: &SqliteConn) -> rusqlite::Result<()> {
+    let kv_exists = conn
+        .query_row(
+            "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = 'kv'",
+            (),
+            |row| row.get::<_, u32>(0),
+        )
+        .await?
+        > 0;
+
+    let version = if kv_exists {
+        match conn.get_kv("version").await?.as_deref() {
+            Some([v]) => *v,
+            Some(_) => {
+                error!("version database field has multiple bytes");
+                return Ok(());
+            }
+            None => {
+                error!("version database field is missing");
+                return Ok(());
+            }
+        }
+    } else {
+        0
+    };
+
+    if version == 0 {
+        debug!("Creating database");
+    } else if version < DATABASE_VERSION {
+        debug!(version, new_version = DATABASE_VERSION, "Upgrading database");
+    }
+
+    if version < 1 {
+        // First turn on WAL mode, this can't be done in the transaction, it fails with
+        // the error message: "cannot change into wal mode from within a transaction".
+        conn.execute_batch("PRAGMA journal_mode = wal;").await?;
+        conn.with_transaction(|txn| {
+            txn.execute_batch(include_str!("../migrations/search_index/001_init.sql"))
+        })
+        .await?;
+    }
+
+    conn.set_kv("version", vec![DATABASE_VERSION]).await?;
+
+    Ok(())
+}
+
+#[async_trait]
+impl SearchIndex for SqliteSearchIndex {
+    async fn index_events(&self, events: Vec<IndexedEvent>) -> Result<(), StoreError> {
+        Ok(self.index_events_inner(events).await?)
+    }
+
+    async fn remove_events(&self, event_ids: Vec<OwnedEventId>) -> Result<(), StoreError> {
+        Ok(self.remove_events_inner(event_ids).await?)
+    }
+
+    async fn search(
+        &self,
+        query: &str,
+        filter: &LocalSearchFilter,
+    ) -> Result<Vec<LocalSearchResult>, StoreError> {
+        Ok(self.search_inner(query, filter).await?)
+    }
+
+    async fn get_event(&self, event_id: &EventId) -> Result<Option<IndexedEvent>, StoreError> {
+        Ok(self.get_event_inner(event_id).await?)
+    }
+
+    async fn save_undecrypted_events(
+        &self,
+        events: Vec<UndecryptedEvent>,
+    ) -> Result<(), StoreError> {
+        Ok(self.save_undecrypted_events_inner(events).await?)
+    }
+
+    async fn undecrypted_events(&self) -> Result<Vec<UndecryptedEvent>, StoreError> {
+        Ok(self.undecrypted_events_inner().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use matrix_sdk_base::search_index::{IndexedEvent, LocalSearchFilter, SearchIndex};
+    use once_cell::sync::Lazy;
+    use ruma::{event_id, room_id, user_id, MilliSecondsSinceUnixEpoch};
+    use tempfile::{tempdir, TempDir};
+
+    use super::SqliteSearchIndex;
+
+    static TMP_DIR: Lazy<TempDir> = Lazy::new(|| tempdir().unwrap());
+
+    fn event(event_id: &str, ts: u32, body: &str) -> IndexedEvent {
+        IndexedEvent {
+            room_id: room_id!("!bakery:localhost").to_owned(),
+            event_id: event_id.try_into().unwrap(),
+            sender: user_id!("@alice:localhost").to_owned(),
+            origin_server_ts: MilliSecondsSinceUnixEpoch(ts.into()),
+            body: body.to_owned(),
+        }
+    }
+
+    async fn search_index(index: SqliteSearchIndex) {
+        index
+            .index_events(vec![
+                event("$1", 1, "I love baking cupcakes"),
+                event("$2", 2, "Cupcakes AND muffins are the best"),
+                event("$3", 3, "Something else"),
+            ])
+            .await
+            .unwrap();
+
+        let results = index.search("cupcakes", &LocalSearchFilter::new()).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].event_id, event_id!("$2"));
+        assert_eq!(results[0].snippet, "Cupcakes AND muffins are the best");
+        assert_eq!(results[1].event_id, event_id!("$1"));
+
+        let results = index.search("muffins and", &LocalSearchFilter::new()).await.unwrap();
+        assert_eq!(results.len(), 1);
+
+        let results = index.search("cupcakes", &LocalSearchFilter::new().limit(1)).await.unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].event_id, event_id!("$2"));
+
+        let filter = LocalSearchFilter::new().rooms(vec![room_id!("!bakery:localhost").to_owned()]);
+        assert_eq!(index.search("cupcakes", &filter).await.unwrap().len(), 2);
+        let filter = LocalSearchFilter::new().rooms(vec![room_id!("!other:localhost").to_owned()]);
+        assert!(index.search("cupcakes", &filter).await.unwrap().is_empty());
+        let filter = LocalSearchFilter::new().senders(vec![user_id!("@bob:localhost").to_owned()]);
+        assert!(index.search("cupcakes", &filter).await.unwrap().is_empty());
+
+        // Re-indexing an event replaces it.
+        index.index_events(vec![event("$1", 1, "I love baking pies")]).await.unwrap();
+        let results = index.search("cupcakes", &LocalSearchFilter::new()).await.unwrap();
+        assert_eq!(results.len(), 1);
+
+        index.remove_events(vec![event_id!("$2").to_owned()]).await.unwrap();
+        assert!(index.search("cupcakes", &LocalSearchFilter::new()).await.unwrap().is_empty());
+        assert_eq!(index.search("pies", &LocalSearchFilter::new()).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn unencrypted_search_index() {
+        search_index(SqliteSearchIndex::open(TMP_DIR.path().join("unencrypted")).await.unwrap())
+            .await;
+    }
+
+    #[cfg(feature = "crypto-store")]
+    #[tokio::test]
+    async fn encrypted_search_index() {
+        use crate::SqliteCryptoStore;
+
+        let store =
+            SqliteCryptoStore::open(TMP_DIR.path().join("crypto"), Some("secret")).await.unwrap();
+        search_index(store.open_search_index(TMP_DIR.path().join("encrypted")).await.unwrap())
+            .await;
+    }
+}
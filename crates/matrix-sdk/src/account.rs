@@ -27,6 +27,7 @@ use ruma::{
             request_3pid_management_token_via_email, request_3pid_management_token_via_msisdn,
         },
         config::set_global_account_data,
+        presence::set_presence,
         profile::{
             get_avatar_url, get_display_name, get_profile, set_avatar_url, set_display_name,
         },
@@ -40,6 +41,7 @@ use ruma::{
         AnyGlobalAccountDataEventContent, GlobalAccountDataEventContent,
         GlobalAccountDataEventType, StaticEventContent,
     },
+    presence::PresenceState,
     push::Ruleset,
     serde::Raw,
     thirdparty::Medium,
@@ -274,6 +276,47 @@ impl Account {
         Ok(self.client.send(request, Some(request_config)).await?)
     }
 
+    /// Set the presence of the account.
+    ///
+    /// Note that the presence is also set by every `/sync` request, according
+    /// to [`SyncSettings::set_presence()`], so this is mostly useful to change
+    /// the status message or when the client isn't syncing.
+    ///
+    /// # Arguments
+    ///
+    /// * `presence` - The new presence state.
+    ///
+    /// * `status_msg` - The status message to attach to the presence, if any.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use futures::executor::block_on;
+    /// # use matrix_sdk::{ruma::presence::PresenceState, Client};
+    /// # use url::Url;
+    /// # block_on(async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// client
+    ///     .account()
+    ///     .set_presence(PresenceState::Unavailable, Some("Out for lunch"))
+    ///     .await?;
+    /// # anyhow::Ok(()) });
+    /// ```
+    ///
+    /// [`SyncSettings::set_presence()`]: crate::config::SyncSettings::set_presence
+    pub async fn set_presence(
+        &self,
+        presence: PresenceState,
+        status_msg: Option<&str>,
+    ) -> Result<()> {
+        let user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
+        let request = assign!(set_presence::v3::Request::new(user_id.to_owned(), presence), {
+            status_msg: status_msg.map(ToOwned::to_owned),
+        });
+        self.client.send(request, None).await?;
+        Ok(())
+    }
+
     /// Change the password of the account.
     ///
     /// # Arguments
@@ -285,6 +328,7 @@ impl Account {
     /// always fail with an [`UiaaResponse`]. The response will contain
     /// information for the interactive auth and the same request needs to be
     /// made but this time with some `auth_data` provided.
+    /// [`Client::uiaa()`] can take care of this exchange.
     ///
     /// # Returns
     ///
@@ -339,6 +383,7 @@ impl Account {
     /// always fail with an [`UiaaResponse`]. The response will contain
     /// information for the interactive auth and the same request needs to be
     /// made but this time with some `auth_data` provided.
+    /// [`Client::uiaa()`] can take care of this exchange.
     ///
     /// # Example
     /// ```no_run
@@ -583,6 +628,7 @@ impl Account {
     /// always fail with an [`UiaaResponse`]. The response will contain
     /// information for the interactive auth and the same request needs to be
     /// made but this time with some `auth_data` provided.
+    /// [`Client::uiaa()`] can take care of this exchange.
     ///
     /// [3pid]: https://spec.matrix.org/v1.2/appendices/#3pid-types
     /// [uiaa]: https://spec.matrix.org/v1.2/client-server-api/#user-interactive-authentication-api
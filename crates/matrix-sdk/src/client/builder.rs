@@ -15,21 +15,27 @@
 
 use std::{fmt, sync::Arc};
 
-use matrix_sdk_base::{store::StoreConfig, BaseClient};
+use matrix_sdk_base::{
+    search_index::{DynSearchIndex, SearchIndex},
+    store::StoreConfig,
+    BaseClient,
+};
 use ruma::{
     api::{client::discovery::discover_homeserver, error::FromHttpResponseError, MatrixVersion},
+    events::room::tombstone::OriginalSyncRoomTombstoneEvent,
     OwnedServerName, ServerName,
 };
 use thiserror::Error;
 use tokio::sync::{broadcast, Mutex, OnceCell, RwLock};
-use tracing::{debug, field::debug, instrument, Span};
+use tracing::{debug, field::debug, instrument, warn, Span};
 use url::Url;
 
 use super::{Client, ClientInner};
 use crate::{
     config::RequestConfig,
     error::RumaApiError,
-    http_client::{HttpClient, HttpSend, HttpSettings},
+    http_client::{apply_layers, HttpClient, HttpLayer, HttpSend, HttpSettings},
+    room::Room,
     HttpError,
 };
 
@@ -76,12 +82,16 @@ use crate::{
 pub struct ClientBuilder {
     homeserver_cfg: Option<HomeserverConfig>,
     http_cfg: Option<HttpConfig>,
+    http_layers: Vec<Arc<dyn HttpLayer>>,
     store_config: BuilderStoreConfig,
     request_config: RequestConfig,
     respect_login_well_known: bool,
     appservice_mode: bool,
     server_versions: Option<Box<[MatrixVersion]>>,
     handle_refresh_tokens: bool,
+    persist_session: bool,
+    auto_join_room_upgrades: bool,
+    search_index: Option<Arc<DynSearchIndex>>,
 }
 
 impl ClientBuilder {
@@ -89,12 +99,16 @@ impl ClientBuilder {
         Self {
             homeserver_cfg: None,
             http_cfg: None,
+            http_layers: Vec::new(),
             store_config: BuilderStoreConfig::Custom(StoreConfig::default()),
             request_config: Default::default(),
             respect_login_well_known: true,
             appservice_mode: false,
             server_versions: None,
             handle_refresh_tokens: false,
+            persist_session: false,
+            auto_join_room_upgrades: false,
+            search_index: None,
         }
     }
 
@@ -241,6 +255,31 @@ impl ClientBuilder {
         self
     }
 
+    /// Add a layer wrapping the HTTP client, to add behaviour to it like
+    /// logging or collecting metrics.
+    ///
+    /// This can be called several times to add several layers. The first
+    /// layer that is added is the outermost one: it sees the requests first
+    /// and the responses last. The layers wrap the HTTP client set with
+    /// [`http_client()`][Self::http_client] too.
+    ///
+    /// Built-in layers are available in the [`http_layers`] module.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use matrix_sdk::{http_layers::MetricsLayer, Client};
+    ///
+    /// let metrics = MetricsLayer::new();
+    /// let client_builder = Client::builder().http_layer(metrics.clone());
+    /// ```
+    ///
+    /// [`http_layers`]: crate::http_layers
+    pub fn http_layer(mut self, layer: impl HttpLayer + 'static) -> Self {
+        self.http_layers.push(Arc::new(layer));
+        self
+    }
+
     /// Puts the client into application service mode
     ///
     /// This is low-level functionality. For an high-level API check the
@@ -308,6 +347,95 @@ impl ClientBuilder {
         self
     }
 
+    /// Persist the [`Session`] in the state store.
+    ///
+    /// By default, the `Client` doesn't store the session, which means that
+    /// applications need to persist it themselves, and watch
+    /// [`Client::session_tokens_stream()`] to save the tokens again when they
+    /// are refreshed.
+    ///
+    /// Enabling this setting means that the `Client` will save the session in
+    /// its state store, encrypted like the rest of the data if the store uses
+    /// a passphrase:
+    ///
+    /// * The session is saved after logging in and when it is restored.
+    ///
+    /// * The new tokens are saved every time [`Client::refresh_access_token()`]
+    ///   succeeds, before the `Client` starts using them.
+    ///
+    /// * The session is removed from the store after logging out.
+    ///
+    /// * If the session was obtained with [OpenID Connect](crate::oidc), the
+    ///   registration of the client is saved along with it.
+    ///
+    /// The session can then be restored with [`Client::restore_from_store()`].
+    ///
+    /// This only makes sense with a store that persists its data.
+    ///
+    /// [`Session`]: crate::Session
+    pub fn persist_session(mut self) -> Self {
+        self.persist_session = true;
+        self
+    }
+
+    /// Automatically join the successor of a joined room when it is upgraded.
+    ///
+    /// When an `m.room.tombstone` event is received in a joined room, the
+    /// `Client` joins the replacement room it points to, using the server of
+    /// the sender of the tombstone to route the join.
+    ///
+    /// The previous room is not left, so its history stays accessible.
+    pub fn auto_join_room_upgrades(mut self) -> Self {
+        self.auto_join_room_upgrades = true;
+        self
+    }
+
+    /// Set the local full-text search index to use.
+    ///
+    /// The `Client` adds the messages it receives with `/sync` and with
+    /// [`Common::messages()`] to the index, after decrypting them, and
+    /// removes them when they are redacted. The index can then be queried
+    /// with [`Client::search_local()`].
+    ///
+    /// # Arguments
+    ///
+    /// * `search_index` - The search index to use, for example a
+    ///   `SqliteSearchIndex` from the `matrix-sdk-sqlite` crate.
+    ///
+    /// [`Common::messages()`]: crate::room::Common::messages
+    pub fn search_index(mut self, search_index: impl SearchIndex + 'static) -> Self {
+        self.search_index = Some(Arc::new(search_index));
+        self
+    }
+
+    /// Create the HTTP client now, so that all the clients built from clones
+    /// of this builder share it, along with its connection pool.
+    #[cfg(feature = "sled")]
+    pub(crate) fn share_http_client(mut self) -> Result<Self, ClientBuildError> {
+        let http_client = self.make_http_client()?;
+        self.http_cfg = Some(HttpConfig::Custom(http_client));
+        // The layers are already applied to the shared client.
+        self.http_layers.clear();
+        Ok(self)
+    }
+
+    fn make_http_client(&self) -> Result<Arc<dyn HttpSend>, ClientBuildError> {
+        let client: Arc<dyn HttpSend> = match self.http_cfg.clone().unwrap_or_default() {
+            #[allow(unused_mut)]
+            HttpConfig::Settings(mut settings) => {
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    settings.timeout = self.request_config.timeout;
+                }
+
+                Arc::new(settings.make_client()?)
+            }
+            HttpConfig::Custom(c) => c,
+        };
+
+        Ok(apply_layers(client, &self.http_layers))
+    }
+
     /// Create a [`Client`] with the options set on this builder.
     ///
     /// # Errors
@@ -324,22 +452,11 @@ impl ClientBuilder {
     pub async fn build(self) -> Result<Client, ClientBuildError> {
         debug!("Starting to build the Client");
 
+        let inner_http_client = self.make_http_client()?;
+
         let homeserver_cfg = self.homeserver_cfg.ok_or(ClientBuildError::MissingHomeserver)?;
         Span::current().record("homeserver", debug(&homeserver_cfg));
 
-        let inner_http_client = match self.http_cfg.unwrap_or_default() {
-            #[allow(unused_mut)]
-            HttpConfig::Settings(mut settings) => {
-                #[cfg(not(target_arch = "wasm32"))]
-                {
-                    settings.timeout = self.request_config.timeout;
-                }
-
-                Arc::new(settings.make_client()?)
-            }
-            HttpConfig::Custom(c) => c,
-        };
-
         #[allow(clippy::infallible_destructuring_match)]
         let store_config = match self.store_config {
             #[cfg(feature = "sled")]
@@ -401,6 +518,7 @@ impl ClientBuilder {
         let sliding_sync_proxy = sliding_sync_proxy.map(RwLock::new);
 
         let (unknown_token_error_sender, _) = broadcast::channel(1);
+        let (presence_sender, _) = broadcast::channel(100);
 
         let inner = Arc::new(ClientInner {
             homeserver,
@@ -417,6 +535,9 @@ impl ClientBuilder {
             members_request_locks: Default::default(),
             encryption_state_request_locks: Default::default(),
             typing_notice_times: Default::default(),
+            typing_keystroke_times: Default::default(),
+            presence: Default::default(),
+            presence_sender,
             event_handlers: Default::default(),
             notification_handlers: Default::default(),
             sync_gap_broadcast_txs: Default::default(),
@@ -424,13 +545,39 @@ impl ClientBuilder {
             respect_login_well_known: self.respect_login_well_known,
             sync_beat: event_listener::Event::new(),
             handle_refresh_tokens: self.handle_refresh_tokens,
+            persist_session: self.persist_session,
             refresh_token_lock: Mutex::new(Ok(())),
             unknown_token_error_sender,
+            soft_logged_out: Default::default(),
+            is_guest: Default::default(),
+            search_index: self.search_index,
+            #[cfg(feature = "experimental-oidc")]
+            oidc: Default::default(),
         });
 
+        let client = Client { inner };
+
+        if self.auto_join_room_upgrades {
+            client.add_event_handler(join_room_successor);
+        }
+
         debug!("Done building the Client");
 
-        Ok(Client { inner })
+        Ok(client)
+    }
+}
+
+async fn join_room_successor(event: OriginalSyncRoomTombstoneEvent, room: Room, client: Client) {
+    let Room::Joined(room) = room else { return };
+    let successor_id = event.content.replacement_room;
+
+    if client.get_joined_room(&successor_id).is_some() {
+        return;
+    }
+
+    let via = [event.sender.server_name().to_owned()];
+    if let Err(e) = client.join_room_by_id_or_alias((&*successor_id).into(), &via).await {
+        warn!(room_id = ?room.room_id(), ?successor_id, "Failed to join the successor room: {e}");
     }
 }
 
@@ -23,11 +23,12 @@ use ruma::{
     api::client::{session::login, uiaa::UserIdentifier},
     assign,
     serde::JsonObject,
+    OwnedDeviceId,
 };
 use tracing::{info, instrument};
 
 use super::Client;
-use crate::{config::RequestConfig, Result};
+use crate::{config::RequestConfig, Error, Result};
 
 /// The login method.
 ///
@@ -120,6 +121,11 @@ impl LoginBuilder {
     /// If not set, the homeserver will create one. Can be an existing device ID
     /// from a previous login call. Note that this should be done only if the
     /// client also holds the corresponding encryption keys.
+    ///
+    /// If the client is already logged in, for example to recover from a soft
+    /// logout, the device ID of its session is used by default, and a
+    /// different device ID is refused with [`Error::SessionMismatch`] before
+    /// the request is sent.
     pub fn device_id(mut self, value: &str) -> Self {
         self.device_id = Some(value.to_owned());
         self
@@ -167,8 +173,18 @@ impl LoginBuilder {
         let homeserver = self.client.homeserver().await;
         info!(homeserver = homeserver.as_str(), identifier = ?self.login_method.id(), "Logging in");
 
+        // Keep the same device when logging in again, to recover from a soft
+        // logout.
+        let current_device_id = self.client.device_id().map(ToOwned::to_owned);
+        let device_id = match (self.device_id.map(OwnedDeviceId::from), current_device_id) {
+            (Some(device_id), Some(current)) if device_id != current => {
+                return Err(Error::SessionMismatch);
+            }
+            (device_id, current) => device_id.or(current),
+        };
+
         let request = assign!(login::v3::Request::new(self.login_method.into_login_info()), {
-            device_id: self.device_id.map(Into::into),
+            device_id,
             initial_device_display_name: self.initial_device_display_name,
             refresh_token: self.request_refresh_token,
         });
@@ -232,6 +248,10 @@ where
     /// If not set, the homeserver will create one. Can be an existing device ID
     /// from a previous login call. Note that this should be done only if the
     /// client also holds the corresponding encryption keys.
+    ///
+    /// If the client is already logged in, for example to recover from a soft
+    /// logout, the device ID of its session is used by default, and a
+    /// different device ID is refused with [`Error::SessionMismatch`].
     pub fn device_id(mut self, value: &str) -> Self {
         self.device_id = Some(value.to_owned());
         self
@@ -19,7 +19,7 @@ use std::{
     fmt::{self, Debug},
     future::Future,
     pin::Pin,
-    sync::{Arc, Mutex as StdMutex},
+    sync::{atomic::AtomicBool, Arc, Mutex as StdMutex, RwLock as StdRwLock},
 };
 
 use dashmap::DashMap;
@@ -27,8 +27,8 @@ use eyeball::{unique::Observable, Subscriber};
 use futures_core::Stream;
 use futures_util::StreamExt;
 use matrix_sdk_base::{
-    store::DynStateStore, BaseClient, RoomState, SendOutsideWasm, Session, SessionMeta,
-    SessionTokens, SyncOutsideWasm,
+    search_index::DynSearchIndex, store::DynStateStore, BaseClient, RoomState, SendOutsideWasm,
+    Session, SessionMeta, SessionTokens, StateStoreDataKey, StateStoreDataValue, SyncOutsideWasm,
 };
 use matrix_sdk_common::instant::Instant;
 #[cfg(feature = "appservice")]
@@ -46,6 +46,7 @@ use ruma::{
             },
             error::ErrorKind,
             filter::{create_filter::v3::Request as FilterUploadRequest, FilterDefinition},
+            knock::knock_room,
             membership::{join_room_by_id, join_room_by_id_or_alias},
             profile::get_profile,
             push::{get_notifications::v3::Notification, set_pusher, Pusher},
@@ -62,8 +63,8 @@ use ruma::{
     },
     assign,
     serde::JsonObject,
-    DeviceId, OwnedDeviceId, OwnedRoomId, OwnedServerName, RoomAliasId, RoomId, RoomOrAliasId,
-    ServerName, UInt, UserId,
+    DeviceId, OwnedDeviceId, OwnedRoomId, OwnedServerName, OwnedUserId, RoomAliasId, RoomId,
+    RoomOrAliasId, ServerName, UInt, UserId,
 };
 use serde::de::DeserializeOwned;
 use tokio::sync::{broadcast, Mutex, OnceCell, RwLock, RwLockReadGuard};
@@ -79,9 +80,10 @@ use crate::{
         EventHandler, EventHandlerDropGuard, EventHandlerHandle, EventHandlerStore, SyncEvent,
     },
     http_client::HttpClient,
+    presence::UserPresence,
     room,
     sync::SyncResponse,
-    Account, Error, Media, RefreshTokenError, Result, RumaApiError,
+    Account, Error, Media, NotificationSettings, RefreshTokenError, Result, RumaApiError,
 };
 
 mod builder;
@@ -144,7 +146,7 @@ pub(crate) struct ClientInner {
     #[cfg(feature = "experimental-sliding-sync")]
     sliding_sync_proxy: Option<RwLock<Url>>,
     /// The underlying HTTP client.
-    http_client: HttpClient,
+    pub(crate) http_client: HttpClient,
     /// User session data.
     base_client: BaseClient,
     /// The Matrix versions the server supports (well-known ones only)
@@ -160,6 +162,15 @@ pub(crate) struct ClientInner {
     /// Locks for requests on the encryption state of rooms.
     pub(crate) encryption_state_request_locks: DashMap<OwnedRoomId, Arc<Mutex<()>>>,
     pub(crate) typing_notice_times: DashMap<OwnedRoomId, Instant>,
+    /// The time of the last keystroke in rooms where the user is typing, see
+    /// [`room::Joined::notify_typing()`].
+    pub(crate) typing_keystroke_times: DashMap<OwnedRoomId, Instant>,
+    /// The latest presence of users received with `/sync`. See
+    /// [`Client::presence()`].
+    pub(crate) presence: DashMap<OwnedUserId, UserPresence>,
+    /// Publisher of the presence updates received with `/sync`. See
+    /// [`Client::subscribe_to_presence()`].
+    pub(crate) presence_sender: broadcast::Sender<UserPresence>,
     /// Event handlers. See `add_event_handler`.
     pub(crate) event_handlers: EventHandlerStore,
     /// Notification handlers. See `register_notification_handler`.
@@ -175,6 +186,9 @@ pub(crate) struct ClientInner {
     /// Whether to try to refresh the access token automatically when an
     /// `M_UNKNOWN_TOKEN` error is encountered.
     handle_refresh_tokens: bool,
+    /// Whether to save the session in the state store. See
+    /// [`ClientBuilder::persist_session()`].
+    persist_session: bool,
     /// Lock making sure we're only doing one token refresh at a time.
     refresh_token_lock: Mutex<Result<(), RefreshTokenError>>,
     /// An event that can be listened on to wait for a successful sync. The
@@ -186,6 +200,17 @@ pub(crate) struct ClientInner {
     /// Client API UnknownToken error publisher. Allows the subscriber logout
     /// the user when any request fails because of an invalid access token
     pub(crate) unknown_token_error_sender: broadcast::Sender<UnknownToken>,
+    /// Whether the session was soft logged out. See
+    /// [`Client::subscribe_to_soft_logout()`].
+    pub(crate) soft_logged_out: StdRwLock<Observable<bool>>,
+    /// Whether the session is a guest session. See [`Client::is_guest()`].
+    pub(crate) is_guest: AtomicBool,
+    /// The local full-text search index, if any. See
+    /// [`ClientBuilder::search_index()`].
+    pub(crate) search_index: Option<Arc<DynSearchIndex>>,
+    /// The state of the OpenID Connect login. See [`Client::oidc()`].
+    #[cfg(feature = "experimental-oidc")]
+    pub(crate) oidc: crate::oidc::OidcContext,
 }
 
 #[cfg(not(tarpaulin_include))]
@@ -229,7 +254,7 @@ impl Client {
     /// # Arguments
     ///
     /// * `homeserver_url` - The new URL to use.
-    async fn set_homeserver(&self, homeserver_url: Url) {
+    pub(crate) async fn set_homeserver(&self, homeserver_url: Url) {
         let mut homeserver = self.inner.homeserver.write().await;
         *homeserver = homeserver_url;
     }
@@ -557,6 +582,17 @@ impl Client {
         Media::new(self.clone())
     }
 
+    /// Get the notification settings of the current owner of the client.
+    ///
+    /// The push rules are loaded from the store and kept up to date for as
+    /// long as the returned [`NotificationSettings`], or one of its clones, is
+    /// alive.
+    ///
+    /// Panics if called when the client is not logged in.
+    pub async fn notification_settings(&self) -> Result<NotificationSettings> {
+        NotificationSettings::new(self.clone()).await
+    }
+
     /// Register a handler for a specific event type.
     ///
     /// The handler is a function or closure with one or more arguments. The
@@ -890,6 +926,15 @@ impl Client {
             .collect()
     }
 
+    /// Returns the knocked rooms this client knows about.
+    pub fn knocked_rooms(&self) -> Vec<room::Knocked> {
+        self.base_client()
+            .get_stripped_rooms()
+            .into_iter()
+            .filter_map(|room| room::Knocked::new(self, room))
+            .collect()
+    }
+
     /// Returns the left rooms this client knows about.
     pub fn left_rooms(&self) -> Vec<room::Left> {
         self.base_client()
@@ -899,6 +944,70 @@ impl Client {
             .collect()
     }
 
+    /// Returns the joined spaces this client knows about.
+    pub fn spaces(&self) -> Vec<room::Space> {
+        self.joined_rooms().into_iter().filter_map(room::Space::new).collect()
+    }
+
+    /// Get a joined space with the given room id.
+    ///
+    /// # Arguments
+    ///
+    /// `room_id` - The unique id of the space that should be fetched.
+    pub fn get_space(&self, room_id: &RoomId) -> Option<room::Space> {
+        self.get_joined_room(room_id).and_then(room::Space::new)
+    }
+
+    /// Compute the tree of the joined spaces from the local state.
+    ///
+    /// Only the joined rooms are part of the tree, which makes it suitable to
+    /// display the spaces of the user while offline. Use
+    /// [`Client::space_hierarchy()`] to browse all the rooms of a space.
+    pub async fn space_tree(&self) -> Result<room::SpaceTree> {
+        room::SpaceTree::build(self).await
+    }
+
+    /// Browse the hierarchy of the space with the given room id from the
+    /// homeserver.
+    ///
+    /// The user doesn't need to be a member of the space, as long as it is
+    /// accessible to them, for example if it is world-readable.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_id` - The unique id of the space to browse.
+    ///
+    /// * `options` - Options to filter the rooms that are returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use futures::executor::block_on;
+    /// # use matrix_sdk::{Client, room::HierarchyOptions};
+    /// # use ruma::{room_id, uint};
+    /// # use url::Url;
+    /// # block_on(async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// let options = HierarchyOptions::new().suggested_only().max_depth(uint!(2));
+    /// let mut hierarchy =
+    ///     client.space_hierarchy(room_id!("!space:localhost"), options);
+    ///
+    /// while let Some(rooms) = hierarchy.next_page().await? {
+    ///     for room in rooms {
+    ///         println!("{}: {:?}", room.room_id, room.name);
+    ///     }
+    /// }
+    /// # anyhow::Ok(()) });
+    /// ```
+    pub fn space_hierarchy(
+        &self,
+        room_id: &RoomId,
+        options: room::HierarchyOptions,
+    ) -> room::SpaceHierarchy {
+        room::SpaceHierarchy::new(self.clone(), room_id.to_owned(), options)
+    }
+
     /// Get a room with the given room id.
     ///
     /// # Arguments
@@ -928,6 +1037,15 @@ impl Client {
         self.base_client().get_room(room_id).and_then(|room| room::Invited::new(self, room))
     }
 
+    /// Get a knocked room with the given room id.
+    ///
+    /// # Arguments
+    ///
+    /// `room_id` - The unique id of the room that should be fetched.
+    pub fn get_knocked_room(&self, room_id: &RoomId) -> Option<room::Knocked> {
+        self.base_client().get_room(room_id).and_then(|room| room::Knocked::new(self, room))
+    }
+
     /// Get a left room with the given room id.
     ///
     /// # Arguments
@@ -951,6 +1069,32 @@ impl Client {
         self.send(request, None).await
     }
 
+    /// Get a preview of a room, usually before joining it.
+    ///
+    /// The information is taken from the local store if the room is known,
+    /// then from the [MSC3266] room summary endpoint, then from the state of
+    /// the room if it is world readable, and finally from the public room
+    /// directory.
+    ///
+    /// Returns [`Error::InsufficientData`] if none of these sources knows the
+    /// room.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_id_or_alias` - The ID or alias of the room.
+    ///
+    /// * `via` - Servers that are likely to know the room, used when the room
+    ///   is not known by the homeserver.
+    ///
+    /// [MSC3266]: https://github.com/matrix-org/matrix-spec-proposals/pull/3266
+    pub async fn room_preview(
+        &self,
+        room_id_or_alias: &RoomOrAliasId,
+        via: Vec<OwnedServerName>,
+    ) -> Result<room::RoomPreview> {
+        room::RoomPreview::load(self, room_id_or_alias, via).await
+    }
+
     /// Gets the homeserver’s supported login types.
     ///
     /// This should be the first step when trying to login so you can call the
@@ -1241,7 +1385,55 @@ impl Client {
             }
         }
 
-        self.inner.base_client.receive_login_response(response).await?;
+        if self.session_meta().is_some() {
+            if let Err(error) = self.resume_session(response.clone().into()) {
+                // The homeserver already created the new session, don't leave
+                // it behind.
+                self.logout_orphaned_session(&response.access_token).await;
+                return Err(error);
+            }
+        } else {
+            self.inner.base_client.receive_login_response(response).await?;
+        }
+
+        if let Some(session) = self.session() {
+            self.save_session(session).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Log out a session that was created by the homeserver but that this
+    /// `Client` can't use, with its access token.
+    pub(crate) async fn logout_orphaned_session(&self, access_token: &str) {
+        let result = async {
+            self.inner
+                .http_client
+                .send(
+                    logout::v3::Request::new(),
+                    None,
+                    self.homeserver().await.to_string(),
+                    Some(access_token),
+                    None,
+                    self.server_versions().await?,
+                )
+                .await
+        }
+        .await;
+
+        if let Err(error) = result {
+            error!("Failed to log out the orphaned session: {error}");
+        }
+    }
+
+    /// Save the given session in the state store, if the `Client` was built
+    /// with [`ClientBuilder::persist_session()`].
+    pub(crate) async fn save_session(&self, session: Session) -> Result<()> {
+        if self.inner.persist_session {
+            self.store()
+                .set_kv_data(StateStoreDataKey::Session, StateStoreDataValue::Session(session))
+                .await?;
+        }
 
         Ok(())
     }
@@ -1251,6 +1443,10 @@ impl Client {
     /// This can be used to restore the client to a logged in state, loading all
     /// the stored state and encryption keys.
     ///
+    /// If the client is already logged in, only the tokens of the session are
+    /// replaced, which can be used to recover from a soft logout. In that case
+    /// the session must be for the same user and device.
+    ///
     /// Alternatively, if the whole session isn't stored the [`login`] method
     /// can be used with a device ID.
     ///
@@ -1309,16 +1505,70 @@ impl Client {
     pub async fn restore_session(&self, session: Session) -> Result<()> {
         debug!("Restoring session");
 
-        let (meta, tokens) = session.into_parts();
+        if self.session_meta().is_some() {
+            self.resume_session(session.clone())?;
+        } else {
+            let (meta, tokens) = session.clone().into_parts();
+
+            self.base_client().set_session_tokens(tokens);
+            self.base_client().set_session_meta(meta).await?;
+        }
 
-        self.base_client().set_session_tokens(tokens);
-        self.base_client().set_session_meta(meta).await?;
+        self.save_session(session).await?;
 
         debug!("Done restoring session");
 
         Ok(())
     }
 
+    /// Restore the session that was saved in the state store.
+    ///
+    /// This only works if the `Client` was built with
+    /// [`ClientBuilder::persist_session()`] and the same store as when the
+    /// session was saved.
+    ///
+    /// Returns `true` if a session was found and restored, `false` if the
+    /// store doesn't contain a session, in which case the user needs to log
+    /// in.
+    ///
+    /// If the session was obtained with [OpenID Connect](crate::oidc), the
+    /// registration of the client is restored too, so the access token keeps
+    /// being refreshed with the provider.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use matrix_sdk::Client;
+    /// # use futures::executor::block_on;
+    /// # block_on(async {
+    /// # let store_config = matrix_sdk::config::StoreConfig::new();
+    ///
+    /// let client = Client::builder()
+    ///     .homeserver_url("http://example.com")
+    ///     .store_config(store_config)
+    ///     .persist_session()
+    ///     .build()
+    ///     .await?;
+    ///
+    /// if !client.restore_from_store().await? {
+    ///     client.login_username("example", "my-password").send().await?;
+    /// }
+    /// # anyhow::Ok(()) });
+    /// ```
+    pub async fn restore_from_store(&self) -> Result<bool> {
+        let Some(session) = self.store().get_kv_data(StateStoreDataKey::Session).await? else {
+            return Ok(false);
+        };
+        let session = session.into_session().ok_or(Error::InconsistentState)?;
+
+        self.restore_session(session).await?;
+        self.restore_guest_flag().await?;
+        #[cfg(feature = "experimental-oidc")]
+        self.oidc().restore_from_store().await?;
+
+        Ok(true)
+    }
+
     /// Refresh the access token.
     ///
     /// When support for [refreshing access tokens] is activated on both the
@@ -1415,20 +1665,8 @@ impl Client {
                 .refresh_token
                 .clone()
                 .ok_or(RefreshTokenError::RefreshTokenRequired)?;
-            let request = refresh_token::v3::Request::new(refresh_token);
 
-            let res = self
-                .inner
-                .http_client
-                .send(
-                    request,
-                    None,
-                    self.homeserver().await.to_string(),
-                    self.access_token().as_deref(),
-                    self.user_id(),
-                    self.server_versions().await?,
-                )
-                .await;
+            let res = self.send_refresh_token_request(refresh_token).await;
 
             match res {
                 Ok(res) => {
@@ -1436,13 +1674,28 @@ impl Client {
 
                     session_tokens.update_with_refresh_response(&res);
 
+                    // Save the new tokens before using them, the previous ones are now invalid.
+                    if let Some(meta) = self.session_meta() {
+                        let session = Session::from_parts(meta.clone(), session_tokens.clone());
+                        if let Err(error) = self.save_session(session).await {
+                            error!("Failed to save the refreshed session tokens: {error}");
+                        }
+                    }
+
                     self.base_client().set_session_tokens(session_tokens);
+                    self.set_soft_logged_out(false);
 
                     // TODO: Let ffi client to know that tokens have changed
 
                     Ok(Some(res))
                 }
                 Err(error) => {
+                    if let Some(ErrorKind::UnknownToken { soft_logout: true }) =
+                        error.client_api_error_kind()
+                    {
+                        self.set_soft_logged_out(true);
+                    }
+
                     *guard = match error.as_ruma_api_error() {
                         Some(RumaApiError::ClientApi(api_error)) => {
                             Err(RefreshTokenError::ClientApi(api_error.to_owned()))
@@ -1461,6 +1714,35 @@ impl Client {
         }
     }
 
+    /// Exchange the refresh token for new tokens, either with the homeserver or
+    /// with the OpenID Connect provider if the session was obtained with it.
+    async fn send_refresh_token_request(
+        &self,
+        refresh_token: String,
+    ) -> HttpResult<refresh_token::v3::Response> {
+        #[cfg(feature = "experimental-oidc")]
+        if self.inner.oidc.is_logged_in() {
+            return self
+                .oidc()
+                .refresh_access_token_inner(&refresh_token)
+                .await
+                .map_err(|error| RefreshTokenError::Oidc(Arc::new(error)).into());
+        }
+
+        let request = refresh_token::v3::Request::new(refresh_token);
+        self.inner
+            .http_client
+            .send(
+                request,
+                None,
+                self.homeserver().await.to_string(),
+                self.access_token().as_deref(),
+                self.user_id(),
+                self.server_versions().await?,
+            )
+            .await
+    }
+
     /// Register a user to the server.
     ///
     /// # Arguments
@@ -1468,6 +1750,9 @@ impl Client {
     /// * `registration` - The easiest way to create this request is using the
     ///   [`register::v3::Request`] itself.
     ///
+    /// If the homeserver requires user-interactive authentication,
+    /// [`Client::uiaa()`] can take care of it.
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -1584,10 +1869,16 @@ impl Client {
     /// Returns a `join_room_by_id::Response` consisting of the
     /// joined rooms `RoomId`.
     ///
+    /// If the client is a [guest](Self::is_guest) and the guest access of the
+    /// room is known to forbid guests from joining it,
+    /// [`Error::GuestAccessForbidden`] is returned.
+    ///
     /// # Arguments
     ///
     /// * `room_id` - The `RoomId` of the room to be joined.
     pub async fn join_room_by_id(&self, room_id: &RoomId) -> Result<room::Joined> {
+        self.ensure_guest_can_join(self.base_client().get_room(room_id).as_ref()).await?;
+
         let request = join_room_by_id::v3::Request::new(room_id.to_owned());
         let response = self.send(request, None).await?;
         let base_room = self.base_client().room_joined(&response.room_id).await?;
@@ -1599,6 +1890,10 @@ impl Client {
     /// Returns a `join_room_by_id_or_alias::Response` consisting of the
     /// joined rooms `RoomId`.
     ///
+    /// If the client is a [guest](Self::is_guest) and the guest access of the
+    /// room is known to forbid guests from joining it,
+    /// [`Error::GuestAccessForbidden`] is returned.
+    ///
     /// # Arguments
     ///
     /// * `alias` - The `RoomId` or `RoomAliasId` of the room to be joined.
@@ -1608,6 +1903,10 @@ impl Client {
         alias: &RoomOrAliasId,
         server_names: &[OwnedServerName],
     ) -> Result<room::Joined> {
+        if let Ok(room_id) = <&RoomId>::try_from(alias) {
+            self.ensure_guest_can_join(self.base_client().get_room(room_id).as_ref()).await?;
+        }
+
         let request = assign!(join_room_by_id_or_alias::v3::Request::new(alias.to_owned()), {
             server_name: server_names.to_owned(),
         });
@@ -1616,6 +1915,36 @@ impl Client {
         room::Joined::new(self, base_room).ok_or(Error::InconsistentState)
     }
 
+    /// Knock on a room, to ask its moderators to be invited to it.
+    ///
+    /// Returns the room in the knocked state. It will become an invited room
+    /// if the knock is accepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `room_id_or_alias` - The `RoomId` or `RoomAliasId` of the room to
+    ///   knock on.
+    ///
+    /// * `reason` - The reason for knocking, shown to the moderators of the
+    ///   room.
+    ///
+    /// * `via` - The servers to attempt to knock through. One of the servers
+    ///   must be participating in the room.
+    pub async fn knock(
+        &self,
+        room_id_or_alias: &RoomOrAliasId,
+        reason: Option<String>,
+        via: &[OwnedServerName],
+    ) -> Result<room::Knocked> {
+        let request = assign!(knock_room::v3::Request::new(room_id_or_alias.to_owned()), {
+            reason,
+            server_name: via.to_owned(),
+        });
+        let response = self.send(request, None).await?;
+        let base_room = self.base_client().room_knocked(&response.room_id).await?;
+        room::Knocked::new(self, base_room).ok_or(Error::InconsistentState)
+    }
+
     /// Search the homeserver's directory of public rooms.
     ///
     /// Sends a request to "_matrix/client/r0/publicRooms", returns
@@ -1672,6 +2001,9 @@ impl Client {
     /// use [`create_dm`][Self::create_dm], which is more convenient than
     /// assembling the [`create_room::v3::Request`] yourself.
     ///
+    /// Guests can't create rooms, [`Error::GuestAccessForbidden`] is returned
+    /// if the client is a [guest](Self::is_guest).
+    ///
     /// # Examples
     ///
     /// ```no_run
@@ -1691,6 +2023,8 @@ impl Client {
     /// # });
     /// ```
     pub async fn create_room(&self, request: create_room::v3::Request) -> Result<room::Joined> {
+        self.ensure_not_guest()?;
+
         let invite = request.invite.clone();
         let is_direct_room = request.is_direct;
         let response = self.send(request, None).await?;
@@ -1916,12 +2250,32 @@ impl Client {
                     .inner
                     .unknown_token_error_sender
                     .send(UnknownToken { soft_logout: *soft_logout });
+
+                // If the token can be refreshed, `send()` takes care of it.
+                let can_refresh =
+                    self.inner.handle_refresh_tokens && self.refresh_token().is_some();
+                if *soft_logout && !can_refresh {
+                    self.set_soft_logged_out(true);
+                }
             }
         }
 
         response
     }
 
+    /// Send a raw HTTP request with the HTTP client of this `Client`.
+    ///
+    /// This is meant for servers other than the homeserver, so the request is
+    /// sent as-is: no access token is added and it is not retried.
+    #[cfg(any(feature = "experimental-oidc", feature = "experimental-qr-login"))]
+    pub(crate) async fn send_raw(
+        &self,
+        request: http::Request<bytes::Bytes>,
+    ) -> HttpResult<http::Response<bytes::Bytes>> {
+        let http_client = &self.inner.http_client;
+        http_client.inner.send_request(request, http_client.request_config.timeout).await
+    }
+
     async fn request_server_versions(&self) -> HttpResult<Box<[MatrixVersion]>> {
         let server_versions: Box<[MatrixVersion]> = self
             .inner
@@ -1992,6 +2346,7 @@ impl Client {
     /// `UiaaResponse`. The response will contain information for the
     /// interactive auth and the same request needs to be made but this time
     /// with some `auth_data` provided.
+    /// [`Client::uiaa()`] can take care of this exchange.
     ///
     /// ```no_run
     /// # use matrix_sdk::{
@@ -2162,11 +2517,16 @@ impl Client {
             error!(error = ?e, "Error while sending outgoing E2EE requests");
         }
 
+        let set_presence = match &sync_settings.idle_presence {
+            Some(idle_presence) => idle_presence.presence(),
+            None => sync_settings.set_presence,
+        };
+
         let request = assign!(sync_events::v3::Request::new(), {
             filter: sync_settings.filter,
             since: sync_settings.token,
             full_state: sync_settings.full_state,
-            set_presence: sync_settings.set_presence,
+            set_presence,
             timeout: sync_settings.timeout,
         });
         let mut request_config = self.request_config();
@@ -2499,9 +2859,37 @@ impl Client {
     }
 
     /// Log out the current user
+    ///
+    /// The tokens of the session are cleared, and the session is removed from
+    /// the store if it is [persisted](ClientBuilder::persist_session).
     pub async fn logout(&self) -> HttpResult<logout::v3::Response> {
         let request = logout::v3::Request::new();
-        self.send(request, None).await
+        let response = self.send(request, None).await?;
+
+        self.clear_session().await;
+
+        Ok(response)
+    }
+
+    /// Forget the session after logging out.
+    ///
+    /// The tokens of the session are cleared, so they are not used anymore,
+    /// and the session is removed from the store if it is persisted.
+    pub(crate) async fn clear_session(&self) {
+        self.base_client().clear_session_tokens();
+
+        if self.inner.persist_session {
+            if let Err(error) = self.store().remove_kv_data(StateStoreDataKey::Session).await {
+                error!("Failed to remove the session from the store: {error}");
+            }
+            if let Err(error) = self.set_guest(false).await {
+                error!("Failed to remove the guest flag from the store: {error}");
+            }
+            #[cfg(feature = "experimental-oidc")]
+            if let Err(error) = self.oidc().remove_from_store().await {
+                error!("Failed to remove the OpenID Connect registration from the store: {error}");
+            }
+        }
     }
 
     /// Subscribes a new receiver to client UnknownToken errors
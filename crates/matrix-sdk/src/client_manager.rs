@@ -0,0 +1,638 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Management of several accounts at once.
+//!
+//! A [`ClientManager`] owns one [`Client`] per account. Every account gets its
+//! own sled store in a directory under a common base path, and the list of
+//! accounts is saved in a manifest file next to them. The session of every
+//! account is persisted in its own store with
+//! [`ClientBuilder::persist_session()`], where it is encrypted if a passphrase
+//! is used.
+//!
+//! **Warning:** without a passphrase, the access tokens of all the accounts
+//! are stored in plain text on disk. Always set one with
+//! [`ClientManagerBuilder::passphrase()`] unless the base path is otherwise
+//! protected.
+//!
+//! All the clients share the same HTTP client, and thus the same connection
+//! pool.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use futures_core::Stream;
+use matrix_sdk_base::sync::UnreadNotificationsCount;
+use matrix_sdk_common::executor::spawn;
+use ruma::{api::client::push::get_notifications::v3::Notification, OwnedUserId, UserId};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{
+    sync::{broadcast, broadcast::error::RecvError, Mutex},
+    task::JoinHandle,
+};
+use tracing::{debug, error, warn};
+
+use crate::{
+    config::SyncSettings, Client, ClientBuildError, ClientBuilder, Error, LoopCtrl, StoreError,
+};
+
+/// The name of the file listing the accounts, in the base path.
+const MANIFEST_FILE: &str = "accounts.json";
+
+/// Errors that can happen when managing accounts.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ClientManagerError {
+    /// Building the client of an account failed.
+    #[error(transparent)]
+    Build(#[from] ClientBuildError),
+
+    /// A request of a client failed.
+    #[error(transparent)]
+    Matrix(#[from] Error),
+
+    /// Reading or writing the store of an account failed.
+    #[error(transparent)]
+    Store(#[from] StoreError),
+
+    /// Reading or writing the manifest failed.
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// The manifest couldn't be (de)serialized.
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// The client wasn't logged in after the login callback.
+    #[error("the client is not logged in")]
+    NotLoggedIn,
+
+    /// The account is already managed.
+    #[error("the account {0} is already managed")]
+    AccountExists(OwnedUserId),
+
+    /// The account is not managed.
+    #[error("the account {0} is not managed")]
+    UnknownAccount(OwnedUserId),
+}
+
+/// A notification received by one of the accounts of a [`ClientManager`].
+#[derive(Clone, Debug)]
+pub struct AccountNotification {
+    /// The user ID of the account that received the notification.
+    pub user_id: OwnedUserId,
+    /// The notification.
+    pub notification: Notification,
+}
+
+/// The unread notification counts of the accounts of a [`ClientManager`].
+#[derive(Clone, Debug, Default)]
+pub struct UnreadCounts {
+    /// The counts of every account, summed over its joined rooms.
+    pub accounts: BTreeMap<OwnedUserId, UnreadNotificationsCount>,
+}
+
+impl UnreadCounts {
+    /// The counts summed over all the accounts.
+    pub fn total(&self) -> UnreadNotificationsCount {
+        self.accounts.values().fold(UnreadNotificationsCount::default(), |mut total, counts| {
+            total.highlight_count += counts.highlight_count;
+            total.notification_count += counts.notification_count;
+            total
+        })
+    }
+}
+
+/// An entry of the manifest.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct ManifestAccount {
+    user_id: OwnedUserId,
+    homeserver: String,
+    /// The name of the directory of the store, in the base path.
+    store: String,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct Manifest {
+    accounts: Vec<ManifestAccount>,
+}
+
+struct ManagedAccount {
+    client: Client,
+    homeserver: String,
+    store: String,
+    sync_task: Option<JoinHandle<()>>,
+}
+
+impl ManagedAccount {
+    fn abort_tasks(&mut self) {
+        if let Some(task) = self.sync_task.take() {
+            task.abort();
+        }
+    }
+}
+
+struct ClientManagerInner {
+    base_path: PathBuf,
+    passphrase: Option<String>,
+    client_builder: ClientBuilder,
+    accounts: Mutex<BTreeMap<OwnedUserId, ManagedAccount>>,
+    /// The accounts of the manifest that couldn't be restored, kept in the
+    /// manifest so they are not lost.
+    unrestored: Vec<ManifestAccount>,
+    /// The settings of the sync loops, if they should be running.
+    syncing: std::sync::Mutex<Option<SyncSettings>>,
+    notification_sender: broadcast::Sender<AccountNotification>,
+    unread_counts_sender: broadcast::Sender<UnreadCounts>,
+}
+
+/// A manager for several accounts, each with its own [`Client`].
+///
+/// Create it with [`ClientManager::builder()`].
+#[derive(Clone)]
+pub struct ClientManager {
+    inner: Arc<ClientManagerInner>,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for ClientManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientManager")
+            .field("base_path", &self.inner.base_path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Builder for [`ClientManager`].
+#[must_use]
+pub struct ClientManagerBuilder {
+    base_path: PathBuf,
+    passphrase: Option<String>,
+    client_builder: ClientBuilder,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for ClientManagerBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientManagerBuilder")
+            .field("base_path", &self.base_path)
+            .field("client_builder", &self.client_builder)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ClientManagerBuilder {
+    /// Set the passphrase used to encrypt the stores of the accounts,
+    /// including their sessions.
+    ///
+    /// Without a passphrase, the access tokens are stored in plain text.
+    pub fn passphrase(mut self, passphrase: &str) -> Self {
+        self.passphrase = Some(passphrase.to_owned());
+        self
+    }
+
+    /// Set the builder used as a template for the clients of the accounts.
+    ///
+    /// The homeserver and the store set on the template are replaced for
+    /// every account, and the session is always persisted in the store.
+    pub fn client_builder(mut self, client_builder: ClientBuilder) -> Self {
+        self.client_builder = client_builder;
+        self
+    }
+
+    /// Create the [`ClientManager`] and restore the accounts listed in its
+    /// manifest, if any.
+    ///
+    /// Accounts whose session can't be restored are skipped, with a warning.
+    pub async fn build(self) -> Result<ClientManager, ClientManagerError> {
+        std::fs::create_dir_all(&self.base_path)?;
+        let manifest = read_manifest(&self.base_path)?;
+
+        let (notification_sender, _) = broadcast::channel(100);
+        let (unread_counts_sender, _) = broadcast::channel(10);
+
+        let mut inner = ClientManagerInner {
+            base_path: self.base_path,
+            passphrase: self.passphrase,
+            client_builder: self.client_builder.share_http_client()?,
+            accounts: Default::default(),
+            unrestored: Vec::new(),
+            syncing: Default::default(),
+            notification_sender,
+            unread_counts_sender,
+        };
+
+        for entry in manifest.accounts {
+            match inner.restore_account(&entry).await {
+                Ok(account) => {
+                    inner.accounts.get_mut().insert(entry.user_id, account);
+                }
+                Err(error) => {
+                    warn!(user_id = ?entry.user_id, "Failed to restore an account: {error}");
+                    inner.unrestored.push(entry);
+                }
+            }
+        }
+
+        Ok(ClientManager { inner: Arc::new(inner) })
+    }
+}
+
+impl ClientManager {
+    /// Create a new [`ClientManagerBuilder`] storing everything under the
+    /// given path.
+    pub fn builder(base_path: impl AsRef<Path>) -> ClientManagerBuilder {
+        ClientManagerBuilder {
+            base_path: base_path.as_ref().to_owned(),
+            passphrase: None,
+            client_builder: Client::builder(),
+        }
+    }
+
+    /// The user IDs of the managed accounts.
+    pub async fn user_ids(&self) -> Vec<OwnedUserId> {
+        self.inner.accounts.lock().await.keys().cloned().collect()
+    }
+
+    /// The client of the given account, if it is managed.
+    pub async fn client(&self, user_id: &UserId) -> Option<Client> {
+        self.inner.accounts.lock().await.get(user_id).map(|account| account.client.clone())
+    }
+
+    /// The clients of all the managed accounts.
+    pub async fn clients(&self) -> Vec<Client> {
+        self.inner.accounts.lock().await.values().map(|account| account.client.clone()).collect()
+    }
+
+    /// Add a new account.
+    ///
+    /// A client with its own store is created for the given homeserver, and
+    /// passed to the `login` callback that must log it in. The account is then
+    /// saved and, if the sync loops are running, starts syncing.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::client_manager::ClientManager;
+    /// # async {
+    /// let manager = ClientManager::builder("/home/example/.local/share/app")
+    ///     .passphrase("secret")
+    ///     .build()
+    ///     .await?;
+    ///
+    /// let client = manager
+    ///     .add_account("https://matrix.example.org", |client| async move {
+    ///         client.login_username("example", "wordpass").send().await
+    ///     })
+    ///     .await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn add_account<F, Fut, T, E>(
+        &self,
+        homeserver_url: &str,
+        login: F,
+    ) -> Result<Client, ClientManagerError>
+    where
+        F: FnOnce(Client) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Into<Error>,
+    {
+        let store = self.create_store_dir()?;
+        let result = self.login_new_account(homeserver_url, &store, login).await;
+
+        if result.is_err() {
+            // Don't leave the store of a failed login behind.
+            if let Err(error) = std::fs::remove_dir_all(self.inner.base_path.join(&store)) {
+                warn!("Failed to remove the store of a failed login: {error}");
+            }
+        }
+
+        result
+    }
+
+    async fn login_new_account<F, Fut, T, E>(
+        &self,
+        homeserver_url: &str,
+        store: &str,
+        login: F,
+    ) -> Result<Client, ClientManagerError>
+    where
+        F: FnOnce(Client) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Into<Error>,
+    {
+        let client = self.inner.build_client(homeserver_url, store).await?;
+        login(client.clone()).await.map_err(|error| ClientManagerError::Matrix(error.into()))?;
+
+        let user_id = client.user_id().ok_or(ClientManagerError::NotLoggedIn)?.to_owned();
+
+        let mut accounts = self.inner.accounts.lock().await;
+        if accounts.contains_key(&user_id) {
+            drop(accounts);
+
+            // Don't leave the new session behind on the homeserver.
+            if let Err(error) = client.logout().await {
+                warn!(?user_id, "Failed to log out the duplicate session: {error}");
+            }
+
+            return Err(ClientManagerError::AccountExists(user_id));
+        }
+
+        let homeserver = client.homeserver().await.to_string();
+        let account = self.inner.manage(client.clone(), homeserver, store.to_owned()).await;
+        accounts.insert(user_id.clone(), account);
+        self.inner.write_manifest(&accounts)?;
+
+        if let Some(settings) = self.sync_settings() {
+            self.start_account_sync(accounts.get_mut(&user_id).expect("just inserted"), settings);
+        }
+        debug!(?user_id, "Added an account");
+
+        Ok(client)
+    }
+
+    /// Remove an account, and delete its store.
+    ///
+    /// If `logout` is `true`, the session is also logged out on the
+    /// homeserver.
+    pub async fn remove_account(
+        &self,
+        user_id: &UserId,
+        logout: bool,
+    ) -> Result<(), ClientManagerError> {
+        let mut accounts = self.inner.accounts.lock().await;
+        let mut account = accounts
+            .remove(user_id)
+            .ok_or_else(|| ClientManagerError::UnknownAccount(user_id.to_owned()))?;
+        account.abort_tasks();
+        self.inner.write_manifest(&accounts)?;
+        drop(accounts);
+
+        if logout {
+            if let Err(error) = account.client.logout().await {
+                warn!(?user_id, "Failed to log out: {error}");
+            }
+        }
+
+        let store_path = self.inner.base_path.join(&account.store);
+        drop(account);
+        std::fs::remove_dir_all(store_path)?;
+        debug!(?user_id, "Removed an account");
+
+        Ok(())
+    }
+
+    /// Start the sync loops of all the accounts, including the accounts added
+    /// later.
+    ///
+    /// The sync loops are restarted if they were already running.
+    pub async fn start_sync(&self, settings: SyncSettings) {
+        *self.inner.syncing.lock().unwrap() = Some(settings.clone());
+
+        for account in self.inner.accounts.lock().await.values_mut() {
+            self.start_account_sync(account, settings.clone());
+        }
+    }
+
+    /// Stop the sync loops of all the accounts.
+    pub async fn stop_sync(&self) {
+        *self.inner.syncing.lock().unwrap() = None;
+
+        for account in self.inner.accounts.lock().await.values_mut() {
+            if let Some(task) = account.sync_task.take() {
+                task.abort();
+            }
+        }
+    }
+
+    /// Get a stream of the notifications received by all the accounts.
+    ///
+    /// The sync loops must be running for the stream to receive
+    /// notifications.
+    pub fn notifications(&self) -> impl Stream<Item = AccountNotification> {
+        let mut receiver = self.inner.notification_sender.subscribe();
+
+        async_stream::stream! {
+            loop {
+                match receiver.recv().await {
+                    Ok(notification) => yield notification,
+                    Err(RecvError::Lagged(count)) => {
+                        warn!("Notification stream lagged behind, skipped {count} notifications");
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+
+    /// The current unread notification counts of all the accounts.
+    pub async fn unread_counts(&self) -> UnreadCounts {
+        unread_counts(&*self.inner.accounts.lock().await)
+    }
+
+    /// Get a stream of the unread notification counts of all the accounts.
+    ///
+    /// The stream first yields the current counts, and then the new counts
+    /// after every sync of any of the accounts.
+    pub fn unread_counts_stream(&self) -> impl Stream<Item = UnreadCounts> {
+        let mut receiver = self.inner.unread_counts_sender.subscribe();
+        let manager = self.clone();
+
+        async_stream::stream! {
+            yield manager.unread_counts().await;
+
+            loop {
+                match receiver.recv().await {
+                    Ok(counts) => yield counts,
+                    // Only the latest counts matter.
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+
+    fn sync_settings(&self) -> Option<SyncSettings> {
+        self.inner.syncing.lock().unwrap().clone()
+    }
+
+    /// Reserve the directory of the store of a new account, and return its
+    /// name.
+    fn create_store_dir(&self) -> Result<String, ClientManagerError> {
+        for index in 0u32.. {
+            let name = format!("account-{index}");
+            match std::fs::create_dir(self.inner.base_path.join(&name)) {
+                Ok(()) => return Ok(name),
+                Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        unreachable!("there is always a free store name")
+    }
+
+    fn start_account_sync(&self, account: &mut ManagedAccount, settings: SyncSettings) {
+        if let Some(task) = account.sync_task.take() {
+            task.abort();
+        }
+
+        let client = account.client.clone();
+        let manager = Arc::downgrade(&self.inner);
+
+        account.sync_task = Some(spawn(async move {
+            let result = client
+                .sync_with_callback(settings, |_| {
+                    let manager = manager.clone();
+                    async move {
+                        if let Some(manager) = manager.upgrade() {
+                            publish_unread_counts(&manager).await;
+                        }
+                        LoopCtrl::Continue
+                    }
+                })
+                .await;
+
+            if let Err(error) = result {
+                error!(user_id = ?client.user_id(), "The sync loop stopped: {error}");
+            }
+        }));
+    }
+}
+
+impl ClientManagerInner {
+    async fn build_client(
+        &self,
+        homeserver_url: &str,
+        store: &str,
+    ) -> Result<Client, ClientBuildError> {
+        self.client_builder
+            .clone()
+            .homeserver_url(homeserver_url)
+            .sled_store(self.base_path.join(store), self.passphrase.as_deref())
+            .persist_session()
+            .build()
+            .await
+    }
+
+    async fn restore_account(
+        &self,
+        entry: &ManifestAccount,
+    ) -> Result<ManagedAccount, ClientManagerError> {
+        let client = self.build_client(&entry.homeserver, &entry.store).await?;
+        if !client.restore_from_store().await? {
+            return Err(ClientManagerError::NotLoggedIn);
+        }
+
+        Ok(self.manage(client, entry.homeserver.clone(), entry.store.clone()).await)
+    }
+
+    /// Start tracking the given logged-in client.
+    async fn manage(&self, client: Client, homeserver: String, store: String) -> ManagedAccount {
+        let notification_sender = self.notification_sender.clone();
+        client
+            .register_notification_handler(move |notification, _room, client: Client| {
+                let notification_sender = notification_sender.clone();
+                async move {
+                    if let Some(user_id) = client.user_id() {
+                        let notification =
+                            AccountNotification { user_id: user_id.to_owned(), notification };
+                        // Nobody might be listening, that's fine.
+                        let _ = notification_sender.send(notification);
+                    }
+                }
+            })
+            .await;
+
+        ManagedAccount { client, homeserver, store, sync_task: None }
+    }
+
+    fn write_manifest(
+        &self,
+        accounts: &BTreeMap<OwnedUserId, ManagedAccount>,
+    ) -> Result<(), ClientManagerError> {
+        let manifest = Manifest {
+            accounts: accounts
+                .iter()
+                .map(|(user_id, account)| ManifestAccount {
+                    user_id: user_id.clone(),
+                    homeserver: account.homeserver.clone(),
+                    store: account.store.clone(),
+                })
+                .chain(self.unrestored.iter().cloned())
+                .collect(),
+        };
+
+        // Write to a temporary file first, so a crash can't leave a truncated
+        // manifest behind.
+        let path = self.base_path.join(MANIFEST_FILE);
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_vec_pretty(&manifest)?)?;
+        std::fs::rename(tmp_path, path)?;
+
+        Ok(())
+    }
+}
+
+fn read_manifest(base_path: &Path) -> Result<Manifest, ClientManagerError> {
+    match std::fs::read(base_path.join(MANIFEST_FILE)) {
+        Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Manifest::default()),
+        Err(error) => Err(error.into()),
+    }
+}
+
+fn unread_counts(accounts: &BTreeMap<OwnedUserId, ManagedAccount>) -> UnreadCounts {
+    let accounts = accounts
+        .iter()
+        .map(|(user_id, account)| {
+            let counts = account.client.joined_rooms().iter().fold(
+                UnreadNotificationsCount::default(),
+                |mut counts, room| {
+                    let room_counts = room.unread_notification_counts();
+                    counts.highlight_count += room_counts.highlight_count;
+                    counts.notification_count += room_counts.notification_count;
+                    counts
+                },
+            );
+            (user_id.clone(), counts)
+        })
+        .collect();
+
+    UnreadCounts { accounts }
+}
+
+async fn publish_unread_counts(manager: &ClientManagerInner) {
+    if manager.unread_counts_sender.receiver_count() == 0 {
+        return;
+    }
+
+    let counts = unread_counts(&*manager.accounts.lock().await);
+    // Nobody might be listening anymore, that's fine.
+    let _ = manager.unread_counts_sender.send(counts);
+}
+
+impl Drop for ClientManagerInner {
+    fn drop(&mut self) {
+        for account in self.accounts.get_mut().values_mut() {
+            account.abort_tasks();
+        }
+    }
+}
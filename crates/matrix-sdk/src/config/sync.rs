@@ -16,6 +16,8 @@ use std::{fmt, time::Duration};
 
 use ruma::{api::client::sync::sync_events, presence::PresenceState};
 
+use crate::presence::IdlePresence;
+
 const DEFAULT_SYNC_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// Settings for a sync call.
@@ -26,6 +28,7 @@ pub struct SyncSettings {
     pub(crate) token: Option<String>,
     pub(crate) full_state: bool,
     pub(crate) set_presence: PresenceState,
+    pub(crate) idle_presence: Option<IdlePresence>,
 }
 
 impl Default for SyncSettings {
@@ -64,6 +67,7 @@ impl SyncSettings {
             token: None,
             full_state: false,
             set_presence: PresenceState::Online,
+            idle_presence: None,
         }
     }
 
@@ -133,4 +137,23 @@ impl SyncSettings {
         self.set_presence = presence;
         self
     }
+
+    /// Set the presence state automatically according to the activity of the
+    /// user.
+    ///
+    /// Every sync request, sent with [`Client::sync_once()`] or by the sync
+    /// loops like [`Client::sync()`], uses the presence state returned by
+    /// [`IdlePresence::presence()`] at the time it is sent. This overrides the
+    /// state set with [`SyncSettings::set_presence()`].
+    ///
+    /// # Arguments
+    /// * `idle_presence` - The tracker of the activity of the user.
+    ///
+    /// [`Client::sync_once()`]: crate::Client::sync_once
+    /// [`Client::sync()`]: crate::Client::sync
+    #[must_use]
+    pub fn idle_presence(mut self, idle_presence: IdlePresence) -> Self {
+        self.idle_presence = Some(idle_presence);
+        self
+    }
 }
@@ -401,6 +401,11 @@ impl Client {
     pub(crate) async fn send_outgoing_requests(&self) -> Result<()> {
         const MAX_CONCURRENT_REQUESTS: usize = 20;
 
+        // The requests would fail, keep them until the session is recovered.
+        if self.is_soft_logged_out() {
+            return Ok(());
+        }
+
         // This is needed because sometimes we need to automatically
         // claim some one-time keys to unwedge an existing Olm session.
         if let Err(e) = self.claim_one_time_keys(iter::empty()).await {
@@ -636,6 +641,7 @@ impl Encryption {
     /// `UiaaResponse`. The response will contain information for the
     /// interactive auth and the same request needs to be made but this time
     /// with some `auth_data` provided.
+    /// [`Client::uiaa()`] can take care of this exchange.
     ///
     /// # Examples
     /// ```no_run
@@ -815,7 +821,16 @@ impl Encryption {
         let task = tokio::task::spawn_blocking(decrypt);
         let import = task.await.expect("Task join error")?;
 
-        Ok(olm.import_room_keys(import, false, |_, _| {}).await?)
+        let result = olm.import_room_keys(import, false, |_, _| {}).await?;
+
+        // Events that couldn't be decrypted before might be decryptable now.
+        if self.client.inner.search_index.is_some() {
+            if let Err(error) = self.client.reindex_undecrypted_events().await {
+                warn!("Failed to index the events decrypted with the imported keys: {error}");
+            }
+        }
+
+        Ok(result)
     }
 }
 
@@ -175,6 +175,31 @@ pub enum Error {
     #[error("Local cache doesn't contain all necessary data to perform the action.")]
     InsufficientData,
 
+    /// The own user doesn't have a high enough power level to perform the
+    /// action.
+    #[error("the user doesn't have a high enough power level to perform the action")]
+    InsufficientPowerLevel,
+
+    /// The local search index is used but none was configured, see
+    /// [`ClientBuilder::search_index()`](crate::ClientBuilder::search_index).
+    #[error("no local search index was configured")]
+    NoSearchIndex,
+
+    /// The client was logged in again, for example to recover from a soft
+    /// logout, but the new session is for another user or device.
+    #[error("the new session doesn't match the user and device of the client")]
+    SessionMismatch,
+
+    /// The action is not allowed for guest users, or the room doesn't allow
+    /// guests to join it.
+    #[error("guest users are not allowed to perform the action")]
+    GuestAccessForbidden,
+
+    /// The action is only possible with a guest session, see
+    /// [`Client::register_guest()`](crate::Client::register_guest).
+    #[error("the session is not a guest session")]
+    NotGuest,
+
     /// Attempting to restore a session after the olm-machine has already been
     /// set up fails
     #[cfg(feature = "e2e-encryption")]
@@ -245,13 +270,18 @@ pub enum Error {
     #[error(transparent)]
     SlidingSync(#[from] crate::sliding_sync::Error),
 
+    /// An error occurred when changing the notification settings.
+    #[error(transparent)]
+    NotificationSettings(#[from] crate::notification_settings::NotificationSettingsError),
+
     /// An error occurred in the timeline.
     #[cfg(feature = "experimental-timeline")]
     #[error(transparent)]
     Timeline(#[from] crate::room::timeline::Error),
 
     /// The client is in inconsistent state. This happens when we set a room to
-    /// a specific type, but then cannot get it in this type.
+    /// a specific type, but then cannot get it in this type, or when the store
+    /// contains data of an unexpected type.
     #[error("The internal client state is inconsistent.")]
     InconsistentState,
 
@@ -413,6 +443,7 @@ pub enum ImageError {
 /// [`Client::refresh_access_token()`]: crate::Client::refresh_access_token()
 /// [handling refresh tokens]: crate::ClientBuilder::handle_refresh_tokens()
 #[derive(Debug, Error, Clone)]
+#[non_exhaustive]
 pub enum RefreshTokenError {
     /// The Matrix endpoint returned an error.
     #[error(transparent)]
@@ -426,4 +457,9 @@ pub enum RefreshTokenError {
     /// not be forwarded.
     #[error("the access token could not be refreshed")]
     UnableToRefreshToken,
+
+    /// The OpenID Connect provider returned an error.
+    #[cfg(feature = "experimental-oidc")]
+    #[error(transparent)]
+    Oidc(std::sync::Arc<crate::oidc::OidcError>),
 }
@@ -0,0 +1,267 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Guest access].
+//!
+//! A guest account is a restricted account that the homeserver creates
+//! without any credentials, with [`Client::register_guest()`]. Guests can only
+//! join rooms whose [`GuestAccess`] is `can_join`, and can't create rooms.
+//!
+//! A guest account can be upgraded to a full account with
+//! [`Client::upgrade_guest()`], which keeps the same user ID, device and rooms.
+//!
+//! [Guest access]: https://spec.matrix.org/v1.6/client-server-api/#guest-access
+//! [`GuestAccess`]: ruma::events::room::guest_access::GuestAccess
+
+use std::sync::atomic::Ordering;
+
+use matrix_sdk_base::{store::StateStoreExt, Room as BaseRoom, Session, SessionTokens};
+use ruma::{
+    api::client::account::register::{self, RegistrationKind},
+    assign,
+    events::room::guest_access::{GuestAccess, RoomGuestAccessEventContent},
+};
+use tracing::{info, instrument, warn};
+
+use crate::{Client, Error, Result};
+
+/// The key of the guest flag in the custom values of the state store, when the
+/// session is persisted.
+const IS_GUEST_KEY: &[u8] = b"matrix-sdk.is_guest";
+
+impl Client {
+    /// Whether the session of this client is a guest session.
+    ///
+    /// This is `true` after [`Client::register_guest()`] or
+    /// [`Client::restore_guest_session()`], until the account is upgraded with
+    /// [`Client::upgrade_guest()`]. If the session is
+    /// [persisted](crate::ClientBuilder::persist_session), it is also `true`
+    /// after restoring a guest session with [`Client::restore_from_store()`].
+    pub fn is_guest(&self) -> bool {
+        self.inner.is_guest.load(Ordering::SeqCst)
+    }
+
+    /// Set whether the session of this client is a guest session, and save it
+    /// in the state store along with the session if it is persisted.
+    pub(crate) async fn set_guest(&self, is_guest: bool) -> Result<()> {
+        self.inner.is_guest.store(is_guest, Ordering::SeqCst);
+
+        if self.inner.persist_session {
+            if is_guest {
+                self.store().set_custom_value(IS_GUEST_KEY, vec![1]).await?;
+            } else {
+                self.store().remove_custom_value(IS_GUEST_KEY).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Restore whether the session of this client is a guest session from the
+    /// state store.
+    pub(crate) async fn restore_guest_flag(&self) -> Result<()> {
+        let is_guest = self.store().get_custom_value(IS_GUEST_KEY).await?.is_some();
+        self.inner.is_guest.store(is_guest, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    /// Register a guest account and log in with it.
+    ///
+    /// The homeserver must allow guest access. The session can be saved with
+    /// [`Client::session()`] and restored later with
+    /// [`Client::restore_guest_session()`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use matrix_sdk::{ruma::room_id, Client};
+    /// # use futures::executor::block_on;
+    /// # block_on(async {
+    ///
+    /// let client = Client::new("https://example.com".parse()?).await?;
+    /// client.register_guest().await?;
+    ///
+    /// let room = client.join_room_by_id(room_id!("!support:example.com")).await?;
+    /// # anyhow::Ok(()) });
+    /// ```
+    #[instrument(skip_all)]
+    pub async fn register_guest(&self) -> Result<()> {
+        let request = assign!(register::v3::Request::new(), { kind: RegistrationKind::Guest });
+        let response = self.register(request).await?;
+
+        // The homeserver always logs in guests.
+        let (Some(access_token), Some(device_id)) = (response.access_token, response.device_id)
+        else {
+            return Err(Error::InconsistentState);
+        };
+
+        self.restore_session(Session {
+            access_token,
+            refresh_token: response.refresh_token,
+            user_id: response.user_id,
+            device_id,
+        })
+        .await?;
+        self.set_guest(true).await?;
+
+        info!("Registered a guest account");
+
+        Ok(())
+    }
+
+    /// Restore a guest session that was created with
+    /// [`Client::register_guest()`].
+    ///
+    /// This is the same as [`Client::restore_session()`], except that the
+    /// client knows that the session is a guest session.
+    pub async fn restore_guest_session(&self, session: Session) -> Result<()> {
+        self.restore_session(session).await?;
+        self.set_guest(true).await?;
+
+        Ok(())
+    }
+
+    /// Upgrade the guest account of this client to a full account.
+    ///
+    /// The registration request is sent with the access token of the guest, so
+    /// the user ID, the device and the rooms of the guest are kept. The kind of
+    /// the request is set to [`RegistrationKind::User`], and its device ID is
+    /// set to the one of the session if it is missing. A different device ID
+    /// is refused with [`Error::SessionMismatch`].
+    ///
+    /// The new tokens replace the ones of the guest. If the homeserver created
+    /// another device anyway, its session is logged out and the one of the
+    /// guest is kept.
+    ///
+    /// If the homeserver requires user-interactive authentication,
+    /// [`Client::uiaa()`] can take care of it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use matrix_sdk::{
+    ///     ruma::{api::client::account::register, assign},
+    ///     uiaa::{UiaaAnswer, UiaaPrompt},
+    ///     Client,
+    /// };
+    /// # use futures::executor::block_on;
+    /// # block_on(async {
+    /// # let client = Client::new("https://example.com".parse()?).await?;
+    ///
+    /// let request = assign!(register::v3::Request::new(), {
+    ///     username: Some("example".to_owned()),
+    ///     password: Some("wordpass".to_owned()),
+    /// });
+    ///
+    /// client
+    ///     .uiaa(|prompt| async move {
+    ///         match prompt {
+    ///             UiaaPrompt::ChooseFlow { .. } => UiaaAnswer::Flow(0),
+    ///             _ => UiaaAnswer::Dummy,
+    ///         }
+    ///     })
+    ///     .run(|auth| client.upgrade_guest(assign!(request.clone(), { auth: auth })))
+    ///     .await?;
+    ///
+    /// assert!(!client.is_guest());
+    /// # anyhow::Ok(()) });
+    /// ```
+    #[instrument(skip_all)]
+    pub async fn upgrade_guest(
+        &self,
+        mut request: register::v3::Request,
+    ) -> Result<register::v3::Response> {
+        if !self.is_guest() {
+            return Err(Error::NotGuest);
+        }
+        let meta = self.base_client().session_meta().ok_or(Error::AuthenticationRequired)?;
+        let user_id = meta.user_id.clone();
+        let device_id = meta.device_id.clone();
+
+        if matches!(&request.device_id, Some(id) if *id != device_id) {
+            return Err(Error::SessionMismatch);
+        }
+        request.kind = RegistrationKind::User;
+        request.device_id = Some(device_id.clone());
+
+        let config = self.request_config().force_auth();
+        let response = self.send(request, Some(config)).await?;
+
+        if response.user_id != user_id {
+            return Err(Error::SessionMismatch);
+        }
+
+        // The homeserver doesn't return a new access token if `inhibit_login`
+        // was set, the one of the guest is then still valid.
+        if let Some(access_token) = &response.access_token {
+            if matches!(&response.device_id, Some(id) if *id != device_id) {
+                // The account is registered, but this client can't use a
+                // session for another device, so keep the one of the guest.
+                warn!("The homeserver created a new device for the upgraded account");
+                self.logout_orphaned_session(access_token).await;
+            } else {
+                let tokens = SessionTokens {
+                    access_token: access_token.clone(),
+                    refresh_token: response.refresh_token.clone(),
+                };
+                self.base_client().set_session_tokens(tokens.clone());
+                self.save_session(Session::from_parts(meta.clone(), tokens)).await?;
+            }
+        }
+        self.set_guest(false).await?;
+
+        info!("Upgraded the guest account");
+
+        Ok(response)
+    }
+
+    /// Make sure that the user of this client can join the given room.
+    ///
+    /// Guests can only join rooms that allow guest access. The join is only
+    /// refused if the room is known to have an `m.room.guest_access` event
+    /// that forbids it, otherwise the homeserver decides.
+    pub(crate) async fn ensure_guest_can_join(&self, room: Option<&BaseRoom>) -> Result<()> {
+        let Some(room) = room.filter(|_| self.is_guest()) else {
+            return Ok(());
+        };
+
+        let forbidden = self
+            .store()
+            .get_state_event_static::<RoomGuestAccessEventContent>(room.room_id())
+            .await?
+            .and_then(|raw| raw.deserialize().ok())
+            .and_then(|event| {
+                event
+                    .as_original()
+                    .map(|event| event.content.guest_access == GuestAccess::Forbidden)
+            })
+            .unwrap_or(false);
+
+        if forbidden {
+            Err(Error::GuestAccessForbidden)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Make sure that the user of this client is not a guest.
+    pub(crate) fn ensure_not_guest(&self) -> Result<()> {
+        if self.is_guest() {
+            Err(Error::GuestAccessForbidden)
+        } else {
+            Ok(())
+        }
+    }
+}
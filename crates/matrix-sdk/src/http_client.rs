@@ -13,8 +13,7 @@
 // limitations under the License.
 
 use std::{
-    any::type_name,
-    fmt::Debug,
+    fmt::{self, Debug},
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc,
@@ -101,6 +100,101 @@ pub trait HttpSend: AsyncTraitDeps {
     ) -> Result<http::Response<Bytes>, HttpError>;
 }
 
+/// A layer that wraps an [`HttpSend`] to add behaviour to it, like logging or
+/// collecting metrics.
+///
+/// Layers are added to the client with
+/// [`ClientBuilder::http_layer()`](crate::ClientBuilder::http_layer). Built-in
+/// layers are available in the [`http_layers`](crate::http_layers) module.
+///
+/// # Examples
+///
+/// ```
+/// use std::{sync::Arc, time::Duration};
+///
+/// use matrix_sdk::{
+///     async_trait, bytes::Bytes, HttpError, HttpLayer, HttpSend,
+/// };
+///
+/// /// A layer that adds a header to all the requests.
+/// #[derive(Debug)]
+/// struct AppNameLayer;
+///
+/// impl HttpLayer for AppNameLayer {
+///     fn layer(&self, inner: Arc<dyn HttpSend>) -> Arc<dyn HttpSend> {
+///         Arc::new(AppName(inner))
+///     }
+/// }
+///
+/// #[derive(Debug)]
+/// struct AppName(Arc<dyn HttpSend>);
+///
+/// #[async_trait]
+/// impl HttpSend for AppName {
+///     async fn send_request(
+///         &self,
+///         mut request: http::Request<Bytes>,
+///         timeout: Duration,
+///     ) -> Result<http::Response<Bytes>, HttpError> {
+///         request
+///             .headers_mut()
+///             .insert("x-app-name", "my-app".parse().unwrap());
+///         self.0.send_request(request, timeout).await
+///     }
+/// }
+/// ```
+pub trait HttpLayer: AsyncTraitDeps {
+    /// Wrap the given `HttpSend`, which sends the requests to the next layer
+    /// or to the homeserver.
+    fn layer(&self, inner: Arc<dyn HttpSend>) -> Arc<dyn HttpSend>;
+}
+
+/// Wrap the given `HttpSend` with the given layers, the first layer being the
+/// outermost one.
+pub(crate) fn apply_layers(
+    inner: Arc<dyn HttpSend>,
+    layers: &[Arc<dyn HttpLayer>],
+) -> Arc<dyn HttpSend> {
+    layers.iter().rev().fold(inner, |inner, layer| layer.layer(inner))
+}
+
+/// The name of the Matrix endpoint of a request.
+///
+/// It is added to the extensions of the requests passed to
+/// [`HttpSend::send_request()`] by the client, so layers can identify the
+/// endpoint without parsing the path. The name is made of the method and the
+/// most recent path of the endpoint in its metadata, like
+/// `GET /_matrix/client/v3/sync` for a `/sync` request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EndpointName {
+    method: http::Method,
+    path: &'static str,
+}
+
+impl EndpointName {
+    fn of<R: OutgoingRequest>() -> Self {
+        let metadata = R::METADATA;
+        let path = metadata.history.all_paths().last().unwrap_or_default();
+        Self { method: metadata.method, path }
+    }
+
+    /// The HTTP method of the endpoint.
+    pub fn method(&self) -> &http::Method {
+        &self.method
+    }
+
+    /// The path of the endpoint, with placeholders for its parameters.
+    pub fn path(&self) -> &'static str {
+        self.path
+    }
+}
+
+impl fmt::Display for EndpointName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.method, self.path)
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct HttpClient {
     pub(crate) inner: Arc<dyn HttpSend>,
@@ -309,7 +403,7 @@ impl HttpClient {
             return Err(HttpError::NotClientRequest);
         }
 
-        let request = self.serialize_request(
+        let mut request = self.serialize_request(
             request,
             config,
             homeserver,
@@ -317,6 +411,7 @@ impl HttpClient {
             user_id,
             server_versions,
         )?;
+        request.extensions_mut().insert(EndpointName::of::<R>());
 
         let request_size = ByteSize(request.body().len().try_into().unwrap_or(u64::MAX));
         span.record("request_size", request_size.to_string_as(true));
@@ -408,7 +503,8 @@ impl HttpSettings {
     }
 }
 
-// Clones all request parts except the extensions which can't be cloned.
+// Clones all request parts except the extensions which can't be cloned, apart
+// from the ones we know about.
 // See also https://github.com/hyperium/http/issues/395
 #[cfg(not(target_arch = "wasm32"))]
 fn clone_request(request: &http::Request<Bytes>) -> http::Request<Bytes> {
@@ -417,6 +513,9 @@ fn clone_request(request: &http::Request<Bytes>) -> http::Request<Bytes> {
         .method(request.method())
         .uri(request.uri());
     *builder.headers_mut().unwrap() = request.headers().clone();
+    if let Some(endpoint_name) = request.extensions().get::<EndpointName>() {
+        builder = builder.extension(endpoint_name.clone());
+    }
     builder.body(request.body().clone()).unwrap()
 }
 
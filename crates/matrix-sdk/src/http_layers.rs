@@ -0,0 +1,645 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Built-in [`HttpLayer`]s.
+//!
+//! Layers wrap the HTTP client of a [`Client`] to add behaviour to it. They are
+//! added with [`ClientBuilder::http_layer()`]:
+//!
+//! ```no_run
+//! use matrix_sdk::{
+//!     http_layers::{LoggingLayer, MetricsLayer},
+//!     Client,
+//! };
+//! # use futures::executor::block_on;
+//! # block_on(async {
+//!
+//! let metrics = MetricsLayer::new();
+//! let client = Client::builder()
+//!     .homeserver_url("https://example.com")
+//!     .http_layer(LoggingLayer::new())
+//!     .http_layer(metrics.clone())
+//!     .build()
+//!     .await?;
+//!
+//! // Later…
+//! for (endpoint, metrics) in metrics.metrics() {
+//!     println!("{endpoint}: {} requests", metrics.requests);
+//! }
+//! # anyhow::Ok(()) });
+//! ```
+//!
+//! [`Client`]: crate::Client
+//! [`ClientBuilder::http_layer()`]: crate::ClientBuilder::http_layer
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex as StdMutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri,
+};
+use matrix_sdk_common::instant::Instant;
+use matrix_sdk_secrets::{redact_form, redact_json};
+use serde_json::{json, Value as JsonValue};
+use tracing::{debug, warn};
+
+use crate::{http_client::EndpointName, utils::sleep, HttpError, HttpLayer, HttpSend};
+
+/// The value that replaces secrets in logs and recordings.
+const REDACTED: &str = "<redacted>";
+
+/// Get the name of the endpoint of the given request.
+///
+/// If the request was not sent by the client, the name is made of the method
+/// and the path, where the segments that look like Matrix identifiers are
+/// replaced by `{id}`.
+fn endpoint_name(request: &http::Request<Bytes>) -> String {
+    if let Some(name) = request.extensions().get::<EndpointName>() {
+        return name.to_string();
+    }
+
+    let path = request
+        .uri()
+        .path()
+        .split('/')
+        .map(|segment| {
+            let is_id = segment.starts_with(['!', '@', '$', '#', '+'])
+                || ["%21", "%40", "%24", "%23", "%2B"]
+                    .iter()
+                    .any(|sigil| segment.to_ascii_uppercase().starts_with(sigil));
+            if is_id {
+                "{id}"
+            } else {
+                segment
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+
+    format!("{} {path}", request.method())
+}
+
+/// Get the URI with the secrets of its query string redacted.
+fn redact_uri(uri: &Uri) -> String {
+    match uri.query() {
+        Some(query) => format!("{}?{}", uri.path(), redact_form(query, REDACTED)),
+        None => uri.to_string(),
+    }
+}
+
+/// Get the headers with the access token redacted.
+fn redact_headers(headers: &HeaderMap) -> HeaderMap {
+    let mut headers = headers.clone();
+    if headers.contains_key(AUTHORIZATION) {
+        headers.insert(AUTHORIZATION, HeaderValue::from_static(REDACTED));
+    }
+    headers
+}
+
+/// Get the body with the values of the secret keys redacted, if it is JSON or
+/// a URL-encoded form.
+fn redact_body(headers: &HeaderMap, body: &[u8]) -> Bytes {
+    let is_form = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| value.starts_with("application/x-www-form-urlencoded"));
+    if is_form {
+        return match std::str::from_utf8(body) {
+            Ok(form) => redact_form(form, REDACTED).into(),
+            Err(_) => Bytes::copy_from_slice(body),
+        };
+    }
+
+    match serde_json::from_slice::<JsonValue>(body) {
+        Ok(mut value) => {
+            redact_json(&mut value, REDACTED);
+            serde_json::to_vec(&value).map(Into::into).unwrap_or_default()
+        }
+        Err(_) => Bytes::copy_from_slice(body),
+    }
+}
+
+/// A layer logging the requests and the responses with [`tracing`], at the
+/// debug level.
+///
+/// The access token, passwords and other secrets are redacted from the logs.
+#[derive(Clone, Debug, Default)]
+pub struct LoggingLayer {
+    log_bodies: bool,
+}
+
+impl LoggingLayer {
+    /// Create a new `LoggingLayer` that doesn't log the bodies.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether to log the bodies of the requests and responses too.
+    ///
+    /// They might contain personal data, like the content of messages.
+    pub fn log_bodies(mut self, value: bool) -> Self {
+        self.log_bodies = value;
+        self
+    }
+}
+
+impl HttpLayer for LoggingLayer {
+    fn layer(&self, inner: Arc<dyn HttpSend>) -> Arc<dyn HttpSend> {
+        Arc::new(LoggingService { inner, log_bodies: self.log_bodies })
+    }
+}
+
+#[derive(Debug)]
+struct LoggingService {
+    inner: Arc<dyn HttpSend>,
+    log_bodies: bool,
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl HttpSend for LoggingService {
+    async fn send_request(
+        &self,
+        request: http::Request<Bytes>,
+        timeout: Duration,
+    ) -> Result<http::Response<Bytes>, HttpError> {
+        let method = request.method().clone();
+        let uri = redact_uri(request.uri());
+
+        let headers = redact_headers(request.headers());
+        debug!(%method, uri, ?headers, "Sending HTTP request");
+        if self.log_bodies {
+            let body = redact_body(request.headers(), request.body());
+            let body = String::from_utf8_lossy(&body);
+            debug!(%method, uri, %body, "HTTP request body");
+        }
+
+        let start = Instant::now();
+        let result = self.inner.send_request(request, timeout).await;
+        let duration = start.elapsed();
+
+        match &result {
+            Ok(response) => {
+                let status = response.status().as_u16();
+                debug!(%method, uri, status, ?duration, "Got HTTP response");
+                if self.log_bodies {
+                    let body = redact_body(response.headers(), response.body());
+                    let body = String::from_utf8_lossy(&body);
+                    debug!(%method, uri, %body, "HTTP response body");
+                }
+            }
+            Err(error) => {
+                debug!(%method, uri, ?duration, "HTTP request failed: {error}");
+            }
+        }
+
+        result
+    }
+}
+
+/// The metrics of the requests to an endpoint, collected by a
+/// [`MetricsLayer`].
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct EndpointMetrics {
+    /// The number of requests.
+    pub requests: u64,
+    /// The number of requests that failed without a response.
+    pub failures: u64,
+    /// The number of responses, per status code.
+    pub statuses: BTreeMap<u16, u64>,
+    /// The sum of the latencies of the requests.
+    pub total_latency: Duration,
+    /// The longest latency of a request.
+    pub max_latency: Duration,
+    /// The number of bytes sent in the bodies of the requests.
+    pub bytes_sent: u64,
+    /// The number of bytes received in the bodies of the responses.
+    pub bytes_received: u64,
+}
+
+impl EndpointMetrics {
+    /// The average latency of the requests, if any.
+    pub fn average_latency(&self) -> Option<Duration> {
+        let requests = u32::try_from(self.requests).ok().filter(|r| *r > 0)?;
+        Some(self.total_latency / requests)
+    }
+}
+
+/// A layer collecting metrics about the requests, per endpoint.
+///
+/// The layer can be cloned before being added to the client, all the clones
+/// share the same metrics.
+#[derive(Clone, Debug, Default)]
+pub struct MetricsLayer {
+    metrics: Arc<StdMutex<BTreeMap<String, EndpointMetrics>>>,
+}
+
+impl MetricsLayer {
+    /// Create a new `MetricsLayer`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the metrics collected so far, per endpoint name.
+    ///
+    /// The names of the endpoints are the ones of [`EndpointName`] for the
+    /// requests sent by the client.
+    ///
+    /// [`EndpointName`]: crate::EndpointName
+    pub fn metrics(&self) -> BTreeMap<String, EndpointMetrics> {
+        self.metrics.lock().unwrap().clone()
+    }
+
+    /// Forget the metrics collected so far.
+    pub fn reset(&self) {
+        self.metrics.lock().unwrap().clear();
+    }
+}
+
+impl HttpLayer for MetricsLayer {
+    fn layer(&self, inner: Arc<dyn HttpSend>) -> Arc<dyn HttpSend> {
+        Arc::new(MetricsService { inner, metrics: self.metrics.clone() })
+    }
+}
+
+#[derive(Debug)]
+struct MetricsService {
+    inner: Arc<dyn HttpSend>,
+    metrics: Arc<StdMutex<BTreeMap<String, EndpointMetrics>>>,
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl HttpSend for MetricsService {
+    async fn send_request(
+        &self,
+        request: http::Request<Bytes>,
+        timeout: Duration,
+    ) -> Result<http::Response<Bytes>, HttpError> {
+        let endpoint = endpoint_name(&request);
+        let bytes_sent = request.body().len() as u64;
+
+        let start = Instant::now();
+        let result = self.inner.send_request(request, timeout).await;
+        let latency = start.elapsed();
+
+        let mut metrics = self.metrics.lock().unwrap();
+        let metrics = metrics.entry(endpoint).or_default();
+        metrics.requests += 1;
+        metrics.total_latency += latency;
+        metrics.max_latency = metrics.max_latency.max(latency);
+        metrics.bytes_sent += bytes_sent;
+
+        match &result {
+            Ok(response) => {
+                *metrics.statuses.entry(response.status().as_u16()).or_default() += 1;
+                metrics.bytes_received += response.body().len() as u64;
+            }
+            Err(_) => metrics.failures += 1,
+        }
+
+        result
+    }
+}
+
+/// A layer adding headers to all the requests.
+///
+/// The headers replace the ones with the same name that might already be
+/// present in the requests.
+#[derive(Clone, Debug, Default)]
+pub struct HeadersLayer {
+    headers: HeaderMap,
+}
+
+impl HeadersLayer {
+    /// Create a new `HeadersLayer` without any header.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the given header to the requests.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+}
+
+impl HttpLayer for HeadersLayer {
+    fn layer(&self, inner: Arc<dyn HttpSend>) -> Arc<dyn HttpSend> {
+        Arc::new(HeadersService { inner, headers: self.headers.clone() })
+    }
+}
+
+#[derive(Debug)]
+struct HeadersService {
+    inner: Arc<dyn HttpSend>,
+    headers: HeaderMap,
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl HttpSend for HeadersService {
+    async fn send_request(
+        &self,
+        mut request: http::Request<Bytes>,
+        timeout: Duration,
+    ) -> Result<http::Response<Bytes>, HttpError> {
+        for (name, value) in &self.headers {
+            request.headers_mut().insert(name, value.clone());
+        }
+
+        self.inner.send_request(request, timeout).await
+    }
+}
+
+/// A request recorded by a [`RecorderLayer`], with its response.
+///
+/// The access token, passwords and other secrets are redacted.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct RecordedRequest {
+    /// The name of the endpoint, see [`MetricsLayer::metrics()`].
+    pub endpoint: String,
+    /// The method of the request.
+    pub method: Method,
+    /// The URI of the request.
+    pub uri: String,
+    /// The headers of the request.
+    pub headers: HeaderMap,
+    /// The body of the request.
+    pub body: Bytes,
+    /// The status of the response, or `None` if the request failed without a
+    /// response.
+    pub status: Option<StatusCode>,
+    /// The body of the response, empty if the request failed without a
+    /// response.
+    pub response_body: Bytes,
+}
+
+/// A layer recording the requests and their responses in memory, for example
+/// to check the requests sent by the client in tests.
+///
+/// The layer can be cloned before being added to the client, all the clones
+/// share the same recording.
+#[derive(Clone, Debug, Default)]
+pub struct RecorderLayer {
+    requests: Arc<StdMutex<Vec<RecordedRequest>>>,
+}
+
+impl RecorderLayer {
+    /// Create a new `RecorderLayer`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the requests recorded so far, in the order they were sent.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    /// Forget the requests recorded so far.
+    pub fn clear(&self) {
+        self.requests.lock().unwrap().clear();
+    }
+}
+
+impl HttpLayer for RecorderLayer {
+    fn layer(&self, inner: Arc<dyn HttpSend>) -> Arc<dyn HttpSend> {
+        Arc::new(RecorderService { inner, requests: self.requests.clone() })
+    }
+}
+
+#[derive(Debug)]
+struct RecorderService {
+    inner: Arc<dyn HttpSend>,
+    requests: Arc<StdMutex<Vec<RecordedRequest>>>,
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl HttpSend for RecorderService {
+    async fn send_request(
+        &self,
+        request: http::Request<Bytes>,
+        timeout: Duration,
+    ) -> Result<http::Response<Bytes>, HttpError> {
+        let mut recorded = RecordedRequest {
+            endpoint: endpoint_name(&request),
+            method: request.method().clone(),
+            uri: redact_uri(request.uri()),
+            headers: redact_headers(request.headers()),
+            body: redact_body(request.headers(), request.body()),
+            status: None,
+            response_body: Bytes::new(),
+        };
+
+        let result = self.inner.send_request(request, timeout).await;
+
+        if let Ok(response) = &result {
+            recorded.status = Some(response.status());
+            recorded.response_body = redact_body(response.headers(), response.body());
+        }
+        self.requests.lock().unwrap().push(recorded);
+
+        result
+    }
+}
+
+/// A fault injected by a [`FaultInjectionLayer`].
+#[derive(Clone, Debug)]
+pub struct Fault {
+    path: Option<String>,
+    kind: FaultKind,
+    times: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+enum FaultKind {
+    Status(StatusCode),
+    LimitExceeded(Duration),
+    Delay(Duration),
+}
+
+impl Fault {
+    fn new(kind: FaultKind) -> Self {
+        Self { path: None, kind, times: None }
+    }
+
+    /// Respond with the given status code and an `M_UNKNOWN` error, instead of
+    /// sending the request.
+    pub fn status(status: StatusCode) -> Self {
+        Self::new(FaultKind::Status(status))
+    }
+
+    /// Respond with an `M_LIMIT_EXCEEDED` error with the given delay to wait
+    /// before trying again, instead of sending the request.
+    pub fn limit_exceeded(retry_after: Duration) -> Self {
+        Self::new(FaultKind::LimitExceeded(retry_after))
+    }
+
+    /// Wait for the given delay before sending the request.
+    pub fn delay(delay: Duration) -> Self {
+        Self::new(FaultKind::Delay(delay))
+    }
+
+    /// Only inject the fault for the requests whose path contains the given
+    /// string.
+    ///
+    /// By default, the fault is injected for all the requests.
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Only inject the fault the given number of times.
+    ///
+    /// By default, the fault is injected every time. A fault that should be
+    /// injected 0 times is never injected.
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    fn error_response(
+        errcode: &str,
+        mut body: JsonValue,
+        status: StatusCode,
+    ) -> http::Response<Bytes> {
+        body["errcode"] = errcode.into();
+        body["error"] = "Injected fault".into();
+
+        http::Response::builder()
+            .status(status)
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&body).unwrap_or_default().into())
+            .expect("The response should be valid")
+    }
+}
+
+/// A layer injecting faults in the requests, to test how an application
+/// handles errors.
+///
+/// The first fault that matches a request is injected.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use http::StatusCode;
+/// use matrix_sdk::http_layers::{Fault, FaultInjectionLayer};
+///
+/// let layer = FaultInjectionLayer::new()
+///     .fault(Fault::status(StatusCode::BAD_GATEWAY).path("/sync").times(2))
+///     .fault(Fault::delay(Duration::from_secs(1)));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FaultInjectionLayer {
+    faults: Arc<StdMutex<Vec<Fault>>>,
+}
+
+impl FaultInjectionLayer {
+    /// Create a new `FaultInjectionLayer` without any fault.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the given fault.
+    pub fn fault(self, fault: Fault) -> Self {
+        self.add_fault(fault);
+        self
+    }
+
+    /// Add the given fault, after the layer was added to the client.
+    pub fn add_fault(&self, fault: Fault) {
+        self.faults.lock().unwrap().push(fault);
+    }
+
+    /// Remove all the faults.
+    pub fn clear(&self) {
+        self.faults.lock().unwrap().clear();
+    }
+
+    /// Take the fault to inject for the given request, if any.
+    fn take_fault(&self, request: &http::Request<Bytes>) -> Option<FaultKind> {
+        let mut faults = self.faults.lock().unwrap();
+        let path = request.uri().path();
+        let index = faults.iter().position(|fault| {
+            fault.times != Some(0) && fault.path.as_deref().map_or(true, |p| path.contains(p))
+        })?;
+
+        let fault = &mut faults[index];
+        let kind = fault.kind.clone();
+
+        if let Some(times) = &mut fault.times {
+            *times -= 1;
+            if *times == 0 {
+                faults.remove(index);
+            }
+        }
+
+        Some(kind)
+    }
+}
+
+impl HttpLayer for FaultInjectionLayer {
+    fn layer(&self, inner: Arc<dyn HttpSend>) -> Arc<dyn HttpSend> {
+        Arc::new(FaultInjectionService { inner, layer: self.clone() })
+    }
+}
+
+#[derive(Debug)]
+struct FaultInjectionService {
+    inner: Arc<dyn HttpSend>,
+    layer: FaultInjectionLayer,
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl HttpSend for FaultInjectionService {
+    async fn send_request(
+        &self,
+        request: http::Request<Bytes>,
+        timeout: Duration,
+    ) -> Result<http::Response<Bytes>, HttpError> {
+        let Some(kind) = self.layer.take_fault(&request) else {
+            return self.inner.send_request(request, timeout).await;
+        };
+
+        warn!(path = request.uri().path(), fault = ?kind, "Injecting a fault");
+
+        match kind {
+            FaultKind::Status(status) => Ok(Fault::error_response("M_UNKNOWN", json!({}), status)),
+            FaultKind::LimitExceeded(retry_after) => {
+                let retry_after_ms = u64::try_from(retry_after.as_millis()).unwrap_or(u64::MAX);
+                Ok(Fault::error_response(
+                    "M_LIMIT_EXCEEDED",
+                    json!({ "retry_after_ms": retry_after_ms }),
+                    StatusCode::TOO_MANY_REQUESTS,
+                ))
+            }
+            FaultKind::Delay(delay) => {
+                sleep(delay).await;
+                self.inner.send_request(request, timeout).await
+            }
+        }
+    }
+}
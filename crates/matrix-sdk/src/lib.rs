@@ -30,13 +30,29 @@ pub use ruma;
 mod account;
 pub mod attachment;
 mod client;
+#[cfg(feature = "sled")]
+pub mod client_manager;
 pub mod config;
 mod error;
 pub mod event_handler;
+mod guest;
 mod http_client;
+pub mod http_layers;
 pub mod media;
+pub mod moderation;
+pub mod notification_settings;
+#[cfg(feature = "experimental-oidc")]
+pub mod oidc;
+pub mod presence;
+#[cfg(feature = "experimental-qr-login")]
+pub mod rendezvous;
 pub mod room;
+pub mod search;
+mod soft_logout;
 pub mod sync;
+pub mod sync_service;
+pub mod uiaa;
+mod utils;
 
 #[cfg(feature = "experimental-sliding-sync")]
 pub mod sliding_sync;
@@ -53,8 +69,9 @@ pub use client::{Client, ClientBuildError, ClientBuilder, LoginBuilder, LoopCtrl
 #[cfg(feature = "image-proc")]
 pub use error::ImageError;
 pub use error::{Error, HttpError, HttpResult, RefreshTokenError, Result, RumaApiError};
-pub use http_client::HttpSend;
+pub use http_client::{EndpointName, HttpLayer, HttpSend};
 pub use media::Media;
+pub use notification_settings::NotificationSettings;
 pub use ruma::{IdParseError, OwnedServerName, ServerName};
 #[cfg(feature = "experimental-sliding-sync")]
 pub use sliding_sync::{
@@ -26,11 +26,12 @@ use mime::Mime;
 #[cfg(not(target_arch = "wasm32"))]
 use mime_guess;
 use ruma::{
-    api::client::media::{create_content, get_content, get_content_thumbnail},
+    api::client::media::{create_content, get_content, get_content_thumbnail, get_media_preview},
     assign,
     events::room::MediaSource,
-    MxcUri,
+    MilliSecondsSinceUnixEpoch, MxcUri, OwnedMxcUri, UInt,
 };
+use serde::{Deserialize, Serialize};
 #[cfg(not(target_arch = "wasm32"))]
 use tempfile::{Builder as TempFileBuilder, NamedTempFile};
 #[cfg(not(target_arch = "wasm32"))]
@@ -45,6 +46,10 @@ use crate::{
 const DEFAULT_UPLOAD_SPEED: u64 = 125_000;
 /// 5 min minimal upload request timeout, used to clamp the request timeout.
 const MIN_UPLOAD_REQUEST_TIMEOUT: Duration = Duration::from_secs(60 * 5);
+/// The prefix of the keys of cached URL previews in the media store.
+const URL_PREVIEW_KEY_PREFIX: &str = "url_preview:";
+/// How long a cached URL preview is used before it is fetched again.
+const URL_PREVIEW_TTL: Duration = Duration::from_secs(60 * 60 * 24);
 
 /// A high-level API to interact with the media API.
 #[derive(Debug, Clone)]
@@ -53,6 +58,66 @@ pub struct Media {
     client: Client,
 }
 
+/// The [OpenGraph] data of a URL, as returned by the homeserver.
+///
+/// [OpenGraph]: https://ogp.me/
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct UrlPreview {
+    /// The title of the page.
+    #[serde(rename = "og:title", skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    /// A short description of the page.
+    #[serde(rename = "og:description", skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// The canonical URL of the page.
+    #[serde(rename = "og:url", skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+
+    /// The name of the site the page is part of.
+    #[serde(rename = "og:site_name", skip_serializing_if = "Option::is_none")]
+    pub site_name: Option<String>,
+
+    /// The image of the page, uploaded to the media repository of the
+    /// homeserver.
+    #[serde(rename = "og:image", skip_serializing_if = "Option::is_none")]
+    pub image: Option<OwnedMxcUri>,
+
+    /// The mimetype of the image.
+    #[serde(rename = "og:image:type", skip_serializing_if = "Option::is_none")]
+    pub image_type: Option<String>,
+
+    /// The width of the image in pixels.
+    #[serde(rename = "og:image:width", skip_serializing_if = "Option::is_none")]
+    pub image_width: Option<UInt>,
+
+    /// The height of the image in pixels.
+    #[serde(rename = "og:image:height", skip_serializing_if = "Option::is_none")]
+    pub image_height: Option<UInt>,
+
+    /// The size of the image in bytes.
+    #[serde(rename = "matrix:image:size", skip_serializing_if = "Option::is_none")]
+    pub image_size: Option<UInt>,
+}
+
+/// A [`UrlPreview`] as it is cached in the media store.
+#[derive(Deserialize, Serialize)]
+struct CachedUrlPreview {
+    /// When the preview was fetched from the homeserver.
+    fetched_at: MilliSecondsSinceUnixEpoch,
+
+    /// The preview.
+    preview: UrlPreview,
+}
+
+/// The request under which the preview of the given URL is cached in the media
+/// store.
+fn url_preview_request(url: &str) -> MediaRequest {
+    let uri = OwnedMxcUri::from(format!("{URL_PREVIEW_KEY_PREFIX}{url}"));
+    MediaRequest { source: MediaSource::Plain(uri), format: MediaFormat::File }
+}
+
 /// A file handle that takes ownership of a media file on disk. When the handle
 /// is dropped, the file will be removed from the disk.
 #[derive(Debug)]
@@ -239,6 +304,81 @@ impl Media {
         Ok(self.client.store().remove_media_content_for_uri(uri).await?)
     }
 
+    /// Get a preview of the given URL.
+    ///
+    /// The homeserver fetches the URL and returns its [`UrlPreview`]. Previews
+    /// are cached per URL in the media store for a day, so subsequent calls
+    /// with the same URL don't make a request, whatever their `ts`. The cached
+    /// preview can be removed with [`Media::remove_url_preview()`].
+    ///
+    /// Note that the homeserver learns about every URL that is previewed, so
+    /// this should only be used with the consent of the user, especially in
+    /// encrypted rooms.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to get a preview of.
+    ///
+    /// * `ts` - The preferred point in time to return a preview for. The
+    ///   homeserver may return a newer version if it doesn't have the requested
+    ///   version available.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use futures::executor::block_on;
+    /// # use matrix_sdk::Client;
+    /// # use url::Url;
+    /// # block_on(async {
+    /// # let homeserver = Url::parse("http://localhost:8080")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// let preview =
+    ///     client.media().get_url_preview("https://matrix.org", None).await?;
+    ///
+    /// if let Some(title) = preview.title {
+    ///     println!("Title: {title}");
+    /// }
+    /// # anyhow::Ok(()) });
+    /// ```
+    pub async fn get_url_preview(
+        &self,
+        url: &str,
+        ts: Option<MilliSecondsSinceUnixEpoch>,
+    ) -> Result<UrlPreview> {
+        let cache_request = url_preview_request(url);
+
+        if let Some(cached) = self.client.store().get_media_content(&cache_request).await? {
+            if let Ok(cached) = serde_json::from_slice::<CachedUrlPreview>(&cached) {
+                let age = MilliSecondsSinceUnixEpoch::now().0.saturating_sub(cached.fetched_at.0);
+                if Duration::from_millis(age.into()) < URL_PREVIEW_TTL {
+                    return Ok(cached.preview);
+                }
+            }
+        }
+
+        let request = assign!(get_media_preview::v3::Request::new(url.to_owned()), { ts });
+        let response = self.client.send(request, None).await?;
+
+        let preview: UrlPreview = match response.data {
+            Some(data) => serde_json::from_str(data.get())?,
+            None => UrlPreview::default(),
+        };
+
+        let cached = CachedUrlPreview { fetched_at: MilliSecondsSinceUnixEpoch::now(), preview };
+        self.client.store().add_media_content(&cache_request, serde_json::to_vec(&cached)?).await?;
+
+        Ok(cached.preview)
+    }
+
+    /// Remove the cached preview of the given URL from the media store.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL whose preview should be removed.
+    pub async fn remove_url_preview(&self, url: &str) -> Result<()> {
+        self.remove_media_content(&url_preview_request(url)).await
+    }
+
     /// Get the file of the given media event content.
     ///
     /// If the content is encrypted and encryption is enabled, the content will
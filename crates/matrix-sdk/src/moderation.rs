@@ -0,0 +1,713 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! High-level moderation tools.
+//!
+//! [`Moderation`] can redact the recent events of a user across several rooms,
+//! and enforce the rules of [moderation policy lists] in the rooms protected by
+//! the client's user.
+//!
+//! [moderation policy lists]: https://spec.matrix.org/v1.6/client-server-api/#moderation-policy-lists
+
+use std::collections::BTreeSet;
+
+use futures_core::Stream;
+use matrix_sdk_common::instant::Duration;
+use ruma::{
+    events::{
+        policy::rule::{
+            room::PolicyRuleRoomEventContent, server::PolicyRuleServerEventContent,
+            user::PolicyRuleUserEventContent, PolicyRuleEventContent, Recommendation,
+        },
+        room::server_acl::RoomServerAclEventContent,
+        AnySyncMessageLikeEvent, AnySyncTimelineEvent, RedactContent, RedactedStateEventContent,
+        StaticStateEventContent, SyncStateEvent,
+    },
+    uint, MilliSecondsSinceUnixEpoch, OwnedEventId, OwnedRoomId, OwnedUserId, RoomId, ServerName,
+    UserId,
+};
+use serde::de::DeserializeOwned;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use crate::{
+    event_handler::{EventHandlerHandle, SyncEvent},
+    room::{self, MessagesOptions},
+    utils::sleep,
+    Client, Error, Result,
+};
+
+/// A high-level API for the moderation of rooms.
+///
+/// Get it with [`Client::moderation()`].
+#[derive(Debug, Clone)]
+pub struct Moderation {
+    client: Client,
+}
+
+impl Moderation {
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Redact the recent events sent by a user in the given rooms.
+    ///
+    /// The history of every room is paginated backwards until
+    /// [`BulkRedactionOptions::max_events_per_room`] events of the user are
+    /// found, or until an event older than [`BulkRedactionOptions::since`] is
+    /// reached. The redactions are sent in batches, with a delay between every
+    /// batch to avoid being rate-limited by the homeserver.
+    ///
+    /// Rooms that the client's user hasn't joined are ignored. Failing to
+    /// redact an event doesn't stop the process, the failures are listed in
+    /// the returned report instead.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::{moderation::BulkRedactionOptions, ruma::{room_id, user_id}, Client};
+    /// # async {
+    /// # let client: Client = todo!();
+    /// let options = BulkRedactionOptions::new().reason("Spam");
+    /// let report = client
+    ///     .moderation()
+    ///     .redact_user_events(
+    ///         user_id!("@spammer:localhost"),
+    ///         [room_id!("!room:localhost").to_owned()],
+    ///         options,
+    ///     )
+    ///     .await?;
+    ///
+    /// println!("Redacted {} events", report.redacted.len());
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn redact_user_events(
+        &self,
+        user_id: &UserId,
+        room_ids: impl IntoIterator<Item = OwnedRoomId>,
+        options: BulkRedactionOptions,
+    ) -> Result<BulkRedactionReport> {
+        let mut report = BulkRedactionReport::default();
+        let mut in_batch = 0;
+
+        for room_id in room_ids {
+            let Some(room) = self.client.get_joined_room(&room_id) else {
+                warn!(?room_id, "Not redacting events in a room that isn't joined");
+                continue;
+            };
+
+            let event_ids = self.find_user_events(&room, user_id, &options).await?;
+            debug!(?room_id, "Found {} events to redact", event_ids.len());
+
+            for event_id in event_ids {
+                if options.dry_run {
+                    report.redacted.push((room_id.clone(), event_id));
+                    continue;
+                }
+
+                if in_batch >= options.batch_size.max(1) {
+                    sleep(options.batch_delay).await;
+                    in_batch = 0;
+                }
+                in_batch += 1;
+
+                match room.redact(&event_id, options.reason.as_deref(), None).await {
+                    Ok(_) => report.redacted.push((room_id.clone(), event_id)),
+                    Err(error) => {
+                        warn!(?room_id, ?event_id, "Failed to redact event: {error}");
+                        report.failed.push((room_id.clone(), event_id, error.into()));
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Find the events sent by the given user that can be redacted, from the
+    /// most recent one.
+    async fn find_user_events(
+        &self,
+        room: &room::Joined,
+        user_id: &UserId,
+        options: &BulkRedactionOptions,
+    ) -> Result<Vec<OwnedEventId>> {
+        let mut event_ids = Vec::new();
+        let mut from = None;
+
+        loop {
+            let mut messages_options = MessagesOptions::backward().from(from.as_deref());
+            messages_options.limit = uint!(100);
+            messages_options.filter.senders = Some(vec![user_id.to_owned()]);
+
+            let messages = room.messages(messages_options).await?;
+
+            for event in &messages.chunk {
+                let Ok(event) = event.event.deserialize_as::<AnySyncTimelineEvent>() else {
+                    continue;
+                };
+
+                if options.since.map_or(false, |since| event.origin_server_ts() < since) {
+                    return Ok(event_ids);
+                }
+
+                // The homeserver should have filtered the events but we can't
+                // trust it to redact the right events.
+                if event.sender() != user_id || !is_redactable(&event) {
+                    continue;
+                }
+
+                event_ids.push(event.event_id().to_owned());
+                if event_ids.len() >= options.max_events_per_room {
+                    return Ok(event_ids);
+                }
+            }
+
+            match messages.end {
+                Some(end) if !messages.chunk.is_empty() => from = Some(end),
+                _ => return Ok(event_ids),
+            }
+        }
+    }
+
+    /// Get the current rules of the given policy lists.
+    ///
+    /// The rules are loaded from the state of the policy rooms in the store,
+    /// so the client's user must be a member of them and the client must have
+    /// synced.
+    pub async fn policy_list(
+        &self,
+        policy_room_ids: impl IntoIterator<Item = OwnedRoomId>,
+    ) -> Result<PolicyList> {
+        let mut list = PolicyList::default();
+
+        for room_id in policy_room_ids {
+            let Some(room) = self.client.get_room(&room_id) else {
+                warn!(?room_id, "Unknown policy room");
+                continue;
+            };
+
+            for raw in room.get_state_events_static::<PolicyRuleUserEventContent>().await? {
+                if let Some(rule) = PolicyRule::from_event(&room_id, raw.deserialize(), |c| c.0) {
+                    list.user_rules.push(rule);
+                }
+            }
+            for raw in room.get_state_events_static::<PolicyRuleRoomEventContent>().await? {
+                if let Some(rule) = PolicyRule::from_event(&room_id, raw.deserialize(), |c| c.0) {
+                    list.room_rules.push(rule);
+                }
+            }
+            for raw in room.get_state_events_static::<PolicyRuleServerEventContent>().await? {
+                if let Some(rule) = PolicyRule::from_event(&room_id, raw.deserialize(), |c| c.0) {
+                    list.server_rules.push(rule);
+                }
+            }
+        }
+
+        Ok(list)
+    }
+
+    /// Subscribe to the rules of the given policy lists.
+    ///
+    /// The stream first yields the current rules, like
+    /// [`Moderation::policy_list()`], and then the updated rules every time a
+    /// rule changes in one of the policy rooms. The client must be syncing for
+    /// the stream to receive updates.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use futures::{pin_mut, StreamExt};
+    /// # use matrix_sdk::{ruma::room_id, Client};
+    /// # async {
+    /// # let client: Client = todo!();
+    /// let moderation = client.moderation();
+    /// let protected_rooms = vec![room_id!("!protected:localhost").to_owned()];
+    /// let policy_list = moderation
+    ///     .subscribe_to_policy_list([room_id!("!policies:localhost").to_owned()]);
+    /// pin_mut!(policy_list);
+    ///
+    /// while let Some(policy_list) = policy_list.next().await {
+    ///     moderation
+    ///         .enforce_policy_list(&policy_list, &protected_rooms, false)
+    ///         .await?;
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub fn subscribe_to_policy_list(
+        &self,
+        policy_room_ids: impl IntoIterator<Item = OwnedRoomId>,
+    ) -> impl Stream<Item = PolicyList> {
+        let policy_room_ids: BTreeSet<_> = policy_room_ids.into_iter().collect();
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+
+        // The state store is updated before the event handlers are called, so
+        // the handlers only need to notify the stream that it should reload
+        // the rules.
+        let handles = [
+            self.add_policy_rule_handler::<PolicyRuleUserEventContent>(&sender),
+            self.add_policy_rule_handler::<PolicyRuleRoomEventContent>(&sender),
+            self.add_policy_rule_handler::<PolicyRuleServerEventContent>(&sender),
+        ];
+        let guards = handles.map(|handle| self.client.event_handler_drop_guard(handle));
+        let moderation = self.clone();
+
+        async_stream::stream! {
+            // Keep the event handlers alive as long as the stream.
+            let _guards = guards;
+            let mut reload = true;
+
+            loop {
+                if reload {
+                    match moderation.policy_list(policy_room_ids.iter().cloned()).await {
+                        Ok(list) => yield list,
+                        Err(error) => warn!("Failed to load the policy list: {error}"),
+                    }
+                }
+
+                let Some(room_id) = receiver.recv().await else { break };
+                reload = policy_room_ids.contains(&room_id);
+            }
+        }
+    }
+
+    fn add_policy_rule_handler<C>(
+        &self,
+        sender: &mpsc::UnboundedSender<OwnedRoomId>,
+    ) -> EventHandlerHandle
+    where
+        SyncStateEvent<C>: SyncEvent + DeserializeOwned + Send + 'static,
+    {
+        let sender = sender.clone();
+        self.client.add_event_handler(move |_: SyncStateEvent<C>, room: room::Room| {
+            let sender = sender.clone();
+            async move {
+                let _ = sender.send(room.room_id().to_owned());
+            }
+        })
+    }
+
+    /// Enforce the rules of a policy list in the given protected rooms.
+    ///
+    /// Only rules with the [`Recommendation::Ban`] recommendation are
+    /// enforced:
+    ///
+    /// * Members of the protected rooms matching a user rule, or whose server
+    ///   matches a server rule, are banned.
+    /// * The servers matching server rules are added to the server ACL of the
+    ///   protected rooms.
+    ///
+    /// Server rules that match the server of the client's user are ignored,
+    /// to avoid locking it out of the rooms.
+    ///
+    /// In dry-run mode, no request is sent and the returned report lists the
+    /// actions that would have been taken. Otherwise, failing to apply an
+    /// action doesn't stop the process, the failures are listed in the
+    /// returned report.
+    pub async fn enforce_policy_list(
+        &self,
+        policy_list: &PolicyList,
+        protected_room_ids: &[OwnedRoomId],
+        dry_run: bool,
+    ) -> Result<EnforcementReport> {
+        let own_user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?;
+        let mut report = EnforcementReport::default();
+
+        for room_id in protected_room_ids {
+            let Some(room) = self.client.get_joined_room(room_id) else {
+                warn!(?room_id, "Not enforcing policies in a room that isn't joined");
+                continue;
+            };
+
+            let mut actions = Vec::new();
+
+            for member in room.joined_members().await? {
+                let user_id = member.user_id();
+                if user_id == own_user_id {
+                    continue;
+                }
+
+                // Server rules matching the own server are ignored, like in the
+                // server ACL.
+                let rule = policy_list
+                    .user_rules
+                    .iter()
+                    .find(|rule| rule.is_ban() && rule.matches(user_id.as_str()))
+                    .or_else(|| {
+                        policy_list.server_rules.iter().find(|rule| {
+                            rule.is_ban()
+                                && rule.matches(user_id.server_name().as_str())
+                                && !rule.matches(own_user_id.server_name().as_str())
+                        })
+                    });
+
+                if let Some(rule) = rule {
+                    actions.push(EnforcementAction::Ban {
+                        room_id: room_id.clone(),
+                        user_id: user_id.to_owned(),
+                        reason: rule.reason.clone(),
+                    });
+                }
+            }
+
+            if let Some(action) = self.server_acl_action(&room, policy_list, own_user_id).await? {
+                actions.push(action);
+            }
+
+            for action in actions {
+                if !dry_run {
+                    if let Err(error) = action.apply(&room).await {
+                        warn!(?room_id, ?action, "Failed to apply enforcement action: {error}");
+                        report.failed.push((action, error));
+                        continue;
+                    }
+                }
+
+                report.applied.push(action);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Compute the server ACL update needed to deny the servers matching the
+    /// server rules of the policy list in the given room, if any.
+    async fn server_acl_action(
+        &self,
+        room: &room::Joined,
+        policy_list: &PolicyList,
+        own_user_id: &UserId,
+    ) -> Result<Option<EnforcementAction>> {
+        let own_server = own_user_id.server_name();
+        let mut content = room
+            .get_state_event_static::<RoomServerAclEventContent>()
+            .await?
+            .and_then(|raw| raw.deserialize().ok())
+            .and_then(|event| event.as_original().map(|event| event.content.clone()))
+            .unwrap_or_else(|| RoomServerAclEventContent::new(true, vec!["*".to_owned()], vec![]));
+
+        let mut changed = false;
+        for rule in policy_list.server_rules.iter().filter(|rule| rule.is_ban()) {
+            if rule.matches(own_server.as_str()) {
+                warn!(entity = rule.entity, "Ignoring server rule matching the own server");
+                continue;
+            }
+
+            if !content.deny.contains(&rule.entity) {
+                content.deny.push(rule.entity.clone());
+                changed = true;
+            }
+        }
+
+        Ok(changed.then(|| EnforcementAction::UpdateServerAcl {
+            room_id: room.room_id().to_owned(),
+            content,
+        }))
+    }
+}
+
+impl Client {
+    /// Get the moderation tools of the client.
+    pub fn moderation(&self) -> Moderation {
+        Moderation::new(self.clone())
+    }
+}
+
+/// Options for [`Moderation::redact_user_events()`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct BulkRedactionOptions {
+    /// The reason of the redactions.
+    pub reason: Option<String>,
+
+    /// The maximum number of events to redact in each room.
+    ///
+    /// Default: 100.
+    pub max_events_per_room: usize,
+
+    /// Only redact events sent after this time.
+    pub since: Option<MilliSecondsSinceUnixEpoch>,
+
+    /// The number of redactions to send before waiting for
+    /// [`BulkRedactionOptions::batch_delay`]. A batch size of 0 is treated as
+    /// 1.
+    ///
+    /// Default: 10.
+    pub batch_size: usize,
+
+    /// The time to wait between batches of redactions.
+    ///
+    /// Default: 1 second.
+    pub batch_delay: Duration,
+
+    /// Whether to only list the events that would be redacted, without
+    /// redacting them.
+    pub dry_run: bool,
+}
+
+impl BulkRedactionOptions {
+    /// Creates `BulkRedactionOptions` with the default parameters.
+    pub fn new() -> Self {
+        Self {
+            reason: None,
+            max_events_per_room: 100,
+            since: None,
+            batch_size: 10,
+            batch_delay: Duration::from_secs(1),
+            dry_run: false,
+        }
+    }
+
+    /// Creates a new `BulkRedactionOptions` from `self` with the given reason.
+    pub fn reason(self, reason: impl Into<String>) -> Self {
+        Self { reason: Some(reason.into()), ..self }
+    }
+
+    /// Creates a new `BulkRedactionOptions` from `self` that only redacts
+    /// events sent after the given time.
+    pub fn since(self, since: MilliSecondsSinceUnixEpoch) -> Self {
+        Self { since: Some(since), ..self }
+    }
+
+    /// Creates a new `BulkRedactionOptions` from `self` with the given batch
+    /// size and delay between batches.
+    ///
+    /// A batch size of 0 is treated as 1.
+    pub fn batching(self, batch_size: usize, batch_delay: Duration) -> Self {
+        Self { batch_size: batch_size.max(1), batch_delay, ..self }
+    }
+
+    /// Creates a new `BulkRedactionOptions` from `self` in dry-run mode.
+    pub fn dry_run(self) -> Self {
+        Self { dry_run: true, ..self }
+    }
+}
+
+impl Default for BulkRedactionOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The result of [`Moderation::redact_user_events()`].
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct BulkRedactionReport {
+    /// The events that were redacted, or that would have been redacted in
+    /// dry-run mode.
+    pub redacted: Vec<(OwnedRoomId, OwnedEventId)>,
+
+    /// The events that could not be redacted, with the error that occurred.
+    pub failed: Vec<(OwnedRoomId, OwnedEventId, Error)>,
+}
+
+/// The rules of a set of moderation policy lists.
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
+pub struct PolicyList {
+    /// The rules affecting users.
+    pub user_rules: Vec<PolicyRule>,
+
+    /// The rules affecting rooms.
+    pub room_rules: Vec<PolicyRule>,
+
+    /// The rules affecting servers.
+    pub server_rules: Vec<PolicyRule>,
+}
+
+impl PolicyList {
+    /// Get the first ban rule matching the given user, either directly or
+    /// through their server.
+    pub fn matching_user_rule(&self, user_id: &UserId) -> Option<&PolicyRule> {
+        self.user_rules
+            .iter()
+            .find(|rule| rule.is_ban() && rule.matches(user_id.as_str()))
+            .or_else(|| self.matching_server_rule(user_id.server_name()))
+    }
+
+    /// Get the first ban rule matching the given room.
+    pub fn matching_room_rule(&self, room_id: &RoomId) -> Option<&PolicyRule> {
+        self.room_rules.iter().find(|rule| rule.is_ban() && rule.matches(room_id.as_str()))
+    }
+
+    /// Get the first ban rule matching the given server.
+    pub fn matching_server_rule(&self, server_name: &ServerName) -> Option<&PolicyRule> {
+        self.server_rules.iter().find(|rule| rule.is_ban() && rule.matches(server_name.as_str()))
+    }
+}
+
+/// A rule of a moderation policy list.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct PolicyRule {
+    /// The ID of the policy room that contains the rule.
+    pub policy_room_id: OwnedRoomId,
+
+    /// The entity affected by the rule, which can contain `*` and `?` globs.
+    pub entity: String,
+
+    /// The suggested action to take.
+    pub recommendation: Recommendation,
+
+    /// The reason of the rule.
+    pub reason: String,
+}
+
+impl PolicyRule {
+    fn from_event<C>(
+        policy_room_id: &RoomId,
+        event: serde_json::Result<SyncStateEvent<C>>,
+        into_content: impl FnOnce(C) -> PolicyRuleEventContent,
+    ) -> Option<Self>
+    where
+        C: StaticStateEventContent + RedactContent,
+        C::Redacted: RedactedStateEventContent,
+    {
+        // Redacted rules are removed from the list.
+        let SyncStateEvent::Original(event) = event.ok()? else {
+            return None;
+        };
+        let content = into_content(event.content);
+
+        Some(Self {
+            policy_room_id: policy_room_id.to_owned(),
+            entity: content.entity,
+            recommendation: content.recommendation,
+            reason: content.reason,
+        })
+    }
+
+    /// Whether this rule recommends to ban the entity.
+    pub fn is_ban(&self) -> bool {
+        self.recommendation == Recommendation::Ban
+    }
+
+    /// Whether the entity of this rule matches the given value.
+    pub fn matches(&self, value: &str) -> bool {
+        glob_matches(&self.entity, value)
+    }
+}
+
+/// An action taken to enforce a policy list.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum EnforcementAction {
+    /// Ban a user from a room.
+    Ban {
+        /// The ID of the room.
+        room_id: OwnedRoomId,
+        /// The ID of the user.
+        user_id: OwnedUserId,
+        /// The reason of the ban, from the matching rule.
+        reason: String,
+    },
+
+    /// Replace the server ACL of a room.
+    UpdateServerAcl {
+        /// The ID of the room.
+        room_id: OwnedRoomId,
+        /// The new server ACL.
+        content: RoomServerAclEventContent,
+    },
+}
+
+impl EnforcementAction {
+    async fn apply(&self, room: &room::Joined) -> Result<()> {
+        match self {
+            Self::Ban { user_id, reason, .. } => {
+                let reason = (!reason.is_empty()).then_some(reason.as_str());
+                room.ban_user(user_id, reason).await
+            }
+            Self::UpdateServerAcl { content, .. } => {
+                room.send_state_event(content.clone()).await?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The result of [`Moderation::enforce_policy_list()`].
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct EnforcementReport {
+    /// The actions that were applied, or that would have been applied in
+    /// dry-run mode.
+    pub applied: Vec<EnforcementAction>,
+
+    /// The actions that could not be applied, with the error that occurred.
+    pub failed: Vec<(EnforcementAction, Error)>,
+}
+
+/// Whether the event can be redacted by a moderator.
+fn is_redactable(event: &AnySyncTimelineEvent) -> bool {
+    match event {
+        // Redacting a redaction has no effect.
+        AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomRedaction(_)) => false,
+        AnySyncTimelineEvent::MessageLike(event) => event.original_content().is_some(),
+        AnySyncTimelineEvent::State(event) => event.original_content().is_some(),
+    }
+}
+
+/// Match a value against a glob pattern where `*` matches any number of
+/// characters and `?` matches a single character.
+fn glob_matches(pattern: &str, value: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let value: Vec<char> = value.chars().collect();
+
+    let (mut p, mut v) = (0, 0);
+    // The position of the last `*` in the pattern and of the value when it
+    // was reached, to backtrack to.
+    let mut backtrack = None;
+
+    while v < value.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, v));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == value[v] => {
+                p += 1;
+                v += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_v)) => {
+                    p = star_p + 1;
+                    v = star_v + 1;
+                    backtrack = Some((star_p, star_v + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_matches;
+
+    #[test]
+    fn glob() {
+        assert!(glob_matches("@spammer:example.org", "@spammer:example.org"));
+        assert!(!glob_matches("@spammer:example.org", "@spammer:example.com"));
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("*.example.org", "evil.example.org"));
+        assert!(!glob_matches("*.example.org", "example.org"));
+        assert!(glob_matches("@*:example.org", "@anyone:example.org"));
+        assert!(glob_matches("@spam??:*", "@spam42:example.org"));
+        assert!(!glob_matches("@spam??:*", "@spam4:example.org"));
+        assert!(glob_matches("*a*b", "xaxxab"));
+    }
+}
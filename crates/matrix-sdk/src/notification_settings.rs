@@ -0,0 +1,409 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! High-level notification settings API, built on top of push rules.
+//!
+//! Most clients offer a per-room choice between receiving a notification for
+//! every message, only for mentions and keywords, or for nothing at all. The
+//! [`NotificationSettings`] type maps these modes to the push rules of the
+//! account and back.
+
+use std::{fmt::Debug, sync::Arc};
+
+use ruma::{
+    api::{
+        client::push::{
+            delete_pushrule, set_pushrule, set_pushrule_actions, set_pushrule_enabled, RuleScope,
+        },
+        error::FromHttpResponseError,
+        OutgoingRequest,
+    },
+    events::push_rules::PushRulesEvent,
+    push::{
+        Action, ConditionalPushRule, NewConditionalPushRule, NewPatternedPushRule, NewPushRule,
+        NewSimplePushRule, PushCondition, RuleKind, Ruleset, Tweak,
+    },
+    RoomId,
+};
+use thiserror::Error;
+use tokio::sync::{broadcast, RwLock};
+
+use crate::{event_handler::EventHandlerDropGuard, Client, HttpError, Result};
+
+/// The rule ID of the default rule for messages in one-to-one rooms.
+const ROOM_ONE_TO_ONE_RULE_ID: &str = ".m.rule.room_one_to_one";
+/// The rule ID of the default rule for encrypted messages in one-to-one rooms.
+const ENCRYPTED_ROOM_ONE_TO_ONE_RULE_ID: &str = ".m.rule.encrypted_room_one_to_one";
+/// The rule ID of the default rule for messages in group rooms.
+const MESSAGE_RULE_ID: &str = ".m.rule.message";
+/// The rule ID of the default rule for encrypted messages in group rooms.
+const ENCRYPTED_RULE_ID: &str = ".m.rule.encrypted";
+
+/// The notification mode of a room.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoomNotificationMode {
+    /// Receive notifications for all messages.
+    AllMessages,
+    /// Only receive notifications for mentions and keywords.
+    MentionsAndKeywordsOnly,
+    /// Do not receive any notifications.
+    Mute,
+}
+
+/// Errors that can occur when changing the notification settings.
+#[derive(Debug, Error)]
+pub enum NotificationSettingsError {
+    /// The default notification mode of rooms can't be set to
+    /// [`RoomNotificationMode::Mute`].
+    #[error("rooms can't be muted by default")]
+    InvalidDefaultMode,
+}
+
+/// A high-level API to read and edit the notification settings of the account.
+///
+/// The push rules are cached when this type is created, and replaced when a
+/// new `m.push_rules` account data event is received. Changes are applied to
+/// the cache before they are sent to the homeserver, so they are visible
+/// immediately, and rolled back if the homeserver rejects them.
+#[derive(Debug, Clone)]
+pub struct NotificationSettings {
+    client: Client,
+    rules: Arc<RwLock<Ruleset>>,
+    changes_sender: broadcast::Sender<()>,
+    _push_rules_handle: Arc<EventHandlerDropGuard>,
+}
+
+impl NotificationSettings {
+    pub(crate) async fn new(client: Client) -> Result<Self> {
+        let rules = Arc::new(RwLock::new(client.account().push_rules().await?));
+        let (changes_sender, _) = broadcast::channel(16);
+
+        let handle = client.add_event_handler({
+            let rules = rules.clone();
+            let changes_sender = changes_sender.clone();
+            move |event: PushRulesEvent| {
+                let rules = rules.clone();
+                let changes_sender = changes_sender.clone();
+                async move {
+                    *rules.write().await = event.content.global;
+                    let _ = changes_sender.send(());
+                }
+            }
+        });
+        let push_rules_handle = Arc::new(client.event_handler_drop_guard(handle));
+
+        Ok(Self { client, rules, changes_sender, _push_rules_handle: push_rules_handle })
+    }
+
+    /// Subscribe to changes of the notification settings.
+    ///
+    /// A message is sent every time the push rules change, either because of a
+    /// change made with this API or because a new `m.push_rules` event was
+    /// received.
+    pub fn subscribe_to_changes(&self) -> broadcast::Receiver<()> {
+        self.changes_sender.subscribe()
+    }
+
+    /// Get the current push rules.
+    pub async fn rules(&self) -> Ruleset {
+        self.rules.read().await.clone()
+    }
+
+    /// Get the notification mode of the given room, as set by the user.
+    ///
+    /// Returns `None` if the user hasn't set a mode for this room, in which
+    /// case the default mode applies.
+    pub async fn user_defined_room_notification_mode(
+        &self,
+        room_id: &RoomId,
+    ) -> Option<RoomNotificationMode> {
+        let rules = self.rules.read().await;
+
+        if rules.override_.iter().any(|rule| is_mute_rule(rule, room_id)) {
+            return Some(RoomNotificationMode::Mute);
+        }
+
+        rules.room.iter().find(|rule| rule.enabled && *rule.rule_id == *room_id).map(|rule| {
+            if rule.actions.iter().any(Action::should_notify) {
+                RoomNotificationMode::AllMessages
+            } else {
+                RoomNotificationMode::MentionsAndKeywordsOnly
+            }
+        })
+    }
+
+    /// Get the default notification mode for rooms with the given properties.
+    ///
+    /// # Arguments
+    ///
+    /// * `is_encrypted` - Whether the room is encrypted.
+    ///
+    /// * `is_one_to_one` - Whether the room has exactly two members, like most
+    ///   DMs.
+    pub async fn default_room_notification_mode(
+        &self,
+        is_encrypted: bool,
+        is_one_to_one: bool,
+    ) -> RoomNotificationMode {
+        let rule_id = default_rule_id(is_encrypted, is_one_to_one);
+        let rules = self.rules.read().await;
+
+        let notifies = rules.underride.iter().any(|rule| {
+            rule.rule_id == rule_id
+                && rule.enabled
+                && rule.actions.iter().any(Action::should_notify)
+        });
+
+        if notifies {
+            RoomNotificationMode::AllMessages
+        } else {
+            RoomNotificationMode::MentionsAndKeywordsOnly
+        }
+    }
+
+    /// Get the effective notification mode of the given room.
+    ///
+    /// This is the mode set by the user if there is one, or the default mode
+    /// for rooms with the same properties otherwise.
+    pub async fn room_notification_mode(
+        &self,
+        room_id: &RoomId,
+        is_encrypted: bool,
+        is_one_to_one: bool,
+    ) -> RoomNotificationMode {
+        match self.user_defined_room_notification_mode(room_id).await {
+            Some(mode) => mode,
+            None => self.default_room_notification_mode(is_encrypted, is_one_to_one).await,
+        }
+    }
+
+    /// Set the notification mode of the given room.
+    pub async fn set_room_notification_mode(
+        &self,
+        room_id: &RoomId,
+        mode: RoomNotificationMode,
+    ) -> Result<()> {
+        match mode {
+            RoomNotificationMode::Mute => {
+                self.delete_room_rule(room_id).await?;
+
+                let rule = NewPushRule::Override(NewConditionalPushRule::new(
+                    room_id.to_string(),
+                    vec![PushCondition::EventMatch {
+                        key: "room_id".to_owned(),
+                        pattern: room_id.to_string(),
+                    }],
+                    vec![],
+                ));
+                self.insert_rule(rule).await?;
+            }
+            RoomNotificationMode::AllMessages | RoomNotificationMode::MentionsAndKeywordsOnly => {
+                self.delete_mute_rule(room_id).await?;
+
+                let actions = if mode == RoomNotificationMode::AllMessages {
+                    vec![Action::Notify]
+                } else {
+                    vec![]
+                };
+                let rule = NewPushRule::Room(NewSimplePushRule::new(room_id.to_owned(), actions));
+                self.insert_rule(rule).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove the notification mode set by the user for the given room, so the
+    /// default mode applies again.
+    pub async fn delete_user_defined_room_rules(&self, room_id: &RoomId) -> Result<()> {
+        self.delete_mute_rule(room_id).await?;
+        self.delete_room_rule(room_id).await
+    }
+
+    /// Set the default notification mode for rooms with the given properties.
+    ///
+    /// Returns an error if `mode` is [`RoomNotificationMode::Mute`], rooms can
+    /// only be muted individually.
+    pub async fn set_default_room_notification_mode(
+        &self,
+        is_encrypted: bool,
+        is_one_to_one: bool,
+        mode: RoomNotificationMode,
+    ) -> Result<()> {
+        let rule_id = default_rule_id(is_encrypted, is_one_to_one);
+
+        let actions = match mode {
+            RoomNotificationMode::AllMessages if is_one_to_one => {
+                vec![Action::Notify, Action::SetTweak(Tweak::Sound("default".to_owned()))]
+            }
+            RoomNotificationMode::AllMessages => vec![Action::Notify],
+            RoomNotificationMode::MentionsAndKeywordsOnly => vec![],
+            RoomNotificationMode::Mute => {
+                return Err(NotificationSettingsError::InvalidDefaultMode.into())
+            }
+        };
+
+        let request = set_pushrule_actions::v3::Request::new(
+            RuleScope::Global,
+            RuleKind::Underride,
+            rule_id.to_owned(),
+            actions.clone(),
+        );
+        self.update_rules(request, |rules| {
+            let _ = rules.set_actions(RuleKind::Underride, rule_id, actions);
+        })
+        .await?;
+
+        let request = set_pushrule_enabled::v3::Request::new(
+            RuleScope::Global,
+            RuleKind::Underride,
+            rule_id.to_owned(),
+            true,
+        );
+        self.update_rules(request, |rules| {
+            let _ = rules.set_enabled(RuleKind::Underride, rule_id, true);
+        })
+        .await
+    }
+
+    /// Get the keywords that trigger a notification.
+    pub async fn keywords(&self) -> Vec<String> {
+        let rules = self.rules.read().await;
+        rules.content.iter().filter(|rule| !rule.default).map(|rule| rule.pattern.clone()).collect()
+    }
+
+    /// Add a keyword that triggers a notification when it appears in a
+    /// message.
+    pub async fn add_keyword(&self, keyword: String) -> Result<()> {
+        if self.keywords().await.contains(&keyword) {
+            return Ok(());
+        }
+
+        let rule = NewPushRule::Content(NewPatternedPushRule::new(
+            keyword.clone(),
+            keyword,
+            vec![Action::Notify, Action::SetTweak(Tweak::Sound("default".to_owned()))],
+        ));
+        self.insert_rule(rule).await
+    }
+
+    /// Remove a keyword that was added with [`add_keyword()`].
+    ///
+    /// [`add_keyword()`]: Self::add_keyword
+    pub async fn remove_keyword(&self, keyword: &str) -> Result<()> {
+        let rule_ids: Vec<_> = {
+            let rules = self.rules.read().await;
+            rules
+                .content
+                .iter()
+                .filter(|rule| !rule.default && rule.pattern == keyword)
+                .map(|rule| rule.rule_id.clone())
+                .collect()
+        };
+
+        for rule_id in rule_ids {
+            self.delete_rule(RuleKind::Content, rule_id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply the given change to the cached rules, then send the request that
+    /// makes the same change on the homeserver.
+    ///
+    /// If the request fails, the cached rules are restored to their previous
+    /// state.
+    async fn update_rules<Request>(
+        &self,
+        request: Request,
+        update: impl FnOnce(&mut Ruleset),
+    ) -> Result<()>
+    where
+        Request: OutgoingRequest + Clone + Debug,
+        HttpError: From<FromHttpResponseError<Request::EndpointError>>,
+    {
+        let previous_rules = {
+            let mut rules = self.rules.write().await;
+            let previous_rules = rules.clone();
+            update(&mut rules);
+            previous_rules
+        };
+        let _ = self.changes_sender.send(());
+
+        if let Err(e) = self.client.send(request, None).await {
+            *self.rules.write().await = previous_rules;
+            let _ = self.changes_sender.send(());
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    async fn insert_rule(&self, rule: NewPushRule) -> Result<()> {
+        let request = set_pushrule::v3::Request::new(RuleScope::Global, rule.clone());
+        self.update_rules(request, |rules| {
+            let _ = rules.insert(rule, None, None);
+        })
+        .await
+    }
+
+    async fn delete_rule(&self, kind: RuleKind, rule_id: String) -> Result<()> {
+        let request =
+            delete_pushrule::v3::Request::new(RuleScope::Global, kind.clone(), rule_id.clone());
+        self.update_rules(request, |rules| {
+            let _ = rules.remove(kind, rule_id);
+        })
+        .await
+    }
+
+    async fn delete_mute_rule(&self, room_id: &RoomId) -> Result<()> {
+        let exists =
+            self.rules.read().await.override_.iter().any(|rule| is_mute_rule(rule, room_id));
+        if exists {
+            self.delete_rule(RuleKind::Override, room_id.to_string()).await?;
+        }
+        Ok(())
+    }
+
+    async fn delete_room_rule(&self, room_id: &RoomId) -> Result<()> {
+        let exists = self.rules.read().await.room.iter().any(|rule| *rule.rule_id == *room_id);
+        if exists {
+            self.delete_rule(RuleKind::Room, room_id.to_string()).await?;
+        }
+        Ok(())
+    }
+}
+
+/// The ID of the underride rule that sets the default mode for rooms with the
+/// given properties.
+fn default_rule_id(is_encrypted: bool, is_one_to_one: bool) -> &'static str {
+    match (is_encrypted, is_one_to_one) {
+        (true, true) => ENCRYPTED_ROOM_ONE_TO_ONE_RULE_ID,
+        (false, true) => ROOM_ONE_TO_ONE_RULE_ID,
+        (true, false) => ENCRYPTED_RULE_ID,
+        (false, false) => MESSAGE_RULE_ID,
+    }
+}
+
+/// Whether the given override rule mutes the room with the given ID.
+fn is_mute_rule(rule: &ConditionalPushRule, room_id: &RoomId) -> bool {
+    rule.enabled
+        && rule.rule_id == room_id.as_str()
+        && !rule.actions.iter().any(Action::should_notify)
+        && matches!(
+            rule.conditions.as_slice(),
+            [PushCondition::EventMatch { key, pattern }]
+                if key == "room_id" && pattern == room_id.as_str()
+        )
+}
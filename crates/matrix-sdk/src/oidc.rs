@@ -0,0 +1,888 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! High-level API for logging in with [OpenID Connect].
+//!
+//! This implements the native OpenID Connect login of the Matrix
+//! specification proposals:
+//!
+//! * [MSC2964]: the authorization code flow with PKCE,
+//! * [MSC2965]: the discovery of the OpenID Connect provider,
+//! * [MSC2966]: the dynamic registration of the client.
+//!
+//! The login happens in three steps:
+//!
+//! 1. Register the client with the provider with [`Oidc::register_client()`],
+//!    or restore a previous registration with
+//!    [`Oidc::restore_registered_client()`].
+//! 2. Get the URL to present to the user with [`Oidc::url_for_login()`].
+//! 3. Once the provider redirected the user to the redirect URI, pass the full
+//!    URL to [`Oidc::finish_login()`].
+//!
+//! When [`ClientBuilder::handle_refresh_tokens()`] is used, the access token
+//! is refreshed with the provider, and the new tokens are published with
+//! [`Client::session_tokens_stream()`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use matrix_sdk::{oidc::ClientMetadata, Client};
+//! # use url::Url;
+//! # async {
+//! # let client: Client = todo!();
+//! # fn open_in_browser(_: &Url) {}
+//! # async fn wait_for_redirect() -> Url { todo!() }
+//! let oidc = client.oidc();
+//! let issuer =
+//!     client.authentication_issuer().await.expect("OIDC is not supported");
+//! let redirect_uri = Url::parse("http://127.0.0.1:1234/callback")?;
+//!
+//! let metadata = ClientMetadata::new(
+//!     Url::parse("https://example.org")?,
+//!     vec![redirect_uri.clone()],
+//! )
+//! .client_name("My App");
+//! let registration = oidc.register_client(&issuer, metadata).await?;
+//! // Persist `registration` to reuse it for the next logins.
+//!
+//! let data = oidc.url_for_login(&redirect_uri).await?;
+//! open_in_browser(&data.url);
+//!
+//! let callback_url = wait_for_redirect().await;
+//! oidc.finish_login(&callback_url).await?;
+//! # anyhow::Ok(()) };
+//! ```
+//!
+//! [OpenID Connect]: https://openid.net/connect/
+//! [MSC2964]: https://github.com/matrix-org/matrix-spec-proposals/pull/2964
+//! [MSC2965]: https://github.com/matrix-org/matrix-spec-proposals/pull/2965
+//! [MSC2966]: https://github.com/matrix-org/matrix-spec-proposals/pull/2966
+//! [`ClientBuilder::handle_refresh_tokens()`]: crate::ClientBuilder::handle_refresh_tokens
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        RwLock as StdRwLock,
+    },
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine};
+use bytes::Bytes;
+use dashmap::DashMap;
+use http::{header, Method, StatusCode};
+use matrix_sdk_base::Session;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use ruma::{
+    api::client::account::{refresh_token, whoami},
+    DeviceId, OwnedDeviceId,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tracing::{debug, instrument, warn};
+use url::Url;
+
+use crate::{Client, HttpError};
+
+/// The scope giving full access to the Matrix client-server API.
+const SCOPE_MATRIX_API: &str = "urn:matrix:org.matrix.msc2967.client:api:*";
+
+/// The prefix of the scope requesting a device ID.
+const SCOPE_MATRIX_DEVICE_PREFIX: &str = "urn:matrix:org.matrix.msc2967.client:device:";
+
+/// The key of the registration of the client in the state store, when the
+/// session is persisted.
+const REGISTRATION_KEY: &[u8] = b"matrix-sdk.oidc_registration";
+
+/// The state of the OpenID Connect login, shared by all the [`Oidc`] handles
+/// of a [`Client`].
+#[derive(Debug, Default)]
+pub(crate) struct OidcContext {
+    /// The metadata of the last discovered provider of the registered client.
+    provider_metadata: StdRwLock<Option<ProviderMetadata>>,
+    /// The registration of the client with the provider.
+    registration: StdRwLock<Option<ClientRegistration>>,
+    /// Whether the current session was obtained with OpenID Connect.
+    logged_in: AtomicBool,
+    /// The ongoing authorizations, by `state` parameter.
+    pending_authorizations: DashMap<String, PendingAuthorization>,
+}
+
+impl OidcContext {
+    /// Whether the session of the client was obtained with OpenID Connect, in
+    /// which case the tokens must be refreshed with the provider.
+    pub(crate) fn is_logged_in(&self) -> bool {
+        self.logged_in.load(Ordering::SeqCst)
+    }
+}
+
+/// The data of an authorization that was started with
+/// [`Oidc::url_for_login()`].
+#[derive(Debug)]
+struct PendingAuthorization {
+    redirect_uri: Url,
+    code_verifier: String,
+    device_id: OwnedDeviceId,
+}
+
+/// A high-level API to log in with OpenID Connect.
+///
+/// Get it with [`Client::oidc()`]. See the [module documentation] for an
+/// overview of the login flow.
+///
+/// [module documentation]: crate::oidc
+#[derive(Debug, Clone)]
+pub struct Oidc {
+    client: Client,
+}
+
+impl Oidc {
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    fn ctx(&self) -> &OidcContext {
+        &self.client.inner.oidc
+    }
+
+    /// Get the registration of the client, if any.
+    pub fn client_registration(&self) -> Option<ClientRegistration> {
+        self.ctx().registration.read().unwrap().clone()
+    }
+
+    /// Discover the metadata of the given OpenID Connect provider.
+    ///
+    /// The issuer of the provider trusted by the homeserver is available with
+    /// [`Client::authentication_issuer()`].
+    pub async fn discover(&self, issuer: &str) -> Result<ProviderMetadata, OidcError> {
+        let url = format!("{}/.well-known/openid-configuration", issuer.trim_end_matches('/'));
+        let request = http::Request::builder()
+            .method(Method::GET)
+            .uri(url)
+            .header(header::ACCEPT, "application/json")
+            .body(Bytes::new())?;
+
+        let metadata: ProviderMetadata = self.send(request).await?;
+
+        if metadata.issuer.trim_end_matches('/') != issuer.trim_end_matches('/') {
+            return Err(OidcError::IssuerMismatch);
+        }
+
+        Ok(metadata)
+    }
+
+    /// Get the metadata of the provider of the registered client.
+    ///
+    /// The metadata is cached, and only discovered again if the issuer of the
+    /// registration changes.
+    pub async fn provider_metadata(&self) -> Result<ProviderMetadata, OidcError> {
+        let issuer = self.client_registration().ok_or(OidcError::NotRegistered)?.issuer;
+
+        if let Some(metadata) = self.ctx().provider_metadata.read().unwrap().as_ref() {
+            if metadata.issuer.trim_end_matches('/') == issuer.trim_end_matches('/') {
+                return Ok(metadata.clone());
+            }
+        }
+
+        let metadata = self.discover(&issuer).await?;
+        *self.ctx().provider_metadata.write().unwrap() = Some(metadata.clone());
+
+        Ok(metadata)
+    }
+
+    /// Register the client with the given OpenID Connect provider.
+    ///
+    /// The returned registration should be persisted and restored with
+    /// [`Oidc::restore_registered_client()`] to avoid registering the client
+    /// every time it is started.
+    #[instrument(skip(self, metadata))]
+    pub async fn register_client(
+        &self,
+        issuer: &str,
+        metadata: ClientMetadata,
+    ) -> Result<ClientRegistration, OidcError> {
+        let provider_metadata = self.discover(issuer).await?;
+        let registration_endpoint = provider_metadata
+            .registration_endpoint
+            .as_ref()
+            .ok_or(OidcError::RegistrationNotSupported)?;
+
+        let body = serde_json::to_vec(&RegistrationRequest::from(metadata))?;
+        let request = http::Request::builder()
+            .method(Method::POST)
+            .uri(registration_endpoint.as_str())
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::ACCEPT, "application/json")
+            .body(Bytes::from(body))?;
+
+        let response: RegistrationResponse = self.send(request).await?;
+        debug!(client_id = response.client_id, "Registered the client");
+
+        let registration =
+            ClientRegistration { issuer: issuer.to_owned(), client_id: response.client_id };
+        self.restore_registered_client(registration.clone());
+        // The metadata was just discovered, no need to request it again.
+        *self.ctx().provider_metadata.write().unwrap() = Some(provider_metadata);
+
+        Ok(registration)
+    }
+
+    /// Restore a registration returned by [`Oidc::register_client()`].
+    pub fn restore_registered_client(&self, registration: ClientRegistration) {
+        *self.ctx().registration.write().unwrap() = Some(registration);
+    }
+
+    /// Restore a session that was obtained with OpenID Connect.
+    ///
+    /// This restores both the registration of the client and the Matrix
+    /// session, and makes sure that the access token is refreshed with the
+    /// provider.
+    pub async fn restore_session(&self, session: OidcSession) -> crate::Result<()> {
+        self.restore_registered_client(session.registration);
+        self.client.restore_session(session.session).await?;
+        self.set_logged_in().await
+    }
+
+    /// Mark the session of the client as obtained with OpenID Connect, and
+    /// save the registration of the client in the state store if the session
+    /// is [persisted](crate::ClientBuilder::persist_session).
+    async fn set_logged_in(&self) -> crate::Result<()> {
+        self.ctx().logged_in.store(true, Ordering::SeqCst);
+
+        if self.client.inner.persist_session {
+            if let Some(registration) = self.client_registration() {
+                let value = serde_json::to_vec(&registration)?;
+                self.client.store().set_custom_value(REGISTRATION_KEY, value).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Restore the registration of the client from the state store, if the
+    /// persisted session was obtained with OpenID Connect.
+    pub(crate) async fn restore_from_store(&self) -> crate::Result<()> {
+        let Some(value) = self.client.store().get_custom_value(REGISTRATION_KEY).await? else {
+            return Ok(());
+        };
+
+        self.restore_registered_client(serde_json::from_slice(&value)?);
+        self.ctx().logged_in.store(true, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    /// Remove the registration of the client from the state store.
+    pub(crate) async fn remove_from_store(&self) -> crate::Result<()> {
+        self.client.store().remove_custom_value(REGISTRATION_KEY).await?;
+        Ok(())
+    }
+
+    /// Get the full session, to persist it and restore it later with
+    /// [`Oidc::restore_session()`].
+    ///
+    /// Returns `None` if the client isn't logged in with OpenID Connect.
+    pub fn full_session(&self) -> Option<OidcSession> {
+        if !self.ctx().is_logged_in() {
+            return None;
+        }
+
+        Some(OidcSession {
+            registration: self.client_registration()?,
+            session: self.client.session()?,
+        })
+    }
+
+    /// Get the URL to present to the user to log in with the provider.
+    ///
+    /// Once the user has logged in, the provider redirects them to
+    /// `redirect_uri`, which must be one of the redirect URIs of the
+    /// registered client. The full URL of the redirect must then be passed to
+    /// [`Oidc::finish_login()`].
+    ///
+    /// A new device is created for the session, unless the client is already
+    /// logged in, for example to recover from a soft logout, in which case the
+    /// device of its session is reused.
+    pub async fn url_for_login(
+        &self,
+        redirect_uri: &Url,
+    ) -> Result<OidcAuthorizationData, OidcError> {
+        let registration = self.client_registration().ok_or(OidcError::NotRegistered)?;
+        let metadata = self.provider_metadata().await?;
+
+        let state = random_string(32);
+        let code_verifier = random_string(64);
+        let code_challenge = BASE64_URL.encode(Sha256::digest(code_verifier.as_bytes()));
+        let device_id =
+            self.client.device_id().map(ToOwned::to_owned).unwrap_or_else(DeviceId::new);
+        let scope = format!("openid {SCOPE_MATRIX_API} {SCOPE_MATRIX_DEVICE_PREFIX}{device_id}");
+
+        let mut url = metadata.authorization_endpoint;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &registration.client_id)
+            .append_pair("redirect_uri", redirect_uri.as_str())
+            .append_pair("scope", &scope)
+            .append_pair("state", &state)
+            .append_pair("code_challenge", &code_challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        self.ctx().pending_authorizations.insert(
+            state.clone(),
+            PendingAuthorization { redirect_uri: redirect_uri.clone(), code_verifier, device_id },
+        );
+
+        Ok(OidcAuthorizationData { url, state })
+    }
+
+    /// Finish the login with the URL the provider redirected the user to.
+    ///
+    /// This exchanges the authorization code for the tokens, and restores the
+    /// Matrix session with them.
+    #[instrument(skip_all)]
+    pub async fn finish_login(&self, callback_url: &Url) -> Result<(), OidcError> {
+        let mut code = None;
+        let mut state = None;
+        let mut error = None;
+        let mut error_description = None;
+
+        for (key, value) in callback_url.query_pairs() {
+            match &*key {
+                "code" => code = Some(value.into_owned()),
+                "state" => state = Some(value.into_owned()),
+                "error" => error = Some(value.into_owned()),
+                "error_description" => error_description = Some(value.into_owned()),
+                _ => {}
+            }
+        }
+
+        let state = state.ok_or(OidcError::InvalidCallbackUrl)?;
+        let (_, pending) =
+            self.ctx().pending_authorizations.remove(&state).ok_or(OidcError::InvalidState)?;
+
+        if let Some(error) = error {
+            return Err(OidcError::Authorization { error, description: error_description });
+        }
+        let code = code.ok_or(OidcError::InvalidCallbackUrl)?;
+
+        let registration = self.client_registration().ok_or(OidcError::NotRegistered)?;
+        let metadata = self.provider_metadata().await?;
+
+        let tokens: TokenResponse = self
+            .send_form(
+                metadata.token_endpoint.as_str(),
+                &[
+                    ("grant_type", "authorization_code"),
+                    ("code", &code),
+                    ("redirect_uri", pending.redirect_uri.as_str()),
+                    ("client_id", &registration.client_id),
+                    ("code_verifier", &pending.code_verifier),
+                ],
+            )
+            .await?;
+
+        // Ask the homeserver who we are, the provider doesn't know about
+        // Matrix IDs.
+        let whoami = self
+            .client
+            .inner
+            .http_client
+            .send(
+                whoami::v3::Request::new(),
+                None,
+                self.client.homeserver().await.to_string(),
+                Some(&tokens.access_token),
+                None,
+                self.client.server_versions().await?,
+            )
+            .await?;
+
+        let session = Session {
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            user_id: whoami.user_id,
+            device_id: whoami.device_id.unwrap_or(pending.device_id),
+        };
+
+        self.client.restore_session(session).await?;
+        self.set_logged_in().await?;
+
+        Ok(())
+    }
+
+    /// Refresh the access token with the provider.
+    ///
+    /// This is called by [`Client::refresh_access_token()`] when the client is
+    /// logged in with OpenID Connect, which takes care of updating the session
+    /// tokens.
+    pub(crate) async fn refresh_access_token_inner(
+        &self,
+        refresh_token: &str,
+    ) -> Result<refresh_token::v3::Response, OidcError> {
+        let registration = self.client_registration().ok_or(OidcError::NotRegistered)?;
+        let metadata = self.provider_metadata().await?;
+
+        let tokens: TokenResponse = self
+            .send_form(
+                metadata.token_endpoint.as_str(),
+                &[
+                    ("grant_type", "refresh_token"),
+                    ("refresh_token", refresh_token),
+                    ("client_id", &registration.client_id),
+                ],
+            )
+            .await?;
+
+        let mut response = refresh_token::v3::Response::new(tokens.access_token);
+        response.refresh_token = tokens.refresh_token;
+        response.expires_in_ms = tokens.expires_in.map(Duration::from_secs);
+
+        Ok(response)
+    }
+
+    /// Get the URL where the user can manage their account with the provider,
+    /// if it advertises one.
+    ///
+    /// The optional `action` is a hint for the page to display.
+    pub async fn account_management_url(
+        &self,
+        action: Option<AccountManagementAction>,
+    ) -> Result<Option<Url>, OidcError> {
+        let metadata = self.provider_metadata().await?;
+        let Some(mut url) = metadata.account_management_uri else {
+            return Ok(None);
+        };
+
+        if let Some(action) = action {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("action", action.as_str());
+            if let AccountManagementAction::SessionView { device_id }
+            | AccountManagementAction::SessionEnd { device_id } = &action
+            {
+                query.append_pair("device_id", device_id.as_str());
+            }
+        }
+
+        Ok(Some(url))
+    }
+
+    /// Log out by revoking the tokens of the session with the provider.
+    ///
+    /// The tokens of the session are then cleared, and the session is removed
+    /// from the store if it is
+    /// [persisted](crate::ClientBuilder::persist_session).
+    ///
+    /// Returns [`OidcError::RevocationNotSupported`] if the provider doesn't
+    /// advertise a revocation endpoint.
+    #[instrument(skip_all)]
+    pub async fn logout(&self) -> Result<(), OidcError> {
+        let registration = self.client_registration().ok_or(OidcError::NotRegistered)?;
+        let metadata = self.provider_metadata().await?;
+        let revocation_endpoint =
+            metadata.revocation_endpoint.ok_or(OidcError::RevocationNotSupported)?;
+        let tokens = self.client.session_tokens().ok_or(OidcError::NotLoggedIn)?;
+
+        // Revoking the refresh token should also revoke the access token, but
+        // revoke both to be sure.
+        if let Some(refresh_token) = &tokens.refresh_token {
+            self.revoke(&revocation_endpoint, &registration, refresh_token, "refresh_token")
+                .await?;
+        }
+        self.revoke(&revocation_endpoint, &registration, &tokens.access_token, "access_token")
+            .await?;
+
+        self.ctx().logged_in.store(false, Ordering::SeqCst);
+        self.client.clear_session().await;
+
+        Ok(())
+    }
+
+    async fn revoke(
+        &self,
+        revocation_endpoint: &Url,
+        registration: &ClientRegistration,
+        token: &str,
+        token_type_hint: &str,
+    ) -> Result<(), OidcError> {
+        let body = serde_html_form::to_string([
+            ("token", token),
+            ("token_type_hint", token_type_hint),
+            ("client_id", &registration.client_id),
+        ])?;
+        let request = http::Request::builder()
+            .method(Method::POST)
+            .uri(revocation_endpoint.as_str())
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Bytes::from(body))?;
+
+        let response = self.client.send_raw(request).await?;
+        if !response.status().is_success() {
+            return Err(provider_error(response.status(), response.body()));
+        }
+
+        Ok(())
+    }
+
+    async fn send_form<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        form: &[(&str, &str)],
+    ) -> Result<T, OidcError> {
+        let body = serde_html_form::to_string(form)?;
+        let request = http::Request::builder()
+            .method(Method::POST)
+            .uri(url)
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(header::ACCEPT, "application/json")
+            .body(Bytes::from(body))?;
+
+        self.send(request).await
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        request: http::Request<Bytes>,
+    ) -> Result<T, OidcError> {
+        let response = self.client.send_raw(request).await?;
+
+        if !response.status().is_success() {
+            return Err(provider_error(response.status(), response.body()));
+        }
+
+        Ok(serde_json::from_slice(response.body())?)
+    }
+}
+
+impl Client {
+    /// Get the OpenID Connect API of the client.
+    pub fn oidc(&self) -> Oidc {
+        Oidc::new(self.clone())
+    }
+}
+
+/// The metadata of an OpenID Connect provider.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ProviderMetadata {
+    /// The issuer of the provider.
+    pub issuer: String,
+
+    /// The URL of the authorization endpoint.
+    pub authorization_endpoint: Url,
+
+    /// The URL of the token endpoint.
+    pub token_endpoint: Url,
+
+    /// The URL of the dynamic client registration endpoint, if supported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registration_endpoint: Option<Url>,
+
+    /// The URL of the token revocation endpoint, if supported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revocation_endpoint: Option<Url>,
+
+    /// The URL where the user can manage their account, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_management_uri: Option<Url>,
+
+    /// The actions supported by the account management URL.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub account_management_actions_supported: Vec<String>,
+}
+
+/// The application type of a client.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApplicationType {
+    /// A web application, its redirect URIs must use HTTPS.
+    Web,
+    /// A native application, its redirect URIs can use a loopback address or
+    /// a custom scheme.
+    Native,
+}
+
+/// The metadata of a client, to register it with an OpenID Connect provider.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ClientMetadata {
+    /// The type of the application.
+    ///
+    /// Default: [`ApplicationType::Native`].
+    pub application_type: ApplicationType,
+
+    /// The URL of the home page of the client.
+    pub client_uri: Url,
+
+    /// The URIs the provider can redirect the user to after the
+    /// authorization.
+    pub redirect_uris: Vec<Url>,
+
+    /// The name of the client presented to the user.
+    pub client_name: Option<String>,
+
+    /// The URL of the logo of the client.
+    pub logo_uri: Option<Url>,
+
+    /// The URL of the terms of service of the client.
+    pub tos_uri: Option<Url>,
+
+    /// The URL of the privacy policy of the client.
+    pub policy_uri: Option<Url>,
+}
+
+impl ClientMetadata {
+    /// Creates `ClientMetadata` for a native application with the given home
+    /// page and redirect URIs.
+    pub fn new(client_uri: Url, redirect_uris: Vec<Url>) -> Self {
+        Self {
+            application_type: ApplicationType::Native,
+            client_uri,
+            redirect_uris,
+            client_name: None,
+            logo_uri: None,
+            tos_uri: None,
+            policy_uri: None,
+        }
+    }
+
+    /// Creates a new `ClientMetadata` from `self` with the given application
+    /// type.
+    pub fn application_type(self, application_type: ApplicationType) -> Self {
+        Self { application_type, ..self }
+    }
+
+    /// Creates a new `ClientMetadata` from `self` with the given name.
+    pub fn client_name(self, client_name: impl Into<String>) -> Self {
+        Self { client_name: Some(client_name.into()), ..self }
+    }
+}
+
+/// The body of a dynamic client registration request.
+#[derive(Serialize)]
+struct RegistrationRequest {
+    application_type: ApplicationType,
+    client_uri: Url,
+    redirect_uris: Vec<Url>,
+    grant_types: [&'static str; 2],
+    response_types: [&'static str; 1],
+    token_endpoint_auth_method: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logo_uri: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tos_uri: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    policy_uri: Option<Url>,
+}
+
+impl From<ClientMetadata> for RegistrationRequest {
+    fn from(metadata: ClientMetadata) -> Self {
+        Self {
+            application_type: metadata.application_type,
+            client_uri: metadata.client_uri,
+            redirect_uris: metadata.redirect_uris,
+            grant_types: ["authorization_code", "refresh_token"],
+            response_types: ["code"],
+            // Native and web clients can't keep a secret.
+            token_endpoint_auth_method: "none",
+            client_name: metadata.client_name,
+            logo_uri: metadata.logo_uri,
+            tos_uri: metadata.tos_uri,
+            policy_uri: metadata.policy_uri,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RegistrationResponse {
+    client_id: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+/// The registration of a client with an OpenID Connect provider.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientRegistration {
+    /// The issuer of the provider.
+    pub issuer: String,
+    /// The ID of the client, as returned by the provider.
+    pub client_id: String,
+}
+
+/// A session obtained with OpenID Connect.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OidcSession {
+    /// The registration of the client.
+    pub registration: ClientRegistration,
+    /// The Matrix session.
+    pub session: Session,
+}
+
+/// The data needed to start an authorization, returned by
+/// [`Oidc::url_for_login()`].
+#[derive(Clone, Debug)]
+pub struct OidcAuthorizationData {
+    /// The URL to present to the user.
+    pub url: Url,
+    /// The unique identifier of this authorization, that will be part of the
+    /// URL the user is redirected to.
+    pub state: String,
+}
+
+/// An action to perform with the account management URL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AccountManagementAction {
+    /// View the profile of the user.
+    Profile,
+    /// View the list of sessions of the user.
+    SessionsList,
+    /// View the details of a session.
+    SessionView {
+        /// The device ID of the session.
+        device_id: OwnedDeviceId,
+    },
+    /// End a session.
+    SessionEnd {
+        /// The device ID of the session.
+        device_id: OwnedDeviceId,
+    },
+}
+
+impl AccountManagementAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Profile => "org.matrix.profile",
+            Self::SessionsList => "org.matrix.sessions_list",
+            Self::SessionView { .. } => "org.matrix.session_view",
+            Self::SessionEnd { .. } => "org.matrix.session_end",
+        }
+    }
+}
+
+/// Errors that can happen when using the OpenID Connect API.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum OidcError {
+    /// No client was registered, see [`Oidc::register_client()`].
+    #[error("the client is not registered with an OpenID Connect provider")]
+    NotRegistered,
+
+    /// The client isn't logged in.
+    #[error("the client is not logged in")]
+    NotLoggedIn,
+
+    /// The issuer in the provider metadata doesn't match the requested one.
+    #[error("the issuer of the provider metadata doesn't match the requested issuer")]
+    IssuerMismatch,
+
+    /// The provider doesn't support dynamic client registration.
+    #[error("the provider doesn't support dynamic client registration")]
+    RegistrationNotSupported,
+
+    /// The provider doesn't support token revocation.
+    #[error("the provider doesn't support token revocation")]
+    RevocationNotSupported,
+
+    /// The URL the user was redirected to is missing required parameters.
+    #[error("the callback URL is missing required parameters")]
+    InvalidCallbackUrl,
+
+    /// The URL the user was redirected to doesn't match an ongoing
+    /// authorization.
+    #[error("the callback URL doesn't match an ongoing authorization")]
+    InvalidState,
+
+    /// The authorization was denied or failed.
+    #[error("the authorization failed: {error}")]
+    Authorization {
+        /// The error code.
+        error: String,
+        /// The description of the error, if any.
+        description: Option<String>,
+    },
+
+    /// The provider returned an error.
+    #[error("the provider returned an error with status {status}: {error}")]
+    Provider {
+        /// The HTTP status of the response.
+        status: StatusCode,
+        /// The error code.
+        error: String,
+        /// The description of the error, if any.
+        description: Option<String>,
+    },
+
+    /// An HTTP error occurred.
+    #[error(transparent)]
+    Http(#[from] HttpError),
+
+    /// Building the request failed.
+    #[error(transparent)]
+    Request(#[from] http::Error),
+
+    /// The response couldn't be deserialized.
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// The form couldn't be serialized.
+    #[error(transparent)]
+    Form(#[from] serde_html_form::ser::Error),
+
+    /// Restoring the Matrix session failed.
+    #[error(transparent)]
+    Matrix(#[from] crate::Error),
+}
+
+impl OidcError {
+    /// Whether the error means that the refresh token is invalid and the user
+    /// needs to log in again.
+    pub fn is_invalid_grant(&self) -> bool {
+        matches!(self, Self::Provider { error, .. } if error == "invalid_grant")
+    }
+}
+
+/// Build an [`OidcError::Provider`] from the body of an error response.
+fn provider_error(status: StatusCode, body: &[u8]) -> OidcError {
+    match serde_json::from_slice::<ErrorResponse>(body) {
+        Ok(ErrorResponse { error, error_description }) => {
+            OidcError::Provider { status, error, description: error_description }
+        }
+        Err(_) => {
+            warn!(%status, "The provider returned an error without a valid body");
+            OidcError::Provider { status, error: status.to_string(), description: None }
+        }
+    }
+}
+
+/// Generate a random alphanumeric string of the given length.
+fn random_string(len: usize) -> String {
+    thread_rng().sample_iter(&Alphanumeric).take(len).map(char::from).collect()
+}
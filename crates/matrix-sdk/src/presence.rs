@@ -0,0 +1,260 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! High-level API for the [presence] of users.
+//!
+//! The presence of other users is received with `/sync` and cached by the
+//! [`Client`], see [`Client::presence()`] and
+//! [`Client::subscribe_to_presence()`]. The presence of the own user can be
+//! changed with [`Account::set_presence()`], or automatically during sync with
+//! an [`IdlePresence`] tracker.
+//!
+//! [presence]: https://spec.matrix.org/v1.6/client-server-api/#presence
+//! [`Account::set_presence()`]: crate::Account::set_presence
+
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex as StdMutex},
+};
+
+use futures_core::Stream;
+use matrix_sdk_common::instant::{Duration, Instant};
+use ruma::{
+    events::presence::PresenceEvent, presence::PresenceState, serde::Raw,
+    MilliSecondsSinceUnixEpoch, OwnedMxcUri, OwnedUserId, UserId,
+};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use crate::{config::SyncSettings, Client, Result};
+
+/// The presence of a user.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct UserPresence {
+    /// The ID of the user.
+    pub user_id: OwnedUserId,
+    /// The presence state of the user.
+    pub state: PresenceState,
+    /// The status message of the user, if any.
+    pub status_msg: Option<String>,
+    /// Whether the user is currently active.
+    pub currently_active: bool,
+    /// When the user was last active, if it is known.
+    pub last_active: Option<MilliSecondsSinceUnixEpoch>,
+    /// The display name of the user, if it was included with the presence.
+    pub displayname: Option<String>,
+    /// The avatar of the user, if it was included with the presence.
+    pub avatar_url: Option<OwnedMxcUri>,
+}
+
+impl UserPresence {
+    /// Create a `UserPresence` from a presence event received at the given
+    /// time.
+    ///
+    /// The `last_active_ago` field of the event is relative to the time the
+    /// event was received, so it is converted to an absolute time here.
+    fn from_event(event: PresenceEvent, received_at: MilliSecondsSinceUnixEpoch) -> Self {
+        let content = event.content;
+        let last_active = content
+            .last_active_ago
+            .map(|ago| MilliSecondsSinceUnixEpoch(received_at.get().saturating_sub(ago)));
+
+        Self {
+            user_id: event.sender,
+            state: content.presence,
+            status_msg: content.status_msg,
+            currently_active: content.currently_active.unwrap_or(false),
+            last_active,
+            displayname: content.displayname,
+            avatar_url: content.avatar_url,
+        }
+    }
+
+    /// How long ago the user was last active, if it is known.
+    pub fn last_active_ago(&self) -> Option<Duration> {
+        let last_active = self.last_active?;
+        let now = MilliSecondsSinceUnixEpoch::now();
+        let ago = now.get().saturating_sub(last_active.get());
+        Some(Duration::from_millis(ago.into()))
+    }
+}
+
+impl Client {
+    /// Get the presence of the given user.
+    ///
+    /// This doesn't send a request, the presence is the latest one received
+    /// with `/sync`. Returns `None` if no presence was received for this user.
+    ///
+    /// Presences loaded from the store after a restart don't know when they
+    /// were received, so their [`UserPresence::last_active`] is computed as if
+    /// they were received when they are loaded.
+    pub async fn presence(&self, user_id: &UserId) -> Result<Option<UserPresence>> {
+        if let Some(presence) = self.inner.presence.get(user_id) {
+            return Ok(Some(presence.clone()));
+        }
+
+        let Some(raw) = self.store().get_presence_event(user_id).await? else {
+            return Ok(None);
+        };
+        let presence =
+            UserPresence::from_event(raw.deserialize()?, MilliSecondsSinceUnixEpoch::now());
+
+        // Don't overwrite a presence that was received in the meantime.
+        let presence =
+            self.inner.presence.entry(user_id.to_owned()).or_insert(presence).value().clone();
+
+        Ok(Some(presence))
+    }
+
+    /// Subscribe to the presence of the given users.
+    ///
+    /// The stream first yields the currently known presence of these users,
+    /// and then every presence update received for them with `/sync`. The
+    /// client must be syncing for the stream to receive updates.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use futures::{pin_mut, StreamExt};
+    /// # use matrix_sdk::{ruma::user_id, Client};
+    /// # async {
+    /// # let client: Client = todo!();
+    /// let presence =
+    ///     client.subscribe_to_presence([user_id!("@alice:localhost").to_owned()]);
+    /// pin_mut!(presence);
+    ///
+    /// while let Some(presence) = presence.next().await {
+    ///     println!("{} is {}", presence.user_id, presence.state);
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub fn subscribe_to_presence(
+        &self,
+        user_ids: impl IntoIterator<Item = OwnedUserId>,
+    ) -> impl Stream<Item = UserPresence> {
+        let user_ids: BTreeSet<_> = user_ids.into_iter().collect();
+        let mut receiver = self.inner.presence_sender.subscribe();
+        let client = self.clone();
+
+        async_stream::stream! {
+            for user_id in &user_ids {
+                match client.presence(user_id).await {
+                    Ok(Some(presence)) => yield presence,
+                    Ok(None) => {}
+                    Err(error) => warn!(?user_id, "Failed to load presence: {error}"),
+                }
+            }
+
+            loop {
+                match receiver.recv().await {
+                    Ok(presence) if user_ids.contains(&presence.user_id) => yield presence,
+                    Ok(_) => {}
+                    Err(RecvError::Lagged(count)) => {
+                        warn!("Presence stream lagged behind, skipped {count} updates");
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+
+    /// Update the presence cache with the presence events received in a sync
+    /// response.
+    pub(crate) fn handle_presence_events(&self, events: &[Raw<PresenceEvent>]) {
+        let received_at = MilliSecondsSinceUnixEpoch::now();
+
+        for raw in events {
+            let event = match raw.deserialize() {
+                Ok(event) => event,
+                Err(error) => {
+                    warn!("Failed to deserialize presence event: {error}");
+                    continue;
+                }
+            };
+
+            let presence = UserPresence::from_event(event, received_at);
+            self.inner.presence.insert(presence.user_id.clone(), presence.clone());
+            // It's fine if there are no subscribers.
+            let _ = self.inner.presence_sender.send(presence);
+        }
+    }
+}
+
+/// A tracker that marks the own user as away when they are idle.
+///
+/// Call [`IdlePresence::record_activity()`] every time the user interacts
+/// with the application. The presence sent with `/sync` is
+/// [`PresenceState::Online`] while the user is active, and switches to
+/// [`PresenceState::Unavailable`] once they have been idle for the configured
+/// timeout.
+///
+/// Pass the tracker to [`SyncSettings::idle_presence()`] so the sync loops
+/// update the presence before every request.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use matrix_sdk::{config::SyncSettings, presence::IdlePresence, Client};
+/// # async {
+/// # let client: Client = todo!();
+/// let idle = IdlePresence::new(Duration::from_secs(5 * 60));
+///
+/// // On every user interaction.
+/// idle.record_activity();
+///
+/// client.sync(SyncSettings::new().idle_presence(idle.clone())).await?;
+/// # anyhow::Ok(()) };
+/// ```
+#[derive(Clone, Debug)]
+pub struct IdlePresence {
+    last_activity: Arc<StdMutex<Instant>>,
+    idle_timeout: Duration,
+}
+
+impl IdlePresence {
+    /// Create a new `IdlePresence` that considers the user away after
+    /// `idle_timeout` without activity.
+    ///
+    /// The user is considered active when the tracker is created.
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self { last_activity: Arc::new(StdMutex::new(Instant::now())), idle_timeout }
+    }
+
+    /// Record that the user interacted with the application.
+    pub fn record_activity(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    /// Whether the user has been idle for longer than the timeout.
+    pub fn is_idle(&self) -> bool {
+        self.last_activity.lock().unwrap().elapsed() >= self.idle_timeout
+    }
+
+    /// The presence state matching the activity of the user.
+    pub fn presence(&self) -> PresenceState {
+        if self.is_idle() {
+            PresenceState::Unavailable
+        } else {
+            PresenceState::Online
+        }
+    }
+
+    /// Set the presence of the given sync settings to the one matching the
+    /// activity of the user.
+    pub fn apply(&self, sync_settings: SyncSettings) -> SyncSettings {
+        sync_settings.set_presence(self.presence())
+    }
+}
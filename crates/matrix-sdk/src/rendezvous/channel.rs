@@ -0,0 +1,145 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The HTTP rendezvous transport of [MSC3886].
+//!
+//! [MSC3886]: https://github.com/matrix-org/matrix-spec-proposals/pull/3886
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use http::{header, Method, StatusCode};
+use tracing::{debug, trace};
+use url::Url;
+
+use super::RendezvousError;
+use crate::{utils::sleep, Client};
+
+/// How long to wait between two requests when polling for a new message.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A rendezvous session on a rendezvous server.
+///
+/// A session holds a single message that both devices overwrite in turn. The
+/// ETag of the session is used to notice when the other device sent a new
+/// message.
+#[derive(Debug)]
+pub(super) struct RendezvousChannel {
+    client: Client,
+    rendezvous_url: Url,
+    etag: Option<String>,
+}
+
+impl RendezvousChannel {
+    /// Create a new rendezvous session on the given rendezvous server.
+    pub(super) async fn create(
+        client: Client,
+        rendezvous_server: &Url,
+    ) -> Result<Self, RendezvousError> {
+        let request = http::Request::builder()
+            .method(Method::POST)
+            .uri(rendezvous_server.as_str())
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .body(Bytes::new())?;
+
+        let response = client.send_raw(request).await?;
+        if response.status() != StatusCode::CREATED {
+            return Err(RendezvousError::UnexpectedStatus(response.status()));
+        }
+
+        let location = header_value(&response, header::LOCATION)
+            .ok_or(RendezvousError::UnexpectedStatus(response.status()))?;
+        let rendezvous_url = rendezvous_server.join(location)?;
+        let etag = header_value(&response, header::ETAG).map(ToOwned::to_owned);
+        debug!(%rendezvous_url, "Created a rendezvous session");
+
+        Ok(Self { client, rendezvous_url, etag })
+    }
+
+    /// Join the rendezvous session at the given URL.
+    pub(super) fn new(client: Client, rendezvous_url: Url) -> Self {
+        Self { client, rendezvous_url, etag: None }
+    }
+
+    /// The URL of the rendezvous session.
+    pub(super) fn rendezvous_url(&self) -> &Url {
+        &self.rendezvous_url
+    }
+
+    /// Replace the message of the session.
+    pub(super) async fn send(&mut self, body: Vec<u8>) -> Result<(), RendezvousError> {
+        let mut request = http::Request::builder()
+            .method(Method::PUT)
+            .uri(self.rendezvous_url.as_str())
+            .header(header::CONTENT_TYPE, "application/octet-stream");
+        if let Some(etag) = &self.etag {
+            request = request.header(header::IF_MATCH, etag);
+        }
+
+        let response = self.client.send_raw(request.body(Bytes::from(body))?).await?;
+        match response.status() {
+            StatusCode::ACCEPTED => {
+                // The ETag is needed to tell our message apart from the next
+                // one.
+                let etag = header_value(&response, header::ETAG)
+                    .ok_or(RendezvousError::UnexpectedStatus(response.status()))?;
+                self.etag = Some(etag.to_owned());
+                Ok(())
+            }
+            StatusCode::NOT_FOUND | StatusCode::GONE => Err(RendezvousError::Expired),
+            StatusCode::PRECONDITION_FAILED => Err(RendezvousError::Conflict),
+            status => Err(RendezvousError::UnexpectedStatus(status)),
+        }
+    }
+
+    /// Wait for the other device to replace the message of the session, and
+    /// return the new message.
+    pub(super) async fn receive(&mut self) -> Result<Vec<u8>, RendezvousError> {
+        loop {
+            let mut request =
+                http::Request::builder().method(Method::GET).uri(self.rendezvous_url.as_str());
+            if let Some(etag) = &self.etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+
+            let response = self.client.send_raw(request.body(Bytes::new())?).await?;
+            match response.status() {
+                StatusCode::OK => {
+                    let etag = header_value(&response, header::ETAG)
+                        .ok_or(RendezvousError::UnexpectedStatus(response.status()))?;
+
+                    // Servers are not required to honor `If-None-Match`, so
+                    // compare the ETag to ignore our own message.
+                    if self.etag.as_deref() != Some(etag) {
+                        self.etag = Some(etag.to_owned());
+
+                        if !response.body().is_empty() {
+                            return Ok(response.into_body().to_vec());
+                        }
+                    }
+                }
+                StatusCode::NOT_MODIFIED => {}
+                StatusCode::NOT_FOUND | StatusCode::GONE => return Err(RendezvousError::Expired),
+                status => return Err(RendezvousError::UnexpectedStatus(status)),
+            }
+
+            trace!("No new message in the rendezvous session, polling again");
+            sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+fn header_value(response: &http::Response<Bytes>, name: header::HeaderName) -> Option<&str> {
+    response.headers().get(name).and_then(|value| value.to_str().ok())
+}
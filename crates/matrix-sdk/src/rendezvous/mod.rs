@@ -0,0 +1,657 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! High-level API to log in a new device by scanning a QR code with a device
+//! that is already logged in.
+//!
+//! This implements the login with a QR code of [MSC3906], over the HTTP
+//! rendezvous sessions of [MSC3886] and the secure channel of [MSC3903]:
+//!
+//! 1. The new device creates a rendezvous session with
+//!    [`Rendezvous::login_with_qr_code()`] and displays the QR code of
+//!    [`QrCodeLogin::qr_code_data()`].
+//! 2. The existing device scans the QR code and joins the rendezvous session
+//!    with [`Rendezvous::grant_login()`], while the new device waits with
+//!    [`QrCodeLogin::wait_for_scan()`].
+//! 3. Both devices display the same [`CheckCode`], which the user should
+//!    compare.
+//! 4. The user approves the login on the existing device with
+//!    [`QrCodeLoginGrant::approve()`], and the new device finishes the login
+//!    with [`ScannedQrCodeLogin::finish()`].
+//!
+//! The existing device hands over a login token, then cross-signs the new
+//! device and shares its private cross-signing keys and the recovery key of
+//! the key backup with it.
+//!
+//! # Examples
+//!
+//! On the new device:
+//!
+//! ```no_run
+//! # use matrix_sdk::Client;
+//! # use url::Url;
+//! # async {
+//! # let client: Client = todo!();
+//! # fn display_qr_code<T>(_: T) {}
+//! let rendezvous_server = Url::parse("https://rendezvous.example.org/")?;
+//! let login =
+//!     client.rendezvous().login_with_qr_code(&rendezvous_server).await?;
+//! display_qr_code(login.qr_code_data().to_qr_code()?);
+//!
+//! let login = login.wait_for_scan().await?;
+//! println!("Check that the other device displays {}", login.check_code());
+//!
+//! let outcome = login.finish(Some("My new device")).await?;
+//! # anyhow::Ok(()) };
+//! ```
+//!
+//! On the existing device:
+//!
+//! ```no_run
+//! # use matrix_sdk::{rendezvous::LoginQrCodeData, Client};
+//! # async {
+//! # let client: Client = todo!();
+//! # let scanned_bytes: Vec<u8> = todo!();
+//! let data = LoginQrCodeData::from_bytes(scanned_bytes)?;
+//! let mut grant = client.rendezvous().grant_login(&data).await?;
+//! println!("Check that the other device displays {}", grant.check_code());
+//!
+//! let device_id = grant.approve(None).await?;
+//! # anyhow::Ok(()) };
+//! ```
+//!
+//! [MSC3906]: https://github.com/matrix-org/matrix-spec-proposals/pull/3906
+//! [MSC3886]: https://github.com/matrix-org/matrix-spec-proposals/pull/3886
+//! [MSC3903]: https://github.com/matrix-org/matrix-spec-proposals/pull/3903
+
+use std::{collections::BTreeMap, fmt};
+
+pub use matrix_sdk_base::crypto::matrix_sdk_qrcode::{
+    DecodingError, EncodingError, LoginQrCodeData,
+};
+use matrix_sdk_base::crypto::{
+    CrossSigningKeyExport, CrossSigningStatus, CryptoStoreError, LocalTrust, SecretImportError,
+};
+use ruma::{
+    api::client::uiaa::AuthData, events::secret::request::SecretName, OwnedDeviceId, TransactionId,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{debug, instrument, warn};
+use url::Url;
+
+pub use self::secure_channel::CheckCode;
+use self::secure_channel::{EstablishedSecureChannel, SecureChannel};
+use crate::{encryption::identities::ManualVerifyError, Client, Error, HttpError};
+
+mod channel;
+mod secure_channel;
+
+/// The only login protocol supported by the rendezvous, the existing device
+/// hands over a login token.
+const LOGIN_TOKEN_PROTOCOL: &str = "login_token";
+
+/// A high-level API to log in a new device with a device that is already
+/// logged in.
+///
+/// Get it with [`Client::rendezvous()`]. See the [module documentation] for
+/// an overview of the login flow.
+///
+/// [module documentation]: crate::rendezvous
+#[derive(Debug, Clone)]
+pub struct Rendezvous {
+    client: Client,
+}
+
+impl Rendezvous {
+    pub(crate) fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Start logging in this client by scanning a QR code with a device that
+    /// is already logged in.
+    ///
+    /// This creates a rendezvous session on the given rendezvous server. The
+    /// client must not be logged in, its homeserver is replaced with the one
+    /// of the existing device during the login.
+    pub async fn login_with_qr_code(
+        &self,
+        rendezvous_server: &Url,
+    ) -> Result<QrCodeLogin, RendezvousError> {
+        if self.client.logged_in() {
+            return Err(RendezvousError::AlreadyLoggedIn);
+        }
+
+        let channel = SecureChannel::new(self.client.clone(), rendezvous_server).await?;
+        Ok(QrCodeLogin { client: self.client.clone(), channel })
+    }
+
+    /// Join the rendezvous session of a QR code displayed by a new device, to
+    /// log it in.
+    ///
+    /// The login isn't granted until [`QrCodeLoginGrant::approve()`] is
+    /// called.
+    pub async fn grant_login(
+        &self,
+        data: &LoginQrCodeData,
+    ) -> Result<QrCodeLoginGrant, RendezvousError> {
+        if !self.client.logged_in() {
+            return Err(RendezvousError::Matrix(Error::AuthenticationRequired));
+        }
+
+        let mut channel = EstablishedSecureChannel::from_qr_code(self.client.clone(), data)?;
+        let homeserver = self.client.homeserver().await.to_string();
+        channel
+            .send(&QrLoginMessage::Protocols {
+                protocols: vec![LOGIN_TOKEN_PROTOCOL.to_owned()],
+                homeserver,
+            })
+            .await?;
+
+        Ok(QrCodeLoginGrant { client: self.client.clone(), channel })
+    }
+}
+
+/// A login with a QR code on the new device, waiting for the QR code to be
+/// scanned.
+pub struct QrCodeLogin {
+    client: Client,
+    channel: SecureChannel,
+}
+
+impl QrCodeLogin {
+    /// The data to display in the QR code.
+    ///
+    /// Use [`LoginQrCodeData::to_qr_code()`] to render it.
+    pub fn qr_code_data(&self) -> LoginQrCodeData {
+        self.channel.qr_code_data()
+    }
+
+    /// Wait for the QR code to be scanned by the existing device.
+    pub async fn wait_for_scan(self) -> Result<ScannedQrCodeLogin, RendezvousError> {
+        let (channel, message) = self.channel.connect().await?;
+
+        let QrLoginMessage::Protocols { protocols, homeserver } = message else {
+            return Err(RendezvousError::UnexpectedMessage);
+        };
+
+        Ok(ScannedQrCodeLogin { client: self.client, channel, protocols, homeserver })
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for QrCodeLogin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QrCodeLogin").finish_non_exhaustive()
+    }
+}
+
+/// A login with a QR code on the new device, once the QR code was scanned.
+pub struct ScannedQrCodeLogin {
+    client: Client,
+    channel: EstablishedSecureChannel,
+    protocols: Vec<String>,
+    homeserver: String,
+}
+
+impl ScannedQrCodeLogin {
+    /// The code that the existing device should display too.
+    pub fn check_code(&self) -> CheckCode {
+        self.channel.check_code()
+    }
+
+    /// Finish the login once the user approved it on the existing device.
+    ///
+    /// The existing device hands over a login token, the device keys of the
+    /// new session are uploaded, then the existing device cross-signs the new
+    /// device and shares its secrets.
+    ///
+    /// # Arguments
+    ///
+    /// * `initial_device_display_name` - The display name of the new device.
+    #[instrument(skip_all)]
+    pub async fn finish(
+        mut self,
+        initial_device_display_name: Option<&str>,
+    ) -> Result<QrCodeLoginOutcome, RendezvousError> {
+        if !self.protocols.iter().any(|protocol| protocol == LOGIN_TOKEN_PROTOCOL) {
+            self.send_failure("unsupported_protocol").await;
+            return Err(RendezvousError::UnsupportedProtocol);
+        }
+        let homeserver = match Url::parse(&self.homeserver) {
+            Ok(homeserver) => homeserver,
+            Err(error) => {
+                self.send_failure("invalid_homeserver").await;
+                return Err(error.into());
+            }
+        };
+        self.client.set_homeserver(homeserver).await;
+
+        self.channel
+            .send(&QrLoginMessage::Protocol { protocol: LOGIN_TOKEN_PROTOCOL.to_owned() })
+            .await?;
+
+        let login_token = match self.channel.receive().await? {
+            QrLoginMessage::Approved { login_token } => login_token,
+            QrLoginMessage::Declined => return Err(RendezvousError::Declined),
+            QrLoginMessage::Failure { reason } => return Err(RendezvousError::Failure(reason)),
+            _ => return Err(RendezvousError::UnexpectedMessage),
+        };
+
+        let mut login = self.client.login_token(&login_token);
+        if let Some(name) = initial_device_display_name {
+            login = login.initial_device_display_name(name);
+        }
+        if let Err(error) = login.send().await {
+            self.send_failure("login_failed").await;
+            return Err(error.into());
+        }
+        debug!("Logged in with the login token of the existing device");
+
+        // Upload the device keys, so the existing device can sign them.
+        self.client.send_outgoing_requests().await?;
+
+        let device_id = self.client.device_id().ok_or(Error::AuthenticationRequired)?.to_owned();
+        let device_key =
+            self.client.encryption().ed25519_key().await.ok_or(Error::AuthenticationRequired)?;
+        self.channel.send(&QrLoginMessage::Success { device_id, device_key }).await?;
+
+        let (verifying_device_id, verifying_device_key, secrets) =
+            match self.channel.receive().await? {
+                QrLoginMessage::Verified { verifying_device_id, verifying_device_key, secrets } => {
+                    (verifying_device_id, verifying_device_key, secrets)
+                }
+                QrLoginMessage::Failure { reason } => return Err(RendezvousError::Failure(reason)),
+                _ => return Err(RendezvousError::UnexpectedMessage),
+            };
+
+        // Make sure that we know the keys of our other devices and our
+        // cross-signing identity.
+        let user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?.to_owned();
+        self.client
+            .keys_query(&TransactionId::new(), BTreeMap::from([(user_id.clone(), Vec::new())]))
+            .await?;
+
+        let verifying_device = self
+            .client
+            .encryption()
+            .get_device(&user_id, &verifying_device_id)
+            .await?
+            .ok_or(RendezvousError::DeviceKeyMismatch)?;
+        if verifying_device.ed25519_key().map(|key| key.to_base64()) != Some(verifying_device_key) {
+            return Err(RendezvousError::DeviceKeyMismatch);
+        }
+        verifying_device.set_local_trust(LocalTrust::Verified).await?;
+
+        let mut outcome = QrCodeLoginOutcome { cross_signing_status: None, backup_key: None };
+        if let Some(secrets) = secrets {
+            let olm = self.client.olm_machine().ok_or(Error::AuthenticationRequired)?;
+
+            if secrets.master_key.is_some()
+                || secrets.self_signing_key.is_some()
+                || secrets.user_signing_key.is_some()
+            {
+                let export = CrossSigningKeyExport {
+                    master_key: secrets.master_key,
+                    self_signing_key: secrets.self_signing_key,
+                    user_signing_key: secrets.user_signing_key,
+                };
+                outcome.cross_signing_status = Some(olm.import_cross_signing_keys(export).await?);
+            }
+
+            outcome.backup_key = secrets.backup_key;
+        }
+
+        Ok(outcome)
+    }
+
+    async fn send_failure(&mut self, reason: &str) {
+        let message = QrLoginMessage::Failure { reason: reason.to_owned() };
+        if let Err(error) = self.channel.send(&message).await {
+            warn!("Failed to notify the existing device of the failure: {error}");
+        }
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for ScannedQrCodeLogin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScannedQrCodeLogin")
+            .field("check_code", &self.check_code())
+            .field("homeserver", &self.homeserver)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The result of a successful login with a QR code.
+#[non_exhaustive]
+pub struct QrCodeLoginOutcome {
+    /// The status of the private cross-signing keys, if the existing device
+    /// shared them.
+    pub cross_signing_status: Option<CrossSigningStatus>,
+    /// The recovery key of the key backup encoded as unpadded base64, if the
+    /// existing device shared it.
+    ///
+    /// The key isn't imported automatically, it should be checked against the
+    /// current backup version on the server before being used.
+    pub backup_key: Option<String>,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for QrCodeLoginOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QrCodeLoginOutcome")
+            .field("cross_signing_status", &self.cross_signing_status)
+            .field("backup_key", &self.backup_key.is_some())
+            .finish()
+    }
+}
+
+/// A login with a QR code on the existing device, waiting for the user to
+/// approve or decline it.
+pub struct QrCodeLoginGrant {
+    client: Client,
+    channel: EstablishedSecureChannel,
+}
+
+impl QrCodeLoginGrant {
+    /// The code that the new device should display too.
+    pub fn check_code(&self) -> CheckCode {
+        self.channel.check_code()
+    }
+
+    /// Approve the login of the new device.
+    ///
+    /// This requests a login token for the new device, waits for it to log
+    /// in, then cross-signs it and shares the private cross-signing keys and
+    /// the recovery key of the key backup with it.
+    ///
+    /// The homeserver might require user-interactive authentication to create
+    /// the login token. In that case, this returns an error with the
+    /// [`UiaaInfo`] and can be called again with the `auth_data`.
+    ///
+    /// Returns the ID of the new device.
+    ///
+    /// [`UiaaInfo`]: ruma::api::client::uiaa::UiaaInfo
+    #[instrument(skip_all)]
+    pub async fn approve(
+        &mut self,
+        auth_data: Option<AuthData>,
+    ) -> Result<OwnedDeviceId, RendezvousError> {
+        let request = get_login_token::Request { auth: auth_data };
+        let login_token = self.client.send(request, None).await?.login_token;
+
+        match self.channel.receive().await? {
+            QrLoginMessage::Protocol { protocol } if protocol == LOGIN_TOKEN_PROTOCOL => {}
+            QrLoginMessage::Failure { reason } => return Err(RendezvousError::Failure(reason)),
+            _ => {
+                self.send_failure("unsupported_protocol").await;
+                return Err(RendezvousError::UnsupportedProtocol);
+            }
+        }
+
+        self.channel.send(&QrLoginMessage::Approved { login_token }).await?;
+
+        let (device_id, device_key) = match self.channel.receive().await? {
+            QrLoginMessage::Success { device_id, device_key } => (device_id, device_key),
+            QrLoginMessage::Failure { reason } => return Err(RendezvousError::Failure(reason)),
+            _ => return Err(RendezvousError::UnexpectedMessage),
+        };
+        debug!(?device_id, "The new device logged in");
+
+        let user_id = self.client.user_id().ok_or(Error::AuthenticationRequired)?.to_owned();
+        self.client
+            .keys_query(
+                &TransactionId::new(),
+                BTreeMap::from([(user_id.clone(), vec![device_id.clone()])]),
+            )
+            .await?;
+
+        let device = self.client.encryption().get_device(&user_id, &device_id).await?;
+        let Some(device) = device.filter(|device| {
+            device.ed25519_key().map(|key| key.to_base64()).as_ref() == Some(&device_key)
+        }) else {
+            self.send_failure("device_key_mismatch").await;
+            return Err(RendezvousError::DeviceKeyMismatch);
+        };
+
+        let olm = self.client.olm_machine().ok_or(Error::AuthenticationRequired)?;
+        if olm.cross_signing_status().await.has_self_signing {
+            device.verify().await?;
+        } else {
+            warn!("Can't cross-sign the new device without the self-signing key");
+        }
+
+        let cross_signing = olm.export_cross_signing_keys().await;
+        let backup_key = olm.export_secret(&SecretName::RecoveryKey).await;
+        let secrets = (cross_signing.is_some() || backup_key.is_some()).then(|| {
+            let cross_signing = cross_signing.as_ref();
+            QrLoginSecrets {
+                master_key: cross_signing.and_then(|keys| keys.master_key.clone()),
+                self_signing_key: cross_signing.and_then(|keys| keys.self_signing_key.clone()),
+                user_signing_key: cross_signing.and_then(|keys| keys.user_signing_key.clone()),
+                backup_key,
+            }
+        });
+
+        let verifying_device_id =
+            self.client.device_id().ok_or(Error::AuthenticationRequired)?.to_owned();
+        let verifying_device_key =
+            self.client.encryption().ed25519_key().await.ok_or(Error::AuthenticationRequired)?;
+        self.channel
+            .send(&QrLoginMessage::Verified { verifying_device_id, verifying_device_key, secrets })
+            .await?;
+
+        Ok(device_id)
+    }
+
+    /// Decline the login of the new device.
+    pub async fn decline(mut self) -> Result<(), RendezvousError> {
+        // Wait for the answer of the new device to our first message, since
+        // the rendezvous session only holds a single message.
+        let _: QrLoginMessage = self.channel.receive().await?;
+        self.channel.send(&QrLoginMessage::Declined).await
+    }
+
+    async fn send_failure(&mut self, reason: &str) {
+        let message = QrLoginMessage::Failure { reason: reason.to_owned() };
+        if let Err(error) = self.channel.send(&message).await {
+            warn!("Failed to notify the new device of the failure: {error}");
+        }
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for QrCodeLoginGrant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QrCodeLoginGrant")
+            .field("check_code", &self.check_code())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Client {
+    /// Get the API to log in a new device with a QR code.
+    pub fn rendezvous(&self) -> Rendezvous {
+        Rendezvous::new(self.clone())
+    }
+}
+
+/// The messages exchanged over the secure channel, loosely following
+/// [MSC3906].
+///
+/// Both devices send messages in turn, since a rendezvous session only holds
+/// a single message.
+///
+/// [MSC3906]: https://github.com/matrix-org/matrix-spec-proposals/pull/3906
+#[derive(Deserialize, Serialize)]
+#[serde(tag = "type")]
+enum QrLoginMessage {
+    /// The login protocols supported by the existing device.
+    #[serde(rename = "m.login.protocols")]
+    Protocols { protocols: Vec<String>, homeserver: String },
+
+    /// The login protocol chosen by the new device.
+    #[serde(rename = "m.login.protocol")]
+    Protocol { protocol: String },
+
+    /// The user approved the login on the existing device.
+    #[serde(rename = "m.login.approved")]
+    Approved { login_token: String },
+
+    /// The user declined the login on the existing device.
+    #[serde(rename = "m.login.declined")]
+    Declined,
+
+    /// The new device is logged in.
+    #[serde(rename = "m.login.success")]
+    Success { device_id: OwnedDeviceId, device_key: String },
+
+    /// The existing device cross-signed the new device.
+    #[serde(rename = "m.login.verified")]
+    Verified {
+        verifying_device_id: OwnedDeviceId,
+        verifying_device_key: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        secrets: Option<QrLoginSecrets>,
+    },
+
+    /// The login failed on the device sending this message.
+    #[serde(rename = "m.login.failure")]
+    Failure { reason: String },
+}
+
+/// The secrets shared with the new device, encoded as unpadded base64.
+#[derive(Deserialize, Serialize)]
+struct QrLoginSecrets {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    master_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    self_signing_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    user_signing_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    backup_key: Option<String>,
+}
+
+/// Errors that can happen when logging in with a QR code.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum RendezvousError {
+    /// The client is already logged in.
+    #[error("the client is already logged in")]
+    AlreadyLoggedIn,
+
+    /// The rendezvous session expired or doesn't exist.
+    #[error("the rendezvous session expired")]
+    Expired,
+
+    /// The rendezvous session was modified concurrently.
+    #[error("the rendezvous session was modified concurrently")]
+    Conflict,
+
+    /// The rendezvous server returned an unexpected response.
+    #[error("the rendezvous server returned an unexpected response with status {0}")]
+    UnexpectedStatus(http::StatusCode),
+
+    /// The other device uses an unsupported secure channel algorithm.
+    #[error("the other device uses an unsupported secure channel algorithm")]
+    UnsupportedAlgorithm,
+
+    /// A message couldn't be encrypted or decrypted.
+    #[error("a message of the secure channel couldn't be encrypted or decrypted")]
+    Encryption,
+
+    /// The other device sent an unexpected message.
+    #[error("the other device sent an unexpected message")]
+    UnexpectedMessage,
+
+    /// The devices don't have a login protocol in common.
+    #[error("the devices don't have a login protocol in common")]
+    UnsupportedProtocol,
+
+    /// The user declined the login on the existing device.
+    #[error("the login was declined on the existing device")]
+    Declined,
+
+    /// The other device reported a failure.
+    #[error("the other device reported a failure: {0}")]
+    Failure(String),
+
+    /// The keys of the other device don't match the ones it announced.
+    #[error("the keys of the other device don't match the ones it announced")]
+    DeviceKeyMismatch,
+
+    /// An HTTP error occurred.
+    #[error(transparent)]
+    Http(#[from] HttpError),
+
+    /// Building a request failed.
+    #[error(transparent)]
+    Request(#[from] http::Error),
+
+    /// A URL couldn't be parsed.
+    #[error(transparent)]
+    Url(#[from] url::ParseError),
+
+    /// A message couldn't be serialized or deserialized.
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+
+    /// An error occurred in the Matrix client.
+    #[error(transparent)]
+    Matrix(#[from] Error),
+
+    /// The crypto store failed.
+    #[error(transparent)]
+    CryptoStore(#[from] CryptoStoreError),
+
+    /// The new device couldn't be cross-signed.
+    #[error(transparent)]
+    ManualVerify(#[from] ManualVerifyError),
+
+    /// The shared secrets couldn't be imported.
+    #[error(transparent)]
+    SecretImport(#[from] SecretImportError),
+}
+
+/// The login token endpoint of [MSC3882].
+///
+/// [MSC3882]: https://github.com/matrix-org/matrix-spec-proposals/pull/3882
+mod get_login_token {
+    use ruma::{
+        api::{client::uiaa::AuthData, request, response, Metadata},
+        metadata,
+    };
+
+    const METADATA: Metadata = metadata! {
+        method: POST,
+        rate_limited: true,
+        authentication: AccessToken,
+        history: {
+            unstable => "/_matrix/client/unstable/org.matrix.msc3882/login/token",
+        }
+    };
+
+    #[request(error = ruma::api::client::uiaa::UiaaResponse)]
+    pub struct Request {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub auth: Option<AuthData>,
+    }
+
+    #[response(error = ruma::api::client::uiaa::UiaaResponse)]
+    pub struct Response {
+        pub login_token: String,
+    }
+}
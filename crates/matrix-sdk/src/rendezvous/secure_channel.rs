@@ -0,0 +1,240 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The secure channel of [MSC3903], established over a rendezvous session.
+//!
+//! Both devices derive a shared key with an ephemeral X25519 key exchange,
+//! the device displaying the QR code shares its public key in the QR code and
+//! the scanning device sends its public key with its first message. Messages
+//! are then encrypted with AES-256-GCM.
+//!
+//! [MSC3903]: https://github.com/matrix-org/matrix-spec-proposals/pull/3903
+
+use std::fmt;
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD_NO_PAD as BASE64, Engine};
+use hkdf::Hkdf;
+use matrix_sdk_base::crypto::matrix_sdk_qrcode::LoginQrCodeData;
+use rand::{thread_rng, RngCore};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::Sha256;
+use url::Url;
+use vodozemac::{Curve25519PublicKey, Curve25519SecretKey};
+use zeroize::Zeroizing;
+
+use super::{channel::RendezvousChannel, RendezvousError};
+use crate::Client;
+
+/// The algorithm of the secure channel.
+const ALGORITHM: &str = "org.matrix.msc3903.rendezvous.v1.curve25519-aes-sha256";
+
+/// The size of the nonces of AES-256-GCM.
+const NONCE_SIZE: usize = 12;
+
+/// A code that both devices display once the secure channel is established.
+///
+/// The user should check that both devices display the same code before going
+/// further, to make sure that nobody intercepted the QR code.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CheckCode(u16);
+
+impl CheckCode {
+    /// The code, as a number between 0 and 9999.
+    pub fn to_digits(self) -> u16 {
+        self.0
+    }
+}
+
+impl fmt::Display for CheckCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}", self.0)
+    }
+}
+
+/// An encrypted message sent over the rendezvous session.
+#[derive(Deserialize, Serialize)]
+struct Envelope {
+    /// The algorithm of the secure channel, only in the first message of the
+    /// scanning device.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    algorithm: Option<String>,
+    /// The public key of the scanning device, only in its first message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    iv: String,
+    ciphertext: String,
+}
+
+/// The secure channel of the device displaying the QR code, before the QR
+/// code is scanned.
+pub(super) struct SecureChannel {
+    channel: RendezvousChannel,
+    secret_key: Curve25519SecretKey,
+    public_key: Curve25519PublicKey,
+}
+
+impl SecureChannel {
+    /// Create a new rendezvous session on the given server.
+    pub(super) async fn new(
+        client: Client,
+        rendezvous_server: &Url,
+    ) -> Result<Self, RendezvousError> {
+        let channel = RendezvousChannel::create(client, rendezvous_server).await?;
+        let secret_key = Curve25519SecretKey::new();
+        let public_key = Curve25519PublicKey::from(&secret_key);
+
+        Ok(Self { channel, secret_key, public_key })
+    }
+
+    /// The data to display in the QR code.
+    pub(super) fn qr_code_data(&self) -> LoginQrCodeData {
+        LoginQrCodeData::new(self.public_key, self.channel.rendezvous_url().to_string())
+    }
+
+    /// Wait for the QR code to be scanned, and return the established channel
+    /// with the first message of the scanning device.
+    pub(super) async fn connect<T: DeserializeOwned>(
+        mut self,
+    ) -> Result<(EstablishedSecureChannel, T), RendezvousError> {
+        let envelope: Envelope = serde_json::from_slice(&self.channel.receive().await?)?;
+
+        if envelope.algorithm.as_deref() != Some(ALGORITHM) {
+            return Err(RendezvousError::UnsupportedAlgorithm);
+        }
+        let their_public_key = envelope
+            .key
+            .as_deref()
+            .and_then(|key| Curve25519PublicKey::from_base64(key).ok())
+            .ok_or(RendezvousError::UnexpectedMessage)?;
+
+        let info = kdf_info(&self.public_key, &their_public_key);
+        let channel =
+            EstablishedSecureChannel::new(self.channel, &self.secret_key, &their_public_key, &info);
+        let message = channel.decrypt(&envelope)?;
+
+        Ok((channel, message))
+    }
+}
+
+/// The info of the key derivation, the initiator is the device that displays
+/// the QR code.
+fn kdf_info(initiator_key: &Curve25519PublicKey, recipient_key: &Curve25519PublicKey) -> String {
+    format!("{ALGORITHM}|{}|{}", initiator_key.to_base64(), recipient_key.to_base64())
+}
+
+/// A secure channel where both devices know the shared key.
+pub(super) struct EstablishedSecureChannel {
+    channel: RendezvousChannel,
+    cipher: Aes256Gcm,
+    check_code: CheckCode,
+    /// Our public key, until it was sent with our first message.
+    pending_public_key: Option<Curve25519PublicKey>,
+}
+
+impl EstablishedSecureChannel {
+    /// Join the rendezvous session of a scanned QR code.
+    ///
+    /// Our public key is sent with the first message.
+    pub(super) fn from_qr_code(
+        client: Client,
+        data: &LoginQrCodeData,
+    ) -> Result<Self, RendezvousError> {
+        let rendezvous_url = Url::parse(&data.rendezvous_url)?;
+        let channel = RendezvousChannel::new(client, rendezvous_url);
+        let secret_key = Curve25519SecretKey::new();
+        let public_key = Curve25519PublicKey::from(&secret_key);
+
+        let info = kdf_info(&data.public_key, &public_key);
+        let mut channel = Self::new(channel, &secret_key, &data.public_key, &info);
+        channel.pending_public_key = Some(public_key);
+
+        Ok(channel)
+    }
+
+    /// Derive the shared key of the channel.
+    fn new(
+        channel: RendezvousChannel,
+        secret_key: &Curve25519SecretKey,
+        their_public_key: &Curve25519PublicKey,
+        info: &str,
+    ) -> Self {
+        let shared_secret = secret_key.diffie_hellman(their_public_key);
+
+        let mut okm = Zeroizing::new([0u8; 34]);
+        Hkdf::<Sha256>::new(None, shared_secret.as_bytes())
+            .expand(info.as_bytes(), okm.as_mut_slice())
+            .expect("34 bytes is a valid output length for HKDF-SHA256");
+
+        let cipher = Aes256Gcm::new_from_slice(&okm[..32]).expect("The key has the right length");
+        let check_code = CheckCode(u16::from_be_bytes([okm[32], okm[33]]) % 10_000);
+
+        Self { channel, cipher, check_code, pending_public_key: None }
+    }
+
+    /// The check code of the channel.
+    pub(super) fn check_code(&self) -> CheckCode {
+        self.check_code
+    }
+
+    /// Encrypt and send the given message.
+    pub(super) async fn send<T: Serialize>(&mut self, message: &T) -> Result<(), RendezvousError> {
+        let plaintext = Zeroizing::new(serde_json::to_vec(message)?);
+
+        let mut nonce = [0u8; NONCE_SIZE];
+        thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+            .map_err(|_| RendezvousError::Encryption)?;
+
+        let envelope = Envelope {
+            algorithm: self.pending_public_key.map(|_| ALGORITHM.to_owned()),
+            key: self.pending_public_key.map(|key| key.to_base64()),
+            iv: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        };
+
+        self.channel.send(serde_json::to_vec(&envelope)?).await?;
+        self.pending_public_key = None;
+
+        Ok(())
+    }
+
+    /// Wait for the next message of the other device and decrypt it.
+    pub(super) async fn receive<T: DeserializeOwned>(&mut self) -> Result<T, RendezvousError> {
+        let envelope: Envelope = serde_json::from_slice(&self.channel.receive().await?)?;
+        self.decrypt(&envelope)
+    }
+
+    fn decrypt<T: DeserializeOwned>(&self, envelope: &Envelope) -> Result<T, RendezvousError> {
+        let nonce = BASE64.decode(&envelope.iv).map_err(|_| RendezvousError::Encryption)?;
+        if nonce.len() != NONCE_SIZE {
+            return Err(RendezvousError::Encryption);
+        }
+        let ciphertext =
+            BASE64.decode(&envelope.ciphertext).map_err(|_| RendezvousError::Encryption)?;
+
+        let plaintext = Zeroizing::new(
+            self.cipher
+                .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+                .map_err(|_| RendezvousError::Encryption)?,
+        );
+
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+}
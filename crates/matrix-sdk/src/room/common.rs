@@ -1,4 +1,4 @@
-use std::{borrow::Borrow, collections::BTreeMap, ops::Deref, sync::Arc};
+use std::{borrow::Borrow, collections::BTreeMap, io::Write, ops::Deref, sync::Arc};
 
 use matrix_sdk_base::{
     deserialized_responses::{MembersResponse, TimelineEvent},
@@ -34,23 +34,23 @@ use ruma::{
             MediaSource,
         },
         tag::{TagInfo, TagName},
-        AnyRoomAccountDataEvent, AnyStateEvent, AnySyncStateEvent, EmptyStateKey, RedactContent,
-        RedactedStateEventContent, RoomAccountDataEvent, RoomAccountDataEventContent,
-        RoomAccountDataEventType, StateEventType, StaticEventContent, StaticStateEventContent,
-        SyncStateEvent,
+        AnyRoomAccountDataEvent, AnyStateEvent, AnySyncStateEvent, AnyTimelineEvent, EmptyStateKey,
+        RedactContent, RedactedStateEventContent, RoomAccountDataEvent,
+        RoomAccountDataEventContent, RoomAccountDataEventType, StateEventType, StaticEventContent,
+        StaticStateEventContent, SyncStateEvent,
     },
     push::{Action, PushConditionRoomCtx},
     serde::Raw,
-    uint, EventId, MatrixToUri, MatrixUri, OwnedEventId, OwnedServerName, OwnedUserId, RoomId,
-    UInt, UserId,
+    uint, EventId, MatrixToUri, MatrixUri, OwnedEventId, OwnedRoomId, OwnedServerName, OwnedUserId,
+    RoomId, UInt, UserId,
 };
 use serde::de::DeserializeOwned;
 use tokio::sync::Mutex;
 use tracing::debug;
 
 #[cfg(feature = "experimental-timeline")]
-use super::timeline::Timeline;
-use super::Joined;
+use super::timeline::{Timeline, TimelineBuilder};
+use super::{ExportOptions, Joined, TranscriptExport};
 use crate::{
     event_handler::{EventHandler, EventHandlerHandle, SyncEvent},
     media::{MediaFormat, MediaRequest},
@@ -119,6 +119,8 @@ impl Common {
     ///
     /// Only invited and left rooms can be joined via this method.
     pub(crate) async fn join(&self) -> Result<Joined> {
+        self.client.ensure_guest_can_join(Some(&self.inner)).await?;
+
         let request = join_room_by_id::v3::Request::new(self.inner.room_id().to_owned());
         let response = self.client.send(request, None).await?;
         let base_room = self.client.base_client().room_joined(&response.room_id).await?;
@@ -202,10 +204,34 @@ impl Common {
     /// # });
     /// ```
     pub async fn messages(&self, options: MessagesOptions) -> Result<Messages> {
-        let room_id = self.inner.room_id();
-        let request = options.into_request(room_id);
+        let request = options.into_request(self.inner.room_id());
         let http_response = self.client.send(request, None).await?;
 
+        self.handle_messages_response(http_response).await
+    }
+
+    /// Same as [`Common::messages()`], but also returns the events as they
+    /// were received from the homeserver, before decryption, in the same
+    /// order as the chunk.
+    pub(crate) async fn messages_with_originals(
+        &self,
+        options: MessagesOptions,
+    ) -> Result<(Messages, Vec<Raw<AnyTimelineEvent>>)> {
+        let request = options.into_request(self.inner.room_id());
+        let http_response = self.client.send(request, None).await?;
+        let originals = http_response.chunk.clone();
+
+        Ok((self.handle_messages_response(http_response).await?, originals))
+    }
+
+    /// Decrypt the events of a `/messages` response if possible and compute
+    /// their push actions.
+    async fn handle_messages_response(
+        &self,
+        http_response: get_message_events::v3::Response,
+    ) -> Result<Messages> {
+        let room_id = self.inner.room_id();
+
         #[allow(unused_mut)]
         let mut response = Messages {
             start: http_response.start,
@@ -247,9 +273,58 @@ impl Common {
             }
         }
 
+        let events = response.chunk.iter().map(|event| event.event.cast_ref());
+        self.client.update_search_index(room_id, events).await;
+
         Ok(response)
     }
 
+    /// Export the history of this room as a transcript.
+    ///
+    /// The history is walked forward from the beginning of the accessible part
+    /// of the room timeline (or from [`ExportOptions::from`] when resuming)
+    /// with [`messages`][Self::messages], so encrypted events are decrypted
+    /// with the keys available on this device. Senders are named according to
+    /// the room member list, with their user ID appended if their display name
+    /// is ambiguous.
+    ///
+    /// Nothing happens until the returned [`TranscriptExport`] is driven with
+    /// [`export_batch`][TranscriptExport::export_batch] or
+    /// [`run`][TranscriptExport::run].
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - Where the transcript should be written to.
+    ///
+    /// * `options` - The format and range of the transcript.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use futures::executor::block_on;
+    /// # use matrix_sdk::{Client, ruma::room_id};
+    /// # use url::Url;
+    /// use matrix_sdk::room::{ExportFormat, ExportOptions};
+    /// # block_on(async {
+    /// # let homeserver = Url::parse("http://example.com")?;
+    /// # let client = Client::new(homeserver).await?;
+    /// let room = client.get_joined_room(room_id!("!roomid:example.com")).unwrap();
+    ///
+    /// let file = std::fs::File::create("transcript.html")?;
+    /// let options = ExportOptions::new(ExportFormat::Html).include_media(true);
+    /// let (_, progress) = room.export_transcript(file, options).run().await?;
+    ///
+    /// println!("Exported {} events", progress.exported_events);
+    /// # anyhow::Ok(()) });
+    /// ```
+    pub fn export_transcript<W: Write>(
+        &self,
+        writer: W,
+        options: ExportOptions,
+    ) -> TranscriptExport<W> {
+        TranscriptExport::new(self.clone(), writer, options)
+    }
+
     /// Register a handler for events of a specific type, within this room.
     ///
     /// This method works the same way as [`Client::add_event_handler`], except
@@ -277,6 +352,16 @@ impl Common {
         Timeline::builder(self).track_read_marker_and_receipts().build().await
     }
 
+    /// Get a [`TimelineBuilder`] for this room, to configure the [`Timeline`]
+    /// before creating it.
+    ///
+    /// Unlike [`timeline()`][Self::timeline], this doesn't enable any options
+    /// by default.
+    #[cfg(feature = "experimental-timeline")]
+    pub fn timeline_builder(&self) -> TimelineBuilder {
+        Timeline::builder(self)
+    }
+
     /// Fetch the event with the given `EventId` in this room.
     pub async fn event(&self, event_id: &EventId) -> Result<TimelineEvent> {
         let request =
@@ -391,6 +476,20 @@ impl Common {
         }
     }
 
+    /// Get the ID of the room that replaces this room, if it was upgraded.
+    ///
+    /// This is the room that the `m.room.tombstone` event of this room points
+    /// to.
+    pub fn successor(&self) -> Option<OwnedRoomId> {
+        self.inner.tombstone().map(|tombstone| tombstone.replacement_room)
+    }
+
+    /// Get the ID of the room that this room replaces, if it was created by
+    /// upgrading another room.
+    pub fn predecessor(&self) -> Option<OwnedRoomId> {
+        self.inner.create_content()?.predecessor.map(|predecessor| predecessor.room_id)
+    }
+
     pub(crate) async fn ensure_members(&self) -> Result<Option<MembersResponse>> {
         if !self.are_events_visible() {
             return Ok(None);
@@ -1150,7 +1249,7 @@ impl MessagesOptions {
         Self { from: from.into().map(ToOwned::to_owned), ..self }
     }
 
-    fn into_request(self, room_id: &RoomId) -> get_message_events::v3::Request {
+    pub(crate) fn into_request(self, room_id: &RoomId) -> get_message_events::v3::Request {
         assign!(get_message_events::v3::Request::new(room_id.to_owned(), self.dir), {
             from: self.from,
             to: self.to,
@@ -0,0 +1,537 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export the history of a room as a transcript.
+//!
+//! See [`Common::export_transcript`] for details.
+
+use std::{collections::BTreeMap, io::Write};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use matrix_sdk_base::deserialized_responses::TimelineEvent;
+use ruma::{
+    events::{
+        room::{
+            member::MembershipState,
+            message::{MessageType, RoomMessageEventContent},
+            MediaSource,
+        },
+        AnyMessageLikeEvent, AnyMessageLikeEventContent, AnyStateEvent, AnyTimelineEvent,
+        MessageLikeEvent, StateEvent,
+    },
+    serde::Raw,
+    uint, MilliSecondsSinceUnixEpoch, OwnedUserId, UInt, UserId,
+};
+use serde_json::json;
+use tracing::{debug, warn};
+
+use super::{Common, MessagesOptions};
+use crate::{
+    media::{MediaFormat, MediaRequest},
+    Result,
+};
+
+/// The format of an exported room transcript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// A self-contained HTML document.
+    ///
+    /// Media is embedded into the document as `data:` URIs if
+    /// [`ExportOptions::include_media`] is set.
+    Html,
+
+    /// One JSON object per line, containing the event (decrypted if
+    /// possible), its encryption info and the resolved name of the sender.
+    ///
+    /// For encrypted events, the event as it was received from the homeserver
+    /// is included too, under `original_event`.
+    JsonLines,
+
+    /// Human-readable plain text, one line per event.
+    PlainText,
+}
+
+/// Options for [`Common::export_transcript`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ExportOptions {
+    /// The format of the transcript.
+    pub format: ExportFormat,
+
+    /// Only export events sent at or after this point in time.
+    pub since: Option<MilliSecondsSinceUnixEpoch>,
+
+    /// Only export events sent at or before this point in time.
+    ///
+    /// The export finishes as soon as the first event after this point in
+    /// time is encountered.
+    pub until: Option<MilliSecondsSinceUnixEpoch>,
+
+    /// The pagination token to resume a previous export from.
+    ///
+    /// This should be the [`ExportProgress::token`] of the last successfully
+    /// written batch.
+    pub from: Option<String>,
+
+    /// Whether to download and embed media in HTML transcripts.
+    pub include_media: bool,
+
+    /// The maximum number of events to request from the server at once.
+    ///
+    /// Default: 100.
+    pub batch_size: UInt,
+}
+
+impl ExportOptions {
+    /// Create `ExportOptions` for the given format.
+    ///
+    /// All other options are defaulted, which exports the whole accessible
+    /// history of the room without media.
+    pub fn new(format: ExportFormat) -> Self {
+        Self {
+            format,
+            since: None,
+            until: None,
+            from: None,
+            include_media: false,
+            batch_size: uint!(100),
+        }
+    }
+
+    /// Only export events sent between `since` and `until`.
+    pub fn date_range(
+        self,
+        since: Option<MilliSecondsSinceUnixEpoch>,
+        until: Option<MilliSecondsSinceUnixEpoch>,
+    ) -> Self {
+        Self { since, until, ..self }
+    }
+
+    /// Resume an export from the given pagination token.
+    pub fn from(self, from: impl Into<Option<String>>) -> Self {
+        Self { from: from.into(), ..self }
+    }
+
+    /// Whether to embed media in HTML transcripts.
+    pub fn include_media(self, include_media: bool) -> Self {
+        Self { include_media, ..self }
+    }
+}
+
+/// The progress of a transcript export.
+#[derive(Debug, Clone, Default)]
+pub struct ExportProgress {
+    /// The pagination token that the next batch will be requested with.
+    ///
+    /// Store this value to resume the export later on, via
+    /// [`ExportOptions::from`].
+    pub token: Option<String>,
+
+    /// The number of events that have been written so far.
+    pub exported_events: u64,
+
+    /// Whether the end of the requested range has been reached.
+    pub finished: bool,
+}
+
+/// An ongoing export of a room transcript.
+///
+/// Created with [`Common::export_transcript`].
+#[derive(Debug)]
+pub struct TranscriptExport<W> {
+    room: Common,
+    options: ExportOptions,
+    writer: W,
+    progress: ExportProgress,
+    started: bool,
+    names: BTreeMap<OwnedUserId, String>,
+}
+
+impl<W: Write> TranscriptExport<W> {
+    pub(super) fn new(room: Common, writer: W, options: ExportOptions) -> Self {
+        let progress = ExportProgress { token: options.from.clone(), ..Default::default() };
+        Self { room, options, writer, progress, started: false, names: BTreeMap::new() }
+    }
+
+    /// The current progress of the export.
+    pub fn progress(&self) -> &ExportProgress {
+        &self.progress
+    }
+
+    /// Export the next batch of events.
+    ///
+    /// Returns the progress after the batch has been written. Once
+    /// [`ExportProgress::finished`] is `true`, calling this method again does
+    /// nothing.
+    pub async fn export_batch(&mut self) -> Result<&ExportProgress> {
+        if self.progress.finished {
+            return Ok(&self.progress);
+        }
+
+        if !self.started {
+            self.start().await?;
+        }
+
+        let mut options = MessagesOptions::forward().from(self.progress.token.as_deref());
+        options.limit = self.options.batch_size;
+
+        let (messages, originals) = self.room.messages_with_originals(options).await?;
+
+        for (event, original) in messages.chunk.iter().zip(&originals) {
+            let deserialized = match event.event.deserialize() {
+                Ok(ev) => ev,
+                Err(e) => {
+                    warn!("Skipping event that couldn't be deserialized: {e}");
+                    continue;
+                }
+            };
+
+            let ts = deserialized.origin_server_ts();
+
+            if self.options.since.map_or(false, |since| ts < since) {
+                continue;
+            }
+
+            if self.options.until.map_or(false, |until| ts > until) {
+                self.progress.finished = true;
+                break;
+            }
+
+            self.write_event(event, original, &deserialized).await?;
+            self.progress.exported_events += 1;
+        }
+
+        // The end of the history is reached if the server didn't return an end
+        // token or didn't return any events at all.
+        if messages.end.is_none() || messages.chunk.is_empty() {
+            self.progress.finished = true;
+        }
+
+        if !self.progress.finished {
+            self.progress.token = messages.end;
+        }
+
+        if self.progress.finished {
+            self.finish()?;
+        }
+
+        self.writer.flush()?;
+
+        debug!(exported_events = self.progress.exported_events, "Exported a batch of events");
+
+        Ok(&self.progress)
+    }
+
+    /// Export all remaining events.
+    ///
+    /// Returns the writer and the final progress.
+    pub async fn run(mut self) -> Result<(W, ExportProgress)> {
+        while !self.progress.finished {
+            self.export_batch().await?;
+        }
+
+        Ok((self.writer, self.progress))
+    }
+
+    async fn start(&mut self) -> Result<()> {
+        self.started = true;
+
+        // Make sure the member list is complete so names can be disambiguated.
+        self.room.sync_members().await?;
+
+        // A resumed export appends to an existing transcript, so it mustn't
+        // repeat the header.
+        if self.options.format == ExportFormat::Html && self.options.from.is_none() {
+            let title = escape_html(&self.room.display_name().await?.to_string());
+            write!(
+                self.writer,
+                "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+                 <title>{title}</title>\n<style>{HTML_STYLE}</style>\n</head>\n<body>\n\
+                 <h1>{title}</h1>\n"
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.options.format == ExportFormat::Html {
+            self.writer.write_all(b"</body>\n</html>\n")?;
+        }
+
+        Ok(())
+    }
+
+    async fn write_event(
+        &mut self,
+        event: &TimelineEvent,
+        original: &Raw<AnyTimelineEvent>,
+        deserialized: &AnyTimelineEvent,
+    ) -> Result<()> {
+        let sender_name = self.sender_name(deserialized.sender()).await?;
+
+        match self.options.format {
+            ExportFormat::JsonLines => {
+                let mut line = json!({
+                    "event": event.event,
+                    "encryption_info": event.encryption_info,
+                    "sender_name": sender_name,
+                });
+                if event.encryption_info.is_some() {
+                    line["original_event"] = serde_json::to_value(original)?;
+                }
+                serde_json::to_writer(&mut self.writer, &line)?;
+                self.writer.write_all(b"\n")?;
+            }
+            ExportFormat::PlainText => {
+                let Some(summary) = summarize_event(deserialized) else { return Ok(()) };
+                writeln!(
+                    self.writer,
+                    "[{}] {sender_name}: {}",
+                    format_timestamp(deserialized.origin_server_ts()),
+                    summary.text,
+                )?;
+            }
+            ExportFormat::Html => {
+                let Some(summary) = summarize_event(deserialized) else { return Ok(()) };
+
+                let body = match summary.media {
+                    Some(media) if self.options.include_media => {
+                        self.embed_media(&summary.text, media).await
+                    }
+                    _ => escape_html(&summary.text),
+                };
+
+                writeln!(
+                    self.writer,
+                    "<div class=\"event\" id=\"{}\"><span class=\"ts\">{}</span> \
+                     <span class=\"sender\" title=\"{}\">{}</span> \
+                     <span class=\"body\">{body}</span></div>",
+                    escape_html(deserialized.event_id().as_str()),
+                    format_timestamp(deserialized.origin_server_ts()),
+                    escape_html(deserialized.sender().as_str()),
+                    escape_html(&sender_name),
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn embed_media(&self, body: &str, media: MediaSummary) -> String {
+        let request = MediaRequest { source: media.source, format: MediaFormat::File };
+        let data = match self.room.client.media().get_media_content(&request, true).await {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("Couldn't download media for the transcript: {e}");
+                return escape_html(body);
+            }
+        };
+
+        let mimetype = media.mimetype.as_deref().unwrap_or("application/octet-stream");
+        let uri = format!("data:{mimetype};base64,{}", BASE64.encode(data));
+
+        if media.is_image {
+            format!("<img src=\"{uri}\" alt=\"{}\">", escape_html(body))
+        } else {
+            format!("<a href=\"{uri}\" download=\"{0}\">{0}</a>", escape_html(body))
+        }
+    }
+
+    /// Get the name of the given user in this room.
+    ///
+    /// If the display name of the user is ambiguous in the room, the user ID is
+    /// appended to it.
+    async fn sender_name(&mut self, user_id: &UserId) -> Result<String> {
+        if let Some(name) = self.names.get(user_id) {
+            return Ok(name.clone());
+        }
+
+        let name = match self.room.get_member_no_sync(user_id).await? {
+            Some(member) if member.name_ambiguous() => format!("{} ({user_id})", member.name()),
+            Some(member) => member.name().to_owned(),
+            None => user_id.to_string(),
+        };
+
+        self.names.insert(user_id.to_owned(), name.clone());
+
+        Ok(name)
+    }
+}
+
+const HTML_STYLE: &str = "body { font-family: sans-serif; } \
+    .event { margin: 0.2em 0; } \
+    .ts { color: #888; } \
+    .sender { font-weight: bold; } \
+    img { display: block; max-width: 480px; }";
+
+/// A human-readable summary of an event.
+struct EventSummary {
+    text: String,
+    media: Option<MediaSummary>,
+}
+
+/// The media attached to an event.
+struct MediaSummary {
+    source: MediaSource,
+    mimetype: Option<String>,
+    is_image: bool,
+}
+
+impl EventSummary {
+    fn text(text: impl Into<String>) -> Self {
+        Self { text: text.into(), media: None }
+    }
+}
+
+/// Summarize the given event for a human-readable transcript.
+///
+/// Returns `None` for events that shouldn't be part of the transcript.
+fn summarize_event(event: &AnyTimelineEvent) -> Option<EventSummary> {
+    match event {
+        AnyTimelineEvent::MessageLike(AnyMessageLikeEvent::RoomEncrypted(
+            MessageLikeEvent::Original(_),
+        )) => Some(EventSummary::text("Unable to decrypt message")),
+        AnyTimelineEvent::MessageLike(ev) => match ev.original_content()? {
+            AnyMessageLikeEventContent::RoomMessage(content) => Some(summarize_message(content)),
+            AnyMessageLikeEventContent::Sticker(content) => {
+                Some(EventSummary::text(format!("sent a sticker: {}", content.body)))
+            }
+            _ => None,
+        },
+        AnyTimelineEvent::State(AnyStateEvent::RoomMember(StateEvent::Original(ev))) => {
+            let target = ev.content.displayname.as_deref().unwrap_or(ev.state_key.as_str());
+            let text = match &ev.content.membership {
+                MembershipState::Join => format!("{target} joined the room"),
+                MembershipState::Leave if ev.sender == ev.state_key => {
+                    format!("{target} left the room")
+                }
+                MembershipState::Leave => format!("{target} was removed from the room"),
+                MembershipState::Invite => format!("{target} was invited"),
+                MembershipState::Ban => format!("{target} was banned"),
+                MembershipState::Knock => format!("{target} requested to join"),
+                _ => return None,
+            };
+            Some(EventSummary::text(text))
+        }
+        AnyTimelineEvent::State(AnyStateEvent::RoomName(StateEvent::Original(ev))) => {
+            Some(EventSummary::text(match &ev.content.name {
+                Some(name) => format!("changed the room name to {name}"),
+                None => "removed the room name".to_owned(),
+            }))
+        }
+        AnyTimelineEvent::State(AnyStateEvent::RoomTopic(StateEvent::Original(ev))) => {
+            Some(EventSummary::text(format!("changed the topic to {}", ev.content.topic)))
+        }
+        AnyTimelineEvent::State(AnyStateEvent::RoomEncryption(StateEvent::Original(_))) => {
+            Some(EventSummary::text("enabled end-to-end encryption"))
+        }
+        AnyTimelineEvent::State(_) => None,
+    }
+}
+
+fn summarize_message(content: RoomMessageEventContent) -> EventSummary {
+    let (source, mimetype, is_image) = match &content.msgtype {
+        MessageType::Image(c) => {
+            (c.source.clone(), c.info.as_ref().and_then(|i| i.mimetype.clone()), true)
+        }
+        MessageType::File(c) => {
+            (c.source.clone(), c.info.as_ref().and_then(|i| i.mimetype.clone()), false)
+        }
+        MessageType::Audio(c) => {
+            (c.source.clone(), c.info.as_ref().and_then(|i| i.mimetype.clone()), false)
+        }
+        MessageType::Video(c) => {
+            (c.source.clone(), c.info.as_ref().and_then(|i| i.mimetype.clone()), false)
+        }
+        MessageType::Emote(c) => return EventSummary::text(format!("* {}", c.body)),
+        _ => return EventSummary::text(content.msgtype.body()),
+    };
+
+    EventSummary {
+        text: content.msgtype.body().to_owned(),
+        media: Some(MediaSummary { source, mimetype, is_image }),
+    }
+}
+
+/// Escape the given text so it can be embedded in HTML.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Format the given timestamp as `YYYY-MM-DD hh:mm:ss` in UTC.
+fn format_timestamp(ts: MilliSecondsSinceUnixEpoch) -> String {
+    let secs = u64::from(ts.0) / 1000;
+    let (days, secs_of_day) = ((secs / 86_400) as i64, secs % 86_400);
+
+    // Convert the number of days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60,
+        secs_of_day % 60,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use ruma::{uint, MilliSecondsSinceUnixEpoch};
+
+    use super::{escape_html, format_timestamp};
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(MilliSecondsSinceUnixEpoch(uint!(0))), "1970-01-01 00:00:00");
+        assert_eq!(
+            format_timestamp(MilliSecondsSinceUnixEpoch(uint!(1_677_672_000_000))),
+            "2023-03-01 12:00:00"
+        );
+        assert_eq!(
+            format_timestamp(MilliSecondsSinceUnixEpoch(uint!(951_825_599_999))),
+            "2000-02-29 11:59:59"
+        );
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            escape_html("<b>\"Tom\" & 'Jerry'</b>"),
+            "&lt;b&gt;&quot;Tom&quot; &amp; &#39;Jerry&#39;&lt;/b&gt;"
+        );
+    }
+}
@@ -17,6 +17,7 @@ use ruma::{
         read_marker::set_read_marker,
         receipt::create_receipt::{self, v3::ReceiptType},
         redact::redact_event,
+        room::upgrade_room,
         state::send_state_event,
         typing::create_typing_event::v3::{Request as TypingRequest, Typing},
     },
@@ -25,26 +26,30 @@ use ruma::{
         receipt::ReceiptThread,
         room::{
             avatar::{ImageInfo, RoomAvatarEventContent},
+            member::MembershipState,
             message::RoomMessageEventContent,
             name::RoomNameEventContent,
             power_levels::RoomPowerLevelsEventContent,
             topic::RoomTopicEventContent,
         },
-        EmptyStateKey, MessageLikeEventContent, StateEventContent,
+        space::parent::SpaceParentEventContent,
+        EmptyStateKey, MessageLikeEventContent, OriginalSyncStateEvent, StateEventContent,
+        SyncStateEvent,
     },
     serde::Raw,
-    EventId, Int, MxcUri, OwnedEventId, OwnedTransactionId, TransactionId, UserId,
+    EventId, Int, MxcUri, OwnedEventId, OwnedRoomId, OwnedTransactionId, RoomId, RoomVersionId,
+    TransactionId, UserId,
 };
 use serde_json::Value;
 #[cfg(feature = "e2e-encryption")]
 use tokio::sync::Mutex;
-use tracing::{debug, instrument};
+use tracing::{debug, instrument, warn};
 
 use super::Left;
 use crate::{
     attachment::AttachmentConfig,
     error::{Error, HttpResult},
-    room::Common,
+    room::{Common, RoomMember},
     BaseRoom, Client, Result, RoomState,
 };
 #[cfg(feature = "image-proc")]
@@ -146,6 +151,42 @@ impl Joined {
         Ok(())
     }
 
+    /// Get the members that knocked on this room and are waiting for their
+    /// knock to be accepted or denied.
+    ///
+    /// *Note*: This method will fetch the members from the homeserver if the
+    /// member list isn't synchronized due to member lazy loading.
+    pub async fn knock_requests(&self) -> Result<Vec<RoomMember>> {
+        Ok(self
+            .members()
+            .await?
+            .into_iter()
+            .filter(|member| *member.membership() == MembershipState::Knock)
+            .collect())
+    }
+
+    /// Accept the knock of the given user, by inviting them to this room.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The `UserId` of the user that knocked on the room.
+    #[instrument(skip_all)]
+    pub async fn accept_knock(&self, user_id: &UserId) -> Result<()> {
+        self.invite_user_by_id(user_id).await
+    }
+
+    /// Deny the knock of the given user, by kicking them from this room.
+    ///
+    /// # Arguments
+    ///
+    /// * `user_id` - The `UserId` of the user that knocked on the room.
+    ///
+    /// * `reason` - Optional reason why the knock is denied.
+    #[instrument(skip_all)]
+    pub async fn deny_knock(&self, user_id: &UserId, reason: Option<&str>) -> Result<()> {
+        self.kick_user(user_id, reason).await
+    }
+
     /// Invite the specified user by third party id to this room.
     ///
     /// # Arguments
@@ -1132,6 +1173,85 @@ impl Joined {
 
         self.client.send(request, None).await
     }
+
+    /// Upgrade this room to the given room version.
+    ///
+    /// The homeserver creates the successor room, copies the important state
+    /// of this room to it (power levels, name, topic, avatar, join rules,
+    /// encryption…), moves the local aliases and sends the `m.room.tombstone`
+    /// event that points to the successor.
+    ///
+    /// The space parents of this room are copied too, and the spaces that list
+    /// this room as a child and that the user is a member of are updated to
+    /// list the successor instead. Failing to update a space doesn't fail the
+    /// upgrade, since the user might not have the permission to do so.
+    ///
+    /// Returns the ID of the successor room.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_version` - The version of the successor room.
+    #[instrument(skip(self))]
+    pub async fn upgrade(&self, new_version: RoomVersionId) -> Result<OwnedRoomId> {
+        let request = upgrade_room::v3::Request::new(self.room_id().to_owned(), new_version);
+        let successor_id = self.client.send(request, None).await?.replacement_room;
+
+        // The room was upgraded, so failing to update its spaces shouldn't lose
+        // the ID of the successor.
+        let parents = match self.get_state_events_static::<SpaceParentEventContent>().await {
+            Ok(parents) => parents,
+            Err(e) => {
+                warn!("Failed to get the space parents of the room: {e}");
+                return Ok(successor_id);
+            }
+        };
+
+        for parent in parents {
+            let Ok(SyncStateEvent::Original(parent)) = parent.deserialize() else { continue };
+            if parent.content.via.as_ref().map_or(true, |via| via.is_empty()) {
+                continue;
+            }
+
+            if let Err(e) = self.copy_space_parent(&successor_id, &parent).await {
+                warn!(space_id = ?parent.state_key, "Failed to copy the space parent: {e}");
+            }
+
+            if let Err(e) = self.replace_in_space(&parent.state_key, &successor_id).await {
+                warn!(space_id = ?parent.state_key, "Failed to replace the room in space: {e}");
+            }
+        }
+
+        Ok(successor_id)
+    }
+
+    /// Send the given space parent event in the successor of this room.
+    async fn copy_space_parent(
+        &self,
+        successor_id: &RoomId,
+        parent: &OriginalSyncStateEvent<SpaceParentEventContent>,
+    ) -> Result<()> {
+        let request = send_state_event::v3::Request::new(
+            successor_id.to_owned(),
+            &parent.state_key,
+            &parent.content,
+        )?;
+        self.client.send(request, None).await?;
+
+        Ok(())
+    }
+
+    /// Replace this room by its successor in the given space, if the user is
+    /// a member of it.
+    async fn replace_in_space(&self, space_id: &RoomId, successor_id: &RoomId) -> Result<()> {
+        let Some(space) = self.client.get_space(space_id) else { return Ok(()) };
+        let children = space.children().await?;
+        let Some(child) = children.into_iter().find(|c| *c.room_id == *self.room_id()) else {
+            return Ok(());
+        };
+
+        space.add_child(successor_id, child.order, child.suggested).await?;
+        space.remove_child(self.room_id()).await
+    }
 }
 
 /// Receipts to send all at once.
@@ -0,0 +1,61 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Deref;
+
+use super::Left;
+use crate::{room::Common, BaseRoom, Client, Result, RoomState};
+
+/// A room in the knocked state.
+///
+/// This struct contains all methods specific to a `Room` with
+/// `RoomState::Knocked`. Operations may fail once the underlying `Room` changes
+/// `RoomState`.
+///
+/// The room becomes an invited room once the knock is accepted, and a left
+/// room if it is denied.
+#[derive(Debug, Clone)]
+pub struct Knocked {
+    pub(crate) inner: Common,
+}
+
+impl Knocked {
+    /// Create a new `room::Knocked` if the underlying `Room` has
+    /// `RoomState::Knocked`.
+    ///
+    /// # Arguments
+    /// * `client` - The client used to make requests.
+    ///
+    /// * `room` - The underlying room.
+    pub(crate) fn new(client: &Client, room: BaseRoom) -> Option<Self> {
+        if room.state() == RoomState::Knocked {
+            Some(Self { inner: Common::new(client.clone(), room) })
+        } else {
+            None
+        }
+    }
+
+    /// Retract the knock.
+    pub async fn retract_knock(&self) -> Result<Left> {
+        self.inner.leave().await
+    }
+}
+
+impl Deref for Knocked {
+    type Target = Common;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
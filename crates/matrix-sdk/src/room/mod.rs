@@ -5,19 +5,31 @@ use std::ops::Deref;
 use crate::RoomState;
 
 mod common;
+mod export;
 mod invited;
 mod joined;
+mod knocked;
 mod left;
 mod member;
+mod pinned_events;
+mod power_levels;
+mod preview;
+mod space;
 #[cfg(feature = "experimental-timeline")]
 pub mod timeline;
+mod typing;
 
 pub use self::{
     common::{Common, Messages, MessagesOptions},
+    export::{ExportFormat, ExportOptions, ExportProgress, TranscriptExport},
     invited::Invited,
     joined::{Joined, Receipts},
+    knocked::Knocked,
     left::Left,
     member::RoomMember,
+    power_levels::RoomPowerLevelChanges,
+    preview::RoomPreview,
+    space::{HierarchyOptions, Space, SpaceChild, SpaceHierarchy, SpaceTree, SpaceTreeNode},
 };
 
 /// An enum that abstracts over the different states a room can be in.
@@ -29,6 +41,8 @@ pub enum Room {
     Left(Left),
     /// The room in the `invited` state.
     Invited(Invited),
+    /// The room in the `knocked` state.
+    Knocked(Knocked),
 }
 
 impl Deref for Room {
@@ -39,6 +53,7 @@ impl Deref for Room {
             Self::Joined(room) => room,
             Self::Left(room) => room,
             Self::Invited(room) => room,
+            Self::Knocked(room) => room,
         }
     }
 }
@@ -49,6 +64,7 @@ impl From<Common> for Room {
             RoomState::Joined => Self::Joined(Joined { inner: room }),
             RoomState::Left => Self::Left(Left { inner: room }),
             RoomState::Invited => Self::Invited(Invited { inner: room }),
+            RoomState::Knocked => Self::Knocked(Knocked { inner: room }),
         }
     }
 }
@@ -60,6 +76,7 @@ impl From<Joined> for Room {
             RoomState::Joined => Self::Joined(Joined { inner: room }),
             RoomState::Left => Self::Left(Left { inner: room }),
             RoomState::Invited => Self::Invited(Invited { inner: room }),
+            RoomState::Knocked => Self::Knocked(Knocked { inner: room }),
         }
     }
 }
@@ -71,6 +88,7 @@ impl From<Left> for Room {
             RoomState::Joined => Self::Joined(Joined { inner: room }),
             RoomState::Left => Self::Left(Left { inner: room }),
             RoomState::Invited => Self::Invited(Invited { inner: room }),
+            RoomState::Knocked => Self::Knocked(Knocked { inner: room }),
         }
     }
 }
@@ -82,6 +100,19 @@ impl From<Invited> for Room {
             RoomState::Joined => Self::Joined(Joined { inner: room }),
             RoomState::Left => Self::Left(Left { inner: room }),
             RoomState::Invited => Self::Invited(Invited { inner: room }),
+            RoomState::Knocked => Self::Knocked(Knocked { inner: room }),
+        }
+    }
+}
+
+impl From<Knocked> for Room {
+    fn from(room: Knocked) -> Self {
+        let room = (*room).clone();
+        match room.state() {
+            RoomState::Joined => Self::Joined(Joined { inner: room }),
+            RoomState::Left => Self::Left(Left { inner: room }),
+            RoomState::Invited => Self::Invited(Invited { inner: room }),
+            RoomState::Knocked => Self::Knocked(Knocked { inner: room }),
         }
     }
 }
@@ -0,0 +1,118 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reading and changing the [pinned events] of a room.
+//!
+//! [pinned events]: https://spec.matrix.org/v1.6/client-server-api/#mroompinned_events
+
+use futures_util::future::join_all;
+use matrix_sdk_base::deserialized_responses::TimelineEvent;
+use ruma::{
+    api::client::state::send_state_event,
+    events::{room::pinned_events::RoomPinnedEventsEventContent, StateEventType},
+    EventId, OwnedEventId,
+};
+use tracing::warn;
+
+use super::{Common, Joined};
+use crate::{Error, Result};
+
+impl Common {
+    /// Get the IDs of the events pinned in this room, in the order they were
+    /// pinned.
+    ///
+    /// This doesn't send a request, the IDs are read from the state of the
+    /// room in the store.
+    pub async fn pinned_event_ids(&self) -> Result<Vec<OwnedEventId>> {
+        let Some(raw) = self.get_state_event_static::<RoomPinnedEventsEventContent>().await? else {
+            return Ok(Vec::new());
+        };
+
+        Ok(raw.deserialize()?.as_original().map(|ev| ev.content.pinned.clone()).unwrap_or_default())
+    }
+
+    /// Load the events pinned in this room.
+    ///
+    /// The events are requested from the homeserver and decrypted if
+    /// possible. Pinned events that can't be loaded, because they were deleted
+    /// or because the user can't see them anymore, are skipped.
+    pub async fn pinned_events(&self) -> Result<Vec<TimelineEvent>> {
+        let event_ids = self.pinned_event_ids().await?;
+        let results = join_all(event_ids.iter().map(|event_id| self.event(event_id))).await;
+
+        let mut events = Vec::with_capacity(event_ids.len());
+        for (event_id, result) in event_ids.iter().zip(results) {
+            match result {
+                Ok(event) => events.push(event),
+                Err(error) => warn!(?event_id, "Failed to load pinned event: {error}"),
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+impl Joined {
+    /// Pin the given event in this room.
+    ///
+    /// The event is added at the end of the pinned events. Returns `None` if
+    /// the event was already pinned, in which case no request is sent.
+    ///
+    /// Returns [`Error::InsufficientPowerLevel`] if the own user isn't allowed
+    /// to change the pinned events of this room.
+    pub async fn pin_event(
+        &self,
+        event_id: &EventId,
+    ) -> Result<Option<send_state_event::v3::Response>> {
+        let mut pinned = self.pinned_event_ids().await?;
+        if pinned.iter().any(|id| id == event_id) {
+            return Ok(None);
+        }
+
+        pinned.push(event_id.to_owned());
+        self.set_pinned_events(pinned).await.map(Some)
+    }
+
+    /// Unpin the given event in this room.
+    ///
+    /// Returns `None` if the event wasn't pinned, in which case no request is
+    /// sent.
+    ///
+    /// Returns [`Error::InsufficientPowerLevel`] if the own user isn't allowed
+    /// to change the pinned events of this room.
+    pub async fn unpin_event(
+        &self,
+        event_id: &EventId,
+    ) -> Result<Option<send_state_event::v3::Response>> {
+        let mut pinned = self.pinned_event_ids().await?;
+        let len = pinned.len();
+        pinned.retain(|id| id != event_id);
+        if pinned.len() == len {
+            return Ok(None);
+        }
+
+        self.set_pinned_events(pinned).await.map(Some)
+    }
+
+    async fn set_pinned_events(
+        &self,
+        pinned: Vec<OwnedEventId>,
+    ) -> Result<send_state_event::v3::Response> {
+        if !self.can_user_send_state(self.own_user_id(), StateEventType::RoomPinnedEvents).await? {
+            return Err(Error::InsufficientPowerLevel);
+        }
+
+        self.send_state_event(RoomPinnedEventsEventContent::new(pinned)).await
+    }
+}
@@ -0,0 +1,296 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Permission checks based on the [power levels] of a room.
+//!
+//! [power levels]: https://spec.matrix.org/v1.6/client-server-api/#mroompower_levels
+
+use std::collections::BTreeMap;
+
+use ruma::{
+    api::client::state::send_state_event,
+    events::{
+        room::power_levels::{RoomPowerLevels, RoomPowerLevelsEventContent},
+        MessageLikeEventType, StateEventType,
+    },
+    Int, OwnedUserId, UserId,
+};
+
+use super::{Common, Joined};
+use crate::{Error, Result};
+
+/// Changes to the power levels of a room.
+///
+/// Fields that are `None` are left untouched. Apply the changes with
+/// [`Joined::apply_power_level_changes()`].
+#[derive(Clone, Debug, Default)]
+pub struct RoomPowerLevelChanges {
+    /// The level required to ban a user.
+    pub ban: Option<Int>,
+    /// The level required to invite a user.
+    pub invite: Option<Int>,
+    /// The level required to kick a user.
+    pub kick: Option<Int>,
+    /// The level required to redact an event sent by another user.
+    pub redact: Option<Int>,
+    /// The default level required to send message events.
+    pub events_default: Option<Int>,
+    /// The default level required to send state events.
+    pub state_default: Option<Int>,
+    /// The default power level of every user in the room.
+    pub users_default: Option<Int>,
+    /// The new power levels of specific users.
+    pub users: BTreeMap<OwnedUserId, Int>,
+}
+
+impl RoomPowerLevelChanges {
+    /// Create an empty set of changes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn apply(self, power_levels: &mut RoomPowerLevels) {
+        let Self { ban, invite, kick, redact, events_default, state_default, users_default, users } =
+            self;
+
+        if let Some(ban) = ban {
+            power_levels.ban = ban;
+        }
+        if let Some(invite) = invite {
+            power_levels.invite = invite;
+        }
+        if let Some(kick) = kick {
+            power_levels.kick = kick;
+        }
+        if let Some(redact) = redact {
+            power_levels.redact = redact;
+        }
+        if let Some(events_default) = events_default {
+            power_levels.events_default = events_default;
+        }
+        if let Some(state_default) = state_default {
+            power_levels.state_default = state_default;
+        }
+        if let Some(users_default) = users_default {
+            power_levels.users_default = users_default;
+        }
+        for (user_id, level) in users {
+            if level == power_levels.users_default {
+                power_levels.users.remove(&user_id);
+            } else {
+                power_levels.users.insert(user_id, level);
+            }
+        }
+    }
+
+    /// The highest level involved in these changes.
+    ///
+    /// This is the highest of the new levels and of the current levels of the
+    /// fields that are changed, since a user can neither set nor lower a
+    /// level that is higher than their own. Users are only taken into account
+    /// if their level changes.
+    fn max_level(&self, power_levels: &RoomPowerLevels) -> Option<Int> {
+        let users = self
+            .users
+            .iter()
+            .map(|(user_id, level)| (Some(*level), power_levels.for_user(user_id)));
+
+        [
+            (self.ban, power_levels.ban),
+            (self.invite, power_levels.invite),
+            (self.kick, power_levels.kick),
+            (self.redact, power_levels.redact),
+            (self.events_default, power_levels.events_default),
+            (self.state_default, power_levels.state_default),
+            (self.users_default, power_levels.users_default),
+        ]
+        .into_iter()
+        .chain(users)
+        .filter_map(|(new, current)| new.filter(|new| *new != current).map(|new| new.max(current)))
+        .max()
+    }
+}
+
+impl Common {
+    /// Get the current power levels of this room.
+    ///
+    /// Returns [`Error::InsufficientData`] if the `m.room.power_levels` event
+    /// of this room isn't known yet.
+    pub async fn power_levels(&self) -> Result<RoomPowerLevels> {
+        let event = self
+            .get_state_event_static::<RoomPowerLevelsEventContent>()
+            .await?
+            .ok_or(Error::InsufficientData)?
+            .deserialize()?;
+
+        Ok(event.power_levels())
+    }
+
+    /// Get the power level of the given user in this room.
+    pub async fn user_power_level(&self, user_id: &UserId) -> Result<Int> {
+        Ok(self.power_levels().await?.for_user(user_id))
+    }
+
+    /// Whether the given user is allowed to send a state event of the given
+    /// type.
+    pub async fn can_user_send_state(
+        &self,
+        user_id: &UserId,
+        state_event: StateEventType,
+    ) -> Result<bool> {
+        Ok(self.power_levels().await?.user_can_send_state(user_id, state_event))
+    }
+
+    /// Whether the given user is allowed to send a message-like event of the
+    /// given type.
+    pub async fn can_user_send_message(
+        &self,
+        user_id: &UserId,
+        message: MessageLikeEventType,
+    ) -> Result<bool> {
+        Ok(self.power_levels().await?.user_can_send_message(user_id, message))
+    }
+
+    /// Whether the given user is allowed to invite other users.
+    pub async fn can_user_invite(&self, user_id: &UserId) -> Result<bool> {
+        Ok(self.power_levels().await?.user_can_invite(user_id))
+    }
+
+    /// Whether the given user is allowed to kick other users.
+    pub async fn can_user_kick(&self, user_id: &UserId) -> Result<bool> {
+        Ok(self.power_levels().await?.user_can_kick(user_id))
+    }
+
+    /// Whether the given user is allowed to ban other users.
+    pub async fn can_user_ban(&self, user_id: &UserId) -> Result<bool> {
+        Ok(self.power_levels().await?.user_can_ban(user_id))
+    }
+
+    /// Whether the given user is allowed to redact events sent by other users.
+    ///
+    /// This requires both the `redact` level and the level to send
+    /// `m.room.redaction` events.
+    pub async fn can_user_redact_other(&self, user_id: &UserId) -> Result<bool> {
+        let power_levels = self.power_levels().await?;
+        Ok(power_levels.for_user(user_id) >= power_levels.redact
+            && power_levels.user_can_send_message(user_id, MessageLikeEventType::RoomRedaction))
+    }
+
+    /// Whether `user_id` is allowed to change the power level of `target` to
+    /// `new_level`.
+    ///
+    /// Following the authorization rules of the spec, a user can't give a
+    /// level higher than their own, and can only change the level of other
+    /// users whose current level is strictly lower than their own. Users can
+    /// always lower their own level, as long as they are allowed to send the
+    /// `m.room.power_levels` event.
+    pub async fn can_user_change_power_level(
+        &self,
+        user_id: &UserId,
+        target: &UserId,
+        new_level: Int,
+    ) -> Result<bool> {
+        let power_levels = self.power_levels().await?;
+        let own_level = power_levels.for_user(user_id);
+
+        if !power_levels.user_can_send_state(user_id, StateEventType::RoomPowerLevels) {
+            return Ok(false);
+        }
+
+        if user_id != target && power_levels.for_user(target) >= own_level {
+            return Ok(false);
+        }
+
+        Ok(new_level <= own_level)
+    }
+}
+
+impl Joined {
+    /// Change the power levels of this room.
+    ///
+    /// Returns [`Error::InsufficientData`] if the power levels aren't known
+    /// yet. The request is only sent if the own user is allowed to send the
+    /// `m.room.power_levels` event, if neither the new nor the current value
+    /// of the changed levels is higher than their own, and if the other users
+    /// whose level changes have a lower level than their own. Otherwise
+    /// [`Error::InsufficientPowerLevel`] is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::{room::RoomPowerLevelChanges, ruma::int};
+    /// # async {
+    /// # let room: matrix_sdk::room::Joined = todo!();
+    /// let mut changes = RoomPowerLevelChanges::new();
+    /// changes.invite = Some(int!(50));
+    /// changes.events_default = Some(int!(0));
+    ///
+    /// room.apply_power_level_changes(changes).await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn apply_power_level_changes(
+        &self,
+        changes: RoomPowerLevelChanges,
+    ) -> Result<send_state_event::v3::Response> {
+        let mut power_levels = self.power_levels().await?;
+        let own_user_id = self.own_user_id();
+        let own_level = power_levels.for_user(own_user_id);
+
+        // The level of other users can only be changed if it is lower than our
+        // own.
+        let changes_peer = changes.users.iter().any(|(user_id, level)| {
+            user_id != own_user_id
+                && *level != power_levels.for_user(user_id)
+                && power_levels.for_user(user_id) >= own_level
+        });
+
+        if !power_levels.user_can_send_state(own_user_id, StateEventType::RoomPowerLevels)
+            || changes.max_level(&power_levels).map_or(false, |max| max > own_level)
+            || changes_peer
+        {
+            return Err(Error::InsufficientPowerLevel);
+        }
+
+        changes.apply(&mut power_levels);
+        self.send_state_event(RoomPowerLevelsEventContent::from(power_levels)).await
+    }
+
+    /// Set the default power level of every user in this room.
+    pub async fn set_users_default_power_level(
+        &self,
+        level: Int,
+    ) -> Result<send_state_event::v3::Response> {
+        let changes = RoomPowerLevelChanges { users_default: Some(level), ..Default::default() };
+        self.apply_power_level_changes(changes).await
+    }
+
+    /// Set the default power level required to send message events in this
+    /// room.
+    pub async fn set_events_default_power_level(
+        &self,
+        level: Int,
+    ) -> Result<send_state_event::v3::Response> {
+        let changes = RoomPowerLevelChanges { events_default: Some(level), ..Default::default() };
+        self.apply_power_level_changes(changes).await
+    }
+
+    /// Set the default power level required to send state events in this room.
+    pub async fn set_state_default_power_level(
+        &self,
+        level: Int,
+    ) -> Result<send_state_event::v3::Response> {
+        let changes = RoomPowerLevelChanges { state_default: Some(level), ..Default::default() };
+        self.apply_power_level_changes(changes).await
+    }
+}
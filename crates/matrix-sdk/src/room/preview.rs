@@ -0,0 +1,331 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Previews of rooms the user isn't a member of.
+
+use matrix_sdk_base::{deserialized_responses::TimelineEvent, RoomState};
+use ruma::{
+    api::client::{directory::get_public_rooms_filtered, state::get_state_events},
+    assign,
+    directory::{Filter, PublicRoomsChunk},
+    events::{
+        room::{
+            guest_access::GuestAccess, history_visibility::HistoryVisibility,
+            member::MembershipState,
+        },
+        AnyStateEvent, AnyStateEventContent,
+    },
+    room::RoomType,
+    serde::Raw,
+    space::SpaceRoomJoinRule,
+    OwnedMxcUri, OwnedRoomAliasId, OwnedRoomId, OwnedServerName, RoomId, RoomOrAliasId,
+};
+use tracing::debug;
+
+use super::{Common, Messages, MessagesOptions};
+use crate::{Client, Error, Result};
+
+/// Information about a room, available before joining it.
+///
+/// Created with [`Client::room_preview()`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct RoomPreview {
+    /// The ID of the room.
+    pub room_id: OwnedRoomId,
+    /// The canonical alias of the room.
+    pub canonical_alias: Option<OwnedRoomAliasId>,
+    /// The name of the room.
+    pub name: Option<String>,
+    /// The topic of the room.
+    pub topic: Option<String>,
+    /// The avatar of the room.
+    pub avatar_url: Option<OwnedMxcUri>,
+    /// The number of joined members.
+    pub num_joined_members: u64,
+    /// The type of the room, if it has one, like `m.space`.
+    pub room_type: Option<RoomType>,
+    /// The join rule of the room, if it is known.
+    pub join_rule: Option<SpaceRoomJoinRule>,
+    /// Whether the history of the room can be read by anyone.
+    pub is_world_readable: bool,
+    /// Whether guests are allowed to join the room.
+    pub guest_can_join: bool,
+    /// Whether the room is encrypted, if it is known.
+    pub is_encrypted: Option<bool>,
+    /// The state of the own user in the room, if the room is known locally.
+    pub state: Option<RoomState>,
+    client: Client,
+}
+
+impl RoomPreview {
+    fn new(client: Client, room_id: OwnedRoomId) -> Self {
+        Self {
+            room_id,
+            canonical_alias: None,
+            name: None,
+            topic: None,
+            avatar_url: None,
+            num_joined_members: 0,
+            room_type: None,
+            join_rule: None,
+            is_world_readable: false,
+            guest_can_join: false,
+            is_encrypted: None,
+            state: None,
+            client,
+        }
+    }
+
+    async fn from_local_room(room: &Common) -> Result<Self> {
+        let mut preview = Self::new(room.client(), room.room_id().to_owned());
+
+        preview.canonical_alias = room.canonical_alias();
+        preview.name = room.name();
+        preview.topic = room.topic();
+        preview.avatar_url = room.avatar_url();
+        preview.num_joined_members = room.joined_user_ids().await?.len() as u64;
+        preview.room_type = room.clone_info().room_type().cloned();
+        preview.join_rule = Some(SpaceRoomJoinRule::from(room.join_rule().as_str()));
+        preview.is_world_readable =
+            matches!(room.history_visibility(), HistoryVisibility::WorldReadable);
+        preview.guest_can_join = matches!(room.guest_access(), GuestAccess::CanJoin);
+        preview.is_encrypted = room.is_encrypted().await.ok();
+        preview.state = Some(room.state());
+
+        Ok(preview)
+    }
+
+    fn from_summary(client: Client, summary: summary::Response) -> Self {
+        let mut preview = Self::new(client, summary.room_id);
+
+        preview.canonical_alias = summary.canonical_alias;
+        preview.name = summary.name;
+        preview.topic = summary.topic;
+        preview.avatar_url = summary.avatar_url;
+        preview.num_joined_members = summary.num_joined_members.into();
+        preview.room_type = summary.room_type;
+        preview.join_rule = summary.join_rule;
+        preview.is_world_readable = summary.world_readable;
+        preview.guest_can_join = summary.guest_can_join;
+        preview.is_encrypted = summary.encryption.map(|_| true);
+
+        preview
+    }
+
+    fn from_state(client: Client, room_id: OwnedRoomId, state: &[Raw<AnyStateEvent>]) -> Self {
+        let mut preview = Self::new(client, room_id);
+        preview.is_encrypted = Some(false);
+
+        for event in state.iter().filter_map(|raw| raw.deserialize().ok()) {
+            let Some(content) = event.original_content() else { continue };
+
+            match content {
+                AnyStateEventContent::RoomCreate(c) => preview.room_type = c.room_type,
+                AnyStateEventContent::RoomName(c) => preview.name = c.name,
+                AnyStateEventContent::RoomTopic(c) => preview.topic = Some(c.topic),
+                AnyStateEventContent::RoomAvatar(c) => preview.avatar_url = c.url,
+                AnyStateEventContent::RoomCanonicalAlias(c) => preview.canonical_alias = c.alias,
+                AnyStateEventContent::RoomJoinRules(c) => {
+                    preview.join_rule = Some(SpaceRoomJoinRule::from(c.join_rule.as_str()));
+                }
+                AnyStateEventContent::RoomHistoryVisibility(c) => {
+                    preview.is_world_readable =
+                        matches!(c.history_visibility, HistoryVisibility::WorldReadable);
+                }
+                AnyStateEventContent::RoomGuestAccess(c) => {
+                    preview.guest_can_join = matches!(c.guest_access, GuestAccess::CanJoin);
+                }
+                AnyStateEventContent::RoomEncryption(_) => preview.is_encrypted = Some(true),
+                AnyStateEventContent::RoomMember(c) if c.membership == MembershipState::Join => {
+                    preview.num_joined_members += 1;
+                }
+                _ => {}
+            }
+        }
+
+        preview
+    }
+
+    fn from_public_rooms_chunk(client: Client, chunk: PublicRoomsChunk) -> Self {
+        let mut preview = Self::new(client, chunk.room_id);
+
+        preview.canonical_alias = chunk.canonical_alias;
+        preview.name = chunk.name;
+        preview.topic = chunk.topic;
+        preview.avatar_url = chunk.avatar_url;
+        preview.num_joined_members = chunk.num_joined_members.into();
+        preview.room_type = chunk.room_type;
+        preview.join_rule = Some(SpaceRoomJoinRule::from(chunk.join_rule.as_str()));
+        preview.is_world_readable = chunk.world_readable;
+        preview.guest_can_join = chunk.guest_can_join;
+
+        preview
+    }
+
+    /// Get recent events of this room without joining it.
+    ///
+    /// This only works for rooms whose history is world readable, see
+    /// [`RoomPreview::is_world_readable`]. The returned events are read-only,
+    /// pass the `end` token of the response as the `from` field of the next
+    /// call's options to paginate further.
+    pub async fn messages(&self, options: MessagesOptions) -> Result<Messages> {
+        let request = options.into_request(&self.room_id);
+        let response = self.client.send(request, None).await?;
+
+        Ok(Messages {
+            start: response.start,
+            end: response.end,
+            chunk: response.chunk.into_iter().map(TimelineEvent::new).collect(),
+            state: response.state,
+        })
+    }
+}
+
+impl RoomPreview {
+    /// Load the preview of a room from the first source that knows it, see
+    /// [`Client::room_preview()`].
+    pub(crate) async fn load(
+        client: &Client,
+        room_id_or_alias: &RoomOrAliasId,
+        mut via: Vec<OwnedServerName>,
+    ) -> Result<Self> {
+        let room_id = match <&RoomId>::try_from(room_id_or_alias) {
+            Ok(room_id) => room_id.to_owned(),
+            Err(alias) => {
+                let response = client.resolve_room_alias(alias).await?;
+                for server in response.servers {
+                    if !via.contains(&server) {
+                        via.push(server);
+                    }
+                }
+                response.room_id
+            }
+        };
+
+        if let Some(room) = client.get_room(&room_id) {
+            if room.state() != RoomState::Left {
+                return Self::from_local_room(&room).await;
+            }
+        }
+
+        let request = summary::Request::new(room_id_or_alias.to_owned(), via.clone());
+        match client.send(request, None).await {
+            Ok(response) => return Ok(Self::from_summary(client.clone(), response)),
+            Err(error) => debug!(?room_id, "Room summary is not available: {error}"),
+        }
+
+        let request = get_state_events::v3::Request::new(room_id.clone());
+        match client.send(request, None).await {
+            Ok(response) => {
+                return Ok(Self::from_state(client.clone(), room_id, &response.room_state))
+            }
+            Err(error) => debug!(?room_id, "Room state is not readable: {error}"),
+        }
+
+        let mut filter = Filter::new();
+        filter.generic_search_term = Some(room_id_or_alias.to_string());
+        let request = assign!(get_public_rooms_filtered::v3::Request::new(), {
+            server: via.into_iter().next(),
+            filter,
+        });
+
+        client
+            .public_rooms_filtered(request)
+            .await?
+            .chunk
+            .into_iter()
+            .find(|chunk| chunk.room_id == room_id)
+            .map(|chunk| Self::from_public_rooms_chunk(client.clone(), chunk))
+            .ok_or(Error::InsufficientData)
+    }
+}
+
+/// The room summary endpoint of [MSC3266].
+///
+/// [MSC3266]: https://github.com/matrix-org/matrix-spec-proposals/pull/3266
+mod summary {
+    use ruma::{
+        api::{request, response, Metadata},
+        events::room::member::MembershipState,
+        metadata,
+        room::RoomType,
+        space::SpaceRoomJoinRule,
+        EventEncryptionAlgorithm, OwnedMxcUri, OwnedRoomAliasId, OwnedRoomId, OwnedRoomOrAliasId,
+        OwnedServerName, RoomVersionId, UInt,
+    };
+
+    const METADATA: Metadata = metadata! {
+        method: GET,
+        rate_limited: false,
+        authentication: AccessTokenOptional,
+        history: {
+            unstable => "/_matrix/client/unstable/im.nheko.summary/rooms/:room_id_or_alias/summary",
+        }
+    };
+
+    #[request(error = ruma::api::client::Error)]
+    pub struct Request {
+        #[ruma_api(path)]
+        pub room_id_or_alias: OwnedRoomOrAliasId,
+
+        #[ruma_api(query)]
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        pub via: Vec<OwnedServerName>,
+    }
+
+    #[response(error = ruma::api::client::Error)]
+    pub struct Response {
+        pub room_id: OwnedRoomId,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub canonical_alias: Option<OwnedRoomAliasId>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub name: Option<String>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub topic: Option<String>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub avatar_url: Option<OwnedMxcUri>,
+
+        pub num_joined_members: UInt,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub room_type: Option<RoomType>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub join_rule: Option<SpaceRoomJoinRule>,
+
+        pub world_readable: bool,
+
+        pub guest_can_join: bool,
+
+        #[serde(alias = "im.nheko.summary.encryption", skip_serializing_if = "Option::is_none")]
+        pub encryption: Option<EventEncryptionAlgorithm>,
+
+        #[serde(alias = "im.nheko.summary.version", skip_serializing_if = "Option::is_none")]
+        pub room_version: Option<RoomVersionId>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub membership: Option<MembershipState>,
+    }
+
+    impl Request {
+        pub fn new(room_id_or_alias: OwnedRoomOrAliasId, via: Vec<OwnedServerName>) -> Self {
+            Self { room_id_or_alias, via }
+        }
+    }
+}
@@ -0,0 +1,424 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! High-level API for [spaces].
+//!
+//! [spaces]: https://spec.matrix.org/v1.6/client-server-api/#spaces
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::Deref,
+};
+
+use ruma::{
+    api::client::space::{get_hierarchy, SpaceHierarchyRoomsChunk},
+    assign,
+    events::{
+        space::{child::SpaceChildEventContent, parent::SpaceParentEventContent},
+        SyncStateEvent,
+    },
+    MilliSecondsSinceUnixEpoch, OwnedRoomId, OwnedServerName, RoomId, UInt,
+};
+use tracing::{instrument, warn};
+
+use super::Joined;
+use crate::{Client, Result};
+
+/// A joined room of type `m.space`.
+///
+/// A space groups other rooms, which can themselves be spaces, by listing
+/// them as its children with `m.space.child` state events.
+#[derive(Debug, Clone)]
+pub struct Space {
+    inner: Joined,
+}
+
+impl Deref for Space {
+    type Target = Joined;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl Space {
+    /// Create a new `Space` if the given joined room is a space.
+    pub(crate) fn new(room: Joined) -> Option<Self> {
+        room.is_space().then_some(Self { inner: room })
+    }
+
+    /// Browse the hierarchy of this space from the homeserver.
+    ///
+    /// Unlike [`Client::space_tree()`], this includes rooms the user is not a
+    /// member of. See [`Client::space_hierarchy()`] for more details.
+    pub fn hierarchy(&self, options: HierarchyOptions) -> SpaceHierarchy {
+        SpaceHierarchy::new(self.client.clone(), self.room_id().to_owned(), options)
+    }
+
+    /// Add a room as a child of this space.
+    ///
+    /// The servers that can be used to join the child are computed with
+    /// [`Common::route()`](super::Common::route) if the room is known to the
+    /// client, and from this space otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `child_id` - The ID of the room to add to this space.
+    ///
+    /// * `order` - A string used to order the children of this space
+    ///   lexicographically. It must consist of at most 50 printable ASCII
+    ///   characters.
+    ///
+    /// * `suggested` - Whether the child should be highlighted to the members
+    ///   of this space.
+    #[instrument(skip(self))]
+    pub async fn add_child(
+        &self,
+        child_id: &RoomId,
+        order: Option<String>,
+        suggested: bool,
+    ) -> Result<()> {
+        let via = match self.client.get_room(child_id) {
+            Some(child) => child.route().await?,
+            None => self.route().await?,
+        };
+        let via = non_empty_via(&self.client, via);
+
+        let content = assign!(SpaceChildEventContent::new(), { via: Some(via), order, suggested });
+        self.send_state_event_for_key(child_id, content).await?;
+
+        Ok(())
+    }
+
+    /// Remove a room from the children of this space.
+    ///
+    /// # Arguments
+    ///
+    /// * `child_id` - The ID of the room to remove from this space.
+    #[instrument(skip(self))]
+    pub async fn remove_child(&self, child_id: &RoomId) -> Result<()> {
+        // A child event without `via` is considered as removed.
+        self.send_state_event_for_key(child_id, SpaceChildEventContent::new()).await?;
+        Ok(())
+    }
+
+    /// Get the children of this space, as known locally.
+    ///
+    /// The children are sorted in the order defined by the spec, and only
+    /// contain the rooms that are still part of this space.
+    pub async fn children(&self) -> Result<Vec<SpaceChild>> {
+        let mut children: Vec<_> = self
+            .get_state_events_static::<SpaceChildEventContent>()
+            .await?
+            .into_iter()
+            .filter_map(|raw| match raw.deserialize() {
+                Ok(SyncStateEvent::Original(ev)) => {
+                    let via = ev.content.via.filter(|via| !via.is_empty())?;
+                    Some(SpaceChild {
+                        room_id: ev.state_key,
+                        via,
+                        order: ev.content.order.filter(|order| is_valid_order(order)),
+                        suggested: ev.content.suggested,
+                        added_at: ev.origin_server_ts,
+                    })
+                }
+                Ok(SyncStateEvent::Redacted(_)) => None,
+                Err(e) => {
+                    warn!(room_id = ?self.room_id(), "Failed to deserialize space child: {e}");
+                    None
+                }
+            })
+            .collect();
+
+        children.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+
+        Ok(children)
+    }
+}
+
+impl Joined {
+    /// Declare the given space as a parent of this room.
+    ///
+    /// The servers that can be used to join the parent are computed with
+    /// [`Common::route()`](super::Common::route) if the space is known to the
+    /// client, and from this room otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `parent_id` - The ID of the parent space.
+    ///
+    /// * `canonical` - Whether the parent is the main parent of this room.
+    #[instrument(skip(self))]
+    pub async fn set_parent(&self, parent_id: &RoomId, canonical: bool) -> Result<()> {
+        let via = match self.client.get_room(parent_id) {
+            Some(parent) => parent.route().await?,
+            None => self.route().await?,
+        };
+        let via = non_empty_via(&self.client, via);
+
+        let content = assign!(SpaceParentEventContent::new(canonical), { via: Some(via) });
+        self.send_state_event_for_key(parent_id, content).await?;
+
+        Ok(())
+    }
+}
+
+/// A child of a [`Space`], as listed in its `m.space.child` state events.
+#[derive(Debug, Clone)]
+pub struct SpaceChild {
+    /// The ID of the child room.
+    pub room_id: OwnedRoomId,
+    /// The servers that can be used to join the child room.
+    pub via: Vec<OwnedServerName>,
+    /// The string used to order the children of the space, if it is valid.
+    pub order: Option<String>,
+    /// Whether the child is suggested to the members of the space.
+    pub suggested: bool,
+    /// When the child was added to the space.
+    pub added_at: MilliSecondsSinceUnixEpoch,
+}
+
+impl SpaceChild {
+    /// The key to sort children as defined in the spec: children with an
+    /// `order` come first, then ties are broken by the time they were added
+    /// and their room ID.
+    fn sort_key(&self) -> (bool, &Option<String>, MilliSecondsSinceUnixEpoch, &RoomId) {
+        (self.order.is_none(), &self.order, self.added_at, &self.room_id)
+    }
+}
+
+/// Options for [`Client::space_hierarchy()`].
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct HierarchyOptions {
+    /// Only return the children that are marked as suggested.
+    pub suggested_only: bool,
+    /// The maximum depth of the hierarchy to return.
+    ///
+    /// If this is `None`, the homeserver chooses the maximum depth.
+    pub max_depth: Option<UInt>,
+    /// The maximum number of rooms to return per page.
+    ///
+    /// If this is `None`, the homeserver chooses the page size.
+    pub limit: Option<UInt>,
+}
+
+impl HierarchyOptions {
+    /// Default options to browse the hierarchy of a space.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only return the children that are marked as suggested.
+    pub fn suggested_only(mut self) -> Self {
+        self.suggested_only = true;
+        self
+    }
+
+    /// Set the maximum depth of the hierarchy to return.
+    pub fn max_depth(mut self, max_depth: UInt) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Set the maximum number of rooms to return per page.
+    pub fn limit(mut self, limit: UInt) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+/// Paginated browsing of the hierarchy of a space.
+///
+/// Created with [`Client::space_hierarchy()`] or [`Space::hierarchy()`].
+#[derive(Debug)]
+pub struct SpaceHierarchy {
+    client: Client,
+    room_id: OwnedRoomId,
+    options: HierarchyOptions,
+    next_batch: Option<String>,
+    finished: bool,
+}
+
+impl SpaceHierarchy {
+    pub(crate) fn new(client: Client, room_id: OwnedRoomId, options: HierarchyOptions) -> Self {
+        Self { client, room_id, options, next_batch: None, finished: false }
+    }
+
+    /// Whether all the pages of the hierarchy have been fetched.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Fetch the next page of the hierarchy.
+    ///
+    /// The rooms are returned in depth-first order, the first page starting
+    /// with the space itself.
+    ///
+    /// Returns `None` once all the pages have been fetched.
+    #[instrument(skip(self), fields(room_id = ?self.room_id))]
+    pub async fn next_page(&mut self) -> Result<Option<Vec<SpaceHierarchyRoomsChunk>>> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let request = assign!(get_hierarchy::v1::Request::new(self.room_id.clone()), {
+            from: self.next_batch.clone(),
+            limit: self.options.limit,
+            max_depth: self.options.max_depth,
+            suggested_only: self.options.suggested_only,
+        });
+        let response = self.client.send(request, None).await?;
+
+        self.finished = response.next_batch.is_none();
+        self.next_batch = response.next_batch;
+
+        Ok(Some(response.rooms))
+    }
+}
+
+/// The tree of the spaces the user has joined, computed from the local state.
+///
+/// Created with [`Client::space_tree()`].
+#[derive(Debug, Clone, Default)]
+pub struct SpaceTree {
+    /// The spaces that are not the child of another joined space.
+    pub roots: Vec<SpaceTreeNode>,
+}
+
+/// A joined room in a [`SpaceTree`].
+#[derive(Debug, Clone)]
+pub struct SpaceTreeNode {
+    /// The joined room.
+    pub room: Joined,
+    /// Whether the room is suggested in its parent space.
+    ///
+    /// This is always `false` for the roots of the tree.
+    pub suggested: bool,
+    /// The joined children of the room, if it is a space.
+    pub children: Vec<SpaceTreeNode>,
+}
+
+impl SpaceTree {
+    pub(crate) async fn build(client: &Client) -> Result<Self> {
+        let joined_rooms: BTreeMap<OwnedRoomId, Joined> = client
+            .joined_rooms()
+            .into_iter()
+            .map(|room| (room.room_id().to_owned(), room))
+            .collect();
+
+        let mut children = BTreeMap::new();
+        for room in joined_rooms.values() {
+            if let Some(space) = Space::new(room.clone()) {
+                children.insert(space.room_id().to_owned(), space.children().await?);
+            }
+        }
+
+        // The roots are the spaces that are not the child of another joined
+        // space.
+        let nested: BTreeSet<_> = children
+            .values()
+            .flatten()
+            .map(|child| &child.room_id)
+            .filter(|room_id| children.contains_key(*room_id))
+            .collect();
+
+        let mut visited = BTreeSet::new();
+        let mut roots: Vec<_> = children
+            .keys()
+            .filter(|room_id| !nested.contains(room_id))
+            .filter_map(|room_id| {
+                build_node(room_id, false, &joined_rooms, &children, &mut Vec::new(), &mut visited)
+            })
+            .collect();
+
+        // Spaces that are only part of a cycle don't have a root, add them as
+        // their own root.
+        for room_id in children.keys() {
+            if !visited.contains(room_id) {
+                roots.extend(build_node(
+                    room_id,
+                    false,
+                    &joined_rooms,
+                    &children,
+                    &mut Vec::new(),
+                    &mut visited,
+                ));
+            }
+        }
+
+        Ok(Self { roots })
+    }
+}
+
+/// Build the node of the given room.
+///
+/// `ancestors` are the spaces on the path from the root to this room, and
+/// `visited` collects all the spaces that were expanded.
+fn build_node(
+    room_id: &RoomId,
+    suggested: bool,
+    joined_rooms: &BTreeMap<OwnedRoomId, Joined>,
+    children: &BTreeMap<OwnedRoomId, Vec<SpaceChild>>,
+    ancestors: &mut Vec<OwnedRoomId>,
+    visited: &mut BTreeSet<OwnedRoomId>,
+) -> Option<SpaceTreeNode> {
+    let room = joined_rooms.get(room_id)?.clone();
+
+    let mut node = SpaceTreeNode { room, suggested, children: Vec::new() };
+
+    // Don't expand a space that is already on the path, to avoid cycles. A
+    // space that is the child of several spaces is expanded under each of
+    // them.
+    if ancestors.iter().any(|ancestor| ancestor == room_id) {
+        return Some(node);
+    }
+
+    if let Some(space_children) = children.get(room_id) {
+        visited.insert(room_id.to_owned());
+        ancestors.push(room_id.to_owned());
+
+        node.children = space_children
+            .iter()
+            .filter_map(|child| {
+                build_node(
+                    &child.room_id,
+                    child.suggested,
+                    joined_rooms,
+                    children,
+                    ancestors,
+                    visited,
+                )
+            })
+            .collect();
+
+        ancestors.pop();
+    }
+
+    Some(node)
+}
+
+/// Fall back to the server of the own user if no server could be found to join
+/// a room.
+fn non_empty_via(client: &Client, via: Vec<OwnedServerName>) -> Vec<OwnedServerName> {
+    match client.user_id() {
+        Some(user_id) if via.is_empty() => vec![user_id.server_name().to_owned()],
+        _ => via,
+    }
+}
+
+/// Whether the given string is a valid `order` for a space child.
+fn is_valid_order(order: &str) -> bool {
+    order.len() <= 50 && order.chars().all(|c| ('\x20'..='\x7E').contains(&c))
+}
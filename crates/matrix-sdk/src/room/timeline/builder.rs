@@ -25,18 +25,23 @@ use tracing::error;
 
 #[cfg(feature = "e2e-encryption")]
 use super::to_device::{handle_forwarded_room_key_event, handle_room_key_event};
-use super::{inner::TimelineInner, Timeline, TimelineEventHandlerHandles};
+use super::{
+    inner::{TimelineInner, UrlPreviewSettings},
+    Timeline, TimelineEventHandlerHandles,
+};
 use crate::room;
 
 /// Builder that allows creating and configuring various parts of a
 /// [`Timeline`].
 #[must_use]
 #[derive(Debug)]
-pub(crate) struct TimelineBuilder {
+pub struct TimelineBuilder {
     room: room::Common,
     prev_token: Option<String>,
     events: Vector<SyncTimelineEvent>,
     track_read_marker_and_receipts: bool,
+    url_previews: UrlPreviewSettings,
+    follow_predecessors: bool,
 }
 
 impl TimelineBuilder {
@@ -46,6 +51,8 @@ impl TimelineBuilder {
             prev_token: None,
             events: Vector::new(),
             track_read_marker_and_receipts: false,
+            url_previews: UrlPreviewSettings::default(),
+            follow_predecessors: false,
         }
     }
 
@@ -63,18 +70,61 @@ impl TimelineBuilder {
 
     /// Enable tracking of the fully-read marker and the read receipts on the
     /// timeline.
-    pub(crate) fn track_read_marker_and_receipts(mut self) -> Self {
+    pub fn track_read_marker_and_receipts(mut self) -> Self {
         self.track_read_marker_and_receipts = true;
         self
     }
 
+    /// Enable URL previews for messages on the timeline.
+    ///
+    /// When enabled, [`Timeline::fetch_event_details`] also requests a preview
+    /// of the first link in the body of a message from the homeserver, which
+    /// is then available via [`EventTimelineItem::url_preview`].
+    ///
+    /// Previews are not fetched in encrypted rooms unless
+    /// [`url_previews_in_encrypted_rooms`][Self::url_previews_in_encrypted_rooms]
+    /// is enabled too, since that leaks the URL to the homeserver.
+    ///
+    /// [`EventTimelineItem::url_preview`]: super::EventTimelineItem::url_preview
+    pub fn url_previews(mut self, enabled: bool) -> Self {
+        self.url_previews.enabled = enabled;
+        self
+    }
+
+    /// Allow fetching URL previews in encrypted rooms.
+    ///
+    /// This has no effect unless [`url_previews`][Self::url_previews] is
+    /// enabled.
+    pub fn url_previews_in_encrypted_rooms(mut self, allow: bool) -> Self {
+        self.url_previews.allow_encrypted_rooms = allow;
+        self
+    }
+
+    /// Continue back-paginating in the predecessors of the room once the start
+    /// of the room is reached.
+    ///
+    /// This merges the history of a room that was upgraded with the history of
+    /// the rooms it replaced, as long as they are known to the client.
+    pub fn follow_predecessors(mut self) -> Self {
+        self.follow_predecessors = true;
+        self
+    }
+
     /// Create a [`Timeline`] with the options set on this builder.
-    pub(crate) async fn build(self) -> Timeline {
-        let Self { room, prev_token, events, track_read_marker_and_receipts } = self;
+    pub async fn build(self) -> Timeline {
+        let Self {
+            room,
+            prev_token,
+            events,
+            track_read_marker_and_receipts,
+            url_previews,
+            follow_predecessors,
+        } = self;
         let has_events = !events.is_empty();
 
-        let mut inner =
-            TimelineInner::new(room).with_read_receipt_tracking(track_read_marker_and_receipts);
+        let mut inner = TimelineInner::new(room)
+            .with_read_receipt_tracking(track_read_marker_and_receipts)
+            .with_url_previews(url_previews);
 
         if track_read_marker_and_receipts {
             match inner
@@ -113,6 +163,11 @@ impl TimelineBuilder {
             }
         }
 
+        match inner.room().pinned_event_ids().await {
+            Ok(event_ids) => inner.set_initial_pinned_events(event_ids),
+            Err(e) => error!("Failed to get the pinned events from the store: {e}"),
+        }
+
         if has_events {
             inner.add_initial_events(events).await;
         }
@@ -180,6 +235,8 @@ impl TimelineBuilder {
             inner,
             start_token: Mutex::new(prev_token),
             _end_token: Mutex::new(None),
+            pagination_room: Mutex::new(room.clone()),
+            follow_predecessors,
             event_handler_handles: Arc::new(TimelineEventHandlerHandles { client, handles }),
         };
 
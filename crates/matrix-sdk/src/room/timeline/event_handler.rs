@@ -12,7 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use chrono::{Datelike, Local, TimeZone};
 use eyeball_im::ObservableVector;
@@ -27,6 +30,7 @@ use ruma::{
             encrypted::RoomEncryptedEventContent,
             member::{Change, RoomMemberEventContent},
             message::{self, MessageType, RoomMessageEventContent, SyncRoomMessageEvent},
+            pinned_events::RoomPinnedEventsEventContent,
             redaction::{
                 OriginalSyncRoomRedactionEvent, RoomRedactionEventContent, SyncRoomRedactionEvent,
             },
@@ -209,6 +213,7 @@ pub(super) struct TimelineEventHandler<'a> {
     track_read_receipts: bool,
     users_read_receipts:
         &'a mut HashMap<OwnedUserId, HashMap<ReceiptType, (OwnedEventId, Receipt)>>,
+    pinned_event_ids: &'a mut HashSet<OwnedEventId>,
     result: HandleEventResult,
 }
 
@@ -244,6 +249,7 @@ impl<'a> TimelineEventHandler<'a> {
             event_should_update_fully_read_marker: &mut state.event_should_update_fully_read_marker,
             track_read_receipts,
             users_read_receipts: &mut state.users_read_receipts,
+            pinned_event_ids: &mut state.pinned_event_ids,
             result: HandleEventResult::default(),
         }
     }
@@ -312,6 +318,9 @@ impl<'a> TimelineEventHandler<'a> {
             }
 
             TimelineEventKind::OtherState { state_key, content } => {
+                if let AnyOtherFullStateEventContent::RoomPinnedEvents(c) = &content {
+                    self.handle_pinned_events(c);
+                }
                 self.add(NewEventTimelineItem::other_state(state_key, content));
             }
 
@@ -541,6 +550,46 @@ impl<'a> TimelineEventHandler<'a> {
         }
     }
 
+    /// Update the pinned flag of the items with the new pinned events of the
+    /// room.
+    #[instrument(skip_all)]
+    fn handle_pinned_events(
+        &mut self,
+        content: &FullStateEventContent<RoomPinnedEventsEventContent>,
+    ) {
+        // State events loaded by back-pagination are older than the current
+        // state of the room.
+        if !matches!(self.flow, Flow::Remote { position: TimelineItemPosition::End, .. }) {
+            return;
+        }
+
+        let pinned_event_ids: HashSet<_> = match content {
+            FullStateEventContent::Original { content, .. } => {
+                content.pinned.iter().cloned().collect()
+            }
+            FullStateEventContent::Redacted(_) => HashSet::new(),
+        };
+
+        for idx in 0..self.items.len() {
+            let Some(event_item) = self.items[idx].as_event() else { continue };
+            let Some(remote_event_item) = event_item.as_remote() else { continue };
+
+            let is_pinned = pinned_event_ids.contains(&remote_event_item.event_id);
+            if remote_event_item.is_pinned == is_pinned {
+                continue;
+            }
+
+            trace!(event_id = ?remote_event_item.event_id, is_pinned, "Updating pinned flag");
+            let remote_event_item =
+                RemoteEventTimelineItem { is_pinned, ..remote_event_item.clone() };
+            let event_item = event_item.with_kind(remote_event_item);
+            self.items.set(idx, Arc::new(event_item.into()));
+            self.result.items_updated += 1;
+        }
+
+        *self.pinned_event_ids = pinned_event_ids;
+    }
+
     /// Add a new event item in the timeline.
     fn add(&mut self, item: NewEventTimelineItem) {
         self.result.item_added = true;
@@ -586,6 +635,7 @@ impl<'a> TimelineEventHandler<'a> {
                     read_receipts: self.meta.read_receipts.clone(),
                     is_own: self.meta.is_own_event,
                     is_highlighted: self.meta.is_highlighted,
+                    is_pinned: self.pinned_event_ids.contains(event_id),
                     encryption_info: self.meta.encryption_info.clone(),
                     original_json: raw_event.clone(),
                     latest_edit_json: None,
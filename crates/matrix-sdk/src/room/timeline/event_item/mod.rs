@@ -24,7 +24,7 @@ use ruma::{
     UserId,
 };
 
-use crate::Error;
+use crate::{media::UrlPreview, Error};
 
 mod content;
 mod local;
@@ -57,6 +57,9 @@ pub struct EventTimelineItem {
     pub(super) content: TimelineItemContent,
     /// The kind of event timeline item, local or remote.
     pub(super) kind: EventTimelineItemKind,
+    /// The preview of the first URL in the message, if URL previews are
+    /// enabled for the timeline.
+    pub(super) url_preview: TimelineDetails<Box<UrlPreview>>,
 }
 
 #[derive(Clone, Debug)]
@@ -75,7 +78,14 @@ impl EventTimelineItem {
         content: TimelineItemContent,
         kind: EventTimelineItemKind,
     ) -> Self {
-        Self { sender, sender_profile, timestamp, content, kind }
+        Self {
+            sender,
+            sender_profile,
+            timestamp,
+            content,
+            kind,
+            url_preview: TimelineDetails::Unavailable,
+        }
     }
 
     /// Check whether this item is a local echo.
@@ -171,6 +181,16 @@ impl EventTimelineItem {
         &self.content
     }
 
+    /// Get the preview of the first URL in the message of this item.
+    ///
+    /// This is only ever available if URL previews were enabled when building
+    /// the timeline. Use [`Timeline::fetch_event_details`] to fetch it.
+    ///
+    /// [`Timeline::fetch_event_details`]: super::Timeline::fetch_event_details
+    pub fn url_preview(&self) -> &TimelineDetails<Box<UrlPreview>> {
+        &self.url_preview
+    }
+
     /// Get the reactions of this item.
     pub fn reactions(&self) -> &BundledReactions {
         // There's not much of a point in allowing reactions to local echoes.
@@ -231,6 +251,17 @@ impl EventTimelineItem {
         }
     }
 
+    /// Whether the event is pinned in the room.
+    ///
+    /// This is updated when the `m.room.pinned_events` state of the room
+    /// changes.
+    pub fn is_pinned(&self) -> bool {
+        match &self.kind {
+            EventTimelineItemKind::Local(_) => false,
+            EventTimelineItemKind::Remote(remote_event) => remote_event.is_pinned,
+        }
+    }
+
     /// Get the encryption information for the event, if any.
     pub fn encryption_info(&self) -> Option<&EncryptionInfo> {
         match &self.kind {
@@ -287,6 +318,11 @@ impl EventTimelineItem {
     pub(super) fn with_sender_profile(&self, sender_profile: TimelineDetails<Profile>) -> Self {
         Self { sender_profile, ..self.clone() }
     }
+
+    /// Clone the current event item, and update its `url_preview`.
+    pub(super) fn with_url_preview(&self, url_preview: TimelineDetails<Box<UrlPreview>>) -> Self {
+        Self { url_preview, ..self.clone() }
+    }
 }
 
 /// This type represents the "send state" of a local event timeline item.
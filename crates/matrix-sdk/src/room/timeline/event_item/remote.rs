@@ -28,6 +28,8 @@ pub(in crate::room::timeline) struct RemoteEventTimelineItem {
     pub is_own: bool,
     /// Whether the item should be highlighted in the timeline.
     pub is_highlighted: bool,
+    /// Whether the event is pinned in the room.
+    pub is_pinned: bool,
     /// Encryption information.
     pub encryption_info: Option<EncryptionInfo>,
     /// JSON of the original event.
@@ -90,6 +92,7 @@ impl fmt::Debug for RemoteEventTimelineItem {
             original_json: _,
             latest_edit_json: _,
             is_highlighted,
+            is_pinned,
             origin,
         } = self;
 
@@ -99,6 +102,7 @@ impl fmt::Debug for RemoteEventTimelineItem {
             .field("read_receipts", read_receipts)
             .field("is_own", is_own)
             .field("is_highlighted", is_highlighted)
+            .field("is_pinned", is_pinned)
             .field("encryption_info", encryption_info)
             .field("origin", origin)
             .finish_non_exhaustive()
@@ -14,7 +14,10 @@
 
 #[cfg(feature = "e2e-encryption")]
 use std::collections::BTreeSet;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use async_trait::async_trait;
 use eyeball_im::{ObservableVector, VectorSubscriber};
@@ -49,6 +52,7 @@ use super::{
         update_read_marker, Flow, HandleEventResult, TimelineEventHandler, TimelineEventKind,
         TimelineEventMetadata, TimelineItemPosition,
     },
+    find_url,
     read_receipts::{
         handle_explicit_read_receipts, latest_user_read_receipt, load_read_receipts_for_event,
         user_receipt,
@@ -64,6 +68,14 @@ pub(super) struct TimelineInner<P: RoomDataProvider = room::Common> {
     state: Mutex<TimelineInnerState>,
     room_data_provider: P,
     track_read_receipts: bool,
+    url_previews: UrlPreviewSettings,
+}
+
+/// Whether and where URL previews should be fetched for a timeline.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct UrlPreviewSettings {
+    pub(super) enabled: bool,
+    pub(super) allow_encrypted_rooms: bool,
 }
 
 #[derive(Debug, Default)]
@@ -85,6 +97,8 @@ pub(super) struct TimelineInnerState {
     /// User ID => Receipt type => Read receipt of the user of the given type.
     pub(super) users_read_receipts:
         HashMap<OwnedUserId, HashMap<ReceiptType, (OwnedEventId, Receipt)>>,
+    /// The IDs of the events that are pinned in the room.
+    pub(super) pinned_event_ids: HashSet<OwnedEventId>,
 }
 
 impl<P: RoomDataProvider> TimelineInner<P> {
@@ -96,7 +110,12 @@ impl<P: RoomDataProvider> TimelineInner<P> {
             items: ObservableVector::with_capacity(32),
             ..Default::default()
         };
-        Self { state: Mutex::new(state), room_data_provider, track_read_receipts: false }
+        Self {
+            state: Mutex::new(state),
+            room_data_provider,
+            track_read_receipts: false,
+            url_previews: UrlPreviewSettings::default(),
+        }
     }
 
     pub(super) fn with_read_receipt_tracking(mut self, track_read_receipts: bool) -> Self {
@@ -104,6 +123,11 @@ impl<P: RoomDataProvider> TimelineInner<P> {
         self
     }
 
+    pub(super) fn with_url_previews(mut self, url_previews: UrlPreviewSettings) -> Self {
+        self.url_previews = url_previews;
+        self
+    }
+
     /// Get a copy of the current items in the list.
     ///
     /// Cheap because `im::Vector` is cheap to clone.
@@ -136,6 +160,10 @@ impl<P: RoomDataProvider> TimelineInner<P> {
             .insert(receipt_type, receipt);
     }
 
+    pub(super) fn set_initial_pinned_events(&mut self, event_ids: Vec<OwnedEventId>) {
+        self.state.get_mut().pinned_event_ids = event_ids.into_iter().collect();
+    }
+
     pub(super) async fn add_initial_events(&mut self, events: Vector<SyncTimelineEvent>) {
         if events.is_empty() {
             return;
@@ -593,6 +621,52 @@ impl TimelineInner {
         Ok(())
     }
 
+    pub(super) async fn fetch_url_preview(&self, event_id: &EventId) -> Result<()> {
+        if !self.url_previews.enabled
+            || (!self.url_previews.allow_encrypted_rooms && self.room().is_encrypted().await?)
+        {
+            return Ok(());
+        }
+
+        let mut state = self.state.lock().await;
+        let (index, item) = rfind_event_by_id(&state.items, event_id)
+            .ok_or(super::Error::RemoteEventNotInTimeline)?;
+
+        if !item.url_preview().is_unavailable() {
+            return Ok(());
+        }
+
+        let Some(url) = item.content().as_message().and_then(|message| find_url(message.body()))
+        else {
+            return Ok(());
+        };
+        let url = url.to_owned();
+        let timestamp = item.timestamp();
+
+        let item = item.with_url_preview(TimelineDetails::Pending);
+        state.items.set(index, Arc::new(item.into()));
+
+        // Don't hold the state lock while the network request is made
+        drop(state);
+
+        let preview = match self.room().client.media().get_url_preview(&url, Some(timestamp)).await
+        {
+            Ok(preview) => TimelineDetails::Ready(Box::new(preview)),
+            Err(e) => TimelineDetails::Error(Arc::new(e)),
+        };
+
+        // We need to be sure to have the latest position of the event as it might have
+        // changed while waiting for the request.
+        let mut state = self.state.lock().await;
+        let (index, item) = rfind_event_by_id(&state.items, event_id)
+            .ok_or(super::Error::RemoteEventNotInTimeline)?;
+
+        let item = item.with_url_preview(preview);
+        state.items.set(index, Arc::new(item.into()));
+
+        Ok(())
+    }
+
     /// Get the latest read receipt for the given user.
     ///
     /// Useful to get the latest read receipt, whether it's private or public.
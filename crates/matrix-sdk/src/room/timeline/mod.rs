@@ -54,9 +54,9 @@ mod tests;
 mod to_device;
 mod virtual_item;
 
-pub(crate) use self::builder::TimelineBuilder;
 use self::inner::{TimelineInner, TimelineInnerState};
 pub use self::{
+    builder::TimelineBuilder,
     event_item::{
         AnyOtherFullStateEventContent, BundledReactions, EncryptedMessage, EventSendState,
         EventTimelineItem, InReplyToDetails, MemberProfileChange, MembershipChange, Message,
@@ -77,6 +77,11 @@ pub struct Timeline {
     inner: Arc<TimelineInner<room::Common>>,
     start_token: Mutex<Option<String>>,
     _end_token: Mutex<Option<String>>,
+    /// The room that back-pagination requests are made in, which is a
+    /// predecessor of the timeline's room once its start was reached and
+    /// `follow_predecessors` is set.
+    pagination_room: Mutex<room::Common>,
+    follow_predecessors: bool,
     event_handler_handles: Arc<TimelineEventHandlerHandles>,
 }
 
@@ -97,6 +102,7 @@ impl Timeline {
 
         *start_lock = None;
         *end_lock = None;
+        *self.pagination_room.lock().await = self.room().clone();
 
         self.inner.clear().await;
     }
@@ -114,12 +120,13 @@ impl Timeline {
 
         self.inner.add_loading_indicator().await;
 
+        let mut pagination_room = self.pagination_room.lock().await;
         let mut from = start_lock.clone();
         let mut outcome = PaginationOutcome::new();
+        let mut start_reached = false;
 
         while let Some(limit) = opts.next_event_limit(outcome) {
-            let messages = self
-                .room()
+            let messages = pagination_room
                 .messages(assign!(MessagesOptions::backward(), {
                     from,
                     limit: limit.into(),
@@ -151,7 +158,14 @@ impl Timeline {
             from = messages.end;
 
             if from.is_none() {
-                break;
+                // Continue in the room that was replaced by this one, if any.
+                match self.predecessor_to_paginate(&pagination_room) {
+                    Some(predecessor) => *pagination_room = predecessor,
+                    None => {
+                        start_reached = true;
+                        break;
+                    }
+                }
             }
 
             if process_events_result.is_none() {
@@ -160,12 +174,23 @@ impl Timeline {
             }
         }
 
-        self.inner.remove_loading_indicator(from.is_some()).await;
+        self.inner.remove_loading_indicator(!start_reached).await;
         *start_lock = from;
 
         Ok(())
     }
 
+    /// Get the predecessor of the given room to continue back-paginating in,
+    /// if this timeline follows predecessors and the predecessor is known.
+    fn predecessor_to_paginate(&self, room: &room::Common) -> Option<room::Common> {
+        if !self.follow_predecessors {
+            return None;
+        }
+
+        let predecessor_id = room.predecessor()?;
+        self.room().client.get_room(&predecessor_id).map(|room| (*room).clone())
+    }
+
     /// Retry decryption of previously un-decryptable events given a list of
     /// session IDs whose keys have been imported.
     ///
@@ -295,7 +320,9 @@ impl Timeline {
     ///
     /// This method tries to make all the requests it can. If an error is
     /// encountered for a given request, it is forwarded with the
-    /// [`TimelineDetails::Error`] variant.
+    /// [`TimelineDetails::Error`] variant. The details of the replied-to event
+    /// and the URL preview are fetched independently, so a failure to fetch
+    /// one doesn't prevent fetching the other.
     ///
     /// # Arguments
     ///
@@ -305,10 +332,21 @@ impl Timeline {
     ///
     /// Returns an error if the identifier doesn't match any event with a remote
     /// echo in the timeline, or if the event is removed from the timeline
-    /// before all requests are handled.
+    /// before all requests are handled. If several requests fail that way,
+    /// the first error is returned.
     #[instrument(skip(self), fields(room_id = ?self.room().room_id()))]
     pub async fn fetch_event_details(&self, event_id: &EventId) -> Result<()> {
-        self.inner.fetch_in_reply_to_details(event_id).await
+        let in_reply_to_result = self.inner.fetch_in_reply_to_details(event_id).await;
+        if let Err(e) = &in_reply_to_result {
+            warn!("Failed to fetch the details of the replied-to event: {e}");
+        }
+
+        let url_preview_result = self.inner.fetch_url_preview(event_id).await;
+        if let Err(e) = &url_preview_result {
+            warn!("Failed to fetch the URL preview: {e}");
+        }
+
+        in_reply_to_result.and(url_preview_result)
     }
 
     /// Fetch all member events for the room this timeline is displaying.
@@ -562,6 +600,15 @@ fn rfind_event_by_id<'a>(
     rfind_event_item(items, |it| it.event_id() == Some(event_id))
 }
 
+/// Find the first `http` or `https` URL in the given plain text, ignoring
+/// trailing punctuation.
+fn find_url(text: &str) -> Option<&str> {
+    text.split_whitespace()
+        .map(|word| word.trim_start_matches(['(', '<', '"', '\'']))
+        .find(|word| word.starts_with("https://") || word.starts_with("http://"))
+        .map(|url| url.trim_end_matches(['.', ',', ':', ';', '!', '?', ')', '>', '"', '\'']))
+}
+
 fn find_read_marker(items: &Vector<Arc<TimelineItem>>) -> Option<usize> {
     items.iter().rposition(|item| item.is_read_marker())
 }
@@ -24,8 +24,8 @@ use serde_json::{json, Value as JsonValue};
 
 use super::{TestTimeline, ALICE, BOB};
 use crate::room::timeline::{
-    event_item::AnyOtherFullStateEventContent, MembershipChange, TimelineItem, TimelineItemContent,
-    VirtualTimelineItem,
+    event_item::AnyOtherFullStateEventContent, find_url, MembershipChange, TimelineItem,
+    TimelineItemContent, VirtualTimelineItem,
 };
 
 fn sync_timeline_event(event: JsonValue) -> SyncTimelineEvent {
@@ -281,3 +281,17 @@ async fn dedup_initial() {
     assert_eq!(timeline_items[1].as_event().unwrap().sender(), *BOB);
     assert_eq!(timeline_items[2].as_event().unwrap().sender(), *ALICE);
 }
+
+#[test]
+fn url_in_message_body() {
+    assert_eq!(find_url("no links here"), None);
+    assert_eq!(find_url("ftp://example.org is not supported"), None);
+    assert_eq!(
+        find_url("Have a look at https://matrix.org/blog, it's great."),
+        Some("https://matrix.org/blog")
+    );
+    assert_eq!(
+        find_url("(see <http://example.org/a?b=c>) and https://matrix.org"),
+        Some("http://example.org/a?b=c")
+    );
+}
@@ -0,0 +1,167 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Observing and sending typing notifications.
+
+use futures_core::Stream;
+use futures_util::{
+    future::{select, Either},
+    pin_mut,
+};
+use matrix_sdk_common::{
+    executor::spawn,
+    instant::{Duration, Instant},
+};
+use ruma::{events::typing::SyncTypingEvent, OwnedUserId};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use super::{Joined, RoomMember};
+use crate::{utils::sleep, Result};
+
+/// How long a user is considered to be typing without receiving a new typing
+/// notification for them.
+///
+/// Homeservers should send an update before that, this only protects against
+/// missed updates, for example when the connection is lost.
+const TYPING_EXPIRY: Duration = Duration::from_secs(30);
+
+/// How long after the last keystroke [`Joined::notify_typing()`] considers
+/// that the user stopped typing.
+const TYPING_IDLE_TIMEOUT: Duration = Duration::from_secs(2);
+
+impl Joined {
+    /// Subscribe to the users typing in this room.
+    ///
+    /// The stream yields the current list of typing members every time it
+    /// changes. The own user and ignored users are never part of the list.
+    /// Works with typing notifications received by `/sync` and by the sliding
+    /// sync typing extension, but the client must be syncing for the stream
+    /// to receive updates.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use futures::{pin_mut, StreamExt};
+    /// # async {
+    /// # let room: matrix_sdk::room::Joined = todo!();
+    /// let typing = room.subscribe_to_typing_notifications();
+    /// pin_mut!(typing);
+    ///
+    /// while let Some(members) = typing.next().await {
+    ///     let names: Vec<_> = members.iter().map(|m| m.name()).collect();
+    ///     println!("Typing: {}", names.join(", "));
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub fn subscribe_to_typing_notifications(&self) -> impl Stream<Item = Vec<RoomMember>> {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+
+        let handle = self.add_event_handler(move |event: SyncTypingEvent| {
+            let sender = sender.clone();
+            async move {
+                let _ = sender.send(event.content.user_ids);
+            }
+        });
+        let guard = self.client.event_handler_drop_guard(handle);
+        let room = self.clone();
+
+        async_stream::stream! {
+            // Keep the event handler alive as long as the stream.
+            let _guard = guard;
+            let mut typing = false;
+
+            loop {
+                let user_ids = if typing {
+                    let next = receiver.recv();
+                    let expiry = sleep(TYPING_EXPIRY);
+                    pin_mut!(next, expiry);
+
+                    match select(next, expiry).await {
+                        Either::Left((user_ids, _)) => user_ids,
+                        // Nobody is typing anymore if we didn't get any update.
+                        Either::Right(_) => Some(Vec::new()),
+                    }
+                } else {
+                    receiver.recv().await
+                };
+
+                let Some(user_ids) = user_ids else { break };
+
+                let members = room.typing_members(user_ids).await;
+                typing = !members.is_empty();
+                yield members;
+            }
+        }
+    }
+
+    async fn typing_members(&self, user_ids: Vec<OwnedUserId>) -> Vec<RoomMember> {
+        let mut members = Vec::with_capacity(user_ids.len());
+
+        for user_id in user_ids {
+            if &*user_id == self.own_user_id() {
+                continue;
+            }
+
+            match self.get_member_no_sync(&user_id).await {
+                Ok(Some(member)) if !member.is_ignored() => members.push(member),
+                Ok(_) => {}
+                Err(error) => warn!(?user_id, "Failed to load typing member: {error}"),
+            }
+        }
+
+        members
+    }
+
+    /// Notify the room that the user is typing.
+    ///
+    /// This can be called on every keystroke: requests are throttled with
+    /// [`Joined::typing_notice()`], and a notification that the user stopped
+    /// typing is sent automatically if this method isn't called again for a
+    /// couple of seconds.
+    pub async fn notify_typing(&self) -> Result<()> {
+        let room_id = self.room_id().to_owned();
+        let previous =
+            self.client.inner.typing_keystroke_times.insert(room_id.clone(), Instant::now());
+
+        // Only spawn a task waiting for the user to stop typing if there isn't
+        // one already.
+        if previous.is_none() {
+            let room = self.clone();
+            spawn(async move {
+                loop {
+                    let Some(last_keystroke) =
+                        room.client.inner.typing_keystroke_times.get(&room_id).map(|t| *t)
+                    else {
+                        break;
+                    };
+
+                    let elapsed = last_keystroke.elapsed();
+                    if elapsed < TYPING_IDLE_TIMEOUT {
+                        sleep(TYPING_IDLE_TIMEOUT - elapsed).await;
+                        continue;
+                    }
+
+                    room.client.inner.typing_keystroke_times.remove(&room_id);
+                    if let Err(error) = room.typing_notice(false).await {
+                        warn!(?room_id, "Failed to send the end of the typing notice: {error}");
+                    }
+                    break;
+                }
+            });
+        }
+
+        self.typing_notice(true).await
+    }
+}
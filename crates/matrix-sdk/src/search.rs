@@ -0,0 +1,473 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Search of room events.
+//!
+//! [`Client::search()`] asks the homeserver to search for events, so it can't
+//! find messages in encrypted rooms. [`Client::search_local()`] searches in a
+//! local index of decrypted messages instead, which needs to be configured
+//! with [`ClientBuilder::search_index()`].
+//!
+//! [`ClientBuilder::search_index()`]: crate::ClientBuilder::search_index
+
+use std::collections::BTreeMap;
+
+pub use matrix_sdk_base::search_index::{
+    LocalSearchFilter, LocalSearchResult, MemorySearchIndex, SearchIndex,
+};
+use matrix_sdk_base::{
+    deserialized_responses::TimelineEvent,
+    search_index::{IndexedEvent, UndecryptedEvent},
+};
+pub use ruma::api::client::search::search_events::v3::{OrderBy, SearchKeys, UserProfile};
+use ruma::{
+    api::client::{
+        filter::RoomEventFilter,
+        search::search_events::{
+            self,
+            v3::{Categories, Criteria, EventContext},
+        },
+    },
+    assign,
+    events::{
+        room::{message::Relation, redaction::SyncRoomRedactionEvent},
+        AnySyncMessageLikeEvent, AnySyncTimelineEvent, AnyTimelineEvent, SyncMessageLikeEvent,
+    },
+    serde::Raw,
+    uint, OwnedRoomId, OwnedUserId, RoomId, UInt,
+};
+use tracing::warn;
+
+use crate::{Client, Error, Result};
+
+/// Options for [`Client::search()`].
+///
+/// See that method and
+/// <https://spec.matrix.org/v1.6/client-server-api/#post_matrixclientv3search>
+/// for details.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct SearchOptions {
+    /// The string to search for.
+    pub search_term: String,
+
+    /// The keys of the events to search in.
+    ///
+    /// If this is `None`, the homeserver searches in all the keys.
+    pub keys: Option<Vec<SearchKeys>>,
+
+    /// A [`RoomEventFilter`] to filter the searched events with, for example
+    /// to only search in some rooms.
+    pub filter: RoomEventFilter,
+
+    /// The order of the results.
+    ///
+    /// If this is `None`, the results are ordered by rank.
+    pub order_by: Option<OrderBy>,
+
+    /// The number of events to return before each result.
+    ///
+    /// Default: 0.
+    pub before_limit: UInt,
+
+    /// The number of events to return after each result.
+    ///
+    /// Default: 0.
+    pub after_limit: UInt,
+
+    /// Whether to return the profiles of the senders of the events of each
+    /// result.
+    ///
+    /// Default: `false`.
+    pub include_profile: bool,
+
+    /// The token to continue a previous search from.
+    ///
+    /// This is the [`SearchResults::next_batch`] of the previous call with the
+    /// same options.
+    pub next_batch: Option<String>,
+}
+
+impl SearchOptions {
+    /// Creates `SearchOptions` with the given search term.
+    ///
+    /// All other parameters will be defaulted.
+    pub fn new(search_term: impl Into<String>) -> Self {
+        Self {
+            search_term: search_term.into(),
+            keys: None,
+            filter: RoomEventFilter::default(),
+            order_by: None,
+            before_limit: uint!(0),
+            after_limit: uint!(0),
+            include_profile: false,
+            next_batch: None,
+        }
+    }
+
+    /// Creates a new `SearchOptions` from `self` that only searches in the
+    /// given rooms.
+    pub fn rooms(mut self, rooms: Vec<OwnedRoomId>) -> Self {
+        self.filter.rooms = Some(rooms);
+        self
+    }
+
+    /// Creates a new `SearchOptions` from `self` with the `order_by` field set
+    /// to the given value.
+    pub fn order_by(self, order_by: OrderBy) -> Self {
+        Self { order_by: Some(order_by), ..self }
+    }
+
+    /// Creates a new `SearchOptions` from `self` with the given number of
+    /// events returned before and after each result.
+    pub fn context(self, before_limit: UInt, after_limit: UInt) -> Self {
+        Self { before_limit, after_limit, ..self }
+    }
+
+    /// Creates a new `SearchOptions` from `self` with the `next_batch` field
+    /// set to the given value.
+    pub fn next_batch<'a>(self, next_batch: impl Into<Option<&'a str>>) -> Self {
+        Self { next_batch: next_batch.into().map(ToOwned::to_owned), ..self }
+    }
+
+    fn into_request(self) -> search_events::v3::Request {
+        let event_context = assign!(EventContext::new(), {
+            before_limit: self.before_limit,
+            after_limit: self.after_limit,
+            include_profile: self.include_profile,
+        });
+        let criteria = assign!(Criteria::new(self.search_term), {
+            keys: self.keys,
+            filter: self.filter,
+            order_by: self.order_by,
+            event_context,
+        });
+        let categories = assign!(Categories::new(), { room_events: Some(criteria) });
+
+        assign!(search_events::v3::Request::new(categories), { next_batch: self.next_batch })
+    }
+}
+
+/// The results of [`Client::search()`].
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct SearchResults {
+    /// An approximate count of the total number of results.
+    pub count: Option<UInt>,
+
+    /// The words that should be highlighted in the results, as interpreted by
+    /// the homeserver.
+    pub highlights: Vec<String>,
+
+    /// The results in this batch.
+    pub results: Vec<SearchResult>,
+
+    /// The token to get the next batch of results, if there are more.
+    ///
+    /// Pass it to [`SearchOptions::next_batch()`] to continue the search.
+    pub next_batch: Option<String>,
+}
+
+impl SearchResults {
+    /// The results of this batch, grouped by room.
+    ///
+    /// The results of each room keep the order of [`SearchResults::results`].
+    pub fn by_room(&self) -> BTreeMap<&RoomId, Vec<&SearchResult>> {
+        let mut groups: BTreeMap<_, Vec<_>> = BTreeMap::new();
+
+        for result in &self.results {
+            groups.entry(&*result.room_id).or_default().push(result);
+        }
+
+        groups
+    }
+}
+
+/// A single result of [`Client::search()`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct SearchResult {
+    /// The room of the event.
+    pub room_id: OwnedRoomId,
+
+    /// The event that matches the search.
+    pub event: TimelineEvent,
+
+    /// A number describing how well the event matches the search, higher is
+    /// closer.
+    pub rank: Option<f64>,
+
+    /// Events that happened just before the matching event, in chronological
+    /// order.
+    pub events_before: Vec<TimelineEvent>,
+
+    /// Events that happened just after the matching event, in chronological
+    /// order.
+    pub events_after: Vec<TimelineEvent>,
+
+    /// The profiles of the senders of the events, as they were at the time of
+    /// the matching event.
+    pub profile_info: BTreeMap<OwnedUserId, UserProfile>,
+}
+
+impl Client {
+    /// Search for events in the rooms of the user.
+    ///
+    /// The search is performed by the homeserver, so it only finds events in
+    /// unencrypted rooms. Encrypted events in the results or their context are
+    /// decrypted if the keys are available.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::{
+    /// #     ruma::room_id,
+    /// #     search::{OrderBy, SearchOptions},
+    /// #     Client,
+    /// # };
+    /// # async {
+    /// # let client: Client = todo!();
+    /// let options = SearchOptions::new("cupcakes")
+    ///     .rooms(vec![room_id!("!bakery:localhost").to_owned()])
+    ///     .order_by(OrderBy::Recent);
+    /// let results = client.search(options).await?;
+    ///
+    /// for result in results.results {
+    ///     println!("Found {:?} in {}", result.event.event, result.room_id);
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn search(&self, options: SearchOptions) -> Result<SearchResults> {
+        let response = self.send(options.into_request(), None).await?;
+        let room_events = response.search_categories.room_events;
+
+        let mut results = Vec::with_capacity(room_events.results.len());
+
+        for result in room_events.results {
+            let Some(event) = result.result else { continue };
+            let room_id = match event.get_field::<OwnedRoomId>("room_id") {
+                Ok(Some(room_id)) => room_id,
+                Ok(None) | Err(_) => {
+                    warn!("Search result without a valid room ID");
+                    continue;
+                }
+            };
+
+            let context = result.context;
+            let event = self.search_result_event(event, &room_id).await;
+            let mut events_before = Vec::with_capacity(context.events_before.len());
+            // The context before the event is in reverse chronological order.
+            for event in context.events_before.into_iter().rev() {
+                events_before.push(self.search_result_event(event, &room_id).await);
+            }
+            let mut events_after = Vec::with_capacity(context.events_after.len());
+            for event in context.events_after {
+                events_after.push(self.search_result_event(event, &room_id).await);
+            }
+
+            results.push(SearchResult {
+                room_id,
+                event,
+                rank: result.rank,
+                events_before,
+                events_after,
+                profile_info: context.profile_info,
+            });
+        }
+
+        Ok(SearchResults {
+            count: room_events.count,
+            highlights: room_events.highlights,
+            results,
+            next_batch: room_events.next_batch,
+        })
+    }
+
+    /// Try to decrypt an event returned by the search endpoint.
+    #[allow(unused_variables)]
+    async fn search_result_event(
+        &self,
+        event: Raw<AnyTimelineEvent>,
+        room_id: &RoomId,
+    ) -> TimelineEvent {
+        #[cfg(feature = "e2e-encryption")]
+        if let Some(machine) = self.olm_machine() {
+            if let Ok(AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomEncrypted(
+                SyncMessageLikeEvent::Original(_),
+            ))) = event.deserialize_as::<AnySyncTimelineEvent>()
+            {
+                if let Ok(event) = machine.decrypt_room_event(event.cast_ref(), room_id).await {
+                    return event;
+                }
+            }
+        }
+
+        TimelineEvent::new(event)
+    }
+}
+
+impl Client {
+    /// Search for messages in the local search index.
+    ///
+    /// Unlike [`Client::search()`], this finds messages in encrypted rooms, as
+    /// long as they were received and decrypted by this client. Only whole
+    /// words are matched, and the results contain all the words of the query.
+    ///
+    /// Returns [`Error::NoSearchIndex`] if no index was configured with
+    /// [`ClientBuilder::search_index()`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::{ruma::room_id, search::LocalSearchFilter, Client};
+    /// # async {
+    /// # let client: Client = todo!();
+    /// let filter = LocalSearchFilter::new()
+    ///     .rooms(vec![room_id!("!bakery:localhost").to_owned()])
+    ///     .limit(10);
+    ///
+    /// for result in client.search_local("cupcakes", filter).await? {
+    ///     println!("{}: {}", result.event_id, result.snippet);
+    /// }
+    /// # anyhow::Ok(()) };
+    /// ```
+    ///
+    /// [`ClientBuilder::search_index()`]: crate::ClientBuilder::search_index
+    pub async fn search_local(
+        &self,
+        query: &str,
+        filter: LocalSearchFilter,
+    ) -> Result<Vec<LocalSearchResult>> {
+        let search_index = self.inner.search_index.as_ref().ok_or(Error::NoSearchIndex)?;
+        Ok(search_index.search(query, &filter).await?)
+    }
+
+    /// Try to decrypt the events of the local search index that couldn't be
+    /// decrypted when they were received, and index them.
+    ///
+    /// This is called automatically after importing room keys with
+    /// [`Encryption::import_room_keys()`], but should be called when keys are
+    /// received in other ways, like from a key backup.
+    ///
+    /// [`Encryption::import_room_keys()`]: crate::encryption::Encryption::import_room_keys
+    #[cfg(feature = "e2e-encryption")]
+    pub async fn reindex_undecrypted_events(&self) -> Result<()> {
+        let search_index = self.inner.search_index.as_ref().ok_or(Error::NoSearchIndex)?;
+        let machine = self.olm_machine().ok_or(Error::NoOlmMachine)?;
+
+        for UndecryptedEvent { room_id, event, .. } in search_index.undecrypted_events().await? {
+            if let Ok(decrypted) = machine.decrypt_room_event(event.cast_ref(), &room_id).await {
+                self.update_search_index(&room_id, [decrypted.event.cast_ref()]).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Update the local search index with the given timeline events, if there
+    /// is one.
+    ///
+    /// Messages are indexed, edits replace the body of the edited message if
+    /// they were sent by the same user, redactions remove the redacted events
+    /// from the index and events that are still encrypted are kept to be
+    /// indexed later.
+    pub(crate) async fn update_search_index<'a>(
+        &self,
+        room_id: &RoomId,
+        events: impl IntoIterator<Item = &'a Raw<AnySyncTimelineEvent>>,
+    ) {
+        let Some(search_index) = &self.inner.search_index else { return };
+
+        let mut indexed: Vec<IndexedEvent> = Vec::new();
+        let mut removed = Vec::new();
+        let mut undecrypted = Vec::new();
+
+        for raw in events {
+            let Ok(AnySyncTimelineEvent::MessageLike(event)) = raw.deserialize() else {
+                continue;
+            };
+
+            match event {
+                AnySyncMessageLikeEvent::RoomMessage(SyncMessageLikeEvent::Original(event)) => {
+                    // An edit replaces the body of the original event in the
+                    // index if it was sent by the same user, other edits are
+                    // indexed as their own event.
+                    let edited = match &event.content.relates_to {
+                        Some(Relation::Replacement(replacement)) => {
+                            let original = match indexed
+                                .iter()
+                                .rfind(|pending| pending.event_id == replacement.event_id)
+                            {
+                                Some(original) => Some(original.clone()),
+                                None => search_index
+                                    .get_event(&replacement.event_id)
+                                    .await
+                                    .map_err(|error| {
+                                        warn!(?room_id, "Failed to get the edited event: {error}")
+                                    })
+                                    .ok()
+                                    .flatten(),
+                            };
+
+                            original
+                                .filter(|original| {
+                                    original.room_id == room_id && original.sender == event.sender
+                                })
+                                .map(|original| IndexedEvent {
+                                    body: replacement.new_content.body().to_owned(),
+                                    ..original
+                                })
+                        }
+                        _ => None,
+                    };
+
+                    indexed.push(edited.unwrap_or_else(|| IndexedEvent {
+                        room_id: room_id.to_owned(),
+                        event_id: event.event_id,
+                        sender: event.sender,
+                        origin_server_ts: event.origin_server_ts,
+                        body: event.content.body().to_owned(),
+                    }));
+                }
+                AnySyncMessageLikeEvent::RoomRedaction(SyncRoomRedactionEvent::Original(event)) => {
+                    removed.push(event.redacts);
+                }
+                AnySyncMessageLikeEvent::RoomEncrypted(SyncMessageLikeEvent::Original(event)) => {
+                    undecrypted.push(UndecryptedEvent {
+                        room_id: room_id.to_owned(),
+                        event_id: event.event_id,
+                        event: raw.clone(),
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        if !indexed.is_empty() {
+            if let Err(error) = search_index.index_events(indexed).await {
+                warn!(?room_id, "Failed to index events: {error}");
+            }
+        }
+        if !removed.is_empty() {
+            if let Err(error) = search_index.remove_events(removed).await {
+                warn!(?room_id, "Failed to remove redacted events from the index: {error}");
+            }
+        }
+        if !undecrypted.is_empty() {
+            if let Err(error) = search_index.save_undecrypted_events(undecrypted).await {
+                warn!(?room_id, "Failed to save undecrypted events for the index: {error}");
+            }
+        }
+    }
+}
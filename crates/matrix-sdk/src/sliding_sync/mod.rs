@@ -529,6 +529,9 @@ impl SlidingSync {
 
         async_stream::stream! {
             loop {
+                // Don't sync while the session is soft logged out.
+                self.inner.client.wait_for_soft_logout_recovery().await;
+
                 let sync_span = info_span!(parent: &instrument_span, "sync_once");
 
                 sync_span.in_scope(|| {
@@ -0,0 +1,142 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recovery from a [soft logout].
+//!
+//! When the homeserver answers a request with an `M_UNKNOWN_TOKEN` error with
+//! `soft_logout` set to `true`, the access token of the session is invalid but
+//! the device still exists. Unless the token can be [refreshed], the `Client`
+//! enters the soft logout state:
+//!
+//! * The sync loops stop sending requests until the session is recovered,
+//!   without returning an error.
+//! * The outgoing E2EE requests are kept for later.
+//! * The stores and the session metadata are left untouched.
+//!
+//! The session is recovered by logging in again with any of the `login_*`
+//! methods or with OpenID Connect, which reuse the device ID of the session,
+//! or by refreshing the access token. The sync loops then resume where they
+//! stopped, with the same device and encryption keys.
+//!
+//! [soft logout]: https://spec.matrix.org/v1.6/client-server-api/#soft-logout
+//! [refreshed]: crate::Client::refresh_access_token
+
+use eyeball::{unique::Observable, Subscriber};
+use futures_util::StreamExt;
+use matrix_sdk_base::Session;
+use tracing::{info, warn};
+
+use crate::{Client, Error, Result};
+
+impl Client {
+    /// Whether the session of this client was soft logged out.
+    ///
+    /// When this is `true`, the user needs to log in again to recover the
+    /// session. See [`Client::subscribe_to_soft_logout()`] for more details.
+    pub fn is_soft_logged_out(&self) -> bool {
+        *Observable::get(&self.inner.soft_logged_out.read().unwrap())
+    }
+
+    /// Subscribe to changes of the soft logout state of the session.
+    ///
+    /// The subscriber yields `true` when the homeserver soft logs out the
+    /// session, and `false` once it was recovered.
+    ///
+    /// While the session is soft logged out, the sync loops are paused and
+    /// the outgoing E2EE requests are kept for later. To recover the session,
+    /// the user needs to log in again with any of the `login_*` methods or
+    /// with OpenID Connect, which reuse the device ID of the session, so the
+    /// stores and the encryption keys stay valid.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use futures::StreamExt;
+    /// use matrix_sdk::Client;
+    /// # use futures::executor::block_on;
+    /// # block_on(async {
+    /// # let client = Client::new("http://example.com".parse()?).await?;
+    /// # fn get_password() -> &'static str { "" }
+    ///
+    /// let mut soft_logout = client.subscribe_to_soft_logout();
+    ///
+    /// while let Some(soft_logged_out) = soft_logout.next().await {
+    ///     if soft_logged_out {
+    ///         let user_id = client.user_id().unwrap();
+    ///         client.login_username(user_id, get_password()).send().await?;
+    ///     }
+    /// }
+    /// # anyhow::Ok(()) });
+    /// ```
+    pub fn subscribe_to_soft_logout(&self) -> Subscriber<bool> {
+        Observable::subscribe(&self.inner.soft_logged_out.read().unwrap())
+    }
+
+    /// Update the soft logout state of the session.
+    pub(crate) fn set_soft_logged_out(&self, soft_logged_out: bool) {
+        let mut lock = self.inner.soft_logged_out.write().unwrap();
+
+        if *Observable::get(&lock) != soft_logged_out {
+            if soft_logged_out {
+                warn!("The session was soft logged out, waiting for the user to log in again");
+            } else {
+                info!("The session was recovered");
+            }
+
+            Observable::set(&mut lock, soft_logged_out);
+        }
+    }
+
+    /// Wait until the session is not soft logged out anymore.
+    ///
+    /// Returns immediately if the session is not soft logged out.
+    pub(crate) async fn wait_for_soft_logout_recovery(&self) {
+        let mut subscriber = {
+            let lock = self.inner.soft_logged_out.read().unwrap();
+
+            if !*Observable::get(&lock) {
+                return;
+            }
+
+            Observable::subscribe(&lock)
+        };
+
+        while let Some(soft_logged_out) = subscriber.next().await {
+            if !soft_logged_out {
+                break;
+            }
+        }
+    }
+
+    /// Replace the tokens of the session with the ones of the given session,
+    /// which must be for the same user and device.
+    ///
+    /// This is used when a client that is already logged in gets a new
+    /// session, to recover from a soft logout.
+    pub(crate) fn resume_session(&self, session: Session) -> Result<()> {
+        let (meta, tokens) = session.into_parts();
+        let Some(current_meta) = self.base_client().session_meta() else {
+            return Err(Error::AuthenticationRequired);
+        };
+
+        if meta.user_id != current_meta.user_id || meta.device_id != current_meta.device_id {
+            return Err(Error::SessionMismatch);
+        }
+
+        self.base_client().set_session_tokens(tokens);
+        self.set_soft_logged_out(false);
+
+        Ok(())
+    }
+}
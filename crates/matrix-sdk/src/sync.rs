@@ -19,7 +19,7 @@ use ruma::{
 };
 use tracing::{debug, error, warn};
 
-use crate::{event_handler::HandlerKind, Client, Result};
+use crate::{event_handler::HandlerKind, utils::sleep, Client, Result};
 
 /// The processed response of a `/sync` request.
 #[derive(Clone, Debug, Default)]
@@ -102,6 +102,7 @@ impl Client {
 
         let now = Instant::now();
         self.handle_sync_events(HandlerKind::GlobalAccountData, &None, account_data).await?;
+        self.handle_presence_events(&presence.events);
         self.handle_sync_events(HandlerKind::Presence, &None, &presence.events).await?;
         self.handle_sync_events(HandlerKind::ToDevice, &None, to_device_events).await?;
 
@@ -122,6 +123,7 @@ impl Client {
             self.handle_sync_events(HandlerKind::RoomAccountData, &room, account_data).await?;
             self.handle_sync_state_events(&room, &state.events).await?;
             self.handle_sync_timeline_events(&room, &timeline.events).await?;
+            self.update_search_index(room_id, timeline.events.iter().map(|e| &e.event)).await;
             // Handle ephemeral events after timeline, read receipts in here
             // could refer to timeline events from the same response.
             self.handle_sync_events(HandlerKind::EphemeralRoomData, &room, &ephemeral.events)
@@ -145,6 +147,7 @@ impl Client {
                 .await?;
             self.handle_sync_state_events(&room, &state.events).await?;
             self.handle_sync_timeline_events(&room, &timeline.events).await?;
+            self.update_search_index(room_id, timeline.events.iter().map(|e| &e.event)).await;
         }
 
         for (room_id, room_info) in &rooms.invite {
@@ -163,6 +166,21 @@ impl Client {
             .await?;
         }
 
+        for (room_id, room_info) in &rooms.knock {
+            let room = self.get_room(room_id);
+            if room.is_none() {
+                error!(?room_id, "Can't call event handler, room not found");
+                continue;
+            }
+
+            self.handle_sync_events(
+                HandlerKind::StrippedState,
+                &room,
+                &room_info.knock_state.events,
+            )
+            .await?;
+        }
+
         debug!("Ran event handlers in {:?}", now.elapsed());
 
         let now = Instant::now();
@@ -193,28 +211,27 @@ impl Client {
         Ok(())
     }
 
-    async fn sleep() {
-        #[cfg(target_arch = "wasm32")]
-        gloo_timers::future::TimeoutFuture::new(1_000).await;
-
-        #[cfg(not(target_arch = "wasm32"))]
-        tokio::time::sleep(Duration::from_secs(1)).await;
-    }
-
     pub(crate) async fn sync_loop_helper(
         &self,
         sync_settings: &mut crate::config::SyncSettings,
     ) -> Result<SyncResponse> {
-        let response = self.sync_once(sync_settings.clone()).await;
-
-        match response {
-            Ok(r) => {
-                sync_settings.token = Some(r.next_batch.clone());
-                Ok(r)
-            }
-            Err(e) => {
-                error!("Received an invalid response: {e}");
-                Err(e)
+        loop {
+            // Don't sync while the session is soft logged out, and sync again
+            // once it is recovered.
+            self.wait_for_soft_logout_recovery().await;
+
+            let response = self.sync_once(sync_settings.clone()).await;
+
+            match response {
+                Ok(r) => {
+                    sync_settings.token = Some(r.next_batch.clone());
+                    return Ok(r);
+                }
+                Err(_) if self.is_soft_logged_out() => {}
+                Err(e) => {
+                    error!("Received an invalid response: {e}");
+                    return Err(e);
+                }
             }
         }
     }
@@ -227,7 +244,7 @@ impl Client {
         // the sync timeout.
         if let Some(t) = last_sync_time {
             if now - *t <= Duration::from_secs(1) {
-                Self::sleep().await;
+                sleep(Duration::from_secs(1)).await;
             }
         }
 
@@ -0,0 +1,479 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A sync loop that manages itself.
+//!
+//! [`Client::sync()`] and its variants loop until an error occurs, and leave it
+//! to the application to detect when the device is offline, to wait before
+//! trying again and to restart the loop. A [`SyncService`] runs the sync loop
+//! in the background and takes care of that:
+//!
+//! * Network errors are retried with an exponential backoff with jitter, in the
+//!   [`Offline`] state.
+//! * The application can give hints about the reachability of the network with
+//!   [`SyncService::set_network_reachable()`], to stop trying while the network
+//!   is unreachable and to retry immediately when it comes back.
+//! * The sync loop can be paused when the application goes to the background,
+//!   and resumed when it comes back to the foreground.
+//! * Stopping the sync loop waits for the request that is in flight, so the
+//!   response is processed and the outgoing requests are sent.
+//!
+//! The current [`SyncServiceState`] can be observed with
+//! [`SyncService::subscribe_to_state()`].
+//!
+//! [`Offline`]: SyncServiceState::Offline
+
+use std::{
+    fmt,
+    sync::{Arc, RwLock as StdRwLock},
+    time::Duration,
+};
+
+use eyeball::{unique::Observable, Subscriber};
+use futures_util::{
+    future::{self, Either},
+    StreamExt,
+};
+#[cfg(target_arch = "wasm32")]
+use matrix_sdk_common::executor::JoinHandle;
+use matrix_sdk_common::{executor::spawn, instant::Instant};
+use rand::{thread_rng, Rng};
+use tokio::sync::{watch, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::task::JoinHandle;
+use tracing::{debug, error, warn};
+
+use crate::{config::SyncSettings, utils::sleep, Client, Error, HttpError};
+
+/// The state of a [`SyncService`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncServiceState {
+    /// The sync loop is not running, because it was not started yet or because
+    /// it is [paused](SyncService::pause).
+    Idle,
+    /// The sync loop is running.
+    Running,
+    /// The homeserver can't be reached, the sync loop is waiting before trying
+    /// again.
+    Offline,
+    /// The sync loop stopped because of an error that is not a network error.
+    ///
+    /// It can be [started](SyncService::start) again.
+    Error,
+    /// The sync loop was [stopped](SyncService::stop) for good.
+    Terminated,
+}
+
+/// Builder for a [`SyncService`].
+///
+/// Created with [`SyncService::builder()`].
+#[derive(Debug)]
+pub struct SyncServiceBuilder {
+    client: Client,
+    sync_settings: SyncSettings,
+    initial_delay: Duration,
+    max_delay: Duration,
+}
+
+impl SyncServiceBuilder {
+    fn new(client: Client) -> Self {
+        Self {
+            client,
+            sync_settings: SyncSettings::new().timeout(Duration::from_secs(30)),
+            initial_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(5 * 60),
+        }
+    }
+
+    /// Set the settings for the sync requests.
+    ///
+    /// Like with [`Client::sync()`], the token of the settings is only used for
+    /// the first request. If it is not set, the last sync token of the client
+    /// is used.
+    ///
+    /// Defaults to settings with a timeout of 30 seconds.
+    pub fn sync_settings(mut self, sync_settings: SyncSettings) -> Self {
+        self.sync_settings = sync_settings;
+        self
+    }
+
+    /// Set the delays to wait before trying again after a network error.
+    ///
+    /// The delay starts at `initial` and doubles after every consecutive
+    /// network error, up to `max`. A random jitter of up to half the delay is
+    /// removed from it, so many clients don't retry all at once.
+    ///
+    /// Defaults to 1 second and 5 minutes.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_delay = initial;
+        self.max_delay = max.max(initial);
+        self
+    }
+
+    /// Build the [`SyncService`].
+    ///
+    /// The sync loop is not started yet, see [`SyncService::start()`].
+    pub fn build(self) -> SyncService {
+        let (stop_sender, _) = watch::channel(false);
+
+        SyncService {
+            inner: Arc::new(SyncServiceInner {
+                client: self.client,
+                sync_settings: self.sync_settings,
+                backoff: Backoff { initial: self.initial_delay, max: self.max_delay },
+                state: Arc::new(StdRwLock::new(Observable::new(SyncServiceState::Idle))),
+                reachable: Arc::new(StdRwLock::new(Observable::new(true))),
+                stop_sender,
+                task: Default::default(),
+            }),
+        }
+    }
+}
+
+/// A sync loop running in the background, that handles network errors and
+/// can be paused and resumed.
+///
+/// See the [module documentation](self) for more details.
+///
+/// The `SyncService` can be cloned freely, all the clones control the same
+/// sync loop. The sync loop keeps running until it is paused or stopped, even
+/// if all the clones are dropped.
+///
+/// # Examples
+///
+/// ```no_run
+/// use futures::StreamExt;
+/// use matrix_sdk::{
+///     sync_service::{SyncService, SyncServiceState},
+///     Client,
+/// };
+/// # use futures::executor::block_on;
+/// # block_on(async {
+/// # let client = Client::new("http://example.com".parse()?).await?;
+///
+/// let sync_service = SyncService::builder(client).build();
+/// let mut state = sync_service.subscribe_to_state();
+/// sync_service.start().await;
+///
+/// while let Some(state) = state.next().await {
+///     match state {
+///         SyncServiceState::Offline => println!("Waiting for the network…"),
+///         SyncServiceState::Error => sync_service.start().await,
+///         _ => {}
+///     }
+/// }
+/// # anyhow::Ok(()) });
+/// ```
+#[derive(Clone)]
+pub struct SyncService {
+    inner: Arc<SyncServiceInner>,
+}
+
+struct SyncServiceInner {
+    client: Client,
+    sync_settings: SyncSettings,
+    backoff: Backoff,
+    state: Arc<StdRwLock<Observable<SyncServiceState>>>,
+    /// The last reachability hint of the application.
+    reachable: Arc<StdRwLock<Observable<bool>>>,
+    /// Sender to ask the running sync loop to stop.
+    stop_sender: watch::Sender<bool>,
+    /// The task running the sync loop, if any.
+    task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl SyncService {
+    /// Create a builder for a `SyncService` using the given client.
+    ///
+    /// The client should be logged in before the sync loop is started.
+    pub fn builder(client: Client) -> SyncServiceBuilder {
+        SyncServiceBuilder::new(client)
+    }
+
+    /// Get the current state of the sync loop.
+    pub fn state(&self) -> SyncServiceState {
+        *Observable::get(&self.inner.state.read().unwrap())
+    }
+
+    /// Subscribe to the changes of the state of the sync loop.
+    pub fn subscribe_to_state(&self) -> Subscriber<SyncServiceState> {
+        Observable::subscribe(&self.inner.state.read().unwrap())
+    }
+
+    /// Start the sync loop, or resume it after it was paused or stopped by an
+    /// error.
+    ///
+    /// Does nothing if the sync loop is already running, or if it was
+    /// [stopped](Self::stop) for good.
+    pub async fn start(&self) {
+        let mut task = self.inner.task.lock().await;
+
+        match self.state() {
+            SyncServiceState::Terminated => {
+                warn!("The sync service was terminated, it can't be started again");
+                return;
+            }
+            SyncServiceState::Running | SyncServiceState::Offline if task.is_some() => return,
+            _ => {}
+        }
+
+        debug!("Starting the sync loop");
+        self.inner.stop_sender.send_replace(false);
+        set_state(&self.inner.state, SyncServiceState::Running);
+
+        let sync_loop = SyncLoop {
+            client: self.inner.client.clone(),
+            sync_settings: self.inner.sync_settings.clone(),
+            backoff: self.inner.backoff,
+            state: self.inner.state.clone(),
+            reachable: self.inner.reachable.clone(),
+            stop_receiver: self.inner.stop_sender.subscribe(),
+        };
+        *task = Some(spawn(sync_loop.run()));
+    }
+
+    /// Pause the sync loop, for example when the application goes to the
+    /// background.
+    ///
+    /// This waits for the request that is in flight, if any, to be done, and
+    /// leaves the sync loop in the [`Idle`](SyncServiceState::Idle) state. It
+    /// can be resumed with [`SyncService::start()`].
+    pub async fn pause(&self) {
+        self.stop_task(SyncServiceState::Idle).await;
+    }
+
+    /// Stop the sync loop for good.
+    ///
+    /// This waits for the request that is in flight, if any, to be done, and
+    /// leaves the sync loop in the [`Terminated`](SyncServiceState::Terminated)
+    /// state.
+    pub async fn stop(&self) {
+        self.stop_task(SyncServiceState::Terminated).await;
+    }
+
+    async fn stop_task(&self, new_state: SyncServiceState) {
+        let mut task = self.inner.task.lock().await;
+
+        if self.state() == SyncServiceState::Terminated {
+            return;
+        }
+
+        if let Some(handle) = task.take() {
+            debug!("Stopping the sync loop");
+            self.inner.stop_sender.send_replace(true);
+
+            if handle.await.is_err() {
+                error!("The sync loop panicked");
+            }
+        }
+
+        set_state(&self.inner.state, new_state);
+    }
+
+    /// Give a hint about the reachability of the network.
+    ///
+    /// When the network is unreachable, the sync loop goes to the
+    /// [`Offline`](SyncServiceState::Offline) state and stops sending requests
+    /// until it is reachable again, or until the maximum backoff delay
+    /// elapsed. When the network becomes reachable, the sync loop tries
+    /// again immediately instead of waiting for the backoff delay.
+    ///
+    /// The network is considered reachable by default.
+    pub fn set_network_reachable(&self, reachable: bool) {
+        let mut lock = self.inner.reachable.write().unwrap();
+
+        if *Observable::get(&lock) != reachable {
+            debug!(reachable, "Network reachability changed");
+            Observable::set(&mut lock, reachable);
+        }
+
+        drop(lock);
+
+        if !reachable && self.state() == SyncServiceState::Running {
+            set_state(&self.inner.state, SyncServiceState::Offline);
+        }
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for SyncService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SyncService").field("state", &self.state()).finish_non_exhaustive()
+    }
+}
+
+/// The delays to wait before trying again after network errors.
+#[derive(Clone, Copy, Debug)]
+struct Backoff {
+    initial: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    /// The delay to wait after the given number of consecutive network errors.
+    fn delay(&self, errors: u32) -> Duration {
+        let delay = self.initial.saturating_mul(2u32.saturating_pow(errors.saturating_sub(1)));
+        let delay = delay.min(self.max);
+
+        delay.mul_f64(thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+/// The state of the task running the sync loop.
+struct SyncLoop {
+    client: Client,
+    sync_settings: SyncSettings,
+    backoff: Backoff,
+    state: Arc<StdRwLock<Observable<SyncServiceState>>>,
+    reachable: Arc<StdRwLock<Observable<bool>>>,
+    stop_receiver: watch::Receiver<bool>,
+}
+
+impl SyncLoop {
+    async fn run(mut self) {
+        let mut last_sync_time: Option<Instant> = None;
+        let mut network_errors = 0;
+
+        if self.sync_settings.token.is_none() {
+            self.sync_settings.token = self.client.sync_token().await;
+        }
+
+        while !self.stop_requested() {
+            if !self.is_reachable() {
+                set_state(&self.state, SyncServiceState::Offline);
+
+                // Probe the network from time to time, in case the hint is
+                // wrong.
+                if !self.wait(self.backoff.max, true).await {
+                    break;
+                }
+
+                continue;
+            }
+
+            // The helper doesn't return while the session is soft logged out,
+            // so stop waiting for it if a stop is requested in the meantime.
+            // No request is in flight then.
+            let sync = Box::pin(self.client.sync_loop_helper(&mut self.sync_settings));
+            let stopped_while_soft_logged_out = Box::pin({
+                let client = self.client.clone();
+                let stop_requested = wait_for_stop(self.stop_receiver.clone());
+                async move {
+                    stop_requested.await;
+
+                    let mut soft_logout = client.subscribe_to_soft_logout();
+                    while !client.is_soft_logged_out() {
+                        if soft_logout.next().await.is_none() {
+                            future::pending::<()>().await;
+                        }
+                    }
+                }
+            });
+
+            let result = match future::select(sync, stopped_while_soft_logged_out).await {
+                Either::Left((result, _)) => result,
+                Either::Right(_) => {
+                    debug!("Stopped the sync loop while the session is soft logged out");
+                    break;
+                }
+            };
+
+            match result {
+                Ok(_) => {
+                    network_errors = 0;
+                    set_state(&self.state, SyncServiceState::Running);
+
+                    // If the last sync happened less than a second ago, wait
+                    // for a while to not hammer the homeserver if it doesn't
+                    // respect the sync timeout.
+                    let too_soon = last_sync_time
+                        .map_or(false, |time| time.elapsed() < Duration::from_secs(1));
+                    last_sync_time = Some(Instant::now());
+
+                    if too_soon && !self.wait(Duration::from_secs(1), false).await {
+                        break;
+                    }
+                }
+                Err(Error::Http(HttpError::Reqwest(error))) => {
+                    network_errors += 1;
+                    let delay = self.backoff.delay(network_errors);
+                    warn!(?delay, "Network error while syncing, trying again later: {error}");
+
+                    set_state(&self.state, SyncServiceState::Offline);
+
+                    if !self.wait(delay, true).await {
+                        break;
+                    }
+                }
+                Err(error) => {
+                    error!("Error while syncing, stopping the sync loop: {error}");
+                    set_state(&self.state, SyncServiceState::Error);
+
+                    return;
+                }
+            }
+        }
+    }
+
+    fn stop_requested(&self) -> bool {
+        *self.stop_receiver.borrow()
+    }
+
+    fn is_reachable(&self) -> bool {
+        *Observable::get(&self.reachable.read().unwrap())
+    }
+
+    /// Wait for the given delay, or until the network becomes reachable if
+    /// `until_reachable` is `true`.
+    ///
+    /// Returns `false` if a stop was requested in the meantime.
+    async fn wait(&self, delay: Duration, until_reachable: bool) -> bool {
+        let mut reachable = Observable::subscribe(&self.reachable.read().unwrap());
+        let became_reachable = async move {
+            if !until_reachable {
+                future::pending::<()>().await;
+            }
+
+            while let Some(reachable) = reachable.next().await {
+                if reachable {
+                    break;
+                }
+            }
+        };
+
+        let stop_requested = wait_for_stop(self.stop_receiver.clone());
+        let wake_up = future::select(Box::pin(became_reachable), Box::pin(sleep(delay)));
+        future::select(Box::pin(stop_requested), wake_up).await;
+
+        !self.stop_requested()
+    }
+}
+
+/// Wait until a stop is requested through the given receiver.
+async fn wait_for_stop(mut stop_receiver: watch::Receiver<bool>) {
+    while !*stop_receiver.borrow() {
+        if stop_receiver.changed().await.is_err() {
+            break;
+        }
+    }
+}
+
+fn set_state(state: &StdRwLock<Observable<SyncServiceState>>, new_state: SyncServiceState) {
+    let mut lock = state.write().unwrap();
+
+    if *Observable::get(&lock) != new_state {
+        debug!(state = ?new_state, "Sync service state changed");
+        Observable::set(&mut lock, new_state);
+    }
+}
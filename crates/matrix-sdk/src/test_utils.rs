@@ -1,10 +1,24 @@
 //!  Testing utilities - DO NOT USE IN PRODUCTION.
 
 #![allow(dead_code)]
+use std::{
+    io,
+    path::Path,
+    sync::{Arc, Mutex as StdMutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
 use matrix_sdk_base::Session;
+use matrix_sdk_test::http_fixture::{HttpExchange, HttpFixture, HttpReplayer, UnmatchedRequest};
 use ruma::{api::MatrixVersion, device_id, user_id};
+use tracing::warn;
 
-use crate::{config::RequestConfig, Client, ClientBuilder};
+use crate::{
+    config::RequestConfig, http_layers::RecorderLayer, Client, ClientBuilder, HttpError, HttpLayer,
+    HttpSend,
+};
 
 pub(crate) fn test_client_builder(homeserver_url: Option<String>) -> ClientBuilder {
     let homeserver = homeserver_url.as_deref().unwrap_or("http://localhost:1234");
@@ -31,3 +45,213 @@ pub(crate) async fn logged_in_client(homeserver_url: Option<String>) -> Client {
 
     client
 }
+
+/// An [`HttpSend`] that records the requests and the responses of another
+/// `HttpSend`, to replay them later with a [`ReplayHttpClient`].
+///
+/// The requests are recorded with a [`RecorderLayer`]. The secrets are
+/// scrubbed from the recorded exchanges, see the
+/// [`http_fixture`](matrix_sdk_test::http_fixture) module of `matrix-sdk-test`.
+/// The requests that fail without a response are not recorded.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::sync::Arc;
+///
+/// use matrix_sdk::{test_utils::RecordingHttpClient, Client};
+/// # use futures::executor::block_on;
+/// # block_on(async {
+///
+/// let recorder =
+///     Arc::new(RecordingHttpClient::new(Arc::new(reqwest::Client::new())));
+/// let client = Client::builder()
+///     .homeserver_url("https://example.com")
+///     .http_client(recorder.clone())
+///     .build()
+///     .await?;
+///
+/// client.login_username("example", "wordpass").send().await?;
+/// recorder.save("login.json")?;
+/// # anyhow::Ok(()) });
+/// ```
+#[derive(Debug)]
+pub struct RecordingHttpClient {
+    inner: Arc<dyn HttpSend>,
+    recorder: RecorderLayer,
+}
+
+impl RecordingHttpClient {
+    /// Create a new `RecordingHttpClient` that sends the requests with the
+    /// given `HttpSend`.
+    pub fn new(inner: Arc<dyn HttpSend>) -> Self {
+        let recorder = RecorderLayer::new();
+        Self { inner: recorder.layer(inner), recorder }
+    }
+
+    /// Get the exchanges recorded so far.
+    pub fn fixture(&self) -> HttpFixture {
+        let mut fixture = HttpFixture::new();
+
+        for recorded in self.recorder.requests() {
+            let Some(status) = recorded.status else { continue };
+
+            // Only the method, the URI and the body are recorded.
+            let mut request = http::Request::new(recorded.body);
+            *request.method_mut() = recorded.method;
+            match recorded.uri.parse() {
+                Ok(uri) => *request.uri_mut() = uri,
+                Err(error) => {
+                    warn!(
+                        uri = recorded.uri,
+                        "Not recording a request with an invalid URI: {error}"
+                    );
+                    continue;
+                }
+            }
+
+            let mut response = http::Response::new(recorded.response_body);
+            *response.status_mut() = status;
+
+            fixture.record(&request, &response);
+        }
+
+        fixture
+    }
+
+    /// Save the exchanges recorded so far to a JSON file at the given path.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.fixture().save(path)
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl HttpSend for RecordingHttpClient {
+    async fn send_request(
+        &self,
+        request: http::Request<Bytes>,
+        timeout: Duration,
+    ) -> Result<http::Response<Bytes>, HttpError> {
+        self.inner.send_request(request, timeout).await
+    }
+}
+
+/// An [`HttpSend`] that replays the exchanges recorded by a
+/// [`RecordingHttpClient`], without sending any request.
+///
+/// See [`HttpReplayer`] for how the requests are matched with the recorded
+/// exchanges.
+#[derive(Debug)]
+pub struct ReplayHttpClient {
+    replayer: StdMutex<HttpReplayer>,
+}
+
+impl ReplayHttpClient {
+    /// Create a new `ReplayHttpClient` for the exchanges of the given fixture.
+    pub fn new(fixture: HttpFixture) -> Self {
+        Self { replayer: StdMutex::new(HttpReplayer::new(fixture)) }
+    }
+
+    /// Create a new `ReplayHttpClient` for the exchanges saved to the JSON
+    /// file at the given path.
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(HttpFixture::load(path)?))
+    }
+
+    /// Get the requests that didn't match any recorded exchange.
+    pub fn unmatched_requests(&self) -> Vec<UnmatchedRequest> {
+        self.replayer.lock().unwrap().unmatched_requests().to_vec()
+    }
+
+    /// Get the recorded exchanges that were not replayed yet.
+    pub fn unused_exchanges(&self) -> Vec<HttpExchange> {
+        self.replayer.lock().unwrap().unused_exchanges().into_iter().cloned().collect()
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl HttpSend for ReplayHttpClient {
+    async fn send_request(
+        &self,
+        request: http::Request<Bytes>,
+        _timeout: Duration,
+    ) -> Result<http::Response<Bytes>, HttpError> {
+        let mut replayer = self.replayer.lock().unwrap();
+        let unmatched_count = replayer.unmatched_requests().len();
+        let response = replayer.respond(&request);
+
+        if replayer.unmatched_requests().len() > unmatched_count {
+            warn!(
+                method = %request.method(),
+                path = request.uri().path(),
+                "The request doesn't match any recorded exchange"
+            );
+        }
+
+        Ok(response.map(Bytes::from))
+    }
+}
+
+// The http mocking library is not supported for wasm32
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use std::sync::Arc;
+
+    use matrix_sdk_test::{async_test, http_fixture::SCRUBBED, test_json};
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::{test_client_builder, RecordingHttpClient, ReplayHttpClient};
+
+    #[async_test]
+    async fn record_and_replay() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/_matrix/client/r0/login"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::LOGIN))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/_matrix/client/r0/account/whoami"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::WHOAMI))
+            .mount(&server)
+            .await;
+
+        let recorder = Arc::new(RecordingHttpClient::new(Arc::new(reqwest::Client::new())));
+        let client = test_client_builder(Some(server.uri()))
+            .http_client(recorder.clone())
+            .build()
+            .await
+            .unwrap();
+        client.login_username("example", "wordpass").send().await.unwrap();
+        client.whoami().await.unwrap();
+
+        let fixture = recorder.fixture();
+        assert_eq!(fixture.exchanges.len(), 2);
+        assert_eq!(fixture.exchanges[0].request_body["password"], SCRUBBED);
+        assert_eq!(fixture.exchanges[0].response_body["access_token"], SCRUBBED);
+
+        let dir = tempfile::tempdir().unwrap();
+        let fixture_path = dir.path().join("fixture.json");
+        recorder.save(&fixture_path).unwrap();
+
+        // The requests are served from the fixture, without any server.
+        let replay = Arc::new(ReplayHttpClient::from_file(&fixture_path).unwrap());
+        let client = test_client_builder(None).http_client(replay.clone()).build().await.unwrap();
+        client.login_username("example", "wordpass").send().await.unwrap();
+        assert_eq!(client.access_token().as_deref(), Some(SCRUBBED));
+        client.whoami().await.unwrap();
+        assert!(replay.unused_exchanges().is_empty());
+        assert!(replay.unmatched_requests().is_empty());
+
+        // Each exchange is only replayed once.
+        client.whoami().await.unwrap_err();
+        let unmatched = replay.unmatched_requests();
+        assert_eq!(unmatched.len(), 1);
+        assert_eq!(unmatched[0].path, "/_matrix/client/r0/account/whoami");
+    }
+}
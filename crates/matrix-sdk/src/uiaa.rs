@@ -0,0 +1,460 @@
+// Copyright 2023 The Matrix.org Foundation C.I.C.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A driver for [user-interactive authentication].
+//!
+//! Some endpoints, like the ones to change the password of the account or to
+//! delete devices, require the user to authenticate again. The homeserver
+//! answers the first request with a [`UiaaInfo`] listing the flows of stages
+//! that can be completed, and the same request must be sent again with the
+//! data of every stage until one of the flows is complete.
+//!
+//! [`Uiaa`] takes care of the whole exchange: it asks a callback to choose a
+//! flow and to provide the data of every stage, keeps track of the session
+//! and sends the request again until the homeserver accepts it.
+//!
+//! [user-interactive authentication]: https://spec.matrix.org/v1.6/client-server-api/#user-interactive-authentication-api
+
+use std::{collections::BTreeMap, fmt, future::Future};
+
+use matrix_sdk_common::instant::{Duration, Instant};
+use ruma::{
+    api::{
+        client::{
+            account::{add_3pid, change_password, deactivate, register},
+            device::{delete_device, delete_devices},
+            uiaa::{
+                AuthData, AuthFlow, AuthType, Dummy, FallbackAcknowledgement, Password, ReCaptcha,
+                RegistrationToken, UiaaInfo, UiaaResponse, UserIdentifier,
+            },
+        },
+        OutgoingRequest,
+    },
+    serde::JsonObject,
+    OwnedClientSecret, OwnedSessionId,
+};
+use serde_json::{json, Value as JsonValue};
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::{utils::sleep, Client, Error, Result};
+
+/// The authentication type of the terms of service stage.
+const TERMS: &str = "m.login.terms";
+
+/// A request to an endpoint protected by user-interactive authentication.
+pub trait UiaaRequest: OutgoingRequest<EndpointError = UiaaResponse> + Clone + fmt::Debug {
+    /// Set the authentication data of the request.
+    fn set_auth(&mut self, auth: Option<AuthData>);
+}
+
+macro_rules! impl_uiaa_request {
+    ($($request:ty),* $(,)?) => {
+        $(
+            impl UiaaRequest for $request {
+                fn set_auth(&mut self, auth: Option<AuthData>) {
+                    self.auth = auth;
+                }
+            }
+        )*
+    };
+}
+
+impl_uiaa_request!(
+    add_3pid::v3::Request,
+    change_password::v3::Request,
+    deactivate::v3::Request,
+    delete_device::v3::Request,
+    delete_devices::v3::Request,
+    register::v3::Request,
+);
+
+#[cfg(feature = "e2e-encryption")]
+impl_uiaa_request!(ruma::api::client::keys::upload_signing_keys::v3::Request);
+
+/// What the homeserver expects from the user to go on with the
+/// authentication.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum UiaaPrompt {
+    /// A flow must be chosen among the flows of the info, with
+    /// [`UiaaAnswer::Flow`].
+    ChooseFlow {
+        /// The latest info returned by the homeserver.
+        info: UiaaInfo,
+    },
+
+    /// A stage of the chosen flow must be completed.
+    Stage(UiaaStage),
+}
+
+/// A stage of user-interactive authentication to complete.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct UiaaStage {
+    /// The type of the stage.
+    pub auth_type: AuthType,
+
+    /// The parameters of the stage, like the public key for ReCaptcha or the
+    /// policies for the terms of service.
+    pub params: Option<JsonValue>,
+
+    /// The URL of the fallback web page of the stage.
+    ///
+    /// It can be used for stages that the client can't complete by itself,
+    /// like single sign-on. Once the user went through the page, the stage
+    /// should be answered with [`UiaaAnswer::FallbackCompleted`].
+    pub fallback_url: Option<Url>,
+
+    /// The latest info returned by the homeserver.
+    ///
+    /// If the previous attempt at this stage failed, the reason is in
+    /// [`UiaaInfo::auth_error`].
+    pub info: UiaaInfo,
+}
+
+/// The answer of the callback of [`Uiaa`] to a [`UiaaPrompt`].
+#[derive(Clone)]
+#[non_exhaustive]
+pub enum UiaaAnswer {
+    /// Choose the flow at the given index.
+    Flow(usize),
+
+    /// Authenticate with the password of the client's user.
+    Password(String),
+
+    /// Complete a dummy stage.
+    Dummy,
+
+    /// Accept the terms of service.
+    Terms,
+
+    /// Use an email address that was validated with a token request to the
+    /// homeserver.
+    ///
+    /// The stage is submitted again until the user clicks on the link in the
+    /// email, see [`Uiaa::email_polling()`].
+    EmailIdentity {
+        /// The session ID returned by the token request.
+        sid: OwnedSessionId,
+        /// The client secret used in the token request.
+        client_secret: OwnedClientSecret,
+    },
+
+    /// Provide the response of the ReCaptcha.
+    ReCaptcha(String),
+
+    /// Provide a registration token.
+    RegistrationToken(String),
+
+    /// The user completed the stage on the fallback web page.
+    FallbackCompleted,
+
+    /// Stop the authentication.
+    ///
+    /// The error of the last response of the homeserver is returned.
+    Abort,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl fmt::Debug for UiaaAnswer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Flow(index) => f.debug_tuple("Flow").field(index).finish(),
+            Self::Password(_) => f.debug_tuple("Password").finish_non_exhaustive(),
+            Self::Dummy => f.write_str("Dummy"),
+            Self::Terms => f.write_str("Terms"),
+            Self::EmailIdentity { sid, .. } => {
+                f.debug_struct("EmailIdentity").field("sid", sid).finish_non_exhaustive()
+            }
+            Self::ReCaptcha(_) => f.debug_tuple("ReCaptcha").finish_non_exhaustive(),
+            Self::RegistrationToken(_) => {
+                f.debug_tuple("RegistrationToken").finish_non_exhaustive()
+            }
+            Self::FallbackCompleted => f.write_str("FallbackCompleted"),
+            Self::Abort => f.write_str("Abort"),
+        }
+    }
+}
+
+/// A driver for user-interactive authentication.
+///
+/// Get it with [`Client::uiaa()`].
+pub struct Uiaa<H> {
+    client: Client,
+    handler: H,
+    email_poll_interval: Duration,
+    email_poll_timeout: Duration,
+}
+
+#[cfg(not(tarpaulin_include))]
+impl<H> fmt::Debug for Uiaa<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Uiaa")
+            .field("email_poll_interval", &self.email_poll_interval)
+            .field("email_poll_timeout", &self.email_poll_timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<H, Fut> Uiaa<H>
+where
+    H: FnMut(UiaaPrompt) -> Fut,
+    Fut: Future<Output = UiaaAnswer>,
+{
+    fn new(client: Client, handler: H) -> Self {
+        Self {
+            client,
+            handler,
+            email_poll_interval: Duration::from_secs(5),
+            email_poll_timeout: Duration::from_secs(10 * 60),
+        }
+    }
+
+    /// Set how often the email identity stage is submitted again while
+    /// waiting for the user to click on the link in the email, and how long
+    /// to wait before asking the callback again.
+    ///
+    /// Defaults to every 5 seconds, for 10 minutes.
+    pub fn email_polling(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.email_poll_interval = interval;
+        self.email_poll_timeout = timeout;
+        self
+    }
+
+    /// Send the given request, going through user-interactive authentication
+    /// if the homeserver requires it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::{
+    /// #     ruma::api::client::account::change_password,
+    /// #     uiaa::{UiaaAnswer, UiaaPrompt},
+    /// #     Client,
+    /// # };
+    /// # async {
+    /// # let client: Client = todo!();
+    /// let request = change_password::v3::Request::new("new password".to_owned());
+    ///
+    /// client
+    ///     .uiaa(|prompt| async move {
+    ///         match prompt {
+    ///             UiaaPrompt::ChooseFlow { .. } => UiaaAnswer::Flow(0),
+    ///             UiaaPrompt::Stage(stage)
+    ///                 if stage.auth_type.as_str() == "m.login.password" =>
+    ///             {
+    ///                 UiaaAnswer::Password("old password".to_owned())
+    ///             }
+    ///             _ => UiaaAnswer::Abort,
+    ///         }
+    ///     })
+    ///     .send(request)
+    ///     .await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn send<R: UiaaRequest>(&mut self, request: R) -> Result<R::IncomingResponse> {
+        let client = self.client.clone();
+
+        self.run(|auth| {
+            let mut request = request.clone();
+            request.set_auth(auth);
+            let client = &client;
+
+            async move { client.send(request, None).await }
+        })
+        .await
+    }
+
+    /// Call the given function, going through user-interactive
+    /// authentication if it fails with a [`UiaaInfo`].
+    ///
+    /// The function is called with the authentication data of every attempt.
+    /// This allows to use the methods of the SDK that take an
+    /// `Option<AuthData>`, like [`Client::delete_devices()`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use matrix_sdk::{ruma::device_id, uiaa::UiaaAnswer, Client};
+    /// # async {
+    /// # let client: Client = todo!();
+    /// let devices = &[device_id!("DEVICEID").to_owned()];
+    ///
+    /// client
+    ///     .uiaa(|_| async { UiaaAnswer::Abort })
+    ///     .run(|auth| client.delete_devices(devices, auth))
+    ///     .await?;
+    /// # anyhow::Ok(()) };
+    /// ```
+    pub async fn run<T, E, F, RFut>(&mut self, mut request: F) -> Result<T>
+    where
+        E: Into<Error>,
+        F: FnMut(Option<AuthData>) -> RFut,
+        RFut: Future<Output = Result<T, E>>,
+    {
+        let mut auth = None;
+        let mut flow: Option<AuthFlow> = None;
+        // The email identity stage being polled, with the time it was first
+        // submitted.
+        let mut pending_email: Option<(AuthData, Instant)> = None;
+
+        loop {
+            let error = match request(auth.take()).await {
+                Ok(response) => return Ok(response),
+                Err(error) => error.into(),
+            };
+            let Some(info) = error.as_uiaa_response().cloned() else {
+                return Err(error);
+            };
+
+            let chosen_flow = match flow.clone() {
+                Some(chosen_flow) => chosen_flow,
+                None => {
+                    let prompt = UiaaPrompt::ChooseFlow { info: info.clone() };
+                    let chosen_flow = match (self.handler)(prompt).await {
+                        UiaaAnswer::Flow(index) => match info.flows.get(index) {
+                            Some(chosen_flow) => chosen_flow.clone(),
+                            None => {
+                                warn!(index, "Chose a flow that doesn't exist, aborting");
+                                return Err(error);
+                            }
+                        },
+                        UiaaAnswer::Abort => return Err(error),
+                        answer => {
+                            warn!(?answer, "Expected a flow to be chosen, aborting");
+                            return Err(error);
+                        }
+                    };
+
+                    flow = Some(chosen_flow.clone());
+                    chosen_flow
+                }
+            };
+
+            let Some(auth_type) =
+                chosen_flow.stages.iter().find(|stage| !info.completed.contains(stage)).cloned()
+            else {
+                // All the stages are complete but the homeserver still refuses
+                // the request, there is nothing left to try.
+                return Err(error);
+            };
+
+            if let Some((data, since)) = pending_email.take() {
+                if auth_type == AuthType::EmailIdentity && since.elapsed() < self.email_poll_timeout
+                {
+                    debug!("The email address is not validated yet, polling again");
+                    sleep(self.email_poll_interval).await;
+                    auth = Some(data.clone());
+                    pending_email = Some((data, since));
+                    continue;
+                }
+            }
+
+            let session = info.session.clone();
+            let stage = UiaaStage {
+                params: stage_params(&info, &auth_type),
+                fallback_url: self.fallback_url(&auth_type, session.as_deref()).await,
+                auth_type,
+                info,
+            };
+
+            let data = match (self.handler)(UiaaPrompt::Stage(stage)).await {
+                UiaaAnswer::Password(password) => {
+                    let Some(user_id) = self.client.user_id() else {
+                        warn!("Can't authenticate with a password without a user ID, aborting");
+                        return Err(error);
+                    };
+                    let identifier = UserIdentifier::UserIdOrLocalpart(user_id.to_string());
+                    let mut password = Password::new(identifier, password);
+                    password.session = session;
+                    AuthData::Password(password)
+                }
+                UiaaAnswer::Dummy => {
+                    let mut dummy = Dummy::new();
+                    dummy.session = session;
+                    AuthData::Dummy(dummy)
+                }
+                UiaaAnswer::Terms => AuthData::new(TERMS, session, JsonObject::new())?,
+                UiaaAnswer::EmailIdentity { sid, client_secret } => {
+                    let mut data = JsonObject::new();
+                    data.insert(
+                        "threepid_creds".to_owned(),
+                        json!({ "sid": sid, "client_secret": client_secret }),
+                    );
+                    let data = AuthData::new(AuthType::EmailIdentity.as_str(), session, data)?;
+                    pending_email = Some((data.clone(), Instant::now()));
+                    data
+                }
+                UiaaAnswer::ReCaptcha(response) => {
+                    let mut recaptcha = ReCaptcha::new(response);
+                    recaptcha.session = session;
+                    AuthData::ReCaptcha(recaptcha)
+                }
+                UiaaAnswer::RegistrationToken(token) => {
+                    let mut registration_token = RegistrationToken::new(token);
+                    registration_token.session = session;
+                    AuthData::RegistrationToken(registration_token)
+                }
+                UiaaAnswer::FallbackCompleted => {
+                    let Some(session) = session else {
+                        warn!("Can't acknowledge a fallback without a session, aborting");
+                        return Err(error);
+                    };
+                    AuthData::FallbackAcknowledgement(FallbackAcknowledgement::new(session))
+                }
+                UiaaAnswer::Abort => return Err(error),
+                answer @ UiaaAnswer::Flow(_) => {
+                    warn!(?answer, "Expected the data of a stage, aborting");
+                    return Err(error);
+                }
+            };
+
+            auth = Some(data);
+        }
+    }
+
+    /// The URL of the fallback web page of the given stage.
+    async fn fallback_url(&self, auth_type: &AuthType, session: Option<&str>) -> Option<Url> {
+        let session = session?;
+        let mut url = self
+            .client
+            .homeserver()
+            .await
+            .join(&format!("_matrix/client/v3/auth/{}/fallback/web", auth_type.as_str()))
+            .ok()?;
+        url.query_pairs_mut().append_pair("session", session);
+
+        Some(url)
+    }
+}
+
+/// The parameters of the given stage in the info.
+fn stage_params(info: &UiaaInfo, auth_type: &AuthType) -> Option<JsonValue> {
+    serde_json::from_str::<BTreeMap<String, JsonValue>>(info.params.get())
+        .ok()?
+        .remove(auth_type.as_str())
+}
+
+impl Client {
+    /// Get a driver for user-interactive authentication, that asks the given
+    /// callback what to do every time the homeserver requires a new stage.
+    pub fn uiaa<H, Fut>(&self, handler: H) -> Uiaa<H>
+    where
+        H: FnMut(UiaaPrompt) -> Fut,
+        Fut: Future<Output = UiaaAnswer>,
+    {
+        Uiaa::new(self.clone(), handler)
+    }
+}
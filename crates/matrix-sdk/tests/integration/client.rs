@@ -1,11 +1,19 @@
 use std::{collections::BTreeMap, str::FromStr, time::Duration};
 
+use futures::{pin_mut, StreamExt};
 use matrix_sdk::{
-    config::SyncSettings,
+    config::{RequestConfig, SyncSettings},
     media::{MediaFormat, MediaRequest, MediaThumbnailSize},
+    moderation::{BulkRedactionOptions, EnforcementAction},
+    presence::IdlePresence,
+    room::MessagesOptions,
+    search::{LocalSearchFilter, MemorySearchIndex, OrderBy, SearchOptions},
     RumaApiError, Session,
 };
-use matrix_sdk_test::{async_test, test_json};
+use matrix_sdk_test::{
+    async_test, test_json, EventBuilder, JoinedRoomBuilder, PresenceTestEvent, StateTestEvent,
+    TimelineTestEvent,
+};
 use ruma::{
     api::client::{
         self as client_api,
@@ -20,17 +28,22 @@ use ruma::{
     },
     assign, device_id,
     directory::Filter,
+    event_id,
     events::room::{message::ImageMessageEventContent, ImageInfo, MediaSource},
-    mxc_uri, room_id, uint, user_id,
+    mxc_uri,
+    presence::PresenceState,
+    room_id,
+    space::SpaceRoomJoinRule,
+    uint, user_id, MilliSecondsSinceUnixEpoch,
 };
 use serde_json::{from_value as from_json_value, json, to_value as to_json_value};
 use url::Url;
 use wiremock::{
-    matchers::{header, method, path, path_regex},
+    matchers::{body_partial_json, header, method, path, path_regex, query_param},
     Mock, ResponseTemplate,
 };
 
-use crate::{logged_in_client, mock_sync, no_retry_test_client};
+use crate::{logged_in_client, mock_sync, no_retry_test_client, test_client_builder};
 
 #[async_test]
 async fn login() {
@@ -428,6 +441,123 @@ async fn join_room_by_id_or_alias() {
     );
 }
 
+#[async_test]
+async fn knock() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"/knock/"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_partial_json(json!({ "reason": "Let me in" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::ROOM_ID))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let room_id = room_id!("!testroom:example.org");
+    let room = client
+        .knock(room_id.into(), Some("Let me in".to_owned()), &["server.com".try_into().unwrap()])
+        .await
+        .unwrap();
+
+    assert_eq!(room.room_id(), room_id);
+    assert!(client.get_knocked_room(room_id).is_some());
+    assert_eq!(client.knocked_rooms().len(), 1);
+    assert!(client.get_joined_room(room_id).is_none());
+}
+
+#[async_test]
+async fn room_preview_from_summary() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"/im.nheko.summary/rooms/.*/summary"))
+        .and(query_param("via", "example.org"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "room_id": "!preview:example.org",
+            "name": "Preview",
+            "num_joined_members": 42,
+            "join_rule": "public",
+            "world_readable": true,
+            "guest_can_join": false,
+            "im.nheko.summary.encryption": "m.megolm.v1.aes-sha2"
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let preview = client
+        .room_preview(
+            room_id!("!preview:example.org").into(),
+            vec!["example.org".try_into().unwrap()],
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(preview.name.as_deref(), Some("Preview"));
+    assert_eq!(preview.num_joined_members, 42);
+    assert_eq!(preview.join_rule, Some(SpaceRoomJoinRule::Public));
+    assert!(preview.is_world_readable);
+    assert_eq!(preview.is_encrypted, Some(true));
+    assert_eq!(preview.state, None);
+}
+
+#[async_test]
+async fn room_preview_from_state() {
+    let (client, server) = logged_in_client().await;
+
+    // The summary endpoint isn't mocked, so the server responds with a 404.
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            {
+                "content": { "name": "Peeked" },
+                "event_id": "$name",
+                "origin_server_ts": 151957878,
+                "sender": "@example:example.org",
+                "state_key": "",
+                "type": "m.room.name"
+            },
+            {
+                "content": { "history_visibility": "world_readable" },
+                "event_id": "$history_visibility",
+                "origin_server_ts": 151957878,
+                "sender": "@example:example.org",
+                "state_key": "",
+                "type": "m.room.history_visibility"
+            },
+            {
+                "content": { "membership": "join" },
+                "event_id": "$member",
+                "origin_server_ts": 151957878,
+                "sender": "@example:example.org",
+                "state_key": "@example:example.org",
+                "type": "m.room.member"
+            }
+        ])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/messages$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::ROOM_MESSAGES))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let preview =
+        client.room_preview(room_id!("!peek:example.org").into(), Vec::new()).await.unwrap();
+
+    assert_eq!(preview.name.as_deref(), Some("Peeked"));
+    assert_eq!(preview.num_joined_members, 1);
+    assert!(preview.is_world_readable);
+    assert_eq!(preview.is_encrypted, Some(false));
+
+    let messages = preview.messages(MessagesOptions::backward()).await.unwrap();
+    assert!(!messages.chunk.is_empty());
+}
+
 #[async_test]
 async fn room_search_all() {
     let (client, server) = no_retry_test_client().await;
@@ -630,3 +760,510 @@ fn serialize_session() {
         })
     );
 }
+
+#[async_test]
+async fn presence() {
+    let (client, server) = logged_in_client().await;
+    let alice = user_id!("@alice:localhost");
+
+    assert!(client.presence(alice).await.unwrap().is_none());
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder.add_presence_event(PresenceTestEvent::Custom(json!({
+        "content": {
+            "currently_active": true,
+            "last_active_ago": 60_000,
+            "presence": "online",
+            "status_msg": "Making cupcakes"
+        },
+        "sender": alice,
+        "type": "m.presence"
+    })));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+    server.reset().await;
+
+    let presence = client.presence(alice).await.unwrap().unwrap();
+    assert_eq!(presence.state, PresenceState::Online);
+    assert_eq!(presence.status_msg.as_deref(), Some("Making cupcakes"));
+    assert!(presence.currently_active);
+    assert!(presence.last_active_ago().unwrap() >= Duration::from_secs(60));
+
+    // The stream starts with the known presence.
+    let stream = client.subscribe_to_presence([alice.to_owned()]);
+    pin_mut!(stream);
+    assert_eq!(stream.next().await.unwrap().state, PresenceState::Online);
+
+    ev_builder.add_presence_event(PresenceTestEvent::Custom(json!({
+        "content": { "presence": "unavailable" },
+        "sender": alice,
+        "type": "m.presence"
+    })));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let presence = stream.next().await.unwrap();
+    assert_eq!(presence.state, PresenceState::Unavailable);
+    assert_eq!(presence.status_msg, None);
+    assert_eq!(presence.last_active_ago(), None);
+}
+
+#[async_test]
+async fn set_presence() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("PUT"))
+        .and(path("/_matrix/client/r0/presence/@example:localhost/status"))
+        .and(body_partial_json(json!({
+            "presence": "unavailable",
+            "status_msg": "Out for lunch",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    client.account().set_presence(PresenceState::Unavailable, Some("Out for lunch")).await.unwrap();
+}
+
+#[async_test]
+async fn sync_once_with_idle_presence() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/sync"))
+        .and(query_param("set_presence", "unavailable"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::SYNC))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // The user is idle right away.
+    let idle_presence = IdlePresence::new(Duration::ZERO);
+    client.sync_once(SyncSettings::new().idle_presence(idle_presence)).await.unwrap();
+}
+
+#[async_test]
+async fn search() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/search"))
+        .and(query_param("next_batch", "abcd"))
+        .and(body_partial_json(json!({
+            "search_categories": {
+                "room_events": {
+                    "search_term": "cupcakes",
+                    "order_by": "recent",
+                    "filter": { "rooms": ["!bakery:localhost"] },
+                    "event_context": { "before_limit": 1, "after_limit": 0 }
+                }
+            }
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "search_categories": {
+                "room_events": {
+                    "count": 1,
+                    "highlights": ["cupcakes"],
+                    "next_batch": "efgh",
+                    "results": [{
+                        "rank": 0.5,
+                        "context": {
+                            "events_before": [{
+                                "content": { "body": "What are you doing?", "msgtype": "m.text" },
+                                "event_id": "$before",
+                                "origin_server_ts": 1432735824653_u64,
+                                "room_id": "!bakery:localhost",
+                                "sender": "@alice:localhost",
+                                "type": "m.room.message"
+                            }],
+                            "events_after": [],
+                            "profile_info": {}
+                        },
+                        "result": {
+                            "content": { "body": "Making cupcakes", "msgtype": "m.text" },
+                            "event_id": "$result",
+                            "origin_server_ts": 1432735824654_u64,
+                            "room_id": "!bakery:localhost",
+                            "sender": "@example:localhost",
+                            "type": "m.room.message"
+                        }
+                    }]
+                }
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let options = SearchOptions::new("cupcakes")
+        .rooms(vec![room_id!("!bakery:localhost").to_owned()])
+        .order_by(OrderBy::Recent)
+        .context(uint!(1), uint!(0))
+        .next_batch("abcd");
+    let results = client.search(options).await.unwrap();
+
+    assert_eq!(results.count, Some(uint!(1)));
+    assert_eq!(results.highlights, ["cupcakes"]);
+    assert_eq!(results.next_batch.as_deref(), Some("efgh"));
+    assert_eq!(results.results.len(), 1);
+
+    let result = &results.results[0];
+    assert_eq!(result.room_id, room_id!("!bakery:localhost"));
+    assert_eq!(result.rank, Some(0.5));
+    let event_id = result.event.event.get_field::<String>("event_id").unwrap();
+    assert_eq!(event_id.as_deref(), Some("$result"));
+    assert_eq!(result.events_before.len(), 1);
+    let event_id = result.events_before[0].event.get_field::<String>("event_id").unwrap();
+    assert_eq!(event_id.as_deref(), Some("$before"));
+    assert!(result.events_after.is_empty());
+
+    let by_room = results.by_room();
+    assert_eq!(by_room[room_id!("!bakery:localhost")].len(), 1);
+}
+
+#[async_test]
+async fn search_local() {
+    let (builder, server) = test_client_builder().await;
+    let client = builder
+        .request_config(RequestConfig::new().disable_retry())
+        .search_index(MemorySearchIndex::new())
+        .build()
+        .await
+        .unwrap();
+    client
+        .restore_session(Session {
+            access_token: "1234".to_owned(),
+            refresh_token: None,
+            user_id: user_id!("@example:localhost").to_owned(),
+            device_id: device_id!("DEVICEID").to_owned(),
+        })
+        .await
+        .unwrap();
+
+    let room_id = room_id!("!bakery:localhost");
+    let message = |event_id: &str, ts: u64, body: &str| {
+        TimelineTestEvent::Custom(json!({
+            "content": { "body": body, "msgtype": "m.text" },
+            "event_id": event_id,
+            "origin_server_ts": ts,
+            "sender": "@alice:localhost",
+            "type": "m.room.message"
+        }))
+    };
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(room_id)
+            .add_timeline_event(message("$1", 1, "I love baking cupcakes"))
+            .add_timeline_event(message("$2", 2, "Cupcakes are the best"))
+            .add_timeline_event(message("$3", 3, "Something else")),
+    );
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+    server.reset().await;
+
+    let results = client.search_local("cupcakes", LocalSearchFilter::new()).await.unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].event_id, event_id!("$2"));
+    assert_eq!(results[0].room_id, room_id);
+    assert_eq!(results[1].event_id, event_id!("$1"));
+
+    // Redacted events are removed from the index.
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id).add_timeline_event(
+        TimelineTestEvent::Custom(json!({
+            "content": {},
+            "event_id": "$4",
+            "origin_server_ts": 4,
+            "redacts": "$2",
+            "sender": "@alice:localhost",
+            "type": "m.room.redaction"
+        })),
+    ));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let results = client.search_local("cupcakes", LocalSearchFilter::new()).await.unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].event_id, event_id!("$1"));
+    assert_eq!(results[0].snippet, "I love baking cupcakes");
+
+    // Edits replace the indexed body of the original event.
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id).add_timeline_event(
+        TimelineTestEvent::Custom(json!({
+            "content": {
+                "body": "* I love baking muffins",
+                "msgtype": "m.text",
+                "m.new_content": { "body": "I love baking muffins", "msgtype": "m.text" },
+                "m.relates_to": { "rel_type": "m.replace", "event_id": "$1" }
+            },
+            "event_id": "$5",
+            "origin_server_ts": 5,
+            "sender": "@alice:localhost",
+            "type": "m.room.message"
+        })),
+    ));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let results = client.search_local("cupcakes", LocalSearchFilter::new()).await.unwrap();
+    assert!(results.is_empty());
+    let results = client.search_local("muffins", LocalSearchFilter::new()).await.unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].event_id, event_id!("$1"));
+    assert_eq!(results[0].sender, "@alice:localhost");
+    assert_eq!(results[0].origin_server_ts, MilliSecondsSinceUnixEpoch(uint!(1)));
+
+    // Edits from other users are indexed as their own event.
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id).add_timeline_event(
+        TimelineTestEvent::Custom(json!({
+            "content": {
+                "body": "* I love baking pies",
+                "msgtype": "m.text",
+                "m.new_content": { "body": "I love baking pies", "msgtype": "m.text" },
+                "m.relates_to": { "rel_type": "m.replace", "event_id": "$1" }
+            },
+            "event_id": "$6",
+            "origin_server_ts": 6,
+            "sender": "@mallory:localhost",
+            "type": "m.room.message"
+        })),
+    ));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let results = client.search_local("muffins", LocalSearchFilter::new()).await.unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].event_id, event_id!("$1"));
+    let results = client.search_local("pies", LocalSearchFilter::new()).await.unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].event_id, event_id!("$6"));
+    assert_eq!(results[0].sender, "@mallory:localhost");
+}
+
+#[async_test]
+async fn moderation_redact_user_events() {
+    let (client, server) = logged_in_client().await;
+    let room_id = room_id!("!protected:localhost");
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/messages$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "start": "t1",
+            "chunk": [
+                {
+                    "content": {},
+                    "event_id": "$redaction",
+                    "origin_server_ts": 3,
+                    "redacts": "$old",
+                    "sender": "@spammer:localhost",
+                    "type": "m.room.redaction"
+                },
+                {
+                    "content": { "body": "Buy now!", "msgtype": "m.text" },
+                    "event_id": "$spam",
+                    "origin_server_ts": 2,
+                    "room_id": "!protected:localhost",
+                    "sender": "@spammer:localhost",
+                    "type": "m.room.message"
+                },
+                {
+                    "content": {},
+                    "event_id": "$old",
+                    "origin_server_ts": 1,
+                    "room_id": "!protected:localhost",
+                    "sender": "@spammer:localhost",
+                    "type": "m.room.message",
+                    "unsigned": { "redacted_because": {
+                        "content": {},
+                        "event_id": "$redaction",
+                        "origin_server_ts": 3,
+                        "redacts": "$old",
+                        "sender": "@spammer:localhost",
+                        "type": "m.room.redaction"
+                    } }
+                }
+            ]
+        })))
+        .mount(&server)
+        .await;
+
+    let user_id = user_id!("@spammer:localhost");
+    let moderation = client.moderation();
+
+    // Nothing is redacted in dry-run mode.
+    let options = BulkRedactionOptions::new().dry_run();
+    let report =
+        moderation.redact_user_events(user_id, [room_id.to_owned()], options).await.unwrap();
+    assert_eq!(report.redacted, [(room_id.to_owned(), event_id!("$spam").to_owned())]);
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/redact/\$spam/.*"))
+        .and(body_partial_json(json!({ "reason": "Spam" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "event_id": "$r" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let options = BulkRedactionOptions::new().reason("Spam").batching(1, Duration::ZERO);
+    let report =
+        moderation.redact_user_events(user_id, [room_id.to_owned()], options).await.unwrap();
+    assert_eq!(report.redacted, [(room_id.to_owned(), event_id!("$spam").to_owned())]);
+    assert!(report.failed.is_empty());
+}
+
+#[async_test]
+async fn moderation_enforce_policy_list() {
+    let (client, server) = logged_in_client().await;
+    let policy_room_id = room_id!("!policies:localhost");
+    let protected_room_id = room_id!("!protected:localhost");
+
+    let rule = |event_type: &str, state_key: &str, entity: &str| {
+        StateTestEvent::Custom(json!({
+            "content": { "entity": entity, "recommendation": "m.ban", "reason": "spam" },
+            "event_id": format!("${state_key}"),
+            "origin_server_ts": 1,
+            "sender": "@moderator:localhost",
+            "state_key": state_key,
+            "type": event_type
+        }))
+    };
+    let member = |user_id: &str| {
+        json!({
+            "content": { "membership": "join" },
+            "event_id": format!("$member_{user_id}"),
+            "origin_server_ts": 1,
+            "room_id": "!protected:localhost",
+            "sender": user_id,
+            "state_key": user_id,
+            "type": "m.room.member"
+        })
+    };
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder
+        .add_joined_room(
+            JoinedRoomBuilder::new(policy_room_id)
+                .add_state_event(rule("m.policy.rule.user", "rule1", "@spam*:localhost"))
+                .add_state_event(rule("m.policy.rule.server", "rule2", "evil.org")),
+        )
+        .add_joined_room(JoinedRoomBuilder::new(protected_room_id));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/members$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "chunk": [
+                member("@example:localhost"),
+                member("@spammer:localhost"),
+                member("@alice:evil.org"),
+                member("@bob:localhost"),
+            ]
+        })))
+        .mount(&server)
+        .await;
+
+    let moderation = client.moderation();
+    let policy_list = moderation.policy_list([policy_room_id.to_owned()]).await.unwrap();
+    assert_eq!(policy_list.user_rules.len(), 1);
+    assert_eq!(policy_list.server_rules.len(), 1);
+
+    // Nothing is sent in dry-run mode.
+    let protected_rooms = [protected_room_id.to_owned()];
+    let report =
+        moderation.enforce_policy_list(&policy_list, &protected_rooms, true).await.unwrap();
+    assert_eq!(report.applied.len(), 3);
+
+    let mut banned = Vec::new();
+    for action in &report.applied {
+        match action {
+            EnforcementAction::Ban { user_id, reason, .. } => {
+                assert_eq!(reason, "spam");
+                banned.push(user_id.as_str());
+            }
+            EnforcementAction::UpdateServerAcl { content, .. } => {
+                assert_eq!(content.allow, ["*"]);
+                assert_eq!(content.deny, ["evil.org"]);
+            }
+            _ => panic!("unexpected action: {action:?}"),
+        }
+    }
+    banned.sort_unstable();
+    assert_eq!(banned, ["@alice:evil.org", "@spammer:localhost"]);
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/ban$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.room.server_acl/?$"))
+        .and(body_partial_json(json!({ "deny": ["evil.org"] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "event_id": "$acl" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let report =
+        moderation.enforce_policy_list(&policy_list, &protected_rooms, false).await.unwrap();
+    assert_eq!(report.applied.len(), 3);
+    assert!(report.failed.is_empty());
+}
+
+#[async_test]
+async fn moderation_enforce_policy_list_ignores_own_server() {
+    let (client, server) = logged_in_client().await;
+    let policy_room_id = room_id!("!policies:localhost");
+    let protected_room_id = room_id!("!protected:localhost");
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder
+        .add_joined_room(JoinedRoomBuilder::new(policy_room_id).add_state_event(
+            StateTestEvent::Custom(json!({
+                "content": { "entity": "*", "recommendation": "m.ban", "reason": "spam" },
+                "event_id": "$rule",
+                "origin_server_ts": 1,
+                "sender": "@moderator:localhost",
+                "state_key": "rule",
+                "type": "m.policy.rule.server"
+            })),
+        ))
+        .add_joined_room(JoinedRoomBuilder::new(protected_room_id));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/members$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "chunk": [{
+                "content": { "membership": "join" },
+                "event_id": "$member",
+                "origin_server_ts": 1,
+                "room_id": "!protected:localhost",
+                "sender": "@bob:localhost",
+                "state_key": "@bob:localhost",
+                "type": "m.room.member"
+            }]
+        })))
+        .mount(&server)
+        .await;
+
+    let moderation = client.moderation();
+    let policy_list = moderation.policy_list([policy_room_id.to_owned()]).await.unwrap();
+    assert_eq!(policy_list.server_rules.len(), 1);
+
+    // The rule matches the own server, so the users of the own server are not
+    // banned.
+    let report = moderation
+        .enforce_policy_list(&policy_list, &[protected_room_id.to_owned()], true)
+        .await
+        .unwrap();
+    assert!(report.applied.is_empty());
+}
@@ -0,0 +1,203 @@
+#![cfg(feature = "sled")]
+
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use matrix_sdk::{
+    client_manager::ClientManager,
+    config::{RequestConfig, SyncSettings},
+    Client, ClientBuilder,
+};
+use matrix_sdk_test::async_test;
+use ruma::{api::MatrixVersion, user_id};
+use serde_json::json;
+use wiremock::{
+    matchers::{body_partial_json, header, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+fn client_builder() -> ClientBuilder {
+    Client::builder()
+        .server_versions([MatrixVersion::V1_0])
+        .request_config(RequestConfig::new().disable_retry())
+}
+
+/// Mount the login endpoint for a user with the given localpart.
+async fn mock_login(server: &MockServer, localpart: &str) {
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/login"))
+        .and(body_partial_json(json!({ "identifier": { "user": localpart } })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "user_id": format!("@{localpart}:localhost"),
+            "access_token": format!("{localpart}_token"),
+            "device_id": format!("{}DEVICE", localpart.to_uppercase()),
+        })))
+        .mount(server)
+        .await;
+}
+
+async fn add_account(manager: &ClientManager, server: &MockServer, localpart: &str) -> Client {
+    manager
+        .add_account(&server.uri(), |client| async move {
+            client.login_username(localpart, "wordpass").send().await
+        })
+        .await
+        .unwrap()
+}
+
+#[async_test]
+async fn accounts_are_restored() {
+    let server = MockServer::start().await;
+    mock_login(&server, "alice").await;
+    mock_login(&server, "bob").await;
+    let dir = tempfile::tempdir().unwrap();
+
+    let manager = ClientManager::builder(dir.path())
+        .passphrase("secret")
+        .client_builder(client_builder())
+        .build()
+        .await
+        .unwrap();
+    add_account(&manager, &server, "alice").await;
+    add_account(&manager, &server, "bob").await;
+    assert_eq!(manager.user_ids().await.len(), 2);
+    drop(manager);
+    // Let the aborted tasks of the manager drop their clients, to close the stores.
+    tokio::task::yield_now().await;
+
+    let manager = ClientManager::builder(dir.path())
+        .passphrase("secret")
+        .client_builder(client_builder())
+        .build()
+        .await
+        .unwrap();
+
+    let alice = manager.client(user_id!("@alice:localhost")).await.unwrap();
+    assert_eq!(alice.access_token().as_deref(), Some("alice_token"));
+    assert_eq!(alice.device_id().unwrap(), "ALICEDEVICE");
+    let bob = manager.client(user_id!("@bob:localhost")).await.unwrap();
+    assert_eq!(bob.access_token().as_deref(), Some("bob_token"));
+    assert_eq!(bob.homeserver().await.as_str(), format!("{}/", server.uri()));
+}
+
+#[async_test]
+async fn removed_account_is_forgotten() {
+    let server = MockServer::start().await;
+    mock_login(&server, "alice").await;
+    mock_login(&server, "bob").await;
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/logout"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let dir = tempfile::tempdir().unwrap();
+
+    let manager =
+        ClientManager::builder(dir.path()).client_builder(client_builder()).build().await.unwrap();
+    add_account(&manager, &server, "alice").await;
+    add_account(&manager, &server, "bob").await;
+    assert!(dir.path().join("account-0").exists());
+
+    manager.remove_account(user_id!("@alice:localhost"), true).await.unwrap();
+    assert!(!dir.path().join("account-0").exists());
+    drop(manager);
+    // Let the aborted tasks of the manager drop their clients, to close the stores.
+    tokio::task::yield_now().await;
+
+    let manager =
+        ClientManager::builder(dir.path()).client_builder(client_builder()).build().await.unwrap();
+    assert_eq!(manager.user_ids().await, [user_id!("@bob:localhost").to_owned()]);
+
+    // The store of the removed account can be reused.
+    add_account(&manager, &server, "alice").await;
+    assert!(dir.path().join("account-0").exists());
+}
+
+#[async_test]
+async fn duplicate_account_is_refused() {
+    let server = MockServer::start().await;
+    mock_login(&server, "alice").await;
+    // The duplicate session is logged out.
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/logout"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+    let dir = tempfile::tempdir().unwrap();
+
+    let manager =
+        ClientManager::builder(dir.path()).client_builder(client_builder()).build().await.unwrap();
+    add_account(&manager, &server, "alice").await;
+
+    let result = manager
+        .add_account(&server.uri(), |client| async move {
+            client.login_username("alice", "wordpass").send().await
+        })
+        .await;
+
+    assert!(result.is_err());
+    assert!(!dir.path().join("account-1").exists());
+}
+
+#[async_test]
+async fn unread_counts_are_aggregated() {
+    let server = MockServer::start().await;
+    mock_login(&server, "alice").await;
+    mock_login(&server, "bob").await;
+
+    let accounts = [("alice_token", "!a:localhost", 2), ("bob_token", "!b:localhost", 3)];
+    for (token, room_id, count) in accounts {
+        Mock::given(method("GET"))
+            .and(path("/_matrix/client/r0/sync"))
+            .and(header("authorization", format!("Bearer {token}").as_str()))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(json!({
+                        "next_batch": "s1",
+                        "rooms": {
+                            "join": {
+                                room_id: {
+                                    "unread_notifications": {
+                                        "highlight_count": 1,
+                                        "notification_count": count,
+                                    },
+                                },
+                            },
+                        },
+                    }))
+                    .set_delay(Duration::from_millis(50)),
+            )
+            .mount(&server)
+            .await;
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let manager =
+        ClientManager::builder(dir.path()).client_builder(client_builder()).build().await.unwrap();
+    add_account(&manager, &server, "alice").await;
+    add_account(&manager, &server, "bob").await;
+
+    let counts = manager.unread_counts_stream();
+    futures_util::pin_mut!(counts);
+    manager.start_sync(SyncSettings::new()).await;
+
+    let total = tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(counts) = counts.next().await {
+            let total = counts.total();
+            if total.notification_count == 5 {
+                return total;
+            }
+        }
+        panic!("The stream ended");
+    })
+    .await
+    .unwrap();
+    manager.stop_sync().await;
+
+    assert_eq!(total.highlight_count, 2);
+    let counts = manager.unread_counts().await;
+    assert_eq!(counts.accounts[user_id!("@alice:localhost")].notification_count, 2);
+    assert_eq!(counts.accounts[user_id!("@bob:localhost")].notification_count, 3);
+}
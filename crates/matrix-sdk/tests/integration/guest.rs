@@ -0,0 +1,235 @@
+use assert_matches::assert_matches;
+use matrix_sdk::{
+    config::{RequestConfig, StoreConfig, SyncSettings},
+    ruma::api::client::{account::register, room::create_room},
+    Client, Error, Session,
+};
+use matrix_sdk_base::store::MemoryStore;
+use matrix_sdk_test::{async_test, EventBuilder, LeftRoomBuilder, StateTestEvent};
+use ruma::{assign, device_id, room_id, user_id};
+use serde_json::json;
+use wiremock::{
+    matchers::{body_partial_json, header, method, path, path_regex, query_param},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use crate::{logged_in_client, mock_sync, no_retry_test_client, test_client_builder};
+
+/// Register a guest account, with the access token `1234`.
+async fn guest_client() -> (Client, MockServer) {
+    let (client, server) = no_retry_test_client().await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/register"))
+        .and(query_param("kind", "guest"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "user_id": "@guest:localhost",
+            "access_token": "1234",
+            "device_id": "GUESTDEVICE",
+        })))
+        .expect(1)
+        .named("register_guest")
+        .mount(&server)
+        .await;
+
+    client.register_guest().await.unwrap();
+
+    (client, server)
+}
+
+#[async_test]
+async fn register_guest() {
+    let (client, _server) = guest_client().await;
+
+    assert!(client.is_guest());
+    assert_eq!(client.user_id().unwrap(), "@guest:localhost");
+    assert_eq!(client.device_id().unwrap(), "GUESTDEVICE");
+    assert_eq!(client.access_token().as_deref(), Some("1234"));
+}
+
+#[async_test]
+async fn guest_room_restrictions() {
+    let (client, server) = guest_client().await;
+
+    // Guests can't create rooms.
+    let error = client.create_room(create_room::v3::Request::new()).await.unwrap_err();
+    assert_matches!(error, Error::GuestAccessForbidden);
+
+    let guest_access = |guest_access: &str| {
+        StateTestEvent::Custom(json!({
+            "content": { "guest_access": guest_access },
+            "event_id": format!("$guest_access_{guest_access}"),
+            "origin_server_ts": 1,
+            "sender": "@example:localhost",
+            "state_key": "",
+            "type": "m.room.guest_access"
+        }))
+    };
+    let forbidden_room_id = room_id!("!forbidden:localhost");
+    let open_room_id = room_id!("!open:localhost");
+    let mut ev_builder = EventBuilder::new();
+    ev_builder
+        .add_left_room(
+            LeftRoomBuilder::new(forbidden_room_id).add_state_event(guest_access("forbidden")),
+        )
+        .add_left_room(LeftRoomBuilder::new(open_room_id));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    // The room forbids guests from joining it.
+    let error = client.join_room_by_id(forbidden_room_id).await.unwrap_err();
+    assert_matches!(error, Error::GuestAccessForbidden);
+    let room = client.get_left_room(forbidden_room_id).unwrap();
+    assert_matches!(room.join().await, Err(Error::GuestAccessForbidden));
+
+    // The homeserver decides for rooms without guest access and unknown rooms.
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/join"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "room_id": "!support:localhost" })),
+        )
+        .expect(2)
+        .mount(&server)
+        .await;
+    client.join_room_by_id(open_room_id).await.unwrap();
+    client.join_room_by_id(room_id!("!support:localhost")).await.unwrap();
+}
+
+#[async_test]
+async fn guest_flag_is_persisted() {
+    let (builder, server) = test_client_builder().await;
+    let store = MemoryStore::new();
+    let persisting_client = || {
+        builder
+            .clone()
+            .request_config(RequestConfig::new().disable_retry())
+            .store_config(StoreConfig::new().state_store(store.clone()))
+            .persist_session()
+            .build()
+    };
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/register"))
+        .and(query_param("kind", "guest"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "user_id": "@guest:localhost",
+            "access_token": "1234",
+            "device_id": "GUESTDEVICE",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/logout"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = persisting_client().await.unwrap();
+    client.register_guest().await.unwrap();
+
+    let restored = persisting_client().await.unwrap();
+    assert!(restored.restore_from_store().await.unwrap());
+    assert!(restored.is_guest());
+
+    // The guest flag is forgotten with the session.
+    restored.logout().await.unwrap();
+    let client = persisting_client().await.unwrap();
+    client
+        .restore_session(Session {
+            access_token: "5678".to_owned(),
+            refresh_token: None,
+            user_id: user_id!("@example:localhost").to_owned(),
+            device_id: device_id!("DEVICEID").to_owned(),
+        })
+        .await
+        .unwrap();
+    let restored = persisting_client().await.unwrap();
+    assert!(restored.restore_from_store().await.unwrap());
+    assert!(!restored.is_guest());
+}
+
+#[async_test]
+async fn upgrade_guest() {
+    let (client, server) = guest_client().await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/register"))
+        .and(header("authorization", "Bearer 1234"))
+        .and(body_partial_json(json!({
+            "username": "guest",
+            "device_id": "GUESTDEVICE",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "user_id": "@guest:localhost",
+            "access_token": "5678",
+            "device_id": "GUESTDEVICE",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let request = assign!(register::v3::Request::new(), {
+        username: Some("guest".to_owned()),
+        password: Some("wordpass".to_owned()),
+    });
+    client.upgrade_guest(request).await.unwrap();
+
+    assert!(!client.is_guest());
+    assert_eq!(client.user_id().unwrap(), "@guest:localhost");
+    assert_eq!(client.device_id().unwrap(), "GUESTDEVICE");
+    assert_eq!(client.access_token().as_deref(), Some("5678"));
+
+    // The account can't be upgraded twice.
+    let error = client.upgrade_guest(register::v3::Request::new()).await.unwrap_err();
+    assert_matches!(error, Error::NotGuest);
+}
+
+#[async_test]
+async fn upgrade_guest_with_another_device() {
+    let (client, server) = guest_client().await;
+
+    // A different device ID is refused before sending the request.
+    let request = assign!(register::v3::Request::new(), {
+        device_id: Some(device_id!("OTHERDEVICE").to_owned()),
+    });
+    let error = client.upgrade_guest(request).await.unwrap_err();
+    assert_matches!(error, Error::SessionMismatch);
+    assert!(client.is_guest());
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/register"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "user_id": "@guest:localhost",
+            "access_token": "5678",
+            "device_id": "NEWDEVICE",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    // The session of the new device is logged out, with its own access token.
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/logout"))
+        .and(header("authorization", "Bearer 5678"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    client.upgrade_guest(register::v3::Request::new()).await.unwrap();
+
+    // The account is upgraded, and keeps the session of the guest.
+    assert!(!client.is_guest());
+    assert_eq!(client.device_id().unwrap(), "GUESTDEVICE");
+    assert_eq!(client.access_token().as_deref(), Some("1234"));
+}
+
+#[async_test]
+async fn upgrade_requires_a_guest() {
+    let (client, _server) = logged_in_client().await;
+
+    let error = client.upgrade_guest(register::v3::Request::new()).await.unwrap_err();
+    assert_matches!(error, Error::NotGuest);
+}
@@ -0,0 +1,131 @@
+use http::{HeaderName, HeaderValue, StatusCode};
+use matrix_sdk::{
+    config::RequestConfig,
+    http_layers::{Fault, FaultInjectionLayer, HeadersLayer, MetricsLayer, RecorderLayer},
+    Client, Session,
+};
+use matrix_sdk_test::{async_test, test_json};
+use ruma::{device_id, user_id};
+use wiremock::{
+    matchers::{header, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use crate::test_client_builder;
+
+async fn client_with_layers(
+    add_layers: impl FnOnce(matrix_sdk::ClientBuilder) -> matrix_sdk::ClientBuilder,
+) -> (Client, MockServer) {
+    let (builder, server) = test_client_builder().await;
+    let client = add_layers(builder.request_config(RequestConfig::new().disable_retry()))
+        .build()
+        .await
+        .unwrap();
+    client
+        .restore_session(Session {
+            access_token: "1234".to_owned(),
+            refresh_token: None,
+            user_id: user_id!("@example:localhost").to_owned(),
+            device_id: device_id!("DEVICEID").to_owned(),
+        })
+        .await
+        .unwrap();
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/account/whoami"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::WHOAMI))
+        .mount(&server)
+        .await;
+
+    (client, server)
+}
+
+#[async_test]
+async fn metrics_layer() {
+    let metrics = MetricsLayer::new();
+    let (client, _server) = client_with_layers(|builder| builder.http_layer(metrics.clone())).await;
+
+    client.whoami().await.unwrap();
+    client.whoami().await.unwrap();
+
+    let metrics = metrics.metrics();
+    let whoami = &metrics["GET /_matrix/client/v3/account/whoami"];
+    assert_eq!(whoami.requests, 2);
+    assert_eq!(whoami.failures, 0);
+    assert_eq!(whoami.statuses.get(&200), Some(&2));
+    assert!(whoami.bytes_received > 0);
+}
+
+#[async_test]
+async fn headers_layer() {
+    let (client, server) = client_with_layers(|builder| {
+        builder.http_layer(
+            HeadersLayer::new()
+                .header(HeaderName::from_static("x-app-name"), HeaderValue::from_static("my-app")),
+        )
+    })
+    .await;
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/devices"))
+        .and(header("x-app-name", "my-app"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::DEVICES))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    client.devices().await.unwrap();
+}
+
+#[async_test]
+async fn recorder_layer_redacts_the_access_token() {
+    let recorder = RecorderLayer::new();
+    let (client, _server) =
+        client_with_layers(|builder| builder.http_layer(recorder.clone())).await;
+
+    client.whoami().await.unwrap();
+
+    let requests = recorder.requests();
+    assert_eq!(requests.len(), 1);
+    let request = &requests[0];
+    assert_eq!(request.endpoint, "GET /_matrix/client/v3/account/whoami");
+    assert_eq!(request.method, http::Method::GET);
+    assert_eq!(request.uri, "/_matrix/client/r0/account/whoami");
+    assert_eq!(request.headers["authorization"], "<redacted>");
+    assert_eq!(request.status, Some(StatusCode::OK));
+
+    recorder.clear();
+    assert!(recorder.requests().is_empty());
+}
+
+#[async_test]
+async fn fault_injection_layer() {
+    let faults = FaultInjectionLayer::new()
+        .fault(Fault::status(StatusCode::SERVICE_UNAVAILABLE).path("/whoami").times(1));
+    let recorder = RecorderLayer::new();
+    let (client, server) = client_with_layers(|builder| {
+        builder.http_layer(recorder.clone()).http_layer(faults.clone())
+    })
+    .await;
+
+    // The fault is injected without sending the request.
+    client.whoami().await.unwrap_err();
+    assert!(server.received_requests().await.unwrap().is_empty());
+
+    // The fault was only injected once.
+    client.whoami().await.unwrap();
+    assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+    // The outer layer saw the injected fault.
+    let statuses: Vec<_> = recorder.requests().into_iter().map(|r| r.status).collect();
+    assert_eq!(statuses, [Some(StatusCode::SERVICE_UNAVAILABLE), Some(StatusCode::OK)]);
+
+    faults.add_fault(Fault::status(StatusCode::NOT_FOUND));
+    client.whoami().await.unwrap_err();
+    faults.clear();
+    client.whoami().await.unwrap();
+
+    // A fault that should be injected 0 times is never injected.
+    faults.add_fault(Fault::status(StatusCode::NOT_FOUND).times(0));
+    client.whoami().await.unwrap();
+}
@@ -14,8 +14,17 @@ use wiremock::{
 };
 
 mod client;
+mod client_manager;
+mod guest;
+mod http_layers;
+mod notification_settings;
+mod oidc;
 mod refresh_token;
+mod rendezvous;
 mod room;
+mod soft_logout;
+mod sync_service;
+mod uiaa;
 
 #[cfg(all(test, not(target_arch = "wasm32")))]
 #[ctor::ctor]
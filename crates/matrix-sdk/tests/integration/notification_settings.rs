@@ -0,0 +1,137 @@
+use assert_matches::assert_matches;
+use matrix_sdk::{
+    config::SyncSettings,
+    notification_settings::{NotificationSettingsError, RoomNotificationMode},
+    Error,
+};
+use matrix_sdk_test::{async_test, EventBuilder, GlobalAccountDataTestEvent};
+use ruma::room_id;
+use serde_json::json;
+use wiremock::{
+    matchers::{method, path_regex},
+    Mock, ResponseTemplate,
+};
+
+use crate::{logged_in_client, mock_sync};
+
+#[async_test]
+async fn room_notification_mode() {
+    let (client, server) = logged_in_client().await;
+    let room_id = room_id!("!room:localhost");
+
+    let settings = client.notification_settings().await.unwrap();
+    assert_eq!(settings.user_defined_room_notification_mode(room_id).await, None);
+
+    let mut changes = settings.subscribe_to_changes();
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder.add_global_account_data_event(GlobalAccountDataTestEvent::Custom(json!({
+        "content": {
+            "global": {
+                "content": [],
+                "override": [],
+                "room": [{
+                    "actions": [],
+                    "default": false,
+                    "enabled": true,
+                    "rule_id": "!room:localhost"
+                }],
+                "sender": [],
+                "underride": []
+            }
+        },
+        "type": "m.push_rules"
+    })));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    changes.recv().await.unwrap();
+    assert_eq!(
+        settings.room_notification_mode(room_id, false, false).await,
+        RoomNotificationMode::MentionsAndKeywordsOnly
+    );
+
+    Mock::given(method("DELETE"))
+        .and(path_regex(r"/pushrules/global/room/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"/pushrules/global/override/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    settings.set_room_notification_mode(room_id, RoomNotificationMode::Mute).await.unwrap();
+    assert_eq!(
+        settings.user_defined_room_notification_mode(room_id).await,
+        Some(RoomNotificationMode::Mute)
+    );
+}
+
+#[async_test]
+async fn default_mode_and_keywords() {
+    let (client, server) = logged_in_client().await;
+    let settings = client.notification_settings().await.unwrap();
+
+    // The server-default push rules notify for all messages.
+    assert_eq!(
+        settings.default_room_notification_mode(true, false).await,
+        RoomNotificationMode::AllMessages
+    );
+
+    let result =
+        settings.set_default_room_notification_mode(true, false, RoomNotificationMode::Mute).await;
+    assert_matches!(
+        result,
+        Err(Error::NotificationSettings(NotificationSettingsError::InvalidDefaultMode))
+    );
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"/pushrules/global/content/rust$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("DELETE"))
+        .and(path_regex(r"/pushrules/global/content/rust$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    settings.add_keyword("rust".to_owned()).await.unwrap();
+    assert_eq!(settings.keywords().await, ["rust"]);
+
+    settings.remove_keyword("rust").await.unwrap();
+    assert!(settings.keywords().await.is_empty());
+}
+
+#[async_test]
+async fn failed_change_is_rolled_back() {
+    let (client, server) = logged_in_client().await;
+    let room_id = room_id!("!room:localhost");
+    let settings = client.notification_settings().await.unwrap();
+    let mut changes = settings.subscribe_to_changes();
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"/pushrules/global/override/"))
+        .respond_with(ResponseTemplate::new(500).set_body_json(json!({
+            "errcode": "M_UNKNOWN",
+            "error": "Something went wrong",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    settings.set_room_notification_mode(room_id, RoomNotificationMode::Mute).await.unwrap_err();
+
+    // The change was applied before the request was sent, then rolled back.
+    changes.recv().await.unwrap();
+    changes.recv().await.unwrap();
+    assert_eq!(settings.user_defined_room_notification_mode(room_id).await, None);
+}
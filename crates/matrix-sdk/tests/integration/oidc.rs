@@ -0,0 +1,262 @@
+#![cfg(feature = "experimental-oidc")]
+
+use assert_matches::assert_matches;
+use matrix_sdk::{
+    config::{RequestConfig, StoreConfig},
+    oidc::{AccountManagementAction, ClientMetadata, ClientRegistration, OidcError, OidcSession},
+    Session,
+};
+use matrix_sdk_base::store::MemoryStore;
+use matrix_sdk_test::async_test;
+use ruma::{device_id, user_id};
+use serde_json::json;
+use url::Url;
+use wiremock::{
+    matchers::{body_partial_json, body_string_contains, header, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use crate::test_client_builder;
+
+/// Mount the discovery endpoint of a mock OpenID Connect provider and return
+/// its issuer.
+async fn mock_provider_metadata(server: &MockServer) -> String {
+    let issuer = format!("{}/oidc/", server.uri());
+
+    Mock::given(method("GET"))
+        .and(path("/oidc/.well-known/openid-configuration"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{issuer}authorize"),
+            "token_endpoint": format!("{issuer}token"),
+            "registration_endpoint": format!("{issuer}register"),
+            "revocation_endpoint": format!("{issuer}revoke"),
+            "account_management_uri": format!("{issuer}account"),
+            "account_management_actions_supported": ["org.matrix.profile"],
+            "response_types_supported": ["code"],
+            "code_challenge_methods_supported": ["S256"],
+        })))
+        .mount(server)
+        .await;
+
+    issuer
+}
+
+#[async_test]
+async fn oidc_login_refresh_and_logout() {
+    let (builder, server) = test_client_builder().await;
+    let client = builder
+        .request_config(RequestConfig::new().disable_retry())
+        .handle_refresh_tokens()
+        .build()
+        .await
+        .unwrap();
+    let oidc = client.oidc();
+    let issuer = mock_provider_metadata(&server).await;
+    let redirect_uri = Url::parse("http://127.0.0.1:1234/callback").unwrap();
+
+    // Registration.
+    Mock::given(method("POST"))
+        .and(path("/oidc/register"))
+        .and(body_partial_json(json!({
+            "redirect_uris": ["http://127.0.0.1:1234/callback"],
+            "token_endpoint_auth_method": "none",
+            "client_name": "Test App",
+        })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "client_id": "CLIENT" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let metadata =
+        ClientMetadata::new(Url::parse("https://example.org").unwrap(), vec![redirect_uri.clone()])
+            .client_name("Test App");
+    let registration = oidc.register_client(&issuer, metadata).await.unwrap();
+    assert_eq!(registration.client_id, "CLIENT");
+    assert_eq!(oidc.client_registration(), Some(registration));
+
+    // Authorization.
+    let data = oidc.url_for_login(&redirect_uri).await.unwrap();
+    assert!(data.url.as_str().starts_with(&format!("{issuer}authorize?")));
+    let query: Vec<_> = data.url.query_pairs().into_owned().collect();
+    let param = |name: &str| query.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str());
+    assert_eq!(param("client_id"), Some("CLIENT"));
+    assert_eq!(param("response_type"), Some("code"));
+    assert_eq!(param("code_challenge_method"), Some("S256"));
+    assert_eq!(param("state"), Some(data.state.as_str()));
+    assert!(param("scope").unwrap().contains("urn:matrix:org.matrix.msc2967.client:api:*"));
+
+    // A redirect for an unknown authorization is rejected.
+    let callback_url = Url::parse(&format!("{redirect_uri}?code=CODE&state=unknown")).unwrap();
+    assert_matches!(oidc.finish_login(&callback_url).await, Err(OidcError::InvalidState));
+
+    // Code exchange.
+    Mock::given(method("POST"))
+        .and(path("/oidc/token"))
+        .and(body_string_contains("grant_type=authorization_code"))
+        .and(body_string_contains("code=CODE"))
+        .and(body_string_contains("code_verifier="))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "AT1",
+            "refresh_token": "RT1",
+            "expires_in": 300,
+            "token_type": "Bearer",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/account/whoami"))
+        .and(header("authorization", "Bearer AT1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "user_id": "@example:localhost",
+            "device_id": "DEVICEID",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let callback_url =
+        Url::parse(&format!("{redirect_uri}?code=CODE&state={}", data.state)).unwrap();
+    oidc.finish_login(&callback_url).await.unwrap();
+
+    assert!(client.logged_in());
+    assert_eq!(client.user_id().unwrap(), "@example:localhost");
+    assert_eq!(client.device_id().unwrap(), "DEVICEID");
+    assert_eq!(client.access_token().as_deref(), Some("AT1"));
+    assert_eq!(client.refresh_token().as_deref(), Some("RT1"));
+    let session = oidc.full_session().unwrap();
+    assert_eq!(session.registration.client_id, "CLIENT");
+
+    // Refresh with the provider.
+    Mock::given(method("POST"))
+        .and(path("/oidc/token"))
+        .and(body_string_contains("grant_type=refresh_token"))
+        .and(body_string_contains("refresh_token=RT1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "AT2",
+            "refresh_token": "RT2",
+            "expires_in": 300,
+            "token_type": "Bearer",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    client.refresh_access_token().await.unwrap().unwrap();
+    assert_eq!(client.access_token().as_deref(), Some("AT2"));
+    assert_eq!(client.refresh_token().as_deref(), Some("RT2"));
+
+    // Account management.
+    let url = oidc.account_management_url(Some(AccountManagementAction::Profile)).await.unwrap();
+    assert_eq!(url.unwrap().as_str(), format!("{issuer}account?action=org.matrix.profile"));
+
+    // Logout.
+    Mock::given(method("POST"))
+        .and(path("/oidc/revoke"))
+        .and(body_string_contains("client_id=CLIENT"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    oidc.logout().await.unwrap();
+    assert!(oidc.full_session().is_none());
+    assert!(client.access_token().is_none());
+}
+
+#[async_test]
+async fn oidc_authorization_denied() {
+    let (builder, server) = test_client_builder().await;
+    let client =
+        builder.request_config(RequestConfig::new().disable_retry()).build().await.unwrap();
+    let oidc = client.oidc();
+    let issuer = mock_provider_metadata(&server).await;
+    let redirect_uri = Url::parse("http://127.0.0.1:1234/callback").unwrap();
+
+    Mock::given(method("POST"))
+        .and(path("/oidc/register"))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({ "client_id": "CLIENT" })))
+        .mount(&server)
+        .await;
+
+    let metadata =
+        ClientMetadata::new(Url::parse("https://example.org").unwrap(), vec![redirect_uri.clone()]);
+    oidc.register_client(&issuer, metadata).await.unwrap();
+
+    let data = oidc.url_for_login(&redirect_uri).await.unwrap();
+    let callback_url =
+        Url::parse(&format!("{redirect_uri}?error=access_denied&state={}", data.state)).unwrap();
+
+    assert_matches!(
+        oidc.finish_login(&callback_url).await,
+        Err(OidcError::Authorization { error, .. }) => {
+            assert_eq!(error, "access_denied");
+        }
+    );
+    assert!(!client.logged_in());
+}
+
+#[async_test]
+async fn oidc_session_is_persisted() {
+    let (builder, server) = test_client_builder().await;
+    let store = MemoryStore::new();
+    let persisting_client = || {
+        builder
+            .clone()
+            .request_config(RequestConfig::new().disable_retry())
+            .store_config(StoreConfig::new().state_store(store.clone()))
+            .persist_session()
+            .build()
+    };
+    let issuer = mock_provider_metadata(&server).await;
+
+    let client = persisting_client().await.unwrap();
+    client
+        .oidc()
+        .restore_session(OidcSession {
+            registration: ClientRegistration { issuer, client_id: "CLIENT".to_owned() },
+            session: Session {
+                access_token: "AT1".to_owned(),
+                refresh_token: Some("RT1".to_owned()),
+                user_id: user_id!("@example:localhost").to_owned(),
+                device_id: device_id!("DEVICEID").to_owned(),
+            },
+        })
+        .await
+        .unwrap();
+
+    let restored = persisting_client().await.unwrap();
+    assert!(restored.restore_from_store().await.unwrap());
+    let session = restored.oidc().full_session().unwrap();
+    assert_eq!(session.registration.client_id, "CLIENT");
+
+    // The token is refreshed with the provider, not the homeserver.
+    Mock::given(method("POST"))
+        .and(path("/oidc/token"))
+        .and(body_string_contains("refresh_token=RT1"))
+        .and(body_string_contains("client_id=CLIENT"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "AT2",
+            "refresh_token": "RT2",
+            "token_type": "Bearer",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    restored.refresh_access_token().await.unwrap().unwrap();
+    assert_eq!(restored.access_token().as_deref(), Some("AT2"));
+
+    // The registration is forgotten with the session.
+    Mock::given(method("POST"))
+        .and(path("/oidc/revoke"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&server)
+        .await;
+
+    restored.oidc().logout().await.unwrap();
+    let client = persisting_client().await.unwrap();
+    assert!(!client.restore_from_store().await.unwrap());
+    assert!(client.oidc().full_session().is_none());
+}
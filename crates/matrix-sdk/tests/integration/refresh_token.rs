@@ -2,7 +2,12 @@ use std::time::Duration;
 
 use assert_matches::assert_matches;
 use futures::{channel::mpsc, StreamExt};
-use matrix_sdk::{config::RequestConfig, executor::spawn, HttpError, RefreshTokenError, Session};
+use matrix_sdk::{
+    config::{RequestConfig, StoreConfig},
+    executor::spawn,
+    ClientBuilder, HttpError, RefreshTokenError, Session,
+};
+use matrix_sdk_base::store::MemoryStore;
 use matrix_sdk_test::{async_test, test_json};
 use ruma::{
     api::{
@@ -533,3 +538,80 @@ async fn refresh_token_handled_other_error() {
 
     client.whoami().await.unwrap_err();
 }
+
+fn persisting_client_builder(builder: ClientBuilder, store: &MemoryStore) -> ClientBuilder {
+    builder
+        .request_config(RequestConfig::new().disable_retry())
+        .server_versions([MatrixVersion::V1_3])
+        .store_config(StoreConfig::new().state_store(store.clone()))
+        .persist_session()
+}
+
+#[async_test]
+async fn persisted_session_is_restored() {
+    let (builder, server) = test_client_builder().await;
+    let store = MemoryStore::new();
+    let client = persisting_client_builder(builder.clone(), &store).build().await.unwrap();
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/v3/login"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(&*test_json::LOGIN_WITH_REFRESH_TOKEN),
+        )
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/v3/logout"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .mount(&server)
+        .await;
+
+    client.login_username("example", "wordpass").request_refresh_token().send().await.unwrap();
+
+    let restored = persisting_client_builder(builder.clone(), &store).build().await.unwrap();
+    assert!(restored.restore_from_store().await.unwrap());
+    assert_eq!(restored.session(), client.session());
+    assert_eq!(restored.refresh_token().as_deref(), Some("zyx987"));
+
+    // The session is forgotten after logging out.
+    restored.logout().await.unwrap();
+    let client = persisting_client_builder(builder, &store).build().await.unwrap();
+    assert!(!client.restore_from_store().await.unwrap());
+    assert!(!client.logged_in());
+}
+
+#[async_test]
+async fn refreshed_tokens_are_persisted() {
+    let (builder, server) = test_client_builder().await;
+    let store = MemoryStore::new();
+    let client = persisting_client_builder(builder.clone(), &store).build().await.unwrap();
+
+    let session = Session {
+        access_token: "1234".to_owned(),
+        refresh_token: Some("abcd".to_owned()),
+        user_id: user_id!("@example:localhost").to_owned(),
+        device_id: device_id!("DEVICEID").to_owned(),
+    };
+    client.restore_session(session).await.unwrap();
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/v3/refresh"))
+        .and(body_partial_json(json!({
+            "refresh_token": "abcd",
+        })))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(&*test_json::REFRESH_TOKEN_WITH_REFRESH_TOKEN),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    client.refresh_access_token().await.unwrap().unwrap();
+
+    let restored = persisting_client_builder(builder, &store).build().await.unwrap();
+    assert!(restored.restore_from_store().await.unwrap());
+    let tokens = restored.session_tokens().unwrap();
+    assert_eq!(tokens.access_token, "9012");
+    assert_eq!(tokens.refresh_token.as_deref(), Some("wxyz"));
+    assert_eq!(restored.user_id().unwrap(), "@example:localhost");
+}
@@ -0,0 +1,295 @@
+#![cfg(feature = "experimental-qr-login")]
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use assert_matches::assert_matches;
+use matrix_sdk::{
+    config::{RequestConfig, SyncSettings},
+    rendezvous::RendezvousError,
+    Client, Session,
+};
+use matrix_sdk_test::async_test;
+use ruma::{api::MatrixVersion, device_id, user_id};
+use serde_json::{json, Value};
+use url::Url;
+use wiremock::{
+    matchers::{body_partial_json, method, path, path_regex},
+    Mock, MockServer, Request, ResponseTemplate,
+};
+
+use crate::mock_sync;
+
+/// Mount a stand-in for an MSC3886 rendezvous server on the given server, and
+/// return its URL.
+///
+/// Conditional requests are not supported, clients have to compare the ETags
+/// themselves.
+async fn mock_rendezvous_server(server: &MockServer) -> Url {
+    let sessions = Arc::new(Mutex::new(BTreeMap::<String, (u32, Vec<u8>)>::new()));
+
+    Mock::given(method("POST"))
+        .and(path("/rendezvous"))
+        .respond_with({
+            let sessions = sessions.clone();
+            move |_: &Request| {
+                let mut sessions = sessions.lock().unwrap();
+                let id = format!("session{}", sessions.len());
+                let location = format!("/rendezvous/{id}");
+                sessions.insert(id, (1, Vec::new()));
+
+                ResponseTemplate::new(201)
+                    .insert_header("location", location.as_str())
+                    .insert_header("etag", "1")
+            }
+        })
+        .mount(server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex("^/rendezvous/"))
+        .respond_with({
+            let sessions = sessions.clone();
+            move |request: &Request| {
+                let id = request.url.path().trim_start_matches("/rendezvous/");
+                let mut sessions = sessions.lock().unwrap();
+                let Some((etag, body)) = sessions.get_mut(id) else {
+                    return ResponseTemplate::new(404);
+                };
+                *etag += 1;
+                *body = request.body.clone();
+
+                ResponseTemplate::new(202).insert_header("etag", etag.to_string().as_str())
+            }
+        })
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex("^/rendezvous/"))
+        .respond_with(move |request: &Request| {
+            let id = request.url.path().trim_start_matches("/rendezvous/");
+            let sessions = sessions.lock().unwrap();
+            let Some((etag, body)) = sessions.get(id) else {
+                return ResponseTemplate::new(404);
+            };
+
+            ResponseTemplate::new(200)
+                .insert_header("etag", etag.to_string().as_str())
+                .set_body_bytes(body.clone())
+        })
+        .mount(server)
+        .await;
+
+    Url::parse(&format!("{}/rendezvous", server.uri())).unwrap()
+}
+
+/// Mount the endpoints to upload and query keys, serving the keys that were
+/// uploaded by all the devices of `@example:localhost`.
+async fn mock_keys_server(server: &MockServer) {
+    let keys = Arc::new(Mutex::new(json!({
+        "device_keys": { "@example:localhost": {} },
+        "master_keys": {},
+        "self_signing_keys": {},
+        "user_signing_keys": {},
+        "failures": {},
+    })));
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"/keys/upload$"))
+        .respond_with({
+            let keys = keys.clone();
+            move |request: &Request| {
+                let body: Value = serde_json::from_slice(&request.body).unwrap();
+                if let Some(device_keys) = body.get("device_keys") {
+                    let device_id = device_keys["device_id"].as_str().unwrap();
+                    keys.lock().unwrap()["device_keys"]["@example:localhost"][device_id] =
+                        device_keys.clone();
+                }
+
+                ResponseTemplate::new(200).set_body_json(json!({
+                    "one_time_key_counts": { "signed_curve25519": 50 },
+                }))
+            }
+        })
+        .mount(server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"/keys/device_signing/upload$"))
+        .respond_with({
+            let keys = keys.clone();
+            move |request: &Request| {
+                let body: Value = serde_json::from_slice(&request.body).unwrap();
+                let mut keys = keys.lock().unwrap();
+                for (field, map) in [
+                    ("master_key", "master_keys"),
+                    ("self_signing_key", "self_signing_keys"),
+                    ("user_signing_key", "user_signing_keys"),
+                ] {
+                    keys[map]["@example:localhost"] = body[field].clone();
+                }
+
+                ResponseTemplate::new(200).set_body_json(json!({}))
+            }
+        })
+        .mount(server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"/keys/signatures/upload$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "failures": {} })))
+        .mount(server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"/keys/query$"))
+        .respond_with(move |_: &Request| {
+            ResponseTemplate::new(200).set_body_json(keys.lock().unwrap().clone())
+        })
+        .mount(server)
+        .await;
+}
+
+/// Create the existing device on the given server, with its device keys
+/// uploaded.
+async fn existing_device(server: &MockServer) -> Client {
+    let client = Client::builder()
+        .homeserver_url(server.uri())
+        .server_versions([MatrixVersion::V1_0])
+        .request_config(RequestConfig::new().disable_retry())
+        .build()
+        .await
+        .unwrap();
+    client
+        .restore_session(Session {
+            access_token: "1234".to_owned(),
+            refresh_token: None,
+            user_id: user_id!("@example:localhost").to_owned(),
+            device_id: device_id!("DEVICEID").to_owned(),
+        })
+        .await
+        .unwrap();
+
+    mock_sync(server, json!({ "next_batch": "s1" }), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    client
+}
+
+/// Create the new device, that doesn't know the homeserver yet.
+async fn new_device() -> Client {
+    Client::builder()
+        .homeserver_url("http://localhost:1")
+        .server_versions([MatrixVersion::V1_0])
+        .request_config(RequestConfig::new().disable_retry())
+        .build()
+        .await
+        .unwrap()
+}
+
+#[async_test]
+async fn login_with_qr_code() {
+    let server = MockServer::start().await;
+    let rendezvous_server = mock_rendezvous_server(&server).await;
+    mock_keys_server(&server).await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/unstable/org.matrix.msc3882/login/token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "login_token": "LOGIN_TOKEN",
+            "expires_in": 120_000,
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/login"))
+        .and(body_partial_json(json!({
+            "type": "m.login.token",
+            "token": "LOGIN_TOKEN",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "user_id": "@example:localhost",
+            "access_token": "5678",
+            "device_id": "NEWDEVICE",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let existing = existing_device(&server).await;
+    existing.encryption().bootstrap_cross_signing(None).await.unwrap();
+    let new = new_device().await;
+
+    let login = new.rendezvous().login_with_qr_code(&rendezvous_server).await.unwrap();
+    let data = login.qr_code_data();
+    assert!(data.rendezvous_url.starts_with(rendezvous_server.as_str()));
+
+    let (new_result, existing_result) = tokio::join!(
+        async {
+            let login = login.wait_for_scan().await?;
+            let check_code = login.check_code();
+            let outcome = login.finish(Some("New device")).await?;
+            Ok::<_, RendezvousError>((check_code, outcome))
+        },
+        async {
+            let mut grant = existing.rendezvous().grant_login(&data).await?;
+            let check_code = grant.check_code();
+            let device_id = grant.approve(None).await?;
+            Ok::<_, RendezvousError>((check_code, device_id))
+        },
+    );
+    let (new_check_code, outcome) = new_result.unwrap();
+    let (existing_check_code, device_id) = existing_result.unwrap();
+
+    assert_eq!(new_check_code, existing_check_code);
+    assert_eq!(device_id, "NEWDEVICE");
+
+    assert!(new.logged_in());
+    assert_eq!(new.homeserver().await.as_str(), format!("{}/", server.uri()));
+    assert_eq!(new.device_id().unwrap(), "NEWDEVICE");
+
+    let status = outcome.cross_signing_status.unwrap();
+    assert!(status.has_master);
+    assert!(status.has_self_signing);
+    assert!(status.has_user_signing);
+
+    let verifying_device = new
+        .encryption()
+        .get_device(user_id!("@example:localhost"), device_id!("DEVICEID"))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(verifying_device.is_locally_trusted());
+}
+
+#[async_test]
+async fn login_with_qr_code_declined() {
+    let server = MockServer::start().await;
+    let rendezvous_server = mock_rendezvous_server(&server).await;
+    mock_keys_server(&server).await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/login"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let existing = existing_device(&server).await;
+    let new = new_device().await;
+
+    let login = new.rendezvous().login_with_qr_code(&rendezvous_server).await.unwrap();
+    let data = login.qr_code_data();
+
+    let new_login = async { login.wait_for_scan().await?.finish(None).await };
+    let existing_login = async { existing.rendezvous().grant_login(&data).await?.decline().await };
+    let (new_result, existing_result) = tokio::join!(new_login, existing_login);
+
+    existing_result.unwrap();
+    assert_matches!(new_result, Err(RendezvousError::Declined));
+    assert!(!new.logged_in());
+}
@@ -1,26 +1,30 @@
 use std::time::Duration;
 
 use assert_matches::assert_matches;
-use matrix_sdk::{config::SyncSettings, room::RoomMember, DisplayName};
+use matrix_sdk::{
+    config::SyncSettings,
+    room::{ExportFormat, ExportOptions, RoomMember},
+    DisplayName, Session,
+};
 use matrix_sdk_test::{
     async_test, bulk_room_members, test_json, EventBuilder, JoinedRoomBuilder, StateTestEvent,
     TimelineTestEvent,
 };
 use ruma::{
-    event_id,
+    device_id, event_id,
     events::{
         room::member::MembershipState, AnyStateEvent, AnySyncStateEvent, AnyTimelineEvent,
         StateEventType,
     },
-    room_id,
+    room_id, user_id,
 };
 use serde_json::json;
 use wiremock::{
-    matchers::{header, method, path_regex},
+    matchers::{header, method, path_regex, query_param},
     Mock, ResponseTemplate,
 };
 
-use crate::{logged_in_client, mock_sync};
+use crate::{logged_in_client, mock_sync, test_client_builder};
 
 #[async_test]
 async fn user_presence() {
@@ -551,3 +555,140 @@ async fn event() {
     assert!(timeline_event.push_actions.iter().any(|a| a.is_highlight()));
     assert!(timeline_event.push_actions.iter().any(|a| a.should_notify()));
 }
+
+#[async_test]
+async fn successor_and_predecessor() {
+    let (client, server) = logged_in_client().await;
+    let room_id = room_id!("!current:localhost");
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(room_id)
+            .add_state_event(StateTestEvent::Custom(json!({
+                "content": {
+                    "creator": "@example:localhost",
+                    "room_version": "10",
+                    "predecessor": {
+                        "room_id": "!previous:localhost",
+                        "event_id": "$tombstone:localhost"
+                    }
+                },
+                "event_id": "$create:localhost",
+                "origin_server_ts": 151957878,
+                "sender": "@example:localhost",
+                "state_key": "",
+                "type": "m.room.create"
+            })))
+            .add_state_event(StateTestEvent::Custom(json!({
+                "content": {
+                    "body": "This room has been replaced",
+                    "replacement_room": "!next:localhost"
+                },
+                "event_id": "$next_tombstone:localhost",
+                "origin_server_ts": 151957879,
+                "sender": "@example:localhost",
+                "state_key": "",
+                "type": "m.room.tombstone"
+            }))),
+    );
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let room = client.get_room(room_id).unwrap();
+    assert_eq!(room.predecessor().unwrap().as_str(), "!previous:localhost");
+    assert_eq!(room.successor().unwrap().as_str(), "!next:localhost");
+}
+
+#[async_test]
+async fn auto_join_room_upgrades() {
+    let (builder, server) = test_client_builder().await;
+    let client = builder.auto_join_room_upgrades().build().await.unwrap();
+    client
+        .restore_session(Session {
+            access_token: "1234".to_owned(),
+            refresh_token: None,
+            user_id: user_id!("@example:localhost").to_owned(),
+            device_id: device_id!("DEVICEID").to_owned(),
+        })
+        .await
+        .unwrap();
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/join/.*"))
+        .and(query_param("server_name", "localhost"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(json!({ "room_id": "!next:localhost" })),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut ev_builder = EventBuilder::new();
+    let room_id = room_id!("!current:localhost");
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id).add_timeline_event(
+        TimelineTestEvent::Custom(json!({
+            "content": {
+                "body": "This room has been replaced",
+                "replacement_room": "!next:localhost"
+            },
+            "event_id": "$tombstone:localhost",
+            "origin_server_ts": 151957879,
+            "sender": "@example:localhost",
+            "state_key": "",
+            "type": "m.room.tombstone"
+        })),
+    ));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    assert!(client.get_joined_room(room_id!("!next:localhost")).is_some());
+}
+
+#[async_test]
+async fn export_transcript() {
+    let (client, server) = logged_in_client().await;
+
+    mock_sync(&server, &*test_json::SYNC, None).await;
+    let _response = client.sync_once(SyncSettings::new()).await.unwrap();
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/members"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::MEMBERS))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/messages$"))
+        .and(query_param("dir", "f"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::ROOM_MESSAGES))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/messages$"))
+        .and(query_param("from", "t47409-4357353_219380_26003_2265"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "chunk": [],
+            "start": "t47409-4357353_219380_26003_2265",
+        })))
+        .mount(&server)
+        .await;
+
+    let room = client.get_joined_room(&test_json::DEFAULT_SYNC_ROOM_ID).unwrap();
+    let options = ExportOptions::new(ExportFormat::JsonLines);
+    let (transcript, progress) = room.export_transcript(Vec::new(), options).run().await.unwrap();
+
+    assert!(progress.finished);
+    assert_eq!(progress.exported_events, 3);
+
+    let lines: Vec<serde_json::Value> = String::from_utf8(transcript)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0]["event"]["content"]["body"], "hello world");
+    assert_eq!(lines[0]["sender_name"], "@alice:example.com");
+    // The events are not encrypted.
+    assert!(lines[0]["encryption_info"].is_null());
+    assert!(lines[0].get("original_event").is_none());
+}
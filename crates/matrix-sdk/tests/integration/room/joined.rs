@@ -1,20 +1,24 @@
 use std::time::Duration;
 
-use futures::future::join_all;
+use assert_matches::assert_matches;
+use futures::{future::join_all, pin_mut, StreamExt};
 use matrix_sdk::{
     attachment::{
         AttachmentConfig, AttachmentInfo, BaseImageInfo, BaseThumbnailInfo, BaseVideoInfo,
         Thumbnail,
     },
     config::SyncSettings,
-    room::Receipts,
+    room::{Receipts, RoomPowerLevelChanges},
+    Error,
+};
+use matrix_sdk_test::{
+    async_test, test_json, EphemeralTestEvent, EventBuilder, JoinedRoomBuilder, StateTestEvent,
 };
-use matrix_sdk_test::{async_test, test_json};
 use ruma::{
     api::client::{membership::Invite3pidInit, receipt::create_receipt::v3::ReceiptType},
     assign, event_id,
-    events::{receipt::ReceiptThread, room::message::RoomMessageEventContent},
-    mxc_uri, thirdparty, uint, user_id, TransactionId,
+    events::{receipt::ReceiptThread, room::message::RoomMessageEventContent, StateEventType},
+    int, mxc_uri, room_id, thirdparty, uint, user_id, RoomVersionId, TransactionId,
 };
 use serde_json::json;
 use wiremock::{
@@ -216,6 +220,91 @@ async fn typing_notice() {
     room.typing_notice(true).await.unwrap();
 }
 
+#[async_test]
+async fn typing_notifications() {
+    let (client, server) = logged_in_client().await;
+    let room_id = room_id!("!typing:localhost");
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(room_id).add_state_event(StateTestEvent::Member).add_state_event(
+            StateTestEvent::Custom(json!({
+                "content": { "membership": "join" },
+                "event_id": "$bob_join",
+                "origin_server_ts": 151800140,
+                "sender": "@bob:localhost",
+                "state_key": "@bob:localhost",
+                "type": "m.room.member"
+            })),
+        ),
+    );
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+    server.reset().await;
+
+    let room = client.get_joined_room(room_id).unwrap();
+    let typing = room.subscribe_to_typing_notifications();
+    pin_mut!(typing);
+
+    // The own user and users that aren't members are left out.
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id).add_ephemeral_event(
+        EphemeralTestEvent::Custom(json!({
+            "content": {
+                "user_ids": ["@example:localhost", "@bob:localhost", "@carol:localhost"]
+            },
+            "type": "m.typing"
+        })),
+    ));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+    server.reset().await;
+
+    let members = typing.next().await.unwrap();
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[0].user_id(), user_id!("@bob:localhost"));
+
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id).add_ephemeral_event(
+        EphemeralTestEvent::Custom(json!({
+            "content": { "user_ids": [] },
+            "type": "m.typing"
+        })),
+    ));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    assert!(typing.next().await.unwrap().is_empty());
+}
+
+#[async_test]
+async fn notify_typing() {
+    let (client, server) = synced_client().await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/typing"))
+        .and(body_partial_json(json!({ "typing": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/typing"))
+        .and(body_partial_json(json!({ "typing": false })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let room = client.get_joined_room(&test_json::DEFAULT_SYNC_ROOM_ID).unwrap();
+
+    // Keystrokes in quick succession only send one request.
+    room.notify_typing().await.unwrap();
+    room.notify_typing().await.unwrap();
+
+    // The end of the typing notice is sent once the user is idle.
+    tokio::time::sleep(Duration::from_secs(3)).await;
+}
+
 #[async_test]
 async fn room_state_event_send() {
     use ruma::events::room::member::{MembershipState, RoomMemberEventContent};
@@ -585,3 +674,346 @@ async fn set_name() {
 
     room.set_name(Some(name.to_owned())).await.unwrap();
 }
+
+#[async_test]
+async fn upgrade() {
+    let (client, server) = logged_in_client().await;
+    let room_id = room_id!("!old:localhost");
+    let space_id = room_id!("!space:localhost");
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id).add_state_event(
+        StateTestEvent::Custom(json!({
+            "content": { "via": ["localhost"], "canonical": true },
+            "event_id": "$parent:localhost",
+            "origin_server_ts": 151957878,
+            "sender": "@example:localhost",
+            "state_key": space_id,
+            "type": "m.space.parent"
+        })),
+    ));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/upgrade$"))
+        .and(body_json(json!({ "new_version": "10" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "replacement_room": "!new:localhost",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.space.parent/.*"))
+        .and(body_json(json!({ "via": ["localhost"], "canonical": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EVENT_ID))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let room = client.get_joined_room(room_id).unwrap();
+    let successor_id = room.upgrade(RoomVersionId::V10).await.unwrap();
+    assert_eq!(successor_id.as_str(), "!new:localhost");
+}
+
+#[async_test]
+async fn upgrade_with_failing_space_parent() {
+    let (client, server) = logged_in_client().await;
+    let room_id = room_id!("!old:localhost");
+    let space_id = room_id!("!space:localhost");
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id).add_state_event(
+        StateTestEvent::Custom(json!({
+            "content": { "via": ["localhost"] },
+            "event_id": "$parent:localhost",
+            "origin_server_ts": 151957878,
+            "sender": "@example:localhost",
+            "state_key": space_id,
+            "type": "m.space.parent"
+        })),
+    ));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/upgrade$"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "replacement_room": "!new:localhost",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.space.parent/.*"))
+        .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+            "errcode": "M_FORBIDDEN",
+            "error": "You don't have permission to send this event",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // The room was upgraded even if the space parent couldn't be copied.
+    let room = client.get_joined_room(room_id).unwrap();
+    let successor_id = room.upgrade(RoomVersionId::V10).await.unwrap();
+    assert_eq!(successor_id.as_str(), "!new:localhost");
+}
+
+#[async_test]
+async fn knock_requests() {
+    let (client, server) = synced_client().await;
+    let room = client.get_joined_room(&test_json::DEFAULT_SYNC_ROOM_ID).unwrap();
+    let knocker = user_id!("@knocker:localhost");
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/members"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "chunk": [{
+                "content": {
+                    "displayname": "Knocker",
+                    "membership": "knock",
+                    "reason": "Let me in"
+                },
+                "event_id": "$knock:localhost",
+                "origin_server_ts": 151800140,
+                "room_id": *test_json::DEFAULT_SYNC_ROOM_ID,
+                "sender": knocker,
+                "state_key": knocker,
+                "type": "m.room.member"
+            }]
+        })))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/invite$"))
+        .and(body_json(json!({ "user_id": knocker })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/kick$"))
+        .and(body_json(json!({ "user_id": knocker, "reason": "Not today" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(&*test_json::EMPTY))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let knocks = room.knock_requests().await.unwrap();
+    assert_eq!(knocks.len(), 1);
+    assert_eq!(knocks[0].user_id(), knocker);
+
+    room.accept_knock(knocker).await.unwrap();
+    room.deny_knock(knocker, Some("Not today")).await.unwrap();
+}
+
+#[async_test]
+async fn power_level_permissions() {
+    let (client, server) = logged_in_client().await;
+    let room_id = room_id!("!power:localhost");
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(room_id).add_state_event(StateTestEvent::PowerLevels),
+    );
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let room = client.get_joined_room(room_id).unwrap();
+    let admin = user_id!("@example:localhost");
+    let bob = user_id!("@bob:localhost");
+
+    assert!(room.can_user_send_state(admin, StateEventType::RoomPowerLevels).await.unwrap());
+    assert!(!room.can_user_send_state(bob, StateEventType::RoomName).await.unwrap());
+    assert!(room.can_user_redact_other(admin).await.unwrap());
+    assert!(!room.can_user_redact_other(bob).await.unwrap());
+    assert!(room.can_user_invite(bob).await.unwrap());
+
+    assert!(room.can_user_change_power_level(admin, bob, int!(50)).await.unwrap());
+    assert!(room.can_user_change_power_level(admin, admin, int!(50)).await.unwrap());
+    assert!(!room.can_user_change_power_level(admin, bob, int!(101)).await.unwrap());
+    assert!(!room.can_user_change_power_level(bob, bob, int!(0)).await.unwrap());
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.room.power_levels/"))
+        .and(body_partial_json(json!({ "invite": 50, "users_default": 10 })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "event_id": "$pl" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut changes = RoomPowerLevelChanges::new();
+    changes.invite = Some(int!(50));
+    changes.users_default = Some(int!(10));
+    room.apply_power_level_changes(changes).await.unwrap();
+
+    let result = room.set_state_default_power_level(int!(200)).await;
+    assert_matches!(result, Err(Error::InsufficientPowerLevel));
+}
+
+#[async_test]
+async fn power_level_changes_above_own_level() {
+    let (client, server) = logged_in_client().await;
+    let room_id = room_id!("!power:localhost");
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id).add_state_event(
+        StateTestEvent::Custom(json!({
+            "content": {
+                "ban": 100,
+                "events": { "m.room.power_levels": 50 },
+                "invite": 0,
+                "users": {
+                    "@admin:localhost": 100,
+                    "@example:localhost": 50,
+                    "@mod:localhost": 50,
+                },
+            },
+            "event_id": "$power_levels",
+            "origin_server_ts": 151957878,
+            "sender": "@admin:localhost",
+            "state_key": "",
+            "type": "m.room.power_levels"
+        })),
+    ));
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let room = client.get_joined_room(room_id).unwrap();
+
+    // The ban level is higher than our own, so we can't lower it.
+    let mut changes = RoomPowerLevelChanges::new();
+    changes.ban = Some(int!(50));
+    assert_matches!(
+        room.apply_power_level_changes(changes).await,
+        Err(Error::InsufficientPowerLevel)
+    );
+
+    // The admin has a higher level than our own, so we can't demote them.
+    let mut changes = RoomPowerLevelChanges::new();
+    changes.users.insert(user_id!("@admin:localhost").to_owned(), int!(0));
+    assert_matches!(
+        room.apply_power_level_changes(changes).await,
+        Err(Error::InsufficientPowerLevel)
+    );
+
+    // Another moderator has the same level as our own.
+    let mut changes = RoomPowerLevelChanges::new();
+    changes.users.insert(user_id!("@mod:localhost").to_owned(), int!(0));
+    assert_matches!(
+        room.apply_power_level_changes(changes).await,
+        Err(Error::InsufficientPowerLevel)
+    );
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.room.power_levels/"))
+        .and(body_partial_json(json!({
+            "ban": 100,
+            "invite": 50,
+            "users": { "@bob:localhost": 50 },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "event_id": "$pl" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // Setting the ban level to its current value isn't a change.
+    let mut changes = RoomPowerLevelChanges::new();
+    changes.ban = Some(int!(100));
+    changes.invite = Some(int!(50));
+    changes.users.insert(user_id!("@bob:localhost").to_owned(), int!(50));
+    room.apply_power_level_changes(changes).await.unwrap();
+}
+
+#[async_test]
+async fn pin_and_unpin_events() {
+    let (client, server) = logged_in_client().await;
+    let room_id = room_id!("!pinned:localhost");
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(room_id)
+            .add_state_event(StateTestEvent::PowerLevels)
+            .add_state_event(StateTestEvent::Custom(json!({
+                "content": { "pinned": ["$a"] },
+                "event_id": "$pinned",
+                "origin_server_ts": 151393755,
+                "sender": "@example:localhost",
+                "state_key": "",
+                "type": "m.room.pinned_events"
+            }))),
+    );
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let room = client.get_joined_room(room_id).unwrap();
+    assert_eq!(room.pinned_event_ids().await.unwrap(), [event_id!("$a")]);
+
+    // Nothing to do, no request is sent.
+    assert!(room.pin_event(event_id!("$a")).await.unwrap().is_none());
+    assert!(room.unpin_event(event_id!("$b")).await.unwrap().is_none());
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.room.pinned_events/"))
+        .and(body_json(json!({ "pinned": ["$a", "$b"] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "event_id": "$p1" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.room.pinned_events/"))
+        .and(body_json(json!({ "pinned": [] })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "event_id": "$p2" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let response = room.pin_event(event_id!("$b")).await.unwrap().unwrap();
+    assert_eq!(response.event_id, event_id!("$p1"));
+    let response = room.unpin_event(event_id!("$a")).await.unwrap().unwrap();
+    assert_eq!(response.event_id, event_id!("$p2"));
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/event/\$a"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "content": { "body": "Important", "msgtype": "m.text" },
+            "event_id": "$a",
+            "origin_server_ts": 151393755,
+            "room_id": "!pinned:localhost",
+            "sender": "@example:localhost",
+            "type": "m.room.message"
+        })))
+        .mount(&server)
+        .await;
+
+    let events = room.pinned_events().await.unwrap();
+    assert_eq!(events.len(), 1);
+    let event_id = events[0].event.get_field::<String>("event_id").unwrap();
+    assert_eq!(event_id.as_deref(), Some("$a"));
+}
+
+#[async_test]
+async fn pin_event_without_permission() {
+    let (client, server) = logged_in_client().await;
+    let room_id = room_id!("!pinned:localhost");
+
+    let mut power_levels = (*test_json::sync_events::POWER_LEVELS).clone();
+    power_levels["content"]["users"]["@example:localhost"] = json!(0);
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(room_id).add_state_event(StateTestEvent::Custom(power_levels)),
+    );
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let room = client.get_joined_room(room_id).unwrap();
+    let result = room.pin_event(event_id!("$a")).await;
+    assert_matches!(result, Err(Error::InsufficientPowerLevel));
+}
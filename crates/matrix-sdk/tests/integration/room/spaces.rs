@@ -0,0 +1,236 @@
+use matrix_sdk::{
+    config::SyncSettings,
+    room::{HierarchyOptions, SpaceTreeNode},
+};
+use matrix_sdk_test::{async_test, EventBuilder, JoinedRoomBuilder, StateTestEvent};
+use ruma::{room_id, uint, RoomId};
+use serde_json::{json, Value as JsonValue};
+use wiremock::{
+    matchers::{body_json, method, path_regex, query_param, query_param_is_missing},
+    Mock, ResponseTemplate,
+};
+
+use crate::{logged_in_client, mock_sync};
+
+fn space_create_event() -> StateTestEvent {
+    StateTestEvent::Custom(json!({
+        "content": {
+            "creator": "@example:localhost",
+            "room_version": "9",
+            "type": "m.space"
+        },
+        "event_id": "$create:localhost",
+        "origin_server_ts": 151957878,
+        "sender": "@example:localhost",
+        "state_key": "",
+        "type": "m.room.create"
+    }))
+}
+
+fn space_child_event(child_id: &RoomId, order: Option<&str>, suggested: bool) -> StateTestEvent {
+    let mut content = json!({ "via": ["localhost"], "suggested": suggested });
+    if let Some(order) = order {
+        content["order"] = order.into();
+    }
+
+    StateTestEvent::Custom(json!({
+        "content": content,
+        "event_id": format!("$child_{}", child_id.as_str().trim_start_matches('!')),
+        "origin_server_ts": 151957879,
+        "sender": "@example:localhost",
+        "state_key": child_id,
+        "type": "m.space.child"
+    }))
+}
+
+fn room_ids(nodes: &[SpaceTreeNode]) -> Vec<&RoomId> {
+    nodes.iter().map(|node| node.room.room_id()).collect()
+}
+
+#[async_test]
+async fn space_tree() {
+    let (client, server) = logged_in_client().await;
+
+    let space_a = room_id!("!a:localhost");
+    let space_b = room_id!("!b:localhost");
+    let room_c = room_id!("!c:localhost");
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder
+        .add_joined_room(
+            JoinedRoomBuilder::new(space_a)
+                .add_state_event(space_create_event())
+                .add_state_event(space_child_event(room_c, None, false))
+                .add_state_event(space_child_event(space_b, Some("a"), false)),
+        )
+        .add_joined_room(
+            JoinedRoomBuilder::new(space_b)
+                .add_state_event(space_create_event())
+                .add_state_event(space_child_event(room_c, None, true)),
+        )
+        .add_joined_room(JoinedRoomBuilder::new(room_c));
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    assert_eq!(client.spaces().len(), 2);
+    assert!(client.get_space(room_c).is_none());
+
+    let tree = client.space_tree().await.unwrap();
+    assert_eq!(room_ids(&tree.roots), [space_a]);
+
+    let a = &tree.roots[0];
+    assert_eq!(room_ids(&a.children), [space_b, room_c]);
+
+    let b = &a.children[0];
+    assert_eq!(room_ids(&b.children), [room_c]);
+    assert!(b.children[0].suggested);
+    assert!(a.children[1].children.is_empty());
+}
+
+#[async_test]
+async fn space_tree_shared_spaces_and_cycles() {
+    let (client, server) = logged_in_client().await;
+
+    let space_p = room_id!("!p:localhost");
+    let space_q = room_id!("!q:localhost");
+    let room_r = room_id!("!r:localhost");
+    let space_s = room_id!("!s:localhost");
+    let space_x = room_id!("!x:localhost");
+    let space_y = room_id!("!y:localhost");
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder
+        .add_joined_room(
+            JoinedRoomBuilder::new(space_p)
+                .add_state_event(space_create_event())
+                .add_state_event(space_child_event(space_q, None, false)),
+        )
+        .add_joined_room(
+            JoinedRoomBuilder::new(space_q)
+                .add_state_event(space_create_event())
+                .add_state_event(space_child_event(space_p, None, false)),
+        )
+        .add_joined_room(JoinedRoomBuilder::new(room_r))
+        .add_joined_room(
+            JoinedRoomBuilder::new(space_s)
+                .add_state_event(space_create_event())
+                .add_state_event(space_child_event(room_r, None, false)),
+        )
+        .add_joined_room(
+            JoinedRoomBuilder::new(space_x)
+                .add_state_event(space_create_event())
+                .add_state_event(space_child_event(space_s, None, false)),
+        )
+        .add_joined_room(
+            JoinedRoomBuilder::new(space_y)
+                .add_state_event(space_create_event())
+                .add_state_event(space_child_event(space_s, None, false)),
+        );
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    let tree = client.space_tree().await.unwrap();
+    assert_eq!(room_ids(&tree.roots), [space_x, space_y, space_p]);
+
+    // A space that is the child of several spaces is expanded under each of
+    // them.
+    for root in &tree.roots[..2] {
+        assert_eq!(room_ids(&root.children), [space_s]);
+        assert_eq!(room_ids(&root.children[0].children), [room_r]);
+    }
+
+    // A cycle is only expanded until it loops back.
+    let p = &tree.roots[2];
+    assert_eq!(room_ids(&p.children), [space_q]);
+    assert_eq!(room_ids(&p.children[0].children), [space_p]);
+    assert!(p.children[0].children[0].children.is_empty());
+}
+
+fn hierarchy_chunk(room_id: &RoomId) -> JsonValue {
+    json!({
+        "children_state": [],
+        "guest_can_join": false,
+        "num_joined_members": 1,
+        "room_id": room_id,
+        "world_readable": false
+    })
+}
+
+#[async_test]
+async fn space_hierarchy_pagination() {
+    let (client, server) = logged_in_client().await;
+    let space_id = room_id!("!space:localhost");
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"/rooms/.*/hierarchy"))
+        .and(query_param("suggested_only", "true"))
+        .and(query_param_is_missing("from"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "next_batch": "next",
+            "rooms": [hierarchy_chunk(space_id)]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex(r"/rooms/.*/hierarchy"))
+        .and(query_param("from", "next"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "rooms": [hierarchy_chunk(room_id!("!child:localhost"))]
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut hierarchy = client
+        .space_hierarchy(space_id, HierarchyOptions::new().suggested_only().max_depth(uint!(1)));
+
+    let rooms = hierarchy.next_page().await.unwrap().unwrap();
+    assert_eq!(rooms[0].room_id, space_id);
+    assert!(!hierarchy.is_finished());
+
+    let rooms = hierarchy.next_page().await.unwrap().unwrap();
+    assert_eq!(rooms[0].room_id, room_id!("!child:localhost"));
+    assert!(hierarchy.is_finished());
+
+    assert!(hierarchy.next_page().await.unwrap().is_none());
+}
+
+#[async_test]
+async fn add_and_remove_child() {
+    let (client, server) = logged_in_client().await;
+    let space_id = room_id!("!space:localhost");
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder.add_joined_room(
+        JoinedRoomBuilder::new(space_id)
+            .add_state_event(space_create_event())
+            .add_state_event(StateTestEvent::Member),
+    );
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    client.sync_once(SyncSettings::new()).await.unwrap();
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.space.child/.*"))
+        .and(body_json(json!({ "via": ["localhost"], "order": "b", "suggested": true })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "event_id": "$add" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("PUT"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/state/m.space.child/.*"))
+        .and(body_json(json!({})))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "event_id": "$remove" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let space = client.get_space(space_id).unwrap();
+    let child_id = room_id!("!child:localhost");
+    space.add_child(child_id, Some("b".to_owned()), true).await.unwrap();
+    space.remove_child(child_id).await.unwrap();
+}
@@ -29,7 +29,7 @@ use ruma::{
 };
 use serde_json::json;
 use wiremock::{
-    matchers::{header, method, path_regex},
+    matchers::{header, method, path_regex, query_param},
     Mock, ResponseTemplate,
 };
 
@@ -759,6 +759,94 @@ async fn in_reply_to_details() {
     assert_matches!(message.in_reply_to().unwrap().event, TimelineDetails::Ready(_));
 }
 
+#[async_test]
+async fn url_preview() {
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let (client, server) = logged_in_client().await;
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id));
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(sync_settings.clone()).await.unwrap();
+    server.reset().await;
+
+    let room = client.get_room(room_id).unwrap();
+    let timeline = room.timeline_builder().url_previews(true).build().await;
+    let (_, mut timeline_stream) = timeline.subscribe().await;
+
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id).add_timeline_event(
+        TimelineTestEvent::Custom(json!({
+            "content": {
+                "body": "look at https://matrix.org!",
+                "msgtype": "m.text",
+                "m.relates_to": {
+                    "m.in_reply_to": {
+                        "event_id": "$remoteevent",
+                    },
+                },
+            },
+            "event_id": "$event1",
+            "origin_server_ts": 152037280,
+            "sender": "@bob:example.org",
+            "type": "m.room.message",
+        })),
+    ));
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(sync_settings.clone()).await.unwrap();
+    server.reset().await;
+
+    let _day_divider = assert_matches!(timeline_stream.next().await, Some(VectorDiff::PushBack { value }) => value);
+    let first = assert_matches!(timeline_stream.next().await, Some(VectorDiff::PushBack { value }) => value);
+    let first_event = first.as_event().unwrap();
+    assert_matches!(first_event.url_preview(), TimelineDetails::Unavailable);
+
+    mock_encryption_state(&server, false).await;
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/client/r0/rooms/.*/event/\$remoteevent"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "errcode": "M_NOT_FOUND",
+            "error": "Event not found.",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex(r"^/_matrix/media/r0/preview_url"))
+        .and(query_param("url", "https://matrix.org"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "og:title": "Matrix.org",
+            "og:description": "An open network for secure, decentralized communication",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    // The URL preview is fetched even if the replied-to event can't be.
+    timeline.fetch_event_details(first_event.event_id().unwrap()).await.unwrap();
+
+    let first = assert_matches!(timeline_stream.next().await, Some(VectorDiff::Set { index: 1, value }) => value);
+    let message = assert_matches!(first.as_event().unwrap().content(), TimelineItemContent::Message(message) => message);
+    assert_matches!(message.in_reply_to().unwrap().event, TimelineDetails::Pending);
+
+    let first = assert_matches!(timeline_stream.next().await, Some(VectorDiff::Set { index: 1, value }) => value);
+    let message = assert_matches!(first.as_event().unwrap().content(), TimelineItemContent::Message(message) => message);
+    assert_matches!(message.in_reply_to().unwrap().event, TimelineDetails::Error(_));
+
+    let first = assert_matches!(timeline_stream.next().await, Some(VectorDiff::Set { index: 1, value }) => value);
+    assert_matches!(first.as_event().unwrap().url_preview(), TimelineDetails::Pending);
+
+    let first = assert_matches!(timeline_stream.next().await, Some(VectorDiff::Set { index: 1, value }) => value);
+    let preview = assert_matches!(
+        first.as_event().unwrap().url_preview(),
+        TimelineDetails::Ready(preview) => preview
+    );
+    assert_eq!(preview.title.as_deref(), Some("Matrix.org"));
+}
+
 #[async_test]
 async fn sync_highlighted() {
     let room_id = room_id!("!a98sd12bjh:example.org");
@@ -933,3 +1021,79 @@ async fn back_pagination_highlighted() {
     // Removal of the loading indicator
     assert_matches!(timeline_stream.next().await, Some(VectorDiff::PopFront));
 }
+
+#[async_test]
+async fn pinned_events() {
+    let room_id = room_id!("!a98sd12bjh:example.org");
+    let (client, server) = logged_in_client().await;
+    let sync_settings = SyncSettings::new().timeout(Duration::from_millis(3000));
+
+    let mut ev_builder = EventBuilder::new();
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id));
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(sync_settings.clone()).await.unwrap();
+    server.reset().await;
+
+    let room = client.get_room(room_id).unwrap();
+    let timeline = room.timeline().await;
+    let (_, mut timeline_stream) = timeline.subscribe().await;
+
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id).add_timeline_event(
+        TimelineTestEvent::Custom(json!({
+            "content": { "body": "Remember this", "msgtype": "m.text" },
+            "event_id": "$important",
+            "origin_server_ts": 152037280,
+            "sender": "@alice:example.org",
+            "type": "m.room.message",
+        })),
+    ));
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(sync_settings.clone()).await.unwrap();
+    server.reset().await;
+
+    let _day_divider = assert_matches!(
+        timeline_stream.next().await,
+        Some(VectorDiff::PushBack { value }) => value
+    );
+    let message = assert_matches!(
+        timeline_stream.next().await,
+        Some(VectorDiff::PushBack { value }) => value
+    );
+    assert!(!message.as_event().unwrap().is_pinned());
+
+    ev_builder.add_joined_room(JoinedRoomBuilder::new(room_id).add_timeline_event(
+        TimelineTestEvent::Custom(json!({
+            "content": { "pinned": ["$important"] },
+            "event_id": "$pinned",
+            "origin_server_ts": 152037290,
+            "sender": "@alice:example.org",
+            "state_key": "",
+            "type": "m.room.pinned_events",
+        })),
+    ));
+
+    mock_sync(&server, ev_builder.build_json_sync_response(), None).await;
+    let _response = client.sync_once(sync_settings.clone()).await.unwrap();
+    server.reset().await;
+
+    let message = assert_matches!(
+        timeline_stream.next().await,
+        Some(VectorDiff::Set { index: 1, value }) => value
+    );
+    let message = message.as_event().unwrap();
+    assert_eq!(message.event_id(), Some(event_id!("$important")));
+    assert!(message.is_pinned());
+
+    let state = assert_matches!(
+        timeline_stream.next().await,
+        Some(VectorDiff::PushBack { value }) => value
+    );
+    assert_matches!(
+        state.as_event().unwrap().content(),
+        TimelineItemContent::OtherState(ev) => {
+            assert_matches!(ev.content(), AnyOtherFullStateEventContent::RoomPinnedEvents(_));
+        }
+    );
+}
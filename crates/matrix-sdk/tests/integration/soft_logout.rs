@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use assert_matches::assert_matches;
+use futures::StreamExt;
+use matrix_sdk::{config::SyncSettings, Error, LoopCtrl};
+use matrix_sdk_test::{async_test, test_json};
+use serde_json::json;
+use wiremock::{
+    matchers::{body_partial_json, header, method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::logged_in_client;
+
+#[async_test]
+async fn sync_resumes_after_login() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/sync"))
+        .and(header("authorization", "Bearer 1234"))
+        .respond_with(
+            ResponseTemplate::new(401).set_body_json(&*test_json::UNKNOWN_TOKEN_SOFT_LOGOUT),
+        )
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/sync"))
+        .and(header("authorization", "Bearer 5678"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "next_batch": "s1" })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/login"))
+        .and(body_partial_json(json!({ "device_id": "DEVICEID" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "user_id": "@example:localhost",
+            "access_token": "5678",
+            "device_id": "DEVICEID",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut soft_logout = client.subscribe_to_soft_logout();
+    let sync = tokio::spawn({
+        let client = client.clone();
+        async move {
+            client
+                .sync_with_callback(SyncSettings::new(), |response| async move {
+                    assert_eq!(response.next_batch, "s1");
+                    LoopCtrl::Break
+                })
+                .await
+        }
+    });
+
+    let soft_logged_out =
+        tokio::time::timeout(Duration::from_secs(5), soft_logout.next()).await.unwrap();
+    assert_eq!(soft_logged_out, Some(true));
+    assert!(client.is_soft_logged_out());
+
+    client.login_username("example", "wordpass").send().await.unwrap();
+
+    assert!(!client.is_soft_logged_out());
+    assert_eq!(soft_logout.next().await, Some(false));
+    tokio::time::timeout(Duration::from_secs(5), sync).await.unwrap().unwrap().unwrap();
+
+    assert_eq!(client.access_token().as_deref(), Some("5678"));
+    assert_eq!(client.device_id().unwrap(), "DEVICEID");
+}
+
+#[async_test]
+async fn login_with_another_device_is_refused() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/login"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "user_id": "@example:localhost",
+            "access_token": "5678",
+            "device_id": "NEWDEVICE",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+    // The new session is logged out, with its own access token.
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/logout"))
+        .and(header("authorization", "Bearer 5678"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let error = client.login_username("example", "wordpass").send().await.unwrap_err();
+
+    assert_matches!(error, Error::SessionMismatch);
+    assert_eq!(client.access_token().as_deref(), Some("1234"));
+    assert_eq!(client.device_id().unwrap(), "DEVICEID");
+
+    // A different device ID is refused before sending the request.
+    let error = client
+        .login_username("example", "wordpass")
+        .device_id("OTHERDEVICE")
+        .send()
+        .await
+        .unwrap_err();
+
+    assert_matches!(error, Error::SessionMismatch);
+    assert_eq!(client.access_token().as_deref(), Some("1234"));
+}
@@ -0,0 +1,207 @@
+use std::time::Duration;
+
+use eyeball::Subscriber;
+use futures::StreamExt;
+use matrix_sdk::{
+    config::{RequestConfig, SyncSettings},
+    sync_service::{SyncService, SyncServiceState},
+    Client, Session,
+};
+use matrix_sdk_test::{async_test, test_json};
+use ruma::{api::MatrixVersion, device_id, user_id};
+use serde_json::json;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+use crate::{logged_in_client, test_client_builder};
+
+/// Wait until the sync service reaches the given state.
+async fn wait_for_state(states: &mut Subscriber<SyncServiceState>, expected: SyncServiceState) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(state) = states.next().await {
+            if state == expected {
+                return;
+            }
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("The sync service never reached the {expected:?} state"));
+}
+
+/// Wait until the server received a `/sync` request.
+async fn wait_for_sync_request(server: &MockServer) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .any(|request| request.url.path().ends_with("/sync"))
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[async_test]
+async fn start_pause_and_stop() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/sync"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "next_batch": "s1" })))
+        .mount(&server)
+        .await;
+
+    let sync_service = SyncService::builder(client).sync_settings(SyncSettings::new()).build();
+    assert_eq!(sync_service.state(), SyncServiceState::Idle);
+
+    sync_service.start().await;
+    assert_eq!(sync_service.state(), SyncServiceState::Running);
+    wait_for_sync_request(&server).await;
+
+    sync_service.pause().await;
+    assert_eq!(sync_service.state(), SyncServiceState::Idle);
+
+    // No request is sent while the sync loop is paused.
+    let requests = server.received_requests().await.unwrap().len();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(server.received_requests().await.unwrap().len(), requests);
+
+    sync_service.start().await;
+    assert_eq!(sync_service.state(), SyncServiceState::Running);
+
+    sync_service.stop().await;
+    assert_eq!(sync_service.state(), SyncServiceState::Terminated);
+
+    // The sync service can't be started again.
+    sync_service.start().await;
+    assert_eq!(sync_service.state(), SyncServiceState::Terminated);
+}
+
+#[async_test]
+async fn network_error_and_reachability_hint() {
+    let (builder, server) = test_client_builder().await;
+    let client = builder
+        .request_config(RequestConfig::new().disable_retry().timeout(Duration::from_millis(200)))
+        .build()
+        .await
+        .unwrap();
+    client
+        .restore_session(Session {
+            access_token: "1234".to_owned(),
+            refresh_token: None,
+            user_id: user_id!("@example:localhost").to_owned(),
+            device_id: device_id!("DEVICEID").to_owned(),
+        })
+        .await
+        .unwrap();
+
+    // The first request times out.
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/sync"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(json!({ "next_batch": "s1" }))
+                .set_delay(Duration::from_secs(2)),
+        )
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/sync"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "next_batch": "s2" })))
+        .mount(&server)
+        .await;
+
+    let sync_service = SyncService::builder(client)
+        .sync_settings(SyncSettings::new())
+        .backoff(Duration::from_secs(3600), Duration::from_secs(3600))
+        .build();
+    let mut states = sync_service.subscribe_to_state();
+
+    sync_service.start().await;
+    wait_for_state(&mut states, SyncServiceState::Offline).await;
+
+    // The network comes back, the sync loop doesn't wait for the backoff delay.
+    sync_service.set_network_reachable(false);
+    sync_service.set_network_reachable(true);
+    wait_for_state(&mut states, SyncServiceState::Running).await;
+
+    sync_service.stop().await;
+    assert_eq!(sync_service.state(), SyncServiceState::Terminated);
+}
+
+#[async_test]
+async fn unreachable_network_goes_offline() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/sync"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "next_batch": "s1" })))
+        .mount(&server)
+        .await;
+
+    let sync_service = SyncService::builder(client).sync_settings(SyncSettings::new()).build();
+    let mut states = sync_service.subscribe_to_state();
+    sync_service.set_network_reachable(false);
+    sync_service.start().await;
+
+    wait_for_state(&mut states, SyncServiceState::Offline).await;
+    assert!(server.received_requests().await.unwrap().is_empty());
+
+    sync_service.set_network_reachable(true);
+    wait_for_sync_request(&server).await;
+
+    sync_service.stop().await;
+}
+
+#[async_test]
+async fn other_error_stops_the_loop() {
+    let client = Client::builder()
+        .homeserver_url("http://localhost:1")
+        .server_versions([MatrixVersion::V1_0])
+        .request_config(RequestConfig::new().disable_retry())
+        .build()
+        .await
+        .unwrap();
+
+    // The client is not logged in.
+    let sync_service = SyncService::builder(client).build();
+    let mut states = sync_service.subscribe_to_state();
+    sync_service.start().await;
+
+    wait_for_state(&mut states, SyncServiceState::Error).await;
+}
+
+#[async_test]
+async fn pause_while_soft_logged_out() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("GET"))
+        .and(path("/_matrix/client/r0/sync"))
+        .respond_with(
+            ResponseTemplate::new(401).set_body_json(&*test_json::UNKNOWN_TOKEN_SOFT_LOGOUT),
+        )
+        .mount(&server)
+        .await;
+
+    let mut soft_logout = client.subscribe_to_soft_logout();
+    let sync_service =
+        SyncService::builder(client.clone()).sync_settings(SyncSettings::new()).build();
+    sync_service.start().await;
+
+    let soft_logged_out =
+        tokio::time::timeout(Duration::from_secs(5), soft_logout.next()).await.unwrap();
+    assert_eq!(soft_logged_out, Some(true));
+
+    // The sync loop is waiting for the session to be recovered, but it can
+    // still be paused.
+    tokio::time::timeout(Duration::from_secs(5), sync_service.pause()).await.unwrap();
+    assert_eq!(sync_service.state(), SyncServiceState::Idle);
+}
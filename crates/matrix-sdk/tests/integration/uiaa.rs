@@ -0,0 +1,267 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use assert_matches::assert_matches;
+use matrix_sdk::uiaa::{UiaaAnswer, UiaaPrompt};
+use matrix_sdk_test::async_test;
+use ruma::{api::client::account::register, device_id};
+use serde_json::json;
+use wiremock::{
+    matchers::{body_partial_json, method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::{logged_in_client, no_retry_test_client};
+
+#[async_test]
+async fn delete_devices_with_password() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/delete_devices"))
+        .and(body_partial_json(json!({
+            "auth": {
+                "type": "m.login.password",
+                "identifier": { "type": "m.id.user", "user": "@example:localhost" },
+                "password": "wordpass",
+                "session": "vBslorikviAjxzYBASOBGfPp",
+            },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/delete_devices"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "flows": [
+                { "stages": ["m.login.sso"] },
+                { "stages": ["m.login.password"] },
+            ],
+            "params": {},
+            "session": "vBslorikviAjxzYBASOBGfPp",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let prompts = Arc::new(Mutex::new(Vec::new()));
+    let devices = &[device_id!("DEVICEID").to_owned()];
+
+    client
+        .uiaa(|prompt| {
+            let prompts = prompts.clone();
+            async move {
+                let answer = match &prompt {
+                    UiaaPrompt::ChooseFlow { .. } => UiaaAnswer::Flow(1),
+                    UiaaPrompt::Stage(_) => UiaaAnswer::Password("wordpass".to_owned()),
+                    _ => UiaaAnswer::Abort,
+                };
+                prompts.lock().unwrap().push(prompt);
+                answer
+            }
+        })
+        .run(|auth| client.delete_devices(devices, auth))
+        .await
+        .unwrap();
+
+    let prompts = prompts.lock().unwrap();
+    assert_eq!(prompts.len(), 2);
+    assert_matches!(&prompts[0], UiaaPrompt::ChooseFlow { info } => {
+        assert_eq!(info.flows.len(), 2);
+    });
+    assert_matches!(&prompts[1], UiaaPrompt::Stage(stage) => {
+        assert_eq!(stage.auth_type.as_str(), "m.login.password");
+        assert_eq!(
+            stage.fallback_url.as_ref().unwrap().as_str(),
+            format!(
+                "{}/_matrix/client/v3/auth/m.login.password/fallback/web\
+                 ?session=vBslorikviAjxzYBASOBGfPp",
+                server.uri()
+            )
+        );
+    });
+}
+
+#[async_test]
+async fn register_with_terms_and_dummy_stages() {
+    let (client, server) = no_retry_test_client().await;
+
+    let info = |completed: &[&str]| {
+        json!({
+            "flows": [{ "stages": ["m.login.terms", "m.login.dummy"] }],
+            "completed": completed,
+            "params": {
+                "m.login.terms": {
+                    "policies": {
+                        "privacy_policy": {
+                            "version": "1.0",
+                            "en": { "name": "Privacy Policy", "url": "https://example.org" },
+                        },
+                    },
+                },
+            },
+            "session": "SESSION",
+        })
+    };
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/register"))
+        .and(body_partial_json(json!({
+            "auth": { "type": "m.login.dummy", "session": "SESSION" },
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "user_id": "@user:localhost",
+            "access_token": "abc",
+            "device_id": "NEWDEVICE",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/register"))
+        .and(body_partial_json(json!({
+            "auth": { "type": "m.login.terms", "session": "SESSION" },
+        })))
+        .respond_with(ResponseTemplate::new(401).set_body_json(info(&["m.login.terms"])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/register"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(info(&[])))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut request = register::v3::Request::new();
+    request.username = Some("user".to_owned());
+    request.password = Some("password".to_owned());
+
+    let response = client
+        .uiaa(|prompt| async move {
+            match prompt {
+                UiaaPrompt::ChooseFlow { .. } => UiaaAnswer::Flow(0),
+                UiaaPrompt::Stage(stage) => match stage.auth_type.as_str() {
+                    "m.login.terms" => {
+                        assert!(stage.params.unwrap()["policies"]["privacy_policy"].is_object());
+                        UiaaAnswer::Terms
+                    }
+                    "m.login.dummy" => UiaaAnswer::Dummy,
+                    _ => UiaaAnswer::Abort,
+                },
+                _ => UiaaAnswer::Abort,
+            }
+        })
+        .send(request)
+        .await
+        .unwrap();
+
+    assert_eq!(response.user_id, "@user:localhost");
+}
+
+#[async_test]
+async fn email_identity_is_polled() {
+    let (client, server) = logged_in_client().await;
+
+    let info = json!({
+        "flows": [{ "stages": ["m.login.email.identity"] }],
+        "params": {},
+        "session": "SESSION",
+    });
+    let email_auth = json!({
+        "auth": {
+            "type": "m.login.email.identity",
+            "threepid_creds": { "sid": "SID", "client_secret": "SECRET" },
+            "session": "SESSION",
+        },
+    });
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/account/password"))
+        .and(body_partial_json(&email_auth))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "errcode": "M_UNAUTHORIZED",
+            "error": "The email address has not been validated yet",
+            "flows": [{ "stages": ["m.login.email.identity"] }],
+            "params": {},
+            "session": "SESSION",
+        })))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/account/password"))
+        .and(body_partial_json(&email_auth))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/account/password"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(info))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let stages = Arc::new(Mutex::new(0));
+    let account = client.account();
+
+    client
+        .uiaa(|prompt| {
+            let stages = stages.clone();
+            async move {
+                match prompt {
+                    UiaaPrompt::ChooseFlow { .. } => UiaaAnswer::Flow(0),
+                    UiaaPrompt::Stage(_) => {
+                        *stages.lock().unwrap() += 1;
+                        UiaaAnswer::EmailIdentity {
+                            sid: "SID".try_into().unwrap(),
+                            client_secret: "SECRET".try_into().unwrap(),
+                        }
+                    }
+                    _ => UiaaAnswer::Abort,
+                }
+            }
+        })
+        .email_polling(Duration::from_millis(10), Duration::from_secs(60))
+        .run(|auth| account.change_password("new password", auth))
+        .await
+        .unwrap();
+
+    // The stage was submitted again without asking the callback.
+    assert_eq!(*stages.lock().unwrap(), 1);
+}
+
+#[async_test]
+async fn abort_returns_the_uiaa_error() {
+    let (client, server) = logged_in_client().await;
+
+    Mock::given(method("POST"))
+        .and(path("/_matrix/client/r0/account/deactivate"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "flows": [{ "stages": ["m.login.password"] }],
+            "params": {},
+            "session": "SESSION",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let account = client.account();
+    let error = client
+        .uiaa(|_| async { UiaaAnswer::Abort })
+        .run(|auth| account.deactivate(None, auth))
+        .await
+        .unwrap_err();
+
+    assert_eq!(error.as_uiaa_response().unwrap().session.as_deref(), Some("SESSION"));
+}
@@ -54,6 +54,10 @@ struct FullSession {
 /// will break the encryption setup and the client will not be able to send or
 /// receive encrypted messages, hence the need to persist the session.
 ///
+/// This example persists the Matrix session by hand to show how it works.
+/// Alternatively, `ClientBuilder::persist_session()` can be used to store it in
+/// the state store, and `Client::restore_from_store()` to restore it.
+///
 /// To use this, just run `cargo run -p example-persist-session`, and everything
 /// is interactive after that. You might want to set the `RUST_LOG` environment
 /// variable to `warn` to reduce the noise in the logs. The program exits
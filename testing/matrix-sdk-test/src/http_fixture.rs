@@ -0,0 +1,236 @@
+//! Fixtures of HTTP requests and responses recorded from a real homeserver.
+//!
+//! An [`HttpFixture`] is a list of [`HttpExchange`]s, that is a request and
+//! the response that the homeserver sent for it. It can be saved to and loaded
+//! from a JSON file, and replayed with an [`HttpReplayer`].
+//!
+//! Access tokens, passwords and other secrets are scrubbed when the exchanges
+//! are recorded, so the fixtures can be committed. The request headers are not
+//! recorded.
+//!
+//! In `matrix-sdk`, the `RecordingHttpClient` and `ReplayHttpClient` of the
+//! `test_utils` module use this to record and replay the requests of a
+//! `Client`.
+
+use std::{fs, io, path::Path};
+
+use http::{header::CONTENT_TYPE, Method, Request, Response, StatusCode};
+use matrix_sdk_secrets::{redact_form, redact_json};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+
+/// The value that replaces secrets in the fixtures.
+pub const SCRUBBED: &str = "<scrubbed>";
+
+/// Normalize the given request path, so it can be compared with the path of a
+/// recorded exchange.
+///
+/// The transaction IDs, which are different every time, are replaced by
+/// `{txn_id}`, and the state keys by `{state_key}`.
+pub fn normalize_path(path: &str) -> String {
+    let segments: Vec<_> = path.split('/').collect();
+
+    segments
+        .iter()
+        .enumerate()
+        .map(|(i, &segment)| {
+            // The placeholder is two segments after the keyword, for example
+            // `/rooms/{room_id}/send/{event_type}/{txn_id}`.
+            match i.checked_sub(2).map(|i| segments[i]) {
+                Some("send" | "sendToDevice" | "redact") => "{txn_id}",
+                Some("state") => "{state_key}",
+                _ => segment,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Normalize the given HTTP body, so it can be compared with the body of a
+/// recorded exchange.
+///
+/// An empty body becomes `null`, a JSON body is parsed and its secrets are
+/// scrubbed, and any other body becomes a string.
+pub fn normalize_body(body: &[u8]) -> JsonValue {
+    if body.is_empty() {
+        return JsonValue::Null;
+    }
+
+    match serde_json::from_slice::<JsonValue>(body) {
+        Ok(mut value) => {
+            redact_json(&mut value, SCRUBBED);
+            value
+        }
+        Err(_) => String::from_utf8_lossy(body).into_owned().into(),
+    }
+}
+
+/// A request and the response that the homeserver sent for it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HttpExchange {
+    /// The method of the request.
+    pub method: String,
+    /// The path of the request.
+    pub path: String,
+    /// The query string of the request, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    /// The normalized body of the request, see [`normalize_body()`].
+    #[serde(default)]
+    pub request_body: JsonValue,
+    /// The status code of the response.
+    pub status: u16,
+    /// The normalized body of the response, see [`normalize_body()`].
+    #[serde(default)]
+    pub response_body: JsonValue,
+}
+
+impl HttpExchange {
+    /// Create a new `HttpExchange` from the given request and response, with
+    /// the secrets scrubbed.
+    pub fn new(request: &Request<impl AsRef<[u8]>>, response: &Response<impl AsRef<[u8]>>) -> Self {
+        Self {
+            method: request.method().to_string(),
+            path: request.uri().path().to_owned(),
+            query: request.uri().query().map(|query| redact_form(query, SCRUBBED)),
+            request_body: normalize_body(request.body().as_ref()),
+            status: response.status().as_u16(),
+            response_body: normalize_body(response.body().as_ref()),
+        }
+    }
+
+    /// Whether the given request matches this exchange.
+    ///
+    /// The method, the normalized path and the normalized body must be the
+    /// same, see [`normalize_path()`] and [`normalize_body()`]. The query
+    /// string is ignored.
+    pub fn matches(&self, request: &Request<impl AsRef<[u8]>>) -> bool {
+        request.method().as_str() == self.method
+            && normalize_path(request.uri().path()) == normalize_path(&self.path)
+            && normalize_body(request.body().as_ref()) == self.request_body
+    }
+
+    /// Build the response of this exchange.
+    pub fn response(&self) -> Response<Vec<u8>> {
+        let body = match &self.response_body {
+            JsonValue::Null => Vec::new(),
+            JsonValue::String(body) => body.clone().into_bytes(),
+            body => body.to_string().into_bytes(),
+        };
+
+        let mut response = Response::builder().status(self.status);
+        if self.response_body.is_object() || self.response_body.is_array() {
+            response = response.header(CONTENT_TYPE, "application/json");
+        }
+
+        response.body(body).expect("The recorded response should be valid")
+    }
+}
+
+/// A list of recorded [`HttpExchange`]s.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HttpFixture {
+    /// The exchanges, in the order they were recorded.
+    pub exchanges: Vec<HttpExchange>,
+}
+
+impl HttpFixture {
+    /// Create an empty `HttpFixture`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load the fixture from the JSON file at the given path.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let json = fs::read(path)?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+
+    /// Save the fixture to a JSON file at the given path.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        fs::write(path, json)
+    }
+
+    /// Record the given request and response.
+    pub fn record(
+        &mut self,
+        request: &Request<impl AsRef<[u8]>>,
+        response: &Response<impl AsRef<[u8]>>,
+    ) {
+        self.exchanges.push(HttpExchange::new(request, response));
+    }
+}
+
+/// A request that didn't match any exchange of an [`HttpReplayer`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnmatchedRequest {
+    /// The method of the request.
+    pub method: Method,
+    /// The path of the request.
+    pub path: String,
+    /// The normalized body of the request, see [`normalize_body()`].
+    pub body: JsonValue,
+}
+
+/// Replays the exchanges of an [`HttpFixture`].
+///
+/// Each exchange is used once, in the order they were recorded: a request gets
+/// the response of the first unused exchange that [matches] it. The requests
+/// that don't match any unused exchange get a `404` response with an
+/// `M_UNRECOGNIZED` error, and are kept to be reported by
+/// [`HttpReplayer::unmatched_requests()`].
+///
+/// [matches]: HttpExchange::matches
+#[derive(Debug)]
+pub struct HttpReplayer {
+    exchanges: Vec<(HttpExchange, bool)>,
+    unmatched: Vec<UnmatchedRequest>,
+}
+
+impl HttpReplayer {
+    /// Create a new `HttpReplayer` for the exchanges of the given fixture.
+    pub fn new(fixture: HttpFixture) -> Self {
+        Self {
+            exchanges: fixture.exchanges.into_iter().map(|exchange| (exchange, false)).collect(),
+            unmatched: Vec::new(),
+        }
+    }
+
+    /// Get the response to the given request.
+    pub fn respond(&mut self, request: &Request<impl AsRef<[u8]>>) -> Response<Vec<u8>> {
+        let exchange =
+            self.exchanges.iter_mut().find(|(exchange, used)| !*used && exchange.matches(request));
+
+        if let Some((exchange, used)) = exchange {
+            *used = true;
+            return exchange.response();
+        }
+
+        self.unmatched.push(UnmatchedRequest {
+            method: request.method().clone(),
+            path: request.uri().path().to_owned(),
+            body: normalize_body(request.body().as_ref()),
+        });
+
+        let body = json!({
+            "errcode": "M_UNRECOGNIZED",
+            "error": "The request doesn't match any recorded exchange",
+        });
+        Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string().into_bytes())
+            .unwrap()
+    }
+
+    /// Get the requests that didn't match any exchange.
+    pub fn unmatched_requests(&self) -> &[UnmatchedRequest] {
+        &self.unmatched
+    }
+
+    /// Get the exchanges that were not used yet.
+    pub fn unused_exchanges(&self) -> Vec<&HttpExchange> {
+        self.exchanges.iter().filter(|(_, used)| !used).map(|(exchange, _)| exchange).collect()
+    }
+}